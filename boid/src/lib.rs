@@ -1,10 +1,14 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+// テストやシミュレータでは std がリンクされて std の f32 のメソッドが使われるので、
+// micromath::F32Ext が未使用になる
+#![cfg_attr(any(test, feature = "sim"), allow(unused_imports))]
 use core::f32::consts::PI;
 use embedded_graphics::pixelcolor::Rgb565;
 use embedded_graphics::pixelcolor::RgbColor;
 use embedded_graphics::prelude::Drawable;
 use embedded_graphics::prelude::Point;
 use embedded_graphics::prelude::Primitive;
+use embedded_graphics::primitives::Circle;
 use embedded_graphics::primitives::Rectangle;
 use embedded_graphics::primitives::Triangle;
 use embedded_graphics::style::PrimitiveStyle;
@@ -24,10 +28,14 @@ const SEPARATION_ANGLE: f32 = PI / 1.5;
 const ALIGNMENT_ANGLE: f32 = PI * 1.2;
 const MIN_VELOCITY: f32 = 0.005;
 const MAX_VELOCITY: f32 = 0.03;
+// 捕食者からこの距離より近い個体は逃げる
+const PREDATOR_DISTANCE: f32 = 0.4;
+const PREDATOR_FORCE: f32 = 0.02;
 const N: usize = 3;
 const M: usize = 100;
 
 const WING_WIDTH: f32 = 4.0;
+const PREDATOR_RADIUS: u32 = 4;
 pub const BG_COLOR: Rgb565 = Rgb565::BLACK;
 pub const BOID_COLOR: Rgb565 = Rgb565::WHITE;
 pub const PLAYER_COLOR: Rgb565 = Rgb565::RED;
@@ -44,6 +52,92 @@ pub struct Boid {
     velocity: [f32; N],
 }
 
+// 群れの動き方を決めるパラメータ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlockParams {
+    pub cohesion_force: f32,
+    pub separation_force: f32,
+    pub alignment_force: f32,
+    pub min_velocity: f32,
+    pub max_velocity: f32, // MAX_VELOCITY 以下にする (描画の形が崩れるため)
+}
+
+impl FlockParams {
+    pub fn new() -> Self {
+        FlockParams {
+            cohesion_force: COHESION_FORCE,
+            separation_force: SEPARATION_FORCE,
+            alignment_force: ALIGNMENT_FORCE,
+            min_velocity: MIN_VELOCITY,
+            max_velocity: MAX_VELOCITY,
+        }
+    }
+}
+
+impl Default for FlockParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// あらかじめ用意した群れの動き方
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preset {
+    Calm,    // ゆっくりまとまって泳ぐ
+    Swarm,   // 密集して素早く向きをそろえる
+    Scatter, // ばらばらに飛び回る
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Calm, Preset::Swarm, Preset::Scatter];
+
+    pub fn next(self) -> Self {
+        match self {
+            Preset::Calm => Preset::Swarm,
+            Preset::Swarm => Preset::Scatter,
+            Preset::Scatter => Preset::Calm,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        match self {
+            Preset::Calm => Preset::Scatter,
+            Preset::Swarm => Preset::Calm,
+            Preset::Scatter => Preset::Swarm,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Calm => "CALM",
+            Preset::Swarm => "SWARM",
+            Preset::Scatter => "SCATTER",
+        }
+    }
+
+    pub fn params(self) -> FlockParams {
+        match self {
+            Preset::Calm => FlockParams {
+                min_velocity: 0.003,
+                max_velocity: 0.015,
+                ..FlockParams::new()
+            },
+            Preset::Swarm => FlockParams {
+                cohesion_force: 0.02,
+                alignment_force: 0.08,
+                ..FlockParams::new()
+            },
+            Preset::Scatter => FlockParams {
+                cohesion_force: 0.0,
+                separation_force: 0.01,
+                alignment_force: 0.005,
+                min_velocity: 0.01,
+                max_velocity: MAX_VELOCITY,
+            },
+        }
+    }
+}
+
 fn clamp<Num>(v: Num, min: Num, max: Num) -> Num where Num: PartialOrd<Num> {
     if v < min {
         min
@@ -62,7 +156,7 @@ fn calc_distance(source: &Boid, target: &Boid) -> f32 {
     norm(minus(target.position, source.position))
 }
 
-fn calc_coherence(source: &Boid, boids: &[Boid; M], dist: [f32; M], angle: [f32; M]) -> [f32; N] {
+fn calc_coherence(source: &Boid, boids: &[Boid; M], dist: [f32; M], angle: [f32; M], params: &FlockParams) -> [f32; N] {
     let mut coh = [0.0; N];
     let mut cnt = 0;
     for i in 0..N {
@@ -73,13 +167,13 @@ fn calc_coherence(source: &Boid, boids: &[Boid; M], dist: [f32; M], angle: [f32;
     }
     if cnt > 0 {
         let average = divide(coh, cnt as f32);
-        multiply(minus(average, source.position), COHESION_FORCE)
+        multiply(minus(average, source.position), params.cohesion_force)
     } else {
         coh // [0.0; N]
     }
 }
 
-fn calc_separation(source: &Boid, boids: &[Boid; M], dist: [f32; M], angle: [f32; M]) -> [f32; N] {
+fn calc_separation(source: &Boid, boids: &[Boid; M], dist: [f32; M], angle: [f32; M], params: &FlockParams) -> [f32; N] {
     let mut sep = [0.0; N];
     let mut cnt = 0;
     for i in 0..N {
@@ -91,13 +185,13 @@ fn calc_separation(source: &Boid, boids: &[Boid; M], dist: [f32; M], angle: [f32
         }
     }
     if cnt > 0 {
-        multiply(sep, SEPARATION_FORCE)
+        multiply(sep, params.separation_force)
     } else {
         sep
     }
 }
 
-fn calc_alignment(source: &Boid, boids: &[Boid; M], dist: [f32; M], angle: [f32; M]) -> [f32; N] {
+fn calc_alignment(source: &Boid, boids: &[Boid; M], dist: [f32; M], angle: [f32; M], params: &FlockParams) -> [f32; N] {
     let mut ali = [0.0; N];
     let mut cnt = 0;
     for i in 0..N {
@@ -108,7 +202,7 @@ fn calc_alignment(source: &Boid, boids: &[Boid; M], dist: [f32; M], angle: [f32;
     }
     if cnt > 0 {
         let average = divide(ali, cnt as f32);
-        multiply(minus(average, source.velocity), ALIGNMENT_FORCE)
+        multiply(minus(average, source.velocity), params.alignment_force)
     } else {
         ali
     }
@@ -125,6 +219,22 @@ fn calc_boundary(source: &Boid, _: &[Boid; M], _: [f32; M], _: [f32; M]) -> [f32
         [0.0; N]
     }
 }
+
+// 捕食者に近いほど強く、捕食者から離れる向きに力を受ける
+fn calc_predator(source: &Boid, predator: Option<[f32; N]>) -> [f32; N] {
+    let predator = match predator {
+        Some(predator) => predator,
+        None => return [0.0; N],
+    };
+    let away = minus(source.position, predator);
+    let dist = norm(away);
+    if dist < PREDATOR_DISTANCE && dist > 0.0 {
+        multiply(away, PREDATOR_FORCE * (1.0 - dist / PREDATOR_DISTANCE) / dist)
+    } else {
+        [0.0; N]
+    }
+}
+
 fn dot(x: [f32; N], y: [f32; N]) -> f32 {
     let mut sum = 0.0;
     for i in 0..N {
@@ -194,6 +304,9 @@ pub struct Boids {
     _dv_sep: [[f32; N]; M],
     _dv_ali: [[f32; N]; M],
     _dv_bnd: [[f32; N]; M],
+    params: FlockParams,
+    force: [f32; N],            // 全ての個体に加わる外力 (風や重力)
+    predator: Option<[f32; N]>, // 捕食者の位置
 }
 
 impl Boids {
@@ -205,8 +318,42 @@ impl Boids {
             _dv_sep: [[0.0; N]; M],
             _dv_ali: [[0.0; N]; M],
             _dv_bnd: [[0.0; N]; M],
+            params: FlockParams::new(),
+            force: [0.0; N],
+            predator: None,
         }
     }
+    pub fn params(&self) -> &FlockParams {
+        &self.params
+    }
+    pub fn set_params(&mut self, params: FlockParams) {
+        self.params = params;
+    }
+    pub fn set_preset(&mut self, preset: Preset) {
+        self.params = preset.params();
+    }
+    // 画面の横 (右が正) と縦 (下が正) の向きに、毎回の更新で速度に加える外力を設定します
+    pub fn set_force(&mut self, x: f32, y: f32) {
+        self.force = [0.0; N];
+        self.force[0] = x;
+        self.force[1] = y;
+    }
+    pub fn force(&self) -> (f32, f32) {
+        (self.force[0], self.force[1])
+    }
+    // 捕食者を画面の平面 (奥行き0) の位置に置きます (None のときは捕食者なし)
+    // 座標は個体と同じで、群れはおよそ -1.0〜1.0 の範囲を動きます
+    pub fn set_predator(&mut self, position: Option<(f32, f32)>) {
+        self.predator = position.map(|(x, y)| {
+            let mut predator = [0.0; N];
+            predator[0] = x;
+            predator[1] = y;
+            predator
+        });
+    }
+    pub fn predator(&self) -> Option<(f32, f32)> {
+        self.predator.map(|predator| (predator[0], predator[1]))
+    }
     pub fn init(&mut self) {
         let mut rng = SmallRng::from_seed([0; 16]);
         for boid in self.boids.iter_mut() {
@@ -229,9 +376,9 @@ impl Boids {
                     angle[j] = calc_angle(source, target);
                 }
             }
-            self._dv_coh[i] = calc_coherence(&source, &self.boids, dist, angle);
-            self._dv_sep[i] = calc_separation(&source, &self.boids, dist, angle);
-            self._dv_ali[i] = calc_alignment(&source, &self.boids, dist, angle);
+            self._dv_coh[i] = calc_coherence(&source, &self.boids, dist, angle, &self.params);
+            self._dv_sep[i] = calc_separation(&source, &self.boids, dist, angle, &self.params);
+            self._dv_ali[i] = calc_alignment(&source, &self.boids, dist, angle, &self.params);
             self._dv_bnd[i] = calc_boundary(&source, &self.boids, dist, angle);
         }
         for (idx, boid) in self.boids.iter_mut().enumerate() {
@@ -245,11 +392,15 @@ impl Boids {
                     ),
                 ), 0.4)
             );
+            boid.velocity = plus(
+                boid.velocity,
+                plus(self.force, calc_predator(boid, self.predator)),
+            );
             let v_abs = norm(boid.velocity);
-            if v_abs < MIN_VELOCITY {
-                boid.velocity = divide(multiply(boid.velocity, MIN_VELOCITY), v_abs);
-            } else if v_abs > MAX_VELOCITY {
-                boid.velocity = divide(multiply(boid.velocity, MAX_VELOCITY), v_abs);
+            if v_abs < self.params.min_velocity {
+                boid.velocity = divide(multiply(boid.velocity, self.params.min_velocity), v_abs);
+            } else if v_abs > self.params.max_velocity {
+                boid.velocity = divide(multiply(boid.velocity, self.params.max_velocity), v_abs);
             }
             boid.position = plus(boid.position, boid.velocity);
         }
@@ -332,6 +483,7 @@ fn calc_points(position: [f32; N], velocity: [f32; N], ctx: &DrawContext, option
 
 pub struct BoidRenderer {
     _points_cache: [Option<(Point, Point, Point)>; M],
+    _predator_cache: Option<Point>,
     option: DrawOption,
 }
 
@@ -339,6 +491,7 @@ impl BoidRenderer {
     pub fn new() -> Self {
         BoidRenderer {
             _points_cache: [None; M],
+            _predator_cache: None,
             option: DrawOption::new(),
         }
     }
@@ -362,6 +515,11 @@ impl BoidRenderer {
                 }
             }
         }
+        if let Some(center) = self._predator_cache {
+            Circle::new(center, PREDATOR_RADIUS)
+                .into_styled(PrimitiveStyle::with_fill(self.option.bg_color))
+                .draw(display)?;
+        }
         Ok(())
     }
 
@@ -403,6 +561,128 @@ impl BoidRenderer {
                     .draw(display)?;
             }
         }
+        // 捕食者は奥行き0の位置に描く
+        self._predator_cache = boids.predator().map(|(x, y)| {
+            Point::new(
+                (x * 1.5 * ctx.scale) as i32 + ctx.center_x,
+                (y * 1.5 * ctx.scale) as i32 + ctx.center_y,
+            )
+        });
+        if let Some(center) = self._predator_cache {
+            Circle::new(center, PREDATOR_RADIUS)
+                .into_styled(PrimitiveStyle::with_fill(self.option.player_color))
+                .draw(display)?;
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 全個体の速度の平均
+    fn mean_velocity(boids: &Boids) -> [f32; N] {
+        let sum = boids
+            .boids
+            .iter()
+            .fold([0.0; N], |sum, boid| plus(sum, boid.velocity));
+        divide(sum, M as f32)
+    }
+
+    fn distance_to(boid: &Boid, predator: [f32; N]) -> f32 {
+        norm(minus(boid.position, predator))
+    }
+
+    #[test]
+    fn external_force_moves_mean_velocity() {
+        let mut calm = Boids::new();
+        calm.init();
+        let mut windy = Boids::new();
+        windy.init();
+        windy.set_force(0.002, 0.0);
+        assert_eq!(windy.force(), (0.002, 0.0));
+        for _ in 0..20 {
+            calm.update();
+            windy.update();
+        }
+        // 外力を加えた向き (右) にだけ、群れ全体の速度が増える
+        let calm_velocity = mean_velocity(&calm);
+        let windy_velocity = mean_velocity(&windy);
+        assert!(
+            windy_velocity[0] - calm_velocity[0] > 0.005,
+            "{:?} {:?}",
+            calm_velocity,
+            windy_velocity
+        );
+        assert!(windy_velocity[0] > 0.0);
+    }
+
+    #[test]
+    fn boids_near_predator_move_away() {
+        let mut free = Boids::new();
+        free.init();
+        let mut chased = Boids::new();
+        chased.init();
+        chased.set_predator(Some((0.1, -0.2)));
+        let predator = chased.predator.unwrap();
+        let near: Vec<usize> = (0..M)
+            .filter(|&i| distance_to(&chased.boids[i], predator) < PREDATOR_DISTANCE)
+            .collect();
+        assert!(near.len() >= 3, "{} boids near the predator", near.len());
+
+        for _ in 0..3 {
+            free.update();
+            chased.update();
+        }
+        // 捕食者がいないときより、捕食者から離れている
+        let mut gained = 0.0;
+        for &i in near.iter() {
+            let free_distance = distance_to(&free.boids[i], predator);
+            let chased_distance = distance_to(&chased.boids[i], predator);
+            assert!(
+                chased_distance > free_distance,
+                "boid {}: {} -> {}",
+                i,
+                free_distance,
+                chased_distance
+            );
+            gained += chased_distance - free_distance;
+        }
+        assert!(gained / near.len() as f32 > 0.001);
+
+        // 遠くの個体は逃げない (1回目の更新では捕食者の影響を受けない)
+        let mut free = Boids::new();
+        free.init();
+        let mut chased = Boids::new();
+        chased.init();
+        chased.set_predator(Some((0.1, -0.2)));
+        free.update();
+        chased.update();
+        for i in (0..M).filter(|i| !near.contains(i)) {
+            assert_eq!(free.boids[i].velocity, chased.boids[i].velocity, "boid {}", i);
+        }
+    }
+
+    #[test]
+    fn presets_cycle() {
+        for &preset in Preset::ALL.iter() {
+            let mut visited = Vec::new();
+            let mut next = preset;
+            for _ in 0..Preset::ALL.len() {
+                visited.push(next);
+                assert_eq!(next.next().prev(), next);
+                next = next.next();
+            }
+            // ひとまわりして元に戻り、途中ですべてのプリセットを通る
+            assert_eq!(next, preset);
+            assert!(Preset::ALL.iter().all(|p| visited.contains(p)));
+
+            let mut prev = preset;
+            for _ in 0..Preset::ALL.len() {
+                prev = prev.prev();
+            }
+            assert_eq!(prev, preset);
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use embedded_graphics_simulator::*;
+use wio_font::{BitmapText, DEFAULT_FONT};
+
+fn main() -> Result<(), core::convert::Infallible> {
+  let mut display: SimulatorDisplay<Rgb565> = SimulatorDisplay::new(Size::new(320, 240));
+  let output_settings = OutputSettingsBuilder::new().scale(2).build();
+  let mut window = Window::new("draw japanese text", &output_settings);
+
+  BitmapText::new("こんにちは\nワイオ", Point::new(16, 16), &DEFAULT_FONT, Rgb565::GREEN)
+    .draw(&mut display)?;
+
+  // フォントに含まれない文字は代替文字で描画される
+  BitmapText::new("起動中...", Point::new(16, 48), &DEFAULT_FONT, Rgb565::WHITE)
+    .with_background(Rgb565::BLUE)
+    .draw(&mut display)?;
+
+  window.show_static(&display);
+
+  Ok(())
+}
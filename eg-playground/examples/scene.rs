@@ -0,0 +1,29 @@
+//! シーンファイル (TOML/JSON) を読み込んでシミュレータに表示します。
+//!
+//! ```sh
+//! $ cargo run --features window --example scene -- examples/assets/line.toml
+//! ```
+
+use eg_playground::scene::Scene;
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use embedded_graphics_simulator::*;
+use std::{env, path::PathBuf, process};
+
+fn main() {
+  let path = env::args()
+    .nth(1)
+    .map(PathBuf::from)
+    .unwrap_or_else(|| PathBuf::from("examples/assets/line.toml"));
+  let scene = Scene::load(&path).unwrap_or_else(|e| {
+    eprintln!("error: {}", e);
+    process::exit(1);
+  });
+
+  let mut display: SimulatorDisplay<Rgb565> =
+    SimulatorDisplay::new(Size::new(scene.width, scene.height));
+  scene.draw(&mut display).unwrap();
+
+  let output_settings = OutputSettingsBuilder::new().build();
+  let mut window = Window::new(&path.display().to_string(), &output_settings);
+  window.show_static(&display);
+}
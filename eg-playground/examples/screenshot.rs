@@ -0,0 +1,106 @@
+//! SDL のウィンドウを開かずに、シーンや Drawable を PNG ファイルへ書き出します。
+//! ヘッドレスな Linux マシン (CI など) でドキュメント用の画像を作るのに使います。
+//!
+//! ```sh
+//! $ cargo run --example screenshot -- examples/assets/line.toml line.png
+//! $ cargo run --example screenshot -- splash splash.png --scale 2 --pixel-spacing 1
+//! $ cargo run --example screenshot -- boid boid.png --frames 200 --theme lcd-green
+//! ```
+
+use boid::{BoidRenderer, Boids};
+use eg_playground::{scene::Scene, screenshot};
+use embedded_graphics::{
+  image::ImageRawLE, pixelcolor::Rgb565, prelude::*, primitives::Rectangle, style::PrimitiveStyle,
+};
+use embedded_graphics_simulator::*;
+use std::{env, path::PathBuf, process};
+use wio_splash::WioSplash;
+
+const USAGE: &str = "usage: screenshot <scene.toml|scene.json|splash|boid> <output.png> \
+  [--scale N] [--pixel-spacing N] [--theme NAME] [--frames N]";
+
+fn parse_theme(name: &str) -> Option<BinaryColorTheme> {
+  let theme = match name {
+    "default" => BinaryColorTheme::Default,
+    "lcd-white" => BinaryColorTheme::LcdWhite,
+    "lcd-green" => BinaryColorTheme::LcdGreen,
+    "lcd-blue" => BinaryColorTheme::LcdBlue,
+    "oled-white" => BinaryColorTheme::OledWhite,
+    "oled-blue" => BinaryColorTheme::OledBlue,
+    _ => return None,
+  };
+  Some(theme)
+}
+
+fn fail(message: &str) -> ! {
+  eprintln!("error: {}\n{}", message, USAGE);
+  process::exit(1);
+}
+
+fn main() {
+  let mut args = env::args().skip(1);
+  let mut positional = Vec::new();
+  let mut builder = OutputSettingsBuilder::new();
+  let mut frames = 100;
+  while let Some(arg) = args.next() {
+    let mut value = |name: &str| {
+      args
+        .next()
+        .unwrap_or_else(|| fail(&format!("{} needs a value", name)))
+    };
+    let number = |name: &str, value: String| {
+      value
+        .parse::<u32>()
+        .unwrap_or_else(|_| fail(&format!("{} must be a number, found `{}`", name, value)))
+    };
+    match arg.as_str() {
+      "--scale" => builder = builder.scale(number("--scale", value("--scale"))),
+      "--pixel-spacing" => {
+        builder = builder.pixel_spacing(number("--pixel-spacing", value("--pixel-spacing")))
+      }
+      "--theme" => {
+        let name = value("--theme");
+        let theme =
+          parse_theme(&name).unwrap_or_else(|| fail(&format!("unknown theme `{}`", name)));
+        builder = builder.theme(theme);
+      }
+      "--frames" => frames = number("--frames", value("--frames")),
+      _ if arg.starts_with("--") => fail(&format!("unknown option `{}`", arg)),
+      _ => positional.push(arg),
+    }
+  }
+  if positional.len() != 2 {
+    fail("expected an input and an output path");
+  }
+  let output_settings = builder.build();
+  let output = PathBuf::from(&positional[1]);
+
+  let result = match positional[0].as_str() {
+    "splash" => {
+      let raw = ImageRawLE::new(include_bytes!("./assets/ferris.raw"), 86, 64);
+      let splash = WioSplash::new(Rgb565::GREEN, raw);
+      screenshot::drawable_to_png(splash, Size::new(320, 240), &output_settings, &output)
+    }
+    "boid" => {
+      let mut boids = Boids::new();
+      boids.init();
+      for _ in 0..frames {
+        boids.update();
+      }
+      let mut renderer = BoidRenderer::new();
+      screenshot::render_png(Size::new(320, 240), &output_settings, &output, |display| {
+        Rectangle::new(Point::new(0, 0), Point::new(319, 239))
+          .into_styled(PrimitiveStyle::with_fill(boid::BG_COLOR))
+          .draw(display)?;
+        renderer.draw(display, &boids)
+      })
+    }
+    scene_path => {
+      let scene = Scene::load(&PathBuf::from(scene_path)).unwrap_or_else(|e| fail(&e.to_string()));
+      screenshot::scene_to_png(&scene, &output_settings, &output)
+    }
+  };
+  if let Err(e) = result {
+    fail(&e.to_string());
+  }
+}
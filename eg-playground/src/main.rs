@@ -1,3 +1,193 @@
+//! シーンファイルのライブプレビューです。
+//! シーンファイルやアセットのディレクトリを監視し、変更されるたびに再描画します。
+//! 読み込みに失敗した場合は、直前の描画結果の上にエラーを重ねて表示します。
+//! ウィンドウの大きさはシーンの `width`/`height` です (`--size` で変えられます)。
+//!
+//! ```sh
+//! $ cargo run --features window -- examples/assets/line.toml --scale 2
+//! ```
+
+use eg_playground::scene::{Scene, SceneError};
+use embedded_graphics::{
+  fonts::{Font6x8, Text},
+  pixelcolor::Rgb565,
+  prelude::*,
+  primitives::Rectangle,
+  style::{PrimitiveStyle, TextStyle},
+};
+use embedded_graphics_simulator::*;
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use std::{
+  env,
+  path::{Path, PathBuf},
+  process,
+  sync::mpsc::channel,
+  thread,
+  time::Duration,
+};
+
+const USAGE: &str = "usage: eg-playground <scene.toml|scene.json> [--scale N] [--size WxH]";
+const DEBOUNCE: Duration = Duration::from_millis(200);
+const FRAME_INTERVAL: Duration = Duration::from_millis(30);
+// シーンを一度も読み込めていないときの大きさ
+const DEFAULT_SIZE: Size = Size::new(320, 240);
+const OVERLAY_BG_COLOR: Rgb565 = Rgb565::RED;
+const OVERLAY_TEXT_COLOR: Rgb565 = Rgb565::WHITE;
+
+fn fail(message: &str) -> ! {
+  eprintln!("error: {}\n{}", message, USAGE);
+  process::exit(1);
+}
+
+fn parse_size(value: &str) -> Option<Size> {
+  let mut parts = value.split('x');
+  let width = parts.next()?.parse().ok()?;
+  let height = parts.next()?.parse().ok()?;
+  if parts.next().is_some() || width == 0 || height == 0 {
+    return None;
+  }
+  Some(Size::new(width, height))
+}
+
+// 画面の下部にエラーメッセージを表示する
+fn draw_error_overlay<D>(display: &mut D, message: &str) -> Result<(), D::Error>
+where
+  D: DrawTarget<Rgb565>,
+{
+  let (width, height) = display.size().into();
+  let columns = (width.saturating_sub(4) / 6).max(1) as usize;
+  let lines: Vec<String> = message
+    .lines()
+    .flat_map(|line| {
+      let chars: Vec<char> = line.chars().collect();
+      chars
+        .chunks(columns)
+        .map(|chunk| chunk.iter().collect::<String>())
+        .collect::<Vec<_>>()
+    })
+    .collect();
+  let top = height as i32 - (lines.len() as i32 * 8 + 4);
+  Rectangle::new(Point::new(0, top), Point::new(width as i32 - 1, height as i32 - 1))
+    .into_styled(PrimitiveStyle::with_fill(OVERLAY_BG_COLOR))
+    .draw(display)?;
+  for (i, line) in lines.iter().enumerate() {
+    Text::new(line, Point::new(2, top + 2 + i as i32 * 8))
+      .into_styled(TextStyle::new(Font6x8, OVERLAY_TEXT_COLOR))
+      .draw(display)?;
+  }
+  Ok(())
+}
+
+fn render(
+  display: &mut SimulatorDisplay<Rgb565>,
+  path: &Path,
+  result: Result<Scene, SceneError>,
+  last_scene: &mut Option<Scene>,
+) {
+  display.clear(Rgb565::BLACK).unwrap();
+  match result {
+    Ok(scene) => {
+      scene.draw(display).unwrap();
+      println!("reloaded {}", path.display());
+      *last_scene = Some(scene);
+    }
+    Err(e) => {
+      // 直前に読み込めたシーンを残したまま、エラーを重ねて表示する
+      if let Some(scene) = last_scene {
+        scene.draw(display).unwrap();
+      }
+      eprintln!("error: {}", e);
+      draw_error_overlay(display, &e.to_string()).unwrap();
+    }
+  }
+}
+
+fn is_change(event: &DebouncedEvent) -> bool {
+  matches!(
+    event,
+    DebouncedEvent::Create(_)
+      | DebouncedEvent::Write(_)
+      | DebouncedEvent::Remove(_)
+      | DebouncedEvent::Rename(_, _)
+      | DebouncedEvent::Rescan
+  )
+}
+
 fn main() {
-    println!("Hello, world!");
+  let mut args = env::args().skip(1);
+  let mut scene_path = None;
+  let mut scale = 1;
+  let mut size = None;
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--scale" => {
+        scale = args
+          .next()
+          .and_then(|v| v.parse().ok())
+          .unwrap_or_else(|| fail("--scale needs a number"))
+      }
+      "--size" => {
+        size = Some(
+          args
+            .next()
+            .and_then(|v| parse_size(&v))
+            .unwrap_or_else(|| fail("--size needs a value like 320x240")),
+        )
+      }
+      _ if arg.starts_with("--") => fail(&format!("unknown option `{}`", arg)),
+      _ if scene_path.is_none() => scene_path = Some(PathBuf::from(arg)),
+      _ => fail("expected a single scene file"),
+    }
+  }
+  let scene_path = scene_path.unwrap_or_else(|| fail("expected a scene file"));
+
+  // シーンファイルのディレクトリごと監視して、画像などのアセットの変更も拾う
+  let (tx, rx) = channel();
+  let mut watcher = watcher(tx, DEBOUNCE).unwrap_or_else(|e| fail(&e.to_string()));
+  let watch_dir = match scene_path.parent() {
+    Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+    _ => PathBuf::from("."),
+  };
+  watcher
+    .watch(&watch_dir, RecursiveMode::Recursive)
+    .unwrap_or_else(|e| fail(&format!("{}: {}", watch_dir.display(), e)));
+
+  let output_settings = OutputSettingsBuilder::new().scale(scale).build();
+  let title = format!("preview: {}", scene_path.display());
+  let mut display = None;
+  let mut window = Window::new(&title, &output_settings);
+
+  let mut last_scene = None;
+  let mut changed = true;
+  'running: loop {
+    if changed {
+      let result = Scene::load(&scene_path);
+      // --size がなければ、シーンファイルの width/height の大きさで表示する
+      // 大きさが変わったときは、ウィンドウを開き直す
+      let scene_size = match &result {
+        Ok(scene) => Size::new(scene.width, scene.height),
+        Err(_) => last_scene
+          .as_ref()
+          .map(|scene: &Scene| Size::new(scene.width, scene.height))
+          .unwrap_or(DEFAULT_SIZE),
+      };
+      let size = size.unwrap_or(scene_size);
+      let display = match &mut display {
+        Some(display) if SimulatorDisplay::size(display) == size => display,
+        _ => {
+          window = Window::new(&title, &output_settings);
+          display.insert(SimulatorDisplay::new(size))
+        }
+      };
+      render(display, &scene_path, result, &mut last_scene);
+    }
+    if let Some(display) = &display {
+      window.update(display);
+    }
+    if window.events().any(|e| e == SimulatorEvent::Quit) {
+      break 'running;
+    }
+    changed = rx.try_iter().filter(is_change).count() > 0;
+    thread::sleep(FRAME_INTERVAL);
+  }
 }
@@ -0,0 +1,659 @@
+//! TOML/JSON で書かれたシーン記述を読み込み、embedded-graphics で描画します。
+//!
+//! ```toml
+//! width = 320
+//! height = 240
+//! background = "black"
+//!
+//! [[shapes]]
+//! type = "circle"
+//! center = [50, 200]
+//! radius = 20
+//! stroke = { color = "red", width = 5 }
+//! ```
+
+use serde::{de, Deserialize, Deserializer};
+use std::{
+  fmt, fs,
+  path::{Path, PathBuf},
+};
+
+mod render;
+
+const DEFAULT_WIDTH: u32 = 320;
+const DEFAULT_HEIGHT: u32 = 240;
+
+/// シーンの読み込みに失敗したときのエラー
+///
+/// ファイル名と行番号 (分かる場合)、シーン内の位置 (`shapes[2].radius` など) を持ちます。
+#[derive(Debug)]
+pub struct SceneError {
+  file: Option<String>,
+  line: Option<usize>,
+  path: String,
+  message: String,
+}
+
+impl SceneError {
+  fn new(path: impl Into<String>, message: impl fmt::Display) -> Self {
+    Self {
+      file: None,
+      line: None,
+      path: path.into(),
+      message: message.to_string(),
+    }
+  }
+
+  fn in_file(mut self, file: &Path) -> Self {
+    self.file = Some(file.display().to_string());
+    self
+  }
+
+  fn in_shape(mut self, index: usize, lines: &[usize]) -> Self {
+    self.path = if self.path.is_empty() {
+      format!("shapes[{}]", index)
+    } else {
+      format!("shapes[{}].{}", index, self.path)
+    };
+    if self.line.is_none() {
+      self.line = lines.get(index).copied();
+    }
+    self
+  }
+
+  /// 1始まりの行番号
+  pub fn line(&self) -> Option<usize> {
+    self.line
+  }
+
+  pub fn path(&self) -> &str {
+    &self.path
+  }
+
+  pub fn message(&self) -> &str {
+    &self.message
+  }
+}
+
+impl fmt::Display for SceneError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match (&self.file, self.line) {
+      (Some(file), Some(line)) => write!(f, "{}:{}: ", file, line)?,
+      (Some(file), None) => write!(f, "{}: ", file)?,
+      (None, Some(line)) => write!(f, "line {}: ", line)?,
+      (None, None) => {}
+    }
+    if !self.path.is_empty() {
+      write!(f, "{}: ", self.path)?;
+    }
+    write!(f, "{}", self.message)
+  }
+}
+
+impl std::error::Error for SceneError {}
+
+/// `"#rrggbb"` または色名 (`"red"` など) で指定する色
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+  pub r: u8,
+  pub g: u8,
+  pub b: u8,
+}
+
+impl Color {
+  const fn new(r: u8, g: u8, b: u8) -> Self {
+    Self { r, g, b }
+  }
+
+  fn parse(value: &str) -> Option<Self> {
+    let color = match value.to_ascii_lowercase().as_str() {
+      "black" => Self::new(0, 0, 0),
+      "white" => Self::new(255, 255, 255),
+      "red" => Self::new(255, 0, 0),
+      "green" => Self::new(0, 255, 0),
+      "blue" => Self::new(0, 0, 255),
+      "yellow" => Self::new(255, 255, 0),
+      "magenta" => Self::new(255, 0, 255),
+      "cyan" => Self::new(0, 255, 255),
+      // 文字数ではなくバイト数で切り出すので、先に16進数の文字だけかどうかを確かめる
+      hex if hex.len() == 7
+        && hex.starts_with('#')
+        && hex[1..].chars().all(|c| c.is_ascii_hexdigit()) =>
+      {
+        let channel = |i| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Self::new(channel(1)?, channel(3)?, channel(5)?)
+      }
+      _ => return None,
+    };
+    Some(color)
+  }
+}
+
+impl<'de> Deserialize<'de> for Color {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Color::parse(&value).ok_or_else(|| {
+      de::Error::invalid_value(
+        de::Unexpected::Str(&value),
+        &"a color name (black, white, red, green, blue, yellow, magenta, cyan) or \"#rrggbb\"",
+      )
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stroke {
+  pub color: Color,
+  #[serde(default = "default_stroke_width")]
+  pub width: u32,
+}
+
+fn default_stroke_width() -> u32 {
+  1
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+pub enum Font {
+  #[serde(rename = "6x8")]
+  Font6x8,
+  #[serde(rename = "6x12")]
+  Font6x12,
+  #[serde(rename = "8x16")]
+  #[default]
+  Font8x16,
+  #[serde(rename = "12x16")]
+  Font12x16,
+  #[serde(rename = "24x32")]
+  Font24x32,
+  /// wio_font のビットマップフォント (日本語を含む)
+  #[serde(rename = "bitmap")]
+  Bitmap,
+}
+
+/// シーンに置く図形。`type` キーで種類を指定します
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Shape {
+  Line {
+    start: [i32; 2],
+    end: [i32; 2],
+    stroke: Stroke,
+  },
+  Circle {
+    center: [i32; 2],
+    radius: u32,
+    stroke: Option<Stroke>,
+    fill: Option<Color>,
+  },
+  Triangle {
+    points: [[i32; 2]; 3],
+    stroke: Option<Stroke>,
+    fill: Option<Color>,
+  },
+  Rectangle {
+    top_left: [i32; 2],
+    bottom_right: [i32; 2],
+    stroke: Option<Stroke>,
+    fill: Option<Color>,
+  },
+  Text {
+    text: String,
+    position: [i32; 2],
+    color: Color,
+    #[serde(default)]
+    font: Font,
+    background: Option<Color>,
+  },
+  /// リトルエンディアンの Rgb565 生データ (`ferris.raw` と同じ形式)
+  Image {
+    path: PathBuf,
+    position: [i32; 2],
+    width: u32,
+    height: u32,
+    #[serde(skip)]
+    data: Vec<u8>,
+  },
+}
+
+impl Shape {
+  fn validate(&self) -> Result<(), SceneError> {
+    let check_stroke = |stroke: &Option<Stroke>| match stroke {
+      Some(s) if s.width == 0 => Err(SceneError::new("stroke.width", "must be at least 1")),
+      _ => Ok(()),
+    };
+    let check_paint = |stroke: &Option<Stroke>, fill: &Option<Color>| {
+      check_stroke(stroke)?;
+      if stroke.is_none() && fill.is_none() {
+        return Err(SceneError::new("", "needs a `stroke` or a `fill`"));
+      }
+      Ok(())
+    };
+    match self {
+      Shape::Line { stroke, .. } => check_stroke(&Some(*stroke)),
+      Shape::Circle { radius, stroke, fill, .. } => {
+        if *radius == 0 {
+          return Err(SceneError::new("radius", "must be greater than 0"));
+        }
+        check_paint(stroke, fill)
+      }
+      Shape::Triangle { stroke, fill, .. } => check_paint(stroke, fill),
+      Shape::Rectangle { top_left, bottom_right, stroke, fill } => {
+        if bottom_right[0] < top_left[0] || bottom_right[1] < top_left[1] {
+          return Err(SceneError::new(
+            "bottom_right",
+            format!(
+              "{:?} must be below and to the right of top_left {:?}",
+              bottom_right, top_left
+            ),
+          ));
+        }
+        check_paint(stroke, fill)
+      }
+      Shape::Text { .. } => Ok(()),
+      Shape::Image { width, height, data, .. } => {
+        if *width == 0 || *height == 0 {
+          return Err(SceneError::new("", "width and height must be greater than 0"));
+        }
+        let expected = match width.checked_mul(*height).and_then(|pixels| pixels.checked_mul(2)) {
+          Some(bytes) => bytes as usize,
+          None => {
+            return Err(SceneError::new(
+              "",
+              format!("{}x{} is too large for an image", width, height),
+            ))
+          }
+        };
+        if data.len() != expected {
+          return Err(SceneError::new(
+            "path",
+            format!(
+              "image has {} bytes, but {}x{} Rgb565 needs {}",
+              data.len(),
+              width,
+              height,
+              expected
+            ),
+          ));
+        }
+        Ok(())
+      }
+    }
+  }
+
+  fn load(&mut self, base_dir: &Path) -> Result<(), SceneError> {
+    if let Shape::Image { path, data, .. } = self {
+      let full_path = base_dir.join(path);
+      *data = fs::read(&full_path)
+        .map_err(|e| SceneError::new("path", format!("{}: {}", full_path.display(), e)))?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+  #[serde(default = "default_width")]
+  pub width: u32,
+  #[serde(default = "default_height")]
+  pub height: u32,
+  pub background: Option<Color>,
+  #[serde(default)]
+  pub shapes: Vec<Shape>,
+  #[serde(skip)]
+  shape_lines: Vec<usize>,
+}
+
+fn default_width() -> u32 {
+  DEFAULT_WIDTH
+}
+
+fn default_height() -> u32 {
+  DEFAULT_HEIGHT
+}
+
+fn path_error<E: fmt::Display>(error: serde_path_to_error::Error<E>) -> SceneError {
+  let path = error.path().to_string();
+  // ルートでのエラーは `.` になるので、位置情報としては使わない
+  let path = if path == "." { String::new() } else { path };
+  SceneError::new(path, error.into_inner())
+}
+
+// `shapes[2].radius` のようなエラーの位置から、図形の番号を取り出す
+fn shape_index<E>(error: &serde_path_to_error::Error<E>) -> Option<usize> {
+  match error.path().iter().nth(1) {
+    Some(serde_path_to_error::Segment::Seq { index }) => Some(*index),
+    _ => None,
+  }
+}
+
+// `[[shapes]]` テーブルが始まる行番号 (1始まり) の一覧
+fn toml_shape_lines(source: &str) -> Vec<usize> {
+  source
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| line.trim().trim_end_matches(|c| c != ']') == "[[shapes]]")
+    .map(|(i, _)| i + 1)
+    .collect()
+}
+
+// `shapes` 配列の各要素 (`{`) が始まる行番号 (1始まり) の一覧
+//
+// serde_json は `type` で図形を選ぶ enum の中のエラーを図形を読み終えた位置で報告するので、
+// 図形の中のエラーはこの行番号で置き換える
+fn json_shape_lines(source: &str) -> Vec<usize> {
+  let mut lines = Vec::new();
+  let mut line = 1;
+  let mut depth = 0;
+  let mut in_string = false;
+  let mut escaped = false;
+  let mut last_key = String::new();
+  let mut key = String::new();
+  let mut shapes_depth = None;
+  for c in source.chars() {
+    if in_string {
+      match c {
+        _ if escaped => escaped = false,
+        '\\' => escaped = true,
+        '"' => in_string = false,
+        _ => key.push(c),
+      }
+      if c == '\n' {
+        line += 1;
+      }
+      continue;
+    }
+    match c {
+      '\n' => line += 1,
+      '"' => {
+        in_string = true;
+        key.clear();
+      }
+      ':' => last_key = key.clone(),
+      '[' | '{' => {
+        if c == '{' && shapes_depth == Some(depth) {
+          lines.push(line);
+        }
+        depth += 1;
+        // ルートのオブジェクト直下の `"shapes": [`
+        if c == '[' && depth == 2 && last_key == "shapes" {
+          shapes_depth = Some(depth);
+        }
+      }
+      ']' | '}' => {
+        if shapes_depth == Some(depth) {
+          shapes_depth = None;
+        }
+        depth -= 1;
+      }
+      ',' => last_key.clear(),
+      _ => {}
+    }
+  }
+  lines
+}
+
+impl Scene {
+  /// 拡張子 (`.toml` / `.json`) で形式を判断してシーンファイルを読み込みます。
+  /// 画像のパスはシーンファイルのディレクトリからの相対パスです。
+  pub fn load(path: &Path) -> Result<Scene, SceneError> {
+    let source = fs::read_to_string(path).map_err(|e| SceneError::new("", e).in_file(path))?;
+    let scene = match path.extension().and_then(|ext| ext.to_str()) {
+      Some("json") => Self::parse_json(&source),
+      _ => Self::parse_toml(&source),
+    };
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    scene
+      .and_then(|scene| scene.finish(base_dir))
+      .map_err(|e| e.in_file(path))
+  }
+
+  pub fn from_toml_str(source: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+    Self::parse_toml(source)?.finish(base_dir)
+  }
+
+  pub fn from_json_str(source: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+    Self::parse_json(source)?.finish(base_dir)
+  }
+
+  fn parse_toml(source: &str) -> Result<Scene, SceneError> {
+    let shape_lines = toml_shape_lines(source);
+    let mut deserializer = toml::Deserializer::new(source);
+    let result: Result<Scene, _> = serde_path_to_error::deserialize(&mut deserializer);
+    match result {
+      Ok(mut scene) => {
+        scene.shape_lines = shape_lines;
+        Ok(scene)
+      }
+      Err(error) => {
+        let line = error.inner().line_col().map(|(line, _)| line + 1);
+        let shape = shape_index(&error);
+        let mut error = path_error(error);
+        // toml 0.5 は配列テーブルの中のエラーを `for key `shapes` at line 1 column 1`
+        // のように報告するので、位置は `[[shapes]]` の行番号で置き換える
+        if let Some(i) = error.message.find(" for key `") {
+          error.message.truncate(i);
+        }
+        match shape {
+          Some(index) if error.path.starts_with("shapes[") => {
+            error.line = shape_lines.get(index).copied();
+          }
+          _ => error.line = line,
+        }
+        if let Some(i) = error.message.find(" at line ") {
+          error.message.truncate(i);
+        }
+        Err(error)
+      }
+    }
+  }
+
+  fn parse_json(source: &str) -> Result<Scene, SceneError> {
+    let shape_lines = json_shape_lines(source);
+    let mut deserializer = serde_json::Deserializer::from_str(source);
+    let result: Result<Scene, _> = serde_path_to_error::deserialize(&mut deserializer);
+    match result {
+      Ok(mut scene) => {
+        scene.shape_lines = shape_lines;
+        Ok(scene)
+      }
+      Err(error) => {
+        let line = error.inner().line();
+        let shape = shape_index(&error);
+        let mut error = path_error(error);
+        if let Some(i) = error.message.find(" at line ") {
+          error.message.truncate(i);
+        }
+        error.line = match shape {
+          Some(index) if error.path.starts_with("shapes[") => shape_lines.get(index).copied(),
+          _ => None,
+        }
+        .or(Some(line));
+        Err(error)
+      }
+    }
+  }
+
+  // 画像を読み込み、値の範囲や座標をチェックします
+  fn finish(mut self, base_dir: &Path) -> Result<Scene, SceneError> {
+    if self.width == 0 || self.height == 0 {
+      return Err(SceneError::new("", "width and height must be greater than 0"));
+    }
+    let lines = &self.shape_lines;
+    for (i, shape) in self.shapes.iter_mut().enumerate() {
+      shape.load(base_dir).map_err(|e| e.in_shape(i, lines))?;
+    }
+    for (i, shape) in self.shapes.iter().enumerate() {
+      shape.validate().map_err(|e| e.in_shape(i, lines))?;
+      let (top_left, bottom_right) = match shape.bounding_box() {
+        Ok(Some(bounds)) => bounds,
+        // 空の文字列は何も描かないので、範囲をチェックしない
+        Ok(None) => continue,
+        Err(e) => return Err(e.in_shape(i, lines)),
+      };
+      if bottom_right[0] < 0
+        || bottom_right[1] < 0
+        || top_left[0] >= self.width as i32
+        || top_left[1] >= self.height as i32
+      {
+        let message = format!(
+          "lies entirely outside the {}x{} canvas ({:?} to {:?})",
+          self.width, self.height, top_left, bottom_right
+        );
+        return Err(SceneError::new("", message).in_shape(i, lines));
+      }
+    }
+    Ok(self)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn color_names_and_hex() {
+    assert_eq!(Color::parse("Red"), Some(Color::new(255, 0, 0)));
+    assert_eq!(Color::parse("#12aBff"), Some(Color::new(0x12, 0xab, 0xff)));
+    assert_eq!(Color::parse("#12abf"), None);
+    assert_eq!(Color::parse("#12abfg"), None);
+  }
+
+  #[test]
+  fn non_ascii_color_is_an_error() {
+    // "#aébbb" は7バイトだが、2バイト目の途中で切り出すとパニックする
+    assert_eq!(Color::parse("#aébbb"), None);
+    let error = Scene::from_toml_str(
+      "background = \"#aébbb\"\n",
+      Path::new("."),
+    )
+    .unwrap_err();
+    assert_eq!(error.path(), "background");
+    assert_eq!(error.line(), Some(1));
+  }
+
+  #[test]
+  fn oversized_image_is_an_error() {
+    let image = Shape::Image {
+      path: PathBuf::from("huge.raw"),
+      position: [0, 0],
+      width: 65536,
+      height: 65536,
+      data: Vec::new(),
+    };
+    let error = image.validate().unwrap_err();
+    assert!(error.message().contains("too large"), "{}", error);
+  }
+
+  // 1番目に円を置き、テストしたい図形を2番目 (9行目の `[[shapes]]` から) に置いたシーンを読み込む
+  fn second_shape(shape: &str) -> Result<Scene, SceneError> {
+    let source = format!(
+      "width = 320\nheight = 240\n\n[[shapes]]\ntype = \"circle\"\ncenter = [10, 10]\n\
+       radius = 5\nfill = \"red\"\n[[shapes]]\n{}",
+      shape
+    );
+    Scene::from_toml_str(&source, Path::new("."))
+  }
+
+  #[test]
+  fn unknown_key_is_located() {
+    let error = second_shape("type = \"text\"\ntext = \"a\"\nposition = [0, 0]\ncolour = \"red\"\n")
+      .unwrap_err();
+    assert_eq!(error.line(), Some(9));
+    assert_eq!(error.path(), "shapes[1]");
+    assert!(
+      error.to_string().starts_with("line 9: shapes[1]: unknown field `colour`"),
+      "{}",
+      error
+    );
+
+    let json = r#"{
+  "shapes": [
+    { "type": "circle", "center": [10, 10], "radius": 5, "fill": "red" },
+    { "type": "line", "start": [0, 0], "end": [1, 1], "stroke": { "colour": "red" } }
+  ]
+}"#;
+    let error = Scene::from_json_str(json, Path::new(".")).unwrap_err();
+    assert_eq!(error.line(), Some(4));
+    assert_eq!(error.path(), "shapes[1]");
+    assert!(error.message().starts_with("unknown field `colour`"), "{}", error);
+  }
+
+  #[test]
+  fn bad_coordinates_are_located() {
+    let error =
+      second_shape("type = \"circle\"\ncenter = [10, \"a\"]\nradius = 5\nfill = \"red\"\n")
+        .unwrap_err();
+    assert_eq!(error.line(), Some(9));
+    // `type` で図形を選ぶ enum は中身をまとめて読むので、位置は図形の単位になる
+    assert_eq!(error.path(), "shapes[1]");
+    assert!(error.message().contains("expected i32"), "{}", error);
+
+    let error = second_shape("type = \"circle\"\ncenter = [10]\nradius = 5\nfill = \"red\"\n")
+      .unwrap_err();
+    assert_eq!(error.line(), Some(9));
+    assert_eq!(error.path(), "shapes[1]");
+
+    let error = second_shape(
+      "type = \"rectangle\"\ntop_left = [10, 10]\nbottom_right = [5, 20]\nfill = \"red\"\n",
+    )
+    .unwrap_err();
+    assert_eq!(error.line(), Some(9));
+    assert_eq!(error.path(), "shapes[1].bottom_right");
+  }
+
+  #[test]
+  fn out_of_range_coordinates_are_located() {
+    let error = second_shape("type = \"circle\"\ncenter = [400, 10]\nradius = 5\nfill = \"red\"\n")
+      .unwrap_err();
+    assert_eq!(error.line(), Some(9));
+    assert_eq!(error.path(), "shapes[1]");
+    assert!(error.message().contains("outside the 320x240 canvas"), "{}", error);
+
+    // 半径を足すとi32からあふれる
+    let error = second_shape(
+      "type = \"circle\"\ncenter = [2147483647, 0]\nradius = 5\nfill = \"red\"\n",
+    )
+    .unwrap_err();
+    assert_eq!(error.line(), Some(9));
+    assert_eq!(error.path(), "shapes[1]");
+    assert!(error.message().contains("i32 coordinate range"), "{}", error);
+
+    // i32に収まらない半径
+    let error = second_shape(
+      "type = \"circle\"\ncenter = [0, 0]\nradius = 3000000000\nfill = \"red\"\n",
+    )
+    .unwrap_err();
+    assert_eq!(error.line(), Some(9));
+    assert!(
+      error.message().contains("[-3000000000, -3000000000] to [3000000000, 3000000000]"),
+      "{}",
+      error
+    );
+  }
+
+  #[test]
+  fn empty_text_is_allowed() {
+    let scene =
+      second_shape("type = \"text\"\ntext = \"\"\nposition = [0, 0]\ncolor = \"red\"\n").unwrap();
+    assert_eq!(scene.shapes.len(), 2);
+  }
+
+  #[test]
+  fn json_shapes_are_located_by_line() {
+    let json = r#"{
+  "background": "red",
+  "shapes": [
+    { "type": "text", "text": "[{\"", "position": [0, 0], "color": "red" },
+    {
+      "type": "circle", "center": [400, 10], "radius": 5, "fill": "red"
+    }
+  ]
+}"#;
+    assert_eq!(json_shape_lines(json), [4, 5]);
+    let error = Scene::from_json_str(json, Path::new(".")).unwrap_err();
+    assert_eq!(error.line(), Some(5));
+    assert_eq!(error.path(), "shapes[1]");
+  }
+}
@@ -0,0 +1,198 @@
+use super::{Color, Font, Scene, SceneError, Shape, Stroke};
+use embedded_graphics::{
+  fonts::{Font as _, *},
+  image::{Image, ImageRawLE},
+  pixelcolor::{Rgb565, Rgb888},
+  prelude::*,
+  primitives::*,
+  style::*,
+};
+use std::convert::TryFrom;
+use wio_font::{BitmapText, DEFAULT_FONT};
+
+impl From<Color> for Rgb565 {
+  fn from(color: Color) -> Self {
+    Rgb888::new(color.r, color.g, color.b).into()
+  }
+}
+
+fn point(p: [i32; 2]) -> Point {
+  Point::new(p[0], p[1])
+}
+
+fn style(stroke: &Option<Stroke>, fill: &Option<Color>) -> PrimitiveStyle<Rgb565> {
+  let mut builder = PrimitiveStyleBuilder::new();
+  if let Some(stroke) = stroke {
+    builder = builder.stroke_color(stroke.color.into()).stroke_width(stroke.width);
+  }
+  if let Some(fill) = fill {
+    builder = builder.fill_color((*fill).into());
+  }
+  builder.build()
+}
+
+fn draw_text<F, D>(
+  font: F,
+  text: &str,
+  position: Point,
+  color: Color,
+  background: Option<Color>,
+  display: &mut D,
+) -> Result<(), D::Error>
+where
+  F: embedded_graphics::fonts::Font + Copy,
+  D: DrawTarget<Rgb565>,
+{
+  let mut builder = TextStyleBuilder::new(font).text_color(color.into());
+  if let Some(background) = background {
+    builder = builder.background_color(background.into());
+  }
+  Text::new(text, position).into_styled(builder.build()).draw(display)
+}
+
+impl Font {
+  fn text_size(&self, text: &str) -> (i64, i64) {
+    let character_size = match self {
+      Font::Font6x8 => Font6x8::CHARACTER_SIZE,
+      Font::Font6x12 => Font6x12::CHARACTER_SIZE,
+      Font::Font8x16 => Font8x16::CHARACTER_SIZE,
+      Font::Font12x16 => Font12x16::CHARACTER_SIZE,
+      Font::Font24x32 => Font24x32::CHARACTER_SIZE,
+      Font::Bitmap => {
+        let lines = text.lines().count().max(1) as i64;
+        return (
+          i64::from(DEFAULT_FONT.text_width(text)),
+          i64::from(DEFAULT_FONT.line_height()) * lines,
+        );
+      }
+    };
+    let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0) as i64;
+    let lines = text.lines().count().max(1) as i64;
+    (
+      i64::from(character_size.width) * columns,
+      i64::from(character_size.height) * lines,
+    )
+  }
+}
+
+// 左上と右下の座標
+type Bounds = ([i32; 2], [i32; 2]);
+
+impl Shape {
+  // 図形が占める範囲。何も描かない図形 (空の文字列) は None
+  //
+  // 座標に半径や線の太さを足すとi32からあふれることがあるので、i64で計算してから
+  // i32に収まるかを確かめる
+  pub(super) fn bounding_box(&self) -> Result<Option<Bounds>, SceneError> {
+    let from_points = |points: &[[i32; 2]], margin: i64| {
+      let min_x = points.iter().map(|p| p[0]).min().unwrap();
+      let min_y = points.iter().map(|p| p[1]).min().unwrap();
+      let max_x = points.iter().map(|p| p[0]).max().unwrap();
+      let max_y = points.iter().map(|p| p[1]).max().unwrap();
+      (
+        [i64::from(min_x) - margin, i64::from(min_y) - margin],
+        [i64::from(max_x) + margin, i64::from(max_y) + margin],
+      )
+    };
+    let from_size = |position: [i32; 2], width: i64, height: i64| {
+      let [x, y] = [i64::from(position[0]), i64::from(position[1])];
+      ([x, y], [x + width - 1, y + height - 1])
+    };
+    let stroke_margin = |stroke: &Option<Stroke>| stroke.map(|s| i64::from(s.width)).unwrap_or(0);
+    let (top_left, bottom_right) = match self {
+      Shape::Line { start, end, stroke } => from_points(&[*start, *end], i64::from(stroke.width)),
+      Shape::Circle { center, radius, stroke, .. } => {
+        from_points(&[*center], i64::from(*radius) + stroke_margin(stroke))
+      }
+      Shape::Triangle { points, stroke, .. } => from_points(points, stroke_margin(stroke)),
+      Shape::Rectangle { top_left, bottom_right, stroke, .. } => {
+        from_points(&[*top_left, *bottom_right], stroke_margin(stroke))
+      }
+      Shape::Text { text, .. } if text.is_empty() => return Ok(None),
+      Shape::Text { text, position, font, .. } => {
+        let (width, height) = font.text_size(text);
+        from_size(*position, width, height)
+      }
+      Shape::Image { position, width, height, .. } => {
+        from_size(*position, i64::from(*width), i64::from(*height))
+      }
+    };
+    let to_i32 = |p: [i64; 2]| Some([i32::try_from(p[0]).ok()?, i32::try_from(p[1]).ok()?]);
+    match (to_i32(top_left), to_i32(bottom_right)) {
+      (Some(top_left), Some(bottom_right)) => Ok(Some((top_left, bottom_right))),
+      _ => Err(SceneError::new(
+        "",
+        format!(
+          "extends beyond the i32 coordinate range ({:?} to {:?})",
+          top_left, bottom_right
+        ),
+      )),
+    }
+  }
+
+  fn draw<D>(&self, display: &mut D) -> Result<(), D::Error>
+  where
+    D: DrawTarget<Rgb565>,
+  {
+    match self {
+      Shape::Line { start, end, stroke } => Line::new(point(*start), point(*end))
+        .into_styled(PrimitiveStyle::with_stroke(stroke.color.into(), stroke.width))
+        .draw(display),
+      Shape::Circle { center, radius, stroke, fill } => Circle::new(point(*center), *radius)
+        .into_styled(style(stroke, fill))
+        .draw(display),
+      Shape::Triangle { points, stroke, fill } => {
+        Triangle::new(point(points[0]), point(points[1]), point(points[2]))
+          .into_styled(style(stroke, fill))
+          .draw(display)
+      }
+      Shape::Rectangle { top_left, bottom_right, stroke, fill } => {
+        Rectangle::new(point(*top_left), point(*bottom_right))
+          .into_styled(style(stroke, fill))
+          .draw(display)
+      }
+      Shape::Text { text, position, color, font, background } => {
+        let position = point(*position);
+        match font {
+          Font::Font6x8 => draw_text(Font6x8, text, position, *color, *background, display),
+          Font::Font6x12 => draw_text(Font6x12, text, position, *color, *background, display),
+          Font::Font8x16 => draw_text(Font8x16, text, position, *color, *background, display),
+          Font::Font12x16 => draw_text(Font12x16, text, position, *color, *background, display),
+          Font::Font24x32 => draw_text(Font24x32, text, position, *color, *background, display),
+          Font::Bitmap => {
+            let mut bitmap_text = BitmapText::new(text, position, &DEFAULT_FONT, (*color).into());
+            if let Some(background) = background {
+              bitmap_text = bitmap_text.with_background((*background).into());
+            }
+            bitmap_text.draw(display)
+          }
+        }
+      }
+      Shape::Image { position, width, height, data, .. } => {
+        let raw = ImageRawLE::<Rgb565>::new(data, *width, *height);
+        Image::new(&raw, point(*position)).draw(display)
+      }
+    }
+  }
+}
+
+impl Scene {
+  /// シーン全体を描画します
+  pub fn draw<D>(&self, display: &mut D) -> Result<(), D::Error>
+  where
+    D: DrawTarget<Rgb565>,
+  {
+    if let Some(background) = self.background {
+      Rectangle::new(
+        Point::new(0, 0),
+        Point::new(self.width as i32 - 1, self.height as i32 - 1),
+      )
+      .into_styled(PrimitiveStyle::with_fill(background.into()))
+      .draw(display)?;
+    }
+    for shape in self.shapes.iter() {
+      shape.draw(display)?;
+    }
+    Ok(())
+  }
+}
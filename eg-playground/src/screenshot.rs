@@ -0,0 +1,104 @@
+//! SDL のウィンドウを開かずに、描画結果を PNG ファイルへ書き出します。
+//! シミュレータの `with-sdl` 機能を使わないので、SDL2 がない環境でも動きます。
+//!
+//! シミュレータの `OutputSettings` (拡大率、ピクセル間隔、テーマ) をそのまま使うので、
+//! `Window` に表示したときと同じ見た目の画像になります。
+
+use crate::scene::Scene;
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use embedded_graphics_simulator::{OutputSettings, SimulatorDisplay};
+use std::{convert::Infallible, fmt, path::Path};
+
+#[derive(Debug)]
+pub struct ScreenshotError {
+  message: String,
+}
+
+impl fmt::Display for ScreenshotError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.message)
+  }
+}
+
+impl std::error::Error for ScreenshotError {}
+
+/// ディスプレイの内容を PNG として保存します
+pub fn save_png(
+  display: &SimulatorDisplay<Rgb565>,
+  output_settings: &OutputSettings,
+  path: &Path,
+) -> Result<(), ScreenshotError> {
+  display
+    .to_image_buffer(output_settings)
+    .save(path)
+    .map_err(|e| ScreenshotError {
+      message: format!("{}: {}", path.display(), e),
+    })
+}
+
+/// 新しいディスプレイに `draw` で描画し、PNG として保存します
+pub fn render_png<F>(
+  size: Size,
+  output_settings: &OutputSettings,
+  path: &Path,
+  draw: F,
+) -> Result<(), ScreenshotError>
+where
+  F: FnOnce(&mut SimulatorDisplay<Rgb565>) -> Result<(), Infallible>,
+{
+  let mut display = SimulatorDisplay::new(size);
+  // SimulatorDisplay への描画は失敗しない
+  if let Err(e) = draw(&mut display) {
+    match e {}
+  }
+  save_png(&display, output_settings, path)
+}
+
+/// `WioSplash` などの `Drawable` を PNG として保存します
+pub fn drawable_to_png<T>(
+  drawable: T,
+  size: Size,
+  output_settings: &OutputSettings,
+  path: &Path,
+) -> Result<(), ScreenshotError>
+where
+  T: Drawable<Rgb565>,
+{
+  render_png(size, output_settings, path, |display| drawable.draw(display))
+}
+
+/// シーンを PNG として保存します。画像サイズはシーンの `width`/`height` です
+pub fn scene_to_png(
+  scene: &Scene,
+  output_settings: &OutputSettings,
+  path: &Path,
+) -> Result<(), ScreenshotError> {
+  let size = Size::new(scene.width, scene.height);
+  render_png(size, output_settings, path, |display| scene.draw(display))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use embedded_graphics_simulator::OutputSettingsBuilder;
+  use std::{env, fs, process};
+
+  // PNG の IHDR チャンクから画像の幅と高さを読む
+  fn png_size(path: &Path) -> (u32, u32) {
+    let data = fs::read(path).unwrap();
+    assert_eq!(&data[12..16], b"IHDR");
+    let read_u32 = |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+    (read_u32(16), read_u32(20))
+  }
+
+  #[test]
+  fn png_size_respects_scale_and_pixel_spacing() {
+    let path = env::temp_dir().join(format!("eg-playground-{}.png", process::id()));
+    let output_settings = OutputSettingsBuilder::new().scale(2).pixel_spacing(1).build();
+    render_png(Size::new(320, 240), &output_settings, &path, |_| Ok(())).unwrap();
+    let size = png_size(&path);
+    fs::remove_file(&path).unwrap();
+    // 320 * 2 + 319 * 1 = 959, 240 * 2 + 239 * 1 = 719
+    assert_eq!(size, (959, 719));
+  }
+}
@@ -0,0 +1,75 @@
+// www/index.html から呼び出すブラウザ向けのデモです
+// `wasm-pack build --target web -- --features demo` でビルドします
+
+use crate::{OutputSettingsBuilder, WebSimulatorDisplay};
+use boid::{BoidRenderer, Boids};
+use embedded_graphics::{
+    image::ImageRawLE, pixelcolor::Rgb565, prelude::*, primitives::Rectangle,
+    style::PrimitiveStyle,
+};
+use wasm_bindgen::prelude::*;
+use wio_splash::WioSplash;
+
+const SCREEN_WIDTH: u32 = 320;
+const SCREEN_HEIGHT: u32 = 240;
+
+fn clear_screen(display: &mut WebSimulatorDisplay) {
+    Rectangle::new(
+        Point::new(0, 0),
+        Point::new(SCREEN_WIDTH as i32 - 1, SCREEN_HEIGHT as i32 - 1),
+    )
+    .into_styled(PrimitiveStyle::with_fill(boid::BG_COLOR))
+    .draw(display)
+    .unwrap();
+}
+
+/// スプラッシュ画面を描画します
+#[wasm_bindgen]
+pub fn draw_splash(canvas_id: &str, scale: u32) -> Result<(), JsValue> {
+    let output_settings = OutputSettingsBuilder::new().scale(scale).build();
+    let mut display = WebSimulatorDisplay::from_element_id(
+        canvas_id,
+        Size::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+        &output_settings,
+    )?;
+    let raw = ImageRawLE::new(include_bytes!("../assets/ferris.raw"), 86, 64);
+    let splash = WioSplash::new(Rgb565::GREEN, raw);
+    splash.draw(&mut display).unwrap();
+    display.flush()
+}
+
+/// ボイドの群れのアニメーション。`requestAnimationFrame` から `tick` を呼び出します
+#[wasm_bindgen]
+pub struct BoidDemo {
+    boids: Boids,
+    renderer: BoidRenderer,
+    display: WebSimulatorDisplay,
+}
+
+#[wasm_bindgen]
+impl BoidDemo {
+    #[wasm_bindgen(constructor)]
+    pub fn new(canvas_id: &str, scale: u32) -> Result<BoidDemo, JsValue> {
+        let output_settings = OutputSettingsBuilder::new().scale(scale).build();
+        let mut display = WebSimulatorDisplay::from_element_id(
+            canvas_id,
+            Size::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+            &output_settings,
+        )?;
+        clear_screen(&mut display);
+        let mut boids = Boids::new();
+        boids.init();
+        Ok(BoidDemo {
+            boids,
+            renderer: BoidRenderer::new(),
+            display,
+        })
+    }
+
+    pub fn tick(&mut self) -> Result<(), JsValue> {
+        self.boids.update();
+        self.renderer.clear(&mut self.display).unwrap();
+        self.renderer.draw(&mut self.display, &self.boids).unwrap();
+        self.display.flush()
+    }
+}
@@ -0,0 +1,150 @@
+use crate::output_settings::OutputSettings;
+use core::convert::Infallible;
+use embedded_graphics::{
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::*,
+    DrawTarget,
+};
+use wasm_bindgen::{Clamped, JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+
+/// canvas を描画先にするディスプレイ
+///
+/// 描画はRGBAのバッファに対して行い、`flush` で canvas に転送します。
+pub struct WebSimulatorDisplay {
+    size: Size,
+    output_settings: OutputSettings,
+    canvas_size: Size,
+    context: Option<CanvasRenderingContext2d>,
+    buffer: Vec<u8>,
+}
+
+impl WebSimulatorDisplay {
+    /// canvas に結び付けないディスプレイを作ります
+    ///
+    /// `flush` は何もしません。DOM のない環境 (Node でのテストなど) で、
+    /// 描画した結果を `get_pixel` で確かめるときに使います。
+    pub fn offscreen(size: Size, output_settings: &OutputSettings) -> Self {
+        let canvas_size = output_settings.canvas_size(size);
+        let mut buffer = vec![0; (canvas_size.width * canvas_size.height * 4) as usize];
+        // 黒、不透明で初期化する
+        for alpha in buffer.iter_mut().skip(3).step_by(4) {
+            *alpha = 0xff;
+        }
+        Self {
+            size,
+            output_settings: *output_settings,
+            canvas_size,
+            context: None,
+            buffer,
+        }
+    }
+
+    /// canvas 要素を描画先にします。canvas の大きさは出力設定に合わせて変更されます
+    pub fn new(
+        size: Size,
+        output_settings: &OutputSettings,
+        canvas: &HtmlCanvasElement,
+    ) -> Result<Self, JsValue> {
+        let mut display = Self::offscreen(size, output_settings);
+        canvas.set_width(display.canvas_size.width);
+        canvas.set_height(display.canvas_size.height);
+        let context = canvas
+            .get_context("2d")?
+            .ok_or_else(|| JsValue::from_str("2d context is not available"))?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+        display.context = Some(context);
+        Ok(display)
+    }
+
+    /// `id` で指定した canvas 要素を描画先にします
+    pub fn from_element_id(
+        id: &str,
+        size: Size,
+        output_settings: &OutputSettings,
+    ) -> Result<Self, JsValue> {
+        let canvas = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.get_element_by_id(id))
+            .ok_or_else(|| JsValue::from_str(&format!("element `{}` not found", id)))?
+            .dyn_into::<HtmlCanvasElement>()?;
+        Self::new(size, output_settings, &canvas)
+    }
+
+    /// バッファの内容を canvas に転送します
+    pub fn flush(&self) -> Result<(), JsValue> {
+        let context = match &self.context {
+            Some(context) => context,
+            None => return Ok(()),
+        };
+        // 0x0 の ImageData は作れないので、転送するものがないときは何もしない
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let image = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&self.buffer),
+            self.canvas_size.width,
+            self.canvas_size.height,
+        )?;
+        context.put_image_data(&image, 0.0, 0.0)
+    }
+
+    /// 拡大とピクセルの間隔を含めた canvas の大きさ
+    pub fn canvas_size(&self) -> Size {
+        self.canvas_size
+    }
+
+    /// 座標のピクセルの色を返します。範囲外の場合は `None` です
+    pub fn get_pixel(&self, point: Point) -> Option<Rgb565> {
+        let offset = self.offset(point)?;
+        let color = Rgb888::new(
+            self.buffer[offset],
+            self.buffer[offset + 1],
+            self.buffer[offset + 2],
+        );
+        Some(color.into())
+    }
+
+    // 拡大後のピクセルの左上にあたるバッファ上の位置
+    fn offset(&self, point: Point) -> Option<usize> {
+        if point.x < 0
+            || point.y < 0
+            || point.x as u32 >= self.size.width
+            || point.y as u32 >= self.size.height
+        {
+            return None;
+        }
+        let pitch = self.output_settings.pixel_pitch();
+        let x = point.x as u32 * pitch;
+        let y = point.y as u32 * pitch;
+        Some(((y * self.canvas_size.width + x) * 4) as usize)
+    }
+}
+
+impl DrawTarget<Rgb565> for WebSimulatorDisplay {
+    type Error = Infallible;
+
+    fn draw_pixel(&mut self, pixel: Pixel<Rgb565>) -> Result<(), Self::Error> {
+        let Pixel(point, color) = pixel;
+        let offset = match self.offset(point) {
+            Some(offset) => offset,
+            None => return Ok(()),
+        };
+        let color = Rgb888::from(color);
+        let rgba = [color.r(), color.g(), color.b(), 0xff];
+        let stride = (self.canvas_size.width * 4) as usize;
+        let scale = self.output_settings.scale as usize;
+        for dy in 0..scale {
+            let row = offset + dy * stride;
+            for dx in 0..scale {
+                let i = row + dx * 4;
+                self.buffer[i..i + 4].copy_from_slice(&rgba);
+            }
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size
+    }
+}
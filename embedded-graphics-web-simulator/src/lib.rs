@@ -0,0 +1,14 @@
+//! HTML の canvas に描画する embedded-graphics の `DrawTarget` です。
+//!
+//! `WebSimulatorDisplay` に描画した内容は、`flush` を呼んだときにまとめて canvas へ転送されます。
+
+mod display;
+mod output_settings;
+
+#[cfg(feature = "demo")]
+mod demo;
+
+pub use crate::{
+    display::WebSimulatorDisplay,
+    output_settings::{OutputSettings, OutputSettingsBuilder},
+};
@@ -0,0 +1,73 @@
+use embedded_graphics::prelude::*;
+
+/// canvas への出力設定
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputSettings {
+    /// 1ピクセルを何倍に拡大して描画するか
+    pub scale: u32,
+    /// 拡大したピクセル同士の間隔
+    pub pixel_spacing: u32,
+}
+
+impl OutputSettings {
+    // 表示サイズに対応するcanvasのサイズ
+    // 最後のピクセルの後ろには間隔を空けない (0x0 のときは 0x0 にする)
+    pub(crate) fn canvas_size(&self, size: Size) -> Size {
+        let pitch = self.pixel_pitch();
+        Size::new(
+            (size.width * pitch).saturating_sub(self.pixel_spacing),
+            (size.height * pitch).saturating_sub(self.pixel_spacing),
+        )
+    }
+
+    pub(crate) fn pixel_pitch(&self) -> u32 {
+        self.scale + self.pixel_spacing
+    }
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        OutputSettingsBuilder::new().build()
+    }
+}
+
+/// `OutputSettings` のビルダー。embedded-graphics-simulator と同じ使い方です
+pub struct OutputSettingsBuilder {
+    scale: Option<u32>,
+    pixel_spacing: Option<u32>,
+}
+
+impl OutputSettingsBuilder {
+    pub fn new() -> Self {
+        Self {
+            scale: None,
+            pixel_spacing: None,
+        }
+    }
+
+    pub fn scale(mut self, scale: u32) -> Self {
+        if scale == 0 {
+            panic!("scale must be > 0");
+        }
+        self.scale = Some(scale);
+        self
+    }
+
+    pub fn pixel_spacing(mut self, pixel_spacing: u32) -> Self {
+        self.pixel_spacing = Some(pixel_spacing);
+        self
+    }
+
+    pub fn build(self) -> OutputSettings {
+        OutputSettings {
+            scale: self.scale.unwrap_or(1),
+            pixel_spacing: self.pixel_spacing.unwrap_or(0),
+        }
+    }
+}
+
+impl Default for OutputSettingsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
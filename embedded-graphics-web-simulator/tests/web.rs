@@ -0,0 +1,66 @@
+//! Node で動かすテストです。DOM がないので、canvas に結び付けないディスプレイで確かめます。
+//!
+//! ```sh
+//! $ wasm-pack test --node
+//! ```
+
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use embedded_graphics_web_simulator::{OutputSettingsBuilder, WebSimulatorDisplay};
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+fn canvas_size_includes_scale_and_spacing() {
+    let settings = OutputSettingsBuilder::new()
+        .scale(2)
+        .pixel_spacing(1)
+        .build();
+    let display = WebSimulatorDisplay::offscreen(Size::new(4, 3), &settings);
+    assert_eq!(display.size(), Size::new(4, 3));
+    // 最後のピクセルの後ろには間隔を空けない
+    assert_eq!(display.canvas_size(), Size::new(4 * 3 - 1, 3 * 3 - 1));
+}
+
+#[wasm_bindgen_test]
+fn zero_sized_display_does_not_panic() {
+    let settings = OutputSettingsBuilder::new()
+        .scale(2)
+        .pixel_spacing(1)
+        .build();
+    let mut display = WebSimulatorDisplay::offscreen(Size::zero(), &settings);
+    assert_eq!(display.canvas_size(), Size::zero());
+    display.draw_pixel(Pixel(Point::zero(), Rgb565::WHITE)).unwrap();
+    assert_eq!(display.get_pixel(Point::zero()), None);
+    display.flush().unwrap();
+}
+
+#[wasm_bindgen_test]
+fn starts_black() {
+    let settings = OutputSettingsBuilder::new().build();
+    let display = WebSimulatorDisplay::offscreen(Size::new(2, 2), &settings);
+    assert_eq!(display.get_pixel(Point::new(1, 1)), Some(Rgb565::BLACK));
+}
+
+#[wasm_bindgen_test]
+fn draw_pixel_round_trips() {
+    let settings = OutputSettingsBuilder::new()
+        .scale(3)
+        .pixel_spacing(2)
+        .build();
+    let mut display = WebSimulatorDisplay::offscreen(Size::new(4, 4), &settings);
+    display.draw_pixel(Pixel(Point::new(2, 1), Rgb565::RED)).unwrap();
+    display.draw_pixel(Pixel(Point::new(3, 3), Rgb565::CYAN)).unwrap();
+    assert_eq!(display.get_pixel(Point::new(2, 1)), Some(Rgb565::RED));
+    assert_eq!(display.get_pixel(Point::new(3, 3)), Some(Rgb565::CYAN));
+    assert_eq!(display.get_pixel(Point::new(1, 2)), Some(Rgb565::BLACK));
+}
+
+#[wasm_bindgen_test]
+fn out_of_range_pixels_are_ignored() {
+    let settings = OutputSettingsBuilder::new().build();
+    let mut display = WebSimulatorDisplay::offscreen(Size::new(2, 2), &settings);
+    display.draw_pixel(Pixel(Point::new(2, 0), Rgb565::WHITE)).unwrap();
+    display.draw_pixel(Pixel(Point::new(-1, 0), Rgb565::WHITE)).unwrap();
+    assert_eq!(display.get_pixel(Point::new(2, 0)), None);
+    assert_eq!(display.get_pixel(Point::new(-1, 0)), None);
+    assert_eq!(display.get_pixel(Point::new(1, 0)), Some(Rgb565::BLACK));
+}
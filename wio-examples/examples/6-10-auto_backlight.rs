@@ -0,0 +1,187 @@
+//! 6-10 光センサで画面のバックライトを自動調整するサンプルコードです。
+//! 周囲が明るいときは画面を明るく、暗いときは暗くします。
+//! 光センサの値と明るさを画面に表示します。
+//!
+//! - ボタン1 (一番右のボタン): 手動で明るくする
+//! - ボタン2: 手動で暗くする
+//! - ボタン3 (一番左のボタン): 自動に戻す
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example 6-10-auto_backlight --features backlight
+//! ```
+
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+use wio_terminal as wio;
+
+use core::fmt::Write;
+use eg::{
+    egrectangle, egtext, fonts::Font12x16, pixelcolor::Rgb565, prelude::*, primitive_style,
+    text_style,
+};
+use embedded_graphics as eg;
+use heapless::consts::*;
+use heapless::String;
+use wio::entry;
+use wio::hal::clock::GenericClockController;
+use wio::hal::delay::Delay;
+use wio::hal::time::Hertz;
+use wio::hal::timer::TimerCounter;
+use wio::pac::{interrupt, CorePeripherals, Peripherals};
+use wio::prelude::*;
+use wio::Pins;
+use wio_backlight::{AutoBrightness, Curve, Mode};
+use wio_examples::backlight::Backlight;
+use wio_examples::clock::CycleClock;
+use wio_examples::{Button, ButtonEvent};
+
+static BACKLIGHT: Backlight = Backlight::new();
+
+const READ_INTERVAL_MS: u32 = 100; // 光センサを読む間隔
+const MANUAL_STEP: u8 = 16; // 手動で1回に変える明るさ
+
+// 光センサの値と明るさ、モードを描画する
+fn draw<T>(display: &mut T, light: f32, level: u8, mode: Mode) -> Result<(), T::Error>
+where
+    T: DrawTarget<Rgb565>,
+{
+    egrectangle!(
+        top_left = (0, 80),
+        bottom_right = (319, 159),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(display)?;
+
+    let mut text = String::<U32>::new();
+    write!(text, "LIGHT: {}", light as u32).ok();
+    egtext!(
+        text = text.as_str(),
+        top_left = (40, 88),
+        style = text_style!(font = Font12x16, text_color = Rgb565::WHITE)
+    )
+    .draw(display)?;
+
+    text.clear();
+    let mode = match mode {
+        Mode::Auto => "AUTO",
+        Mode::Manual(_) => "MANUAL",
+    };
+    write!(text, "LEVEL: {} ({})", level, mode).ok();
+    egtext!(
+        text = text.as_str(),
+        top_left = (40, 120),
+        style = text_style!(font = Font12x16, text_color = Rgb565::WHITE)
+    )
+    .draw(display)?;
+    Ok(())
+}
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let mut core = CorePeripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+    let core_frequency: Hertz = clocks.gclk0().into();
+    let mut clock = CycleClock::new(&mut core.DCB, &mut core.DWT, core_frequency);
+
+    let mut sets = Pins::new(peripherals.PORT).split();
+    let mut delay = Delay::new(core.SYST, &mut clocks);
+
+    let (mut light, mut pd1) = sets.light_sensor.init(
+        peripherals.ADC1,
+        &mut clocks,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+
+    let (mut display, backlight) = sets
+        .display
+        .init(
+            &mut clocks,
+            peripherals.SERCOM7,
+            &mut peripherals.MCLK,
+            &mut sets.port,
+            58.mhz(),
+            &mut delay,
+        )
+        .unwrap();
+    egrectangle!(
+        top_left = (0, 0),
+        bottom_right = (319, 239),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(&mut display)
+    .unwrap();
+
+    // 2MHzのクロックでTC5を動かし、バックライトのピンをPWMで切り替える
+    let gclk5 = clocks
+        .get_gclk(wio::pac::gclk::pchctrl::GEN_A::GCLK5)
+        .unwrap();
+    let timer_clock = clocks.tc4_tc5(&gclk5).unwrap();
+    let tc5 = TimerCounter::tc5_(&timer_clock, peripherals.TC5, &mut peripherals.MCLK);
+    BACKLIGHT.start(backlight, tc5);
+
+    let port = &mut sets.port;
+    let mut button_up = Button::new(sets.buttons.button1.into_floating_input(port));
+    let mut button_down = Button::new(sets.buttons.button2.into_floating_input(port));
+    let mut button_auto = Button::new(sets.buttons.button3.into_floating_input(port));
+
+    // 折れ線を変えるときは Curve::new に (光センサの値, 明るさ) の点を並べて渡す
+    let mut brightness = AutoBrightness::new(Curve::default());
+    let mut read_at = clock.now_ms();
+    loop {
+        let now = clock.now_ms();
+        button_up.update(now);
+        button_down.update(now);
+        button_auto.update(now);
+
+        // 手動の明るさはいまの明るさから変え始める
+        let mut manual_step = 0;
+        if let Some(ButtonEvent::Pressed) | Some(ButtonEvent::Repeat) = button_up.next_event() {
+            manual_step = MANUAL_STEP as i16;
+        }
+        if let Some(ButtonEvent::Pressed) | Some(ButtonEvent::Repeat) = button_down.next_event() {
+            manual_step = -(MANUAL_STEP as i16);
+        }
+        let mut changed = false;
+        if manual_step != 0 {
+            let level = (brightness.level() as i16 + manual_step).max(0).min(255) as u8;
+            BACKLIGHT.set_level(brightness.set_mode(Mode::Manual(level)));
+            changed = true;
+        }
+        if button_auto.next_event() == Some(ButtonEvent::Pressed) {
+            BACKLIGHT.set_level(brightness.set_mode(Mode::Auto));
+            changed = true;
+        }
+
+        if now.wrapping_sub(read_at) >= READ_INTERVAL_MS {
+            read_at = now;
+            let value: Result<u16, ()> = nb::block!(light.read(&mut pd1));
+            if let Ok(value) = value {
+                if let Some(level) = brightness.update(value, now) {
+                    BACKLIGHT.set_level(level);
+                }
+                changed = true;
+            }
+        }
+
+        if changed {
+            let smoothed = brightness.light().unwrap_or(0.0);
+            draw(&mut display, smoothed, brightness.level(), brightness.mode()).unwrap();
+        }
+    }
+}
+
+#[interrupt]
+fn TC5() {
+    BACKLIGHT.on_interrupt();
+}
@@ -17,13 +17,14 @@ use wio::entry;
 use wio::hal::gpio::*;
 use wio::pac::Peripherals;
 use wio::prelude::*; // 主要な構造体やトレイトをインポートする
+use wio_examples::user_led;
 
 #[entry]
 fn main() -> ! {
     let peripherals = Peripherals::take().unwrap();
     let mut pins = wio::Pins::new(peripherals.PORT);
 
-    let mut led = Led::new(pins.user_led, &mut pins.port);
+    let mut led = user_led(pins.user_led, &mut pins.port);
     let mut button1 = Button1::new(pins.button1, &mut pins.port);
 
     loop {
@@ -52,27 +53,3 @@ impl Button1 {
         self.pin.is_high().unwrap()
     }
 }
-
-struct Led {
-    pin: Pa15<Output<PushPull>>
-}
-
-impl Led {
-    fn new(pin: Pa15<Input<Floating>>, port: &mut Port) -> Led {
-        Led {
-            pin: pin.into_push_pull_output(port)
-        }
-    }
-
-    fn turn_on(&mut self) {
-        self.pin.set_high().unwrap();
-    }
-
-    fn turn_off(&mut self) {
-        self.pin.set_low().unwrap();
-    }
-
-    fn toggle(&mut self) {
-        self.pin.toggle();
-    }
-}
\ No newline at end of file
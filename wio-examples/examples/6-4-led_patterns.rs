@@ -0,0 +1,125 @@
+//! LEDの点滅パターンのサンプルコードです。
+//! TC3の割り込みで10[ms]ごとにパターンを進めます。
+//! ボタン1 (一番右のボタン) を押すたびにパターンが切り替わり、
+//! ボタン3 (一番左のボタン) を押すとパニックしてSOSを送り続けます。
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example 6-4-led_patterns
+//! ```
+
+#![no_std]
+#![no_main]
+
+use wio_terminal as wio;
+
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::peripheral::NVIC;
+use wio::hal::clock::GenericClockController;
+use wio::hal::time::Hertz;
+use wio::hal::timer::TimerCounter;
+use wio::pac::{interrupt, CorePeripherals, Peripherals, TC3};
+use wio::prelude::*;
+use wio::{entry, Pins, Sets};
+use wio_examples::clock::CycleClock;
+use wio_examples::led::{self, Pattern};
+use wio_examples::shared::Shared;
+use wio_examples::{user_led, Button, ButtonEvent, UserLed};
+
+const TICK_MS: u32 = 10;
+
+const PATTERNS: [Pattern; 5] = [
+    Pattern::Blink {
+        on_ms: 500,
+        off_ms: 500,
+    },
+    Pattern::Blink {
+        on_ms: 100,
+        off_ms: 100,
+    },
+    Pattern::Heartbeat,
+    Pattern::Morse {
+        text: "HELLO WIO",
+        unit_ms: 120,
+    },
+    Pattern::Off,
+];
+
+// main()関数と割り込みハンドラとで共有するリソース
+struct Ctx {
+    led: UserLed,
+    tc3: TimerCounter<TC3>,
+}
+static CTX: Shared<Ctx> = Shared::new();
+// パニックハンドラがSOSの長さを数えるのに使うCPUクロック [Hz]
+static CORE_FREQUENCY: AtomicU32 = AtomicU32::new(0);
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let mut core = CorePeripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+    let core_frequency: Hertz = clocks.gclk0().into();
+    CORE_FREQUENCY.store(core_frequency.0, Ordering::SeqCst);
+    let mut clock = CycleClock::new(&mut core.DCB, &mut core.DWT, core_frequency);
+
+    let mut sets: Sets = Pins::new(peripherals.PORT).split();
+    let mut button1 = Button::new(sets.buttons.button1.into_floating_input(&mut sets.port));
+    let mut button3 = Button::new(sets.buttons.button3.into_floating_input(&mut sets.port));
+
+    let mut led = user_led(sets.user_led, &mut sets.port);
+    let mut current = 0;
+    led.set_pattern(PATTERNS[current]);
+
+    // 2MHzのクロックでTC3を動かす
+    let gclk5 = clocks
+        .get_gclk(wio::pac::gclk::pchctrl::GEN_A::GCLK5)
+        .unwrap();
+    let timer_clock = clocks.tc2_tc3(&gclk5).unwrap();
+    let mut tc3 = TimerCounter::tc3_(&timer_clock, peripherals.TC3, &mut peripherals.MCLK);
+    unsafe {
+        NVIC::unmask(interrupt::TC3);
+    }
+    tc3.start((TICK_MS * 1000).us());
+    tc3.enable_interrupt();
+
+    CTX.init(Ctx { led, tc3 });
+
+    loop {
+        let now = clock.now_ms();
+        button1.update(now);
+        button3.update(now);
+
+        if button1.next_event() == Some(ButtonEvent::Pressed) {
+            current = (current + 1) % PATTERNS.len();
+            CTX.lock(|ctx| ctx.led.set_pattern(PATTERNS[current]));
+        }
+        if button3.next_event() == Some(ButtonEvent::Pressed) {
+            panic!("button3 pressed");
+        }
+    }
+}
+
+// TICK_MS周期で呼ばれ、LEDの点滅パターンを進める
+#[interrupt]
+fn TC3() {
+    CTX.lock(|ctx| {
+        ctx.tc3.wait().ok();
+        ctx.led.tick(TICK_MS);
+    });
+}
+
+#[inline(never)]
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    // 割り込みを止めて、ユーザーLEDでSOSを送り続ける
+    cortex_m::interrupt::disable();
+    led::sos_forever(Hertz(CORE_FREQUENCY.load(Ordering::SeqCst)))
+}
@@ -0,0 +1,110 @@
+//! ソフトウェアタイマのサンプルコードです。
+//! 1[ms]周期のTC4の割り込みだけを使って、複数のタイマを動かします。
+//! - 500[ms]ごとにユーザーLEDを点滅させる (フラグで通知)
+//! - 1秒ごとにシリアルターミナルへ経過秒数を出力する (フラグで通知)
+//! - 起動から3秒後に1回だけメッセージを出力する (コールバックで通知)
+//! - ボタン1 (一番右のボタン) を押すと、LEDの点滅を止めたり再開したりする
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example 6-4-soft_timers
+//! ```
+
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+use wio_terminal as wio;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use wio::hal::clock::GenericClockController;
+use wio::hal::timer::TimerCounter;
+use wio::pac::{interrupt, Peripherals};
+use wio::prelude::*;
+use wio::{entry, Pins, Sets};
+use wio_examples::soft_timer::{Action, TimerService};
+use wio_examples::{user_led, Button, ButtonEvent};
+
+const TICK_MS: u32 = 1;
+
+static TIMERS: TimerService = TimerService::new();
+static GREETING_DUE: AtomicBool = AtomicBool::new(false);
+
+// 割り込みハンドラの中で呼ばれるので、フラグを立てるだけにしておく
+fn on_greeting() {
+    GREETING_DUE.store(true, Ordering::SeqCst);
+}
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+
+    let mut sets: Sets = Pins::new(peripherals.PORT).split();
+    let mut serial = sets.uart.init(
+        &mut clocks,
+        115200.hz(),
+        peripherals.SERCOM2,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+    let mut led = user_led(sets.user_led, &mut sets.port);
+    let mut button1 = Button::new(sets.buttons.button1.into_floating_input(&mut sets.port));
+
+    // 2MHzのクロックでTC4を動かす
+    let gclk5 = clocks
+        .get_gclk(wio::pac::gclk::pchctrl::GEN_A::GCLK5)
+        .unwrap();
+    let timer_clock = clocks.tc4_tc5(&gclk5).unwrap();
+    let tc4 = TimerCounter::tc4_(&timer_clock, peripherals.TC4, &mut peripherals.MCLK);
+    TIMERS.start_hardware(tc4, (TICK_MS * 1000).us());
+
+    let mut blink = Some(TIMERS.start_periodic(500 / TICK_MS, Action::Flag).unwrap());
+    let second = TIMERS.start_periodic(1000 / TICK_MS, Action::Flag).unwrap();
+    TIMERS
+        .start_once(3000 / TICK_MS, Action::Callback(on_greeting))
+        .unwrap();
+
+    let mut seconds = 0;
+    loop {
+        // ボタンのチャタリング除去にはソフトウェアタイマのティックを時刻として使う
+        let now = TIMERS.with_wheel(|wheel| wheel.now()) * TICK_MS;
+        button1.update(now);
+        if button1.next_event() == Some(ButtonEvent::Pressed) {
+            blink = match blink.take() {
+                Some(id) => {
+                    TIMERS.cancel(id);
+                    led.turn_off();
+                    None
+                }
+                None => Some(TIMERS.start_periodic(500 / TICK_MS, Action::Flag).unwrap()),
+            };
+        }
+
+        if let Some(id) = blink {
+            if TIMERS.take_fired(id) {
+                led.toggle();
+            }
+        }
+        if TIMERS.take_fired(second) {
+            seconds += 1;
+            writeln!(&mut serial, "{} s", seconds).ok();
+        }
+        if GREETING_DUE.swap(false, Ordering::SeqCst) {
+            writeln!(&mut serial, "3 seconds have passed").ok();
+        }
+    }
+}
+
+// TICK_MS周期で呼ばれ、すべてのソフトウェアタイマを進める
+#[interrupt]
+fn TC4() {
+    TIMERS.on_interrupt();
+}
@@ -17,7 +17,7 @@ use wio::hal::delay::Delay;
 use wio::pac::{CorePeripherals, Peripherals};
 use wio::prelude::*;
 use wio::{entry, Pins, Sets};
-use wio_examples::Led;
+use wio_examples::user_led;
 
 #[entry]
 fn main() -> ! {
@@ -25,7 +25,7 @@ fn main() -> ! {
 
     // LEDドライバオブジェクトを初期化する
     let mut sets: Sets = Pins::new(peripherals.PORT).split();
-    let mut led = Led::new(sets.user_led, &mut sets.port);
+    let mut led = user_led(sets.user_led, &mut sets.port);
 
     // TODO: Delay構造体オブジェクトを取得する
     // Cortex-M共通のペリフェラルを取得する
@@ -19,14 +19,15 @@ use wio::hal::timer::TimerCounter;
 use wio::pac::{interrupt, Peripherals, TC3};
 use wio::prelude::*;
 use wio::{entry, Pins, Sets};
-use wio_examples::Led;
+use wio_examples::shared::Shared;
+use wio_examples::{user_led, UserLed};
 
 // main()関数と割り込みハンドラとで共有するリソース
 struct Ctx {
-    led: Led,
+    led: UserLed,
     tc3: TimerCounter<TC3>,
 }
-static mut CTX: Option<Ctx> = None;
+static CTX: Shared<Ctx> = Shared::new();
 
 #[entry]
 fn main() -> ! {
@@ -73,12 +74,10 @@ fn main() -> ! {
     tc3.enable_interrupt();
 
     // TODO: 割り込みハンドラと共有するリソースを格納する
-    unsafe {
-        CTX = Some(Ctx {
-            led: Led::new(sets.user_led, &mut sets.port),
-            tc3,
-        })
-    }
+    CTX.init(Ctx {
+        led: user_led(sets.user_led, &mut sets.port),
+        tc3,
+    });
 
     // TODO: シリアルターミナルにechoし続ける
     loop {
@@ -92,9 +91,8 @@ fn main() -> ! {
 #[interrupt]
 fn TC3() {
     // 割り込みハンドラで行う処理
-    unsafe {
-        let ctx = CTX.as_mut().unwrap();
+    CTX.lock(|ctx| {
         ctx.tc3.wait().unwrap();
         ctx.led.toggle();
-    }
+    });
 }
@@ -0,0 +1,124 @@
+//! ブザーでメロディを鳴らすサンプルコードです。
+//! TC3の割り込みで10[ms]ごとに再生を進めるので、メインループは止まりません。
+//! ボタン1 (一番右のボタン) でRTTTL、ボタン2 (真ん中のボタン) でMMLのメロディを鳴らし、
+//! ボタン3 (一番左のボタン) で止めます。再生中はユーザーLEDが点灯します。
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example 6-5-buzzer_melody
+//! ```
+
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+use wio_terminal as wio;
+
+use cortex_m::peripheral::NVIC;
+use wio::hal::clock::GenericClockController;
+use wio::hal::time::Hertz;
+use wio::hal::timer::TimerCounter;
+use wio::pac::{interrupt, CorePeripherals, Peripherals, TC3};
+use wio::prelude::*;
+use wio::{entry, Pins, Sets};
+use wio_examples::clock::CycleClock;
+use wio_examples::melody::{Buzzer, Melody, Player};
+use wio_examples::shared::Shared;
+use wio_examples::{user_led, Button, ButtonEvent};
+
+const TICK_MS: u32 = 10;
+
+const RTTTL: &str = "Scale:d=8,o=5,b=160:c,d,e,f,g,a,b,c6,4p,c6,b,a,g,f,e,d,4c";
+const MML: &str = "T140 O5 L8 E D+ E D+ E <B >D C <A4 R C E A B4 R E G+ B >C4";
+
+// main()関数と割り込みハンドラとで共有するリソース
+struct Ctx {
+    player: Player<Buzzer>,
+    tc3: TimerCounter<TC3>,
+}
+static CTX: Shared<Ctx> = Shared::new();
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let mut core = CorePeripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+    let core_frequency: Hertz = clocks.gclk0().into();
+    let mut clock = CycleClock::new(&mut core.DCB, &mut core.DWT, core_frequency);
+
+    let mut sets: Sets = Pins::new(peripherals.PORT).split();
+    let buzzer = sets.buzzer.init(
+        &mut clocks,
+        peripherals.TCC0,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+    let mut led = user_led(sets.user_led, &mut sets.port);
+    let mut button1 = Button::new(sets.buttons.button1.into_floating_input(&mut sets.port));
+    let mut button2 = Button::new(sets.buttons.button2.into_floating_input(&mut sets.port));
+    let mut button3 = Button::new(sets.buttons.button3.into_floating_input(&mut sets.port));
+
+    // 2MHzのクロックでTC3を動かす
+    let gclk5 = clocks
+        .get_gclk(wio::pac::gclk::pchctrl::GEN_A::GCLK5)
+        .unwrap();
+    let timer_clock = clocks.tc2_tc3(&gclk5).unwrap();
+    let mut tc3 = TimerCounter::tc3_(&timer_clock, peripherals.TC3, &mut peripherals.MCLK);
+    unsafe {
+        NVIC::unmask(interrupt::TC3);
+    }
+    tc3.start((TICK_MS * 1000).us());
+    tc3.enable_interrupt();
+
+    CTX.init(Ctx {
+        player: Player::new(Buzzer::new(buzzer)),
+        tc3,
+    });
+
+    loop {
+        let now = clock.now_ms();
+        button1.update(now);
+        button2.update(now);
+        button3.update(now);
+
+        let melody = if button1.next_event() == Some(ButtonEvent::Pressed) {
+            Melody::rtttl(RTTTL).ok()
+        } else if button2.next_event() == Some(ButtonEvent::Pressed) {
+            Some(Melody::mml(MML))
+        } else {
+            None
+        };
+        let stop = button3.next_event() == Some(ButtonEvent::Pressed);
+
+        let playing = CTX.lock(|ctx| {
+            let player = &mut ctx.player;
+            if let Some(melody) = melody {
+                player.play(melody);
+            }
+            if stop {
+                player.stop();
+            }
+            player.is_playing()
+        });
+        if playing {
+            led.turn_on();
+        } else {
+            led.turn_off();
+        }
+    }
+}
+
+// TICK_MS周期で呼ばれ、メロディの再生を進める
+#[interrupt]
+fn TC3() {
+    CTX.lock(|ctx| {
+        ctx.tc3.wait().ok();
+        ctx.player.tick(TICK_MS);
+    });
+}
@@ -9,7 +9,7 @@
 #![no_std]
 #![no_main]
 
-use wio_examples::Led;
+use wio_examples::user_led;
 use panic_halt as _;
 use wio_terminal as wio;
 
@@ -54,7 +54,7 @@ fn main() -> ! {
         &mut sets.port,
     );
 
-    let mut led = Led::new(sets.user_led, &mut sets.port);
+    let mut led = user_led(sets.user_led, &mut sets.port);
 
     loop {
         // TODO: ADC入力を1秒に1回取得して、UARTに出力する
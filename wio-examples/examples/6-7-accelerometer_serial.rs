@@ -1,9 +1,10 @@
 //! 6-7 加速度センサ/I2Cのサンプルコードです。
 //! 1秒ごとに加速度センサから値を読み出します。
+//! 6-9 で保存した較正値があれば、それをかけた値を出力します。
 //!
 //! ### 実行方法
 //! ```sh
-//! $ cargo hf2 --example 6-7-accelerometer_serial
+//! $ cargo hf2 --example 6-7-accelerometer_serial --features motion
 //! ```
 
 #![no_std]
@@ -19,6 +20,8 @@ use wio::hal::clock::GenericClockController;
 use wio::hal::delay::Delay;
 use wio::pac::{CorePeripherals, Peripherals};
 use wio::prelude::*;
+use wio_examples::accel_calibration;
+use wio_examples::storage::Storage;
 
 #[entry]
 fn main() -> ! {
@@ -45,16 +48,18 @@ fn main() -> ! {
         &mut sets.port,
     );
 
-    // 加速度センサドライバオブジェクトを初期化する
-    let mut accel = sets.accelerometer.init(
+    // 加速度センサドライバオブジェクトを初期化し、フラッシュに保存されている較正値をかける
+    let accel = sets.accelerometer.init(
         &mut clocks,
         peripherals.SERCOM4,
         &mut peripherals.MCLK,
         &mut sets.port,
     );
+    let storage = Storage::new(peripherals.NVMCTRL);
+    let mut accel = accel_calibration::calibrated(accel, &storage);
 
     // TODO: デバイスIDを取得、0x33が格納されている
-    let accel_id = accel.get_device_id().unwrap();
+    let accel_id = accel.inner().get_device_id().unwrap();
     writeln!(&mut serial, "Accelerometer ID: 0x{:X}", accel_id).unwrap();
 
     // TODO: 1秒ごとに加速度センサから読み取った値をシリアルに出力する
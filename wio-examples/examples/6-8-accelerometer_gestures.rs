@@ -0,0 +1,101 @@
+//! 6-8 加速度センサで傾きとジェスチャを検出するサンプルコードです。
+//! 画面の向きが変わったとき、タップ・ダブルタップ・シェイクを検出したときにシリアルに出力します。
+//! タップするとユーザーLEDが切り替わり、0.5秒ごとに傾き (ピッチ・ロール) も出力します。
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example 6-8-accelerometer_gestures --features motion
+//! ```
+
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+use wio_terminal as wio;
+
+use accelerometer::Accelerometer;
+use core::fmt::Write;
+use wio::entry;
+use wio::hal::clock::GenericClockController;
+use wio::hal::time::Hertz;
+use wio::pac::{CorePeripherals, Peripherals};
+use wio::prelude::*;
+use wio_examples::accel_calibration;
+use wio_examples::clock::CycleClock;
+use wio_examples::storage::Storage;
+use wio_examples::user_led;
+use wio_motion::{Motion, MotionEvent};
+
+const REPORT_INTERVAL_MS: u32 = 500; // 傾きを出力する間隔
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let mut core = CorePeripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+    let core_frequency: Hertz = clocks.gclk0().into();
+    let mut clock = CycleClock::new(&mut core.DCB, &mut core.DWT, core_frequency);
+
+    let mut sets = wio::Pins::new(peripherals.PORT).split();
+    let mut led = user_led(sets.user_led, &mut sets.port);
+
+    let mut serial = sets.uart.init(
+        &mut clocks,
+        115200.hz(),
+        peripherals.SERCOM2,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+
+    // 6-9 で保存した較正値があれば、それをかけた値を使う
+    let accel = sets.accelerometer.init(
+        &mut clocks,
+        peripherals.SERCOM4,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+    let storage = Storage::new(peripherals.NVMCTRL);
+    let mut accel = accel_calibration::calibrated(accel, &storage);
+
+    // しきい値を変えるときは MotionConfig を作って Motion::with_config に渡す
+    let mut motion = Motion::new();
+    let mut reported_at = 0;
+    loop {
+        let now = clock.now_ms();
+        // 読めなかったときは次の値を待つ
+        if let Ok(sample) = accel.accel_norm() {
+            motion.update(sample, now);
+        }
+
+        while let Some(event) = motion.next_event() {
+            match event {
+                MotionEvent::OrientationChanged(orientation) => {
+                    writeln!(&mut serial, "orientation: {}", orientation.name()).unwrap()
+                }
+                MotionEvent::Tap => {
+                    led.toggle();
+                    writeln!(&mut serial, "tap").unwrap();
+                }
+                MotionEvent::DoubleTap => writeln!(&mut serial, "double tap").unwrap(),
+                MotionEvent::Shake => writeln!(&mut serial, "shake").unwrap(),
+            }
+        }
+
+        if now.wrapping_sub(reported_at) >= REPORT_INTERVAL_MS {
+            let tilt = motion.tilt();
+            writeln!(
+                &mut serial,
+                "pitch: {:.1}, roll: {:.1}",
+                tilt.pitch, tilt.roll
+            )
+            .unwrap();
+            reported_at = now;
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! 6-9 加速度センサを較正するサンプルコードです。
+//! シリアルの指示に従って6つの向きに置き、ボタン1 (一番右のボタン) を押すと測定します。
+//! 6つの向きを測り終えると、軸ごとのオフセットと感度を求めてフラッシュに保存します。
+//! 保存した較正値は、リセットした後も `accel_calibration::calibrated` で読み出して使えます。
+//! ボタン3 (一番左のボタン) を押すと、保存した較正値を消去して最初からやり直します。
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example 6-9-accelerometer_calibration --features motion
+//! ```
+
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+use wio_terminal as wio;
+
+use accelerometer::Accelerometer;
+use core::fmt::Write;
+use wio::entry;
+use wio::hal::clock::GenericClockController;
+use wio::hal::time::Hertz;
+use wio::pac::{CorePeripherals, Peripherals};
+use wio::prelude::*;
+use wio_examples::accel_calibration;
+use wio_examples::clock::CycleClock;
+use wio_examples::storage::Storage;
+use wio_examples::{user_led, Button, ButtonEvent};
+use wio_motion::{Calibrator, PositionError};
+
+const SAMPLES_PER_POSITION: u32 = 200; // 1つの向きで平均をとるサンプル数
+const SETTLE_MS: u32 = 500; // ボタンを押してから揺れが収まるまで待つ時間
+// これより当てはまりが悪いときは保存しない [G]
+const MAX_RESIDUAL: f32 = 0.05;
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let mut core = CorePeripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+    let core_frequency: Hertz = clocks.gclk0().into();
+    let mut clock = CycleClock::new(&mut core.DCB, &mut core.DWT, core_frequency);
+
+    let mut sets = wio::Pins::new(peripherals.PORT).split();
+    let mut led = user_led(sets.user_led, &mut sets.port);
+    let mut button_measure = Button::new(sets.buttons.button1.into_floating_input(&mut sets.port));
+    let mut button_erase = Button::new(sets.buttons.button3.into_floating_input(&mut sets.port));
+
+    let mut serial = sets.uart.init(
+        &mut clocks,
+        115200.hz(),
+        peripherals.SERCOM2,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+
+    // 較正前の値を測るので、ここでは較正値をかけない
+    let mut accel = sets.accelerometer.init(
+        &mut clocks,
+        peripherals.SERCOM4,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+    let mut storage = Storage::new(peripherals.NVMCTRL);
+
+    match accel_calibration::load(&storage) {
+        Ok(calibration) => writeln!(&mut serial, "stored: {:?}", calibration).unwrap(),
+        Err(error) => writeln!(&mut serial, "stored: none ({:?})", error).unwrap(),
+    }
+
+    let mut calibrator = Calibrator::new(SAMPLES_PER_POSITION);
+    // 測定を始める時刻 (ボタンが押されるまでは None)
+    let mut measure_at: Option<u32> = None;
+    let mut prompted = false;
+    loop {
+        let now = clock.now_ms();
+        button_measure.update(now);
+        button_erase.update(now);
+
+        if button_erase.next_event() == Some(ButtonEvent::Pressed) {
+            match storage.erase() {
+                Ok(()) => writeln!(&mut serial, "erased").unwrap(),
+                Err(error) => writeln!(&mut serial, "erase failed: {:?}", error).unwrap(),
+            }
+            calibrator.reset();
+            measure_at = None;
+            prompted = false;
+        }
+
+        let position = match calibrator.next_position() {
+            Some(position) => position,
+            None => continue,
+        };
+        if !prompted {
+            writeln!(
+                &mut serial,
+                "[{}/6] place with {} and press button1",
+                calibrator.completed() + 1,
+                position.name()
+            )
+            .unwrap();
+            prompted = true;
+        }
+
+        match measure_at {
+            None => {
+                if button_measure.next_event() == Some(ButtonEvent::Pressed) {
+                    measure_at = Some(now.wrapping_add(SETTLE_MS));
+                    led.turn_on();
+                }
+                continue;
+            }
+            Some(at) if (now.wrapping_sub(at) as i32) < 0 => continue,
+            Some(_) => {}
+        }
+
+        if let Ok(sample) = accel.accel_norm() {
+            calibrator.add_sample(sample);
+        }
+        if !calibrator.is_position_complete() {
+            continue;
+        }
+
+        // 1つの向きを測り終えた
+        measure_at = None;
+        prompted = false;
+        led.turn_off();
+        match calibrator.finish_position() {
+            Ok(position) => {
+                let mean = calibrator.measurement(position).unwrap();
+                writeln!(
+                    &mut serial,
+                    "  X: {:.3}, Y: {:.3}, Z: {:.3}",
+                    mean.x, mean.y, mean.z
+                )
+                .unwrap();
+            }
+            Err(PositionError::Moving) => {
+                writeln!(&mut serial, "  moved while measuring, try again").unwrap()
+            }
+            Err(PositionError::WrongPosition) => {
+                writeln!(&mut serial, "  wrong position, try again").unwrap()
+            }
+        }
+
+        if calibrator.next_position().is_some() {
+            continue;
+        }
+        // 6つの向きがそろったら、較正値を求めて保存する
+        match calibrator.fit() {
+            Some(fit) if fit.residual <= MAX_RESIDUAL => {
+                let calibration = fit.calibration;
+                writeln!(&mut serial, "offset: {:.3?}", calibration.offset).unwrap();
+                writeln!(&mut serial, "scale: {:.3?}", calibration.scale).unwrap();
+                writeln!(&mut serial, "residual: {:.4}", fit.residual).unwrap();
+                match accel_calibration::save(&mut storage, &calibration) {
+                    Ok(()) => writeln!(&mut serial, "saved (press button3 to start over)").unwrap(),
+                    Err(error) => writeln!(&mut serial, "save failed: {:?}", error).unwrap(),
+                }
+            }
+            _ => {
+                writeln!(&mut serial, "calibration failed, start over").unwrap();
+                calibrator.reset();
+            }
+        }
+    }
+}
@@ -35,6 +35,7 @@ fn main() -> ! {
     let mut sets = Pins::new(peripherals.PORT).split();
 
     // TODO: ディスプレイドライバを初期化する
+    // バックライトは点灯したままにする (明るさを変えるときは 6-10 を参照)
     let (mut display, _backlight) = sets
         .display
         .init(
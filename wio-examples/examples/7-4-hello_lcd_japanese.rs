@@ -0,0 +1,64 @@
+//! 7-4 Wio TerminalのLCDに日本語を表示するサンプルコードです。
+//! BDFフォントから生成したビットマップフォントで、LCD に `こんにちは` と表示します。
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example 7-4-hello_lcd_japanese --features font --release
+//! ```
+
+#![no_std]
+#![no_main]
+
+use embedded_graphics as eg;
+use panic_halt as _;
+use wio_terminal as wio;
+
+use eg::{pixelcolor::*, prelude::*, primitives::*, style::*};
+use wio::hal::clock::GenericClockController;
+use wio::hal::delay::Delay;
+use wio::pac::{CorePeripherals, Peripherals};
+use wio::prelude::*;
+use wio::{entry, Pins};
+use wio_font::{BitmapText, DEFAULT_FONT};
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let core = CorePeripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+    let mut delay = Delay::new(core.SYST, &mut clocks);
+    let mut sets = Pins::new(peripherals.PORT).split();
+
+    // ディスプレイドライバを初期化する
+    // バックライトは点灯したままにする (明るさを変えるときは 6-10 を参照)
+    let (mut display, _backlight) = sets
+        .display
+        .init(
+            &mut clocks,
+            peripherals.SERCOM7,
+            &mut peripherals.MCLK,
+            &mut sets.port,
+            58.mhz(),
+            &mut delay,
+        )
+        .unwrap();
+
+    // LCDを黒色で塗りつぶす
+    let style = PrimitiveStyleBuilder::new()
+        .fill_color(Rgb565::BLACK).build();
+    let background = Rectangle::new(Point::new(0, 0), Point::new(319, 239))
+        .into_styled(style);
+    background.draw(&mut display).unwrap();
+
+    // 生成済みのフォントで日本語を描画する
+    BitmapText::new("こんにちは\nワイオ", Point::new(30, 30), &DEFAULT_FONT, Rgb565::GREEN)
+        .draw(&mut display)
+        .unwrap();
+    loop {}
+}
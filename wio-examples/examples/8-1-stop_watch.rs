@@ -1,5 +1,7 @@
 //! 8-1 ストップウォッチをつくる のサンプルコードです。
 //! Wio Terminalをストップウォッチとして使えます。
+//! 周辺機能は board モジュールでまとめて初期化し、ボタンと画面は WioBoard を通して使います。
+//! 画面の明るさは、光センサの値に合わせて自動で変えます (6-10 と同じ)。
 //!
 //! ### 実行方法
 //! ```sh
@@ -9,134 +11,58 @@
 #![no_std]
 #![no_main]
 
-use cortex_m::peripheral::NVIC;
 use wio_terminal as wio;
 
-use core::cell::RefCell;
 use core::fmt::Write;
-use core::ops::DerefMut;
-use cortex_m::interrupt::{self as cortex_interrupt, Mutex};
-use eg::{
-    egrectangle, egtext, fonts::Font24x32, pixelcolor::Rgb565,
-    prelude::*, primitive_style, text_style,
-};
+use eg::{egrectangle, pixelcolor::Rgb565, prelude::*, primitive_style};
 use embedded_graphics as eg;
-use heapless::consts::*;
-use heapless::String;
-use wio::hal::gpio::{Pb26, Pb27, PfC};
-use wio::hal::pwm::Channel;
-use wio::hal::sercom::{Sercom2Pad0, Sercom2Pad1, UART2};
-use wio::hal::time::Hertz;
-use wio::hal::{clock::GenericClockController, timer::TimerCounter};
-use wio::hal::{delay::Delay, pwm::Tcc0Pwm};
-use wio::pac::{interrupt, CorePeripherals, Peripherals, TC3};
-use wio::prelude::*;
-use wio::{entry, Pins, Sets};
-
-struct Ctx {
-    timer_counter: u32,
-    tc3: TimerCounter<TC3>,
-}
-static mut CTX: Option<Ctx> = None;
+use wio::entry;
+use wio::hal::timer::TimerCounter;
+use wio::pac::{interrupt, CorePeripherals, Peripherals};
+use wio_backlight::{AutoBrightness, Curve};
+use wio_board::stopwatch::{self, Sound, StopwatchApp};
+use wio_board::WioBoard;
+use wio_examples::backlight::Backlight;
+use wio_examples::board::{self, Rest, Uart};
+use wio_examples::melody::{Melody, Note, Player};
+use wio_examples::shared::Shared;
+use wio_examples::timebase::{self, TimeBase};
 
 // デバッグ用UART
-static UART: Mutex<RefCell<Option<
-    UART2<Sercom2Pad1<Pb27<PfC>>, Sercom2Pad0<Pb26<PfC>>, (), ()>
->>> = Mutex::new(RefCell::new(None));
+static UART: Shared<Uart> = Shared::new();
 
-enum State {
-    Initializing, // 初期化処理
-    Idle,         // ストップウォッチ停止中
-    Running,      // ストップウォッチ動作中（時刻カウント中）
-}
+static BACKLIGHT: Backlight = Backlight::new();
 
-// ブザーを鳴らすヘルパー関数
-fn beep<P: Into<Hertz>>(
-    buzzer_pwm: &mut Tcc0Pwm,
-    delay: &mut Delay,
-    frequency: P,
-    duration_ms: u16,
-) {
-    buzzer_pwm.set_period(frequency.into());
-    buzzer_pwm.enable(Channel::_4);
-    delay.delay_ms(duration_ms);
-    buzzer_pwm.disable(Channel::_4);
-}
+// 操作音 (メインループを止めずに鳴らす)
+static START_SOUND: [Note; 1] = [Note::new(880, 200)];
+static CLEAR_SOUND: [Note; 1] = [Note::new(1760, 200)];
+static STOP_SOUND: [Note; 3] = [Note::new(880, 50), Note::rest(50), Note::new(880, 100)];
 
 const SCREEN_WIDTH: i32 = 320; // 画面幅
 const SCREEN_HEIGHT: i32 = 240; // 画面高さ
-
-// 描画処理を各ステートで呼び出せるようにしておく
-fn draw<T>(display: &mut T) -> Result<(), T::Error>
-where
-    T: embedded_graphics::DrawTarget<Rgb565>,
-{
-    // TODO: カウント表示エリアをクリアする
-    const FONT_WIDTH: i32 = 24;
-    const FONT_HEIGHT: i32 = 32;
-    egrectangle!(
-        top_left = (0, 0),
-        bottom_right = (SCREEN_WIDTH - 1, FONT_HEIGHT),
-        style = primitive_style!(fill_color = Rgb565::BLACK)
-    ).draw(display)?;
-
-    // TODO: 現在のタイムスタンプを取得する
-    let counter = unsafe{
-        CTX.as_ref().unwrap().timer_counter
-    };
-    let elapsed_s = (counter as f32) / 16.0;
-
-    // TODO: タイムスタンプを描画する
-    let mut textbuffer = String::<U256>::new();
-    write!(&mut textbuffer, "{:.2}", elapsed_s).unwrap();
-
-    let length = textbuffer.len();
-    let left = SCREEN_WIDTH - (length as i32) * FONT_WIDTH;
-    egtext!(
-        text = textbuffer.as_str(),
-        top_left = (left, 0),
-        style = text_style!(font = Font24x32, text_color = Rgb565::WHITE)
-    ).draw(display)?;
-
-    Ok(())
-}
+const LIGHT_INTERVAL_MS: u32 = 100; // 光センサを読む間隔
 
 #[entry]
 fn main() -> ! {
-    let mut peripherals = Peripherals::take().unwrap();
+    let peripherals = Peripherals::take().unwrap();
     let core = CorePeripherals::take().unwrap();
-    let mut clocks = GenericClockController::with_external_32kosc(
-        peripherals.GCLK,
-        &mut peripherals.MCLK,
-        &mut peripherals.OSC32KCTRL,
-        &mut peripherals.OSCCTRL,
-        &mut peripherals.NVMCTRL,
-    );
-
-    let mut sets: Sets = Pins::new(peripherals.PORT).split();
-    let mut delay = Delay::new(core.SYST, &mut clocks);
-
-    // パニックハンドラ用にUARTを初期化する（ストップウォッチの機能では不使用）
-    let serial = sets.uart.init(
-        &mut clocks,
-        Hertz(115200u32),
-        peripherals.SERCOM2,
-        &mut peripherals.MCLK,
-        &mut sets.port,
-    );
-    cortex_interrupt::free(|cs| UART.borrow(cs).replace(Some(serial)));
-
-    // ブザーの初期化（TCC0を使ったPWM信号生成）
-    let mut buzzer = sets.buzzer.init(
-        &mut clocks,
-        peripherals.TCC0,
-        &mut peripherals.MCLK,
-        &mut sets.port,
-    );
-    // デューティ比を0.5に音色変えたい場合は比率を変えるとよい
-    let max_duty = buzzer.get_max_duty();
-    buzzer.set_duty(Channel::_4, max_duty / 2);
-    buzzer.disable(Channel::_4);
+    // 画面とボタンは WioBoard を通して使い、タイマは個別に使う
+    let (mut board, rest) = board::split(peripherals, core);
+    let Rest {
+        mut clocks,
+        mut mclk,
+        backlight,
+        tc2,
+        tc3,
+        tc5,
+        ..
+    } = rest;
+
+    // パニックハンドラ用にUARTを取り出す（ストップウォッチの機能では不使用）
+    UART.init(board.take_uart().unwrap());
+
+    // 操作音はメインループを止めずに鳴らすので、ブザーはプレイヤーに渡す
+    let mut player = Player::new(board.take_buzzer().unwrap());
 
     // 時間を計測するためにタイマを初期化する
     // 正確に時間を計測するために、XOSC32K（外部32.768[kHz]水晶発振器）
@@ -152,30 +78,18 @@ fn main() -> ! {
         .unwrap();
     // GCLK6を使うTC2/TC3向けのクロック設定を構築
     let timer_clock = clocks.tc2_tc3(&gclk6).unwrap();
-    // TC3を初期化
-    let mut tc3 = wio::hal::timer::TimerCounter::tc3_(
-        &timer_clock,
-        peripherals.TC3,
-        &mut peripherals.MCLK,
-    );
-    unsafe { NVIC::unmask(interrupt::TC3); }
-    // 62.5[ms] = 1/16[s]周期のカウンタとしてTC3の動作を開始
-    tc3.start(62500.us());
+    // TC2/TC3を32bitのフリーランカウンタとして動かし、約30.5[us]の分解能で時刻を得る
+    // 周期ごとにタイマを再スタートしないので、誤差が積み重ならない
+    let timebase = TimeBase::new(tc2, tc3, &mut mclk, &timer_clock);
 
-    unsafe { CTX = Some(Ctx { timer_counter: 0, tc3 }); }
-
-    // LCDの初期化
-    let (mut display, _backlight) = sets
-        .display
-        .init(
-            &mut clocks,
-            peripherals.SERCOM7,
-            &mut peripherals.MCLK,
-            &mut sets.port,
-            60.mhz(),
-            &mut delay,
-        )
+    // 2MHzのクロックでTC5を動かし、バックライトのピンをPWMで切り替える
+    let gclk5 = clocks
+        .get_gclk(wio::pac::gclk::pchctrl::GEN_A::GCLK5)
         .unwrap();
+    let backlight_clock = clocks.tc4_tc5(&gclk5).unwrap();
+    let tc5 = TimerCounter::tc5_(&backlight_clock, tc5, &mut mclk);
+    BACKLIGHT.start(backlight, tc5);
+    let mut brightness = AutoBrightness::new(Curve::default());
 
     // LCDのクリア（全体を黒で塗りつぶす）
     egrectangle!(
@@ -183,79 +97,54 @@ fn main() -> ! {
         bottom_right = (SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1),
         style = primitive_style!(fill_color = Rgb565::BLACK)
     )
-    .draw(&mut display)
+    .draw(board.display())
     .unwrap();
 
-    // ボタンのGPIOを初期化
-    let button_start =
-        sets.buttons.button3.into_floating_input(&mut sets.port);
-    let button_stop =
-        sets.buttons.button2.into_floating_input(&mut sets.port);
-    let button_clear =
-        sets.buttons.button1.into_floating_input(&mut sets.port);
-
-    let mut state = State::Initializing;
+    // ボタンの操作と描画は wio_board::stopwatch にあり、SimBoard でも同じように動く
+    // ボタン3で開始、ボタン2で停止、ボタン1でクリア (計測中はラップを記録する)
+    let mut app = StopwatchApp::new();
+    stopwatch::draw(board.display(), 0, None).unwrap();
+    let mut last_ms = timebase.now().since_start().as_millis() as u32;
+    let mut light_read_at = last_ms;
     loop {
-        match state {
-            // TODO: ステートマシンを実装する
-            State::Initializing => {
-                draw(&mut display).unwrap();
-                state = State::Idle;
-            }
-            State::Idle => {
-                if button_start.is_low().unwrap() {
-                    unsafe {
-                        CTX.as_mut().unwrap().tc3.enable_interrupt()
-                    }
-                    beep(&mut buzzer, &mut delay, 880.hz(), 200u16);
-                    state = State::Running
-                } else if button_clear.is_low().unwrap() {
-                    unsafe {
-                        CTX.as_mut().unwrap().timer_counter = 0
-                    }
-                    beep(&mut buzzer, &mut delay, 1760.hz(), 200u16);
-                    draw(&mut display).unwrap();
-                }
-            } 
-            State::Running => {
-                if button_stop.is_low().unwrap() {
-                    unsafe {
-                        CTX.as_mut().unwrap().tc3.disable_interrupt()
-                    }
-                    draw(&mut display).unwrap();
-                    beep(&mut buzzer, &mut delay, 880.hz(), 50u16);
-                    delay.delay_ms(50u16);
-                    beep(&mut buzzer, &mut delay, 880.hz(), 100u16);
-                    state = State::Idle;
-                } else {
-                    draw(&mut display).unwrap();
-                }
+        let now = timebase.now().since_start().as_millis() as u32;
+        player.tick(now.wrapping_sub(last_ms));
+        last_ms = now;
+
+        if now.wrapping_sub(light_read_at) >= LIGHT_INTERVAL_MS {
+            light_read_at = now;
+            if let Some(level) = brightness.update(board.read_light(), now) {
+                BACKLIGHT.set_level(level);
             }
         }
+
+        match app.update(&mut board, now).unwrap() {
+            Some(Sound::Start) => player.play(Melody::notes(&START_SOUND)),
+            Some(Sound::Stop) => player.play(Melody::notes(&STOP_SOUND)),
+            Some(Sound::Clear) => player.play(Melody::notes(&CLEAR_SOUND)),
+            None => {}
+        }
     }
 }
 
-/// TC3の割り込みハンドラ (62.5[ms]周期で呼ばれる)
+/// TC2の割り込みハンドラ (32bitカウンタがオーバーフローしたときに呼ばれる)
 #[interrupt]
-fn TC3() {
-    unsafe {
-        // TODO: タイマカウンタをインクリメントして次のタイマを再開する
-        let ctx = CTX.as_mut().unwrap();
-        ctx.tc3.wait().ok();
-        ctx.timer_counter += 1;
-    }
+fn TC2() {
+    timebase::on_interrupt();
+}
+
+#[interrupt]
+fn TC5() {
+    BACKLIGHT.on_interrupt();
 }
 
 use core::panic::PanicInfo;
 #[inline(never)]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    cortex_interrupt::free(|cs| {
-        if let Some(ref mut serial) =
-            UART.borrow(cs).borrow_mut().deref_mut()
-        {
-            writeln!(serial, "panic: {}", info).ok();
-        }
+    // UARTの初期化前や使用中にパニックしたときは何も出力しない
+    UART.try_lock(|serial| {
+        writeln!(serial, "panic: {}", info).ok();
     });
 
     loop {}
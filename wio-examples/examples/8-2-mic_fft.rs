@@ -1,5 +1,7 @@
 //! 8-2 マイク音声の信号処理をする
 //! マイクから入力した音声をフーリエ変換してパワースペクトラムを表示します
+//! ボタン3で棒グラフとスペクトログラム (色を切り替え) の表示を切り替えます
+//! 周辺機能は board モジュールでまとめて初期化し、ボタンと画面は WioBoard を通して使います。
 //!
 //! ### 実行方法
 //! ```sh
@@ -14,44 +16,52 @@ use wio_terminal as wio;
 
 use core::fmt::Write;
 use cortex_m::peripheral::NVIC;
-use heapless::consts::*;
-use heapless::Vec;
 use micromath::F32Ext;
 use wio::entry;
 use wio::hal::adc::{FreeRunning, InterruptAdc};
-use wio::hal::clock::GenericClockController;
-use wio::hal::delay::Delay;
-use wio::hal::time::Hertz;
 use wio::pac::{interrupt, CorePeripherals, Peripherals, ADC1};
-use wio::prelude::*;
-use wio::Pins;
+use wio_board::{Button, WioBoard};
+use wio_dsp::spectrum::{self, Spectrum256, Window};
+use wio_dsp::Decimator;
+use wio_examples::acquisition::{Acquisition, Writer};
+use wio_examples::board::{self, Rest};
+use wio_examples::shared::Shared;
+use wio_examples::timebase::{self, Instant, TimeBase};
+use wio_waterfall::{Colormap, Waterfall};
 
 use eg::{egrectangle, pixelcolor::Rgb565, primitive_style};
 use eg::{pixelcolor::Rgb888, prelude::*};
 use embedded_graphics as eg;
 
-// main() 関数とADCの割り込みハンドラで共有するリソース
-struct Ctx {
+// 表示モード
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DisplayMode {
+    Bars,      // 棒グラフ
+    Waterfall, // スペクトログラム
+}
+
+// ADCの割り込みハンドラが使うリソース
+struct AdcCtx {
     adc: InterruptAdc<ADC1, FreeRunning>,
-    buffers: [SamplingBuffer; 2], // ADC結果のバッファ2面分
-    // 現在ADC結果取り込み先のバッファへの参照
-    sampling_buffer: Option<&'static mut SamplingBuffer>,
-    // 現在信号処理中のバッファへの参照
-    processing_buffer: Option<&'static mut SamplingBuffer>,
+    samples: Writer<'static, f32, Instant, FFT_POINTS, 2>, // 間引いたサンプルの書き込み側
+    timebase: TimeBase,                           // ブロックの時刻を記録する
+    decimator: Decimator<DECIMATOR_TAPS>,         // 折り返しを防ぎながら間引く
 }
 
-static mut CTX: Option<Ctx> = None;
+static ADC_CTX: Shared<AdcCtx> = Shared::new();
+// 割り込みハンドラから main() 関数へFFT_POINTS個ずつサンプルを渡すピンポンバッファ
+// main() 関数はロックせずに読み出せるので、FFTの間もサンプリングは止まらない
+static SAMPLES: Acquisition<f32, Instant, FFT_POINTS, 2> =
+    Acquisition::new(0.0, Instant::from_ticks(0));
 
-const AVERAGING_FACTOR: u32 = 4; // 平均化フィルタのサンプル点数
+const DECIMATION_FACTOR: usize = 4; // ADCのサンプルを間引く割合
+const DECIMATOR_TAPS: usize = 32; // 間引く前にかける低域通過フィルタのタップ数
 const FFT_POINTS: usize = 256; // FFTをするサンプル点数
 const ADC_SAMPLING_RATE: f32 = 83333.0; // ADCのサンプリングレート
-#[allow(dead_code)]
-// 平均化フィルタ後のサンプリングレート
-const SAMPLING_RATE: f32 = ADC_SAMPLING_RATE / AVERAGING_FACTOR as f32;
+// 間引いた後のサンプリングレート
+const SAMPLING_RATE: f32 = ADC_SAMPLING_RATE / DECIMATION_FACTOR as f32;
 const AMPLITUDE: f32 = 4096.0; // サンプル値の最大振幅
 
-type SamplingBuffer = heapless::Vec<f32, U256>; //サンプリングバッファの型
-
 // f32::max,f32::minが
 // プラットフォームのライブラリとしてfmaxf,fminfがあることを前提としているが、
 // 現在の環境にはfmaxf,fminfがないので、最低限のものを実装しておく
@@ -77,86 +87,56 @@ fn fmaxf(a: f32, b: f32) -> f32 {
 
 #[entry]
 fn main() -> ! {
-    let mut peripherals = Peripherals::take().unwrap();
+    let peripherals = Peripherals::take().unwrap();
     let core = CorePeripherals::take().unwrap();
-    let mut clocks = GenericClockController::with_external_32kosc(
-        peripherals.GCLK,
-        &mut peripherals.MCLK,
-        &mut peripherals.OSC32KCTRL,
-        &mut peripherals.OSCCTRL,
-        &mut peripherals.NVMCTRL,
-    );
-
-    let mut sets = Pins::new(peripherals.PORT).split();
-    let mut delay = Delay::new(core.SYST, &mut clocks);
+    // 画面とボタンは WioBoard を通して使い、ADCとタイマは個別に使う
+    // 光センサがつながっているADC1はマイクの音の取り込みに使うので、バックライトは点灯したままにする
+    let (mut board, rest) = board::split(peripherals, core);
+    let Rest {
+        mut clocks,
+        mut mclk,
+        tc2,
+        tc3,
+        ..
+    } = rest;
 
     // TODO: フリーランニングモードでADCを動かすようにInterruptAdc型を構築する
-    let (microphone_adc, mut microphone_pin) = sets.microphone.init(
-        peripherals.ADC1,
-        &mut clocks,
-        &mut peripherals.MCLK,
-        &mut sets.port,
-    );
-    let mut microphone_adc: InterruptAdc<_, FreeRunning> = InterruptAdc::from(microphone_adc);
-
-    microphone_adc.start_conversion(&mut microphone_pin);
+    let microphone_adc = board.start_microphone_sampling().unwrap();
 
-    // デバッグ用UARTを初期化する
-    let mut serial = sets.uart.init(
-        &mut clocks,
-        Hertz(115200u32),
-        peripherals.SERCOM2,
-        &mut peripherals.MCLK,
-        &mut sets.port,
-    );
+    // デバッグ用UARTを取り出す
+    let mut serial = board.take_uart().unwrap();
 
-    // 画面を初期化する
-    let (mut display, _backlight) = sets
-        .display
-        .init(
-            &mut clocks,
-            peripherals.SERCOM7,
-            &mut peripherals.MCLK,
-            &mut sets.port,
-            60.mhz(),
-            &mut delay,
+    // ブロックの時刻を記録するために、XOSC32Kを基準にしたGCLK6でTC2/TC3を動かす
+    let gclk6 = clocks
+        .configure_gclk_divider_and_source(
+            wio::pac::gclk::pchctrl::GEN_A::GCLK6,
+            1,
+            wio::pac::gclk::genctrl::SRC_A::XOSC32K,
+            false,
         )
         .unwrap();
+    let timer_clock = clocks.tc2_tc3(&gclk6).unwrap();
+    let timebase = TimeBase::new(tc2, tc3, &mut mclk, &timer_clock);
 
     // TODO: 共有リソースを初期化する
-    unsafe {
-        CTX = Some(Ctx {
-            adc: microphone_adc,
-            buffers: [Vec::new(), Vec::new()],
-            sampling_buffer: None,
-            processing_buffer: None,
-        });
-        let mut ctx = CTX.as_mut().unwrap();
-        let (first, rest) = ctx.buffers.split_first_mut().unwrap();
-        ctx.sampling_buffer = Some(first);
-        ctx.processing_buffer = Some(&mut rest[0]);
-    }
+    let (writer, mut samples) = SAMPLES.split().unwrap();
+    ADC_CTX.init(AdcCtx {
+        adc: microphone_adc,
+        samples: writer,
+        timebase,
+        decimator: Decimator::lowpass(DECIMATION_FACTOR),
+    });
+    let mut reported_overruns = 0;
 
     // ADC変換完了割り込み(RESRDY)を有効にしてサンプリングを開始する
     writeln!(&mut serial, "start").unwrap();
 
     unsafe { NVIC::unmask(interrupt::ADC1_RESRDY); }
 
-    let button_restart =
-        sets.buttons.button1.into_floating_input(&mut sets.port);
-    let button_stop =
-        sets.buttons.button2.into_floating_input(&mut sets.port);
+    // ボタン1で再開、ボタン2で停止、ボタン3で表示モードと色を切り替える
 
-    // FFTの窓関数としてHann窓を使うので係数を計算しておく
-    // 振幅の正規化用に最大振幅で割っておく
-    let mut hann_factor = [0f32; FFT_POINTS];
-    for i in 0..FFT_POINTS {
-        use core::f32::consts::PI;
-        hann_factor[i] = 0.5f32
-            * (1f32 - (PI * 2.0f32 * i as f32 / FFT_POINTS as f32).cos())
-            / AMPLITUDE;
-    }
-    let hann_factor = hann_factor;
+    // FFTの窓関数としてHann窓を使う
+    let mut analyzer = Spectrum256::new(Window::Hann, SAMPLING_RATE, AMPLITUDE);
 
     // 画面のスペクトラム表示領域の内容を消す
     const SCREEN_WIDTH: i32 = 320;
@@ -171,8 +151,11 @@ fn main() -> ! {
         )
         .draw(display)
     }
-    clear_screen(&mut display).unwrap();
+    clear_screen(board.display()).unwrap();
 
+    // 画面の上端と下端に対応するレベル [dBFS]
+    const TOP_DBFS: f32 = -38.0;
+    const BOTTOM_DBFS: f32 = -86.0;
     const BAR_WIDTH: i32 = 2;
     const REAL_POINTS: usize = FFT_POINTS / 2;
     const NUMBER_OF_BARS: usize = REAL_POINTS;
@@ -182,108 +165,154 @@ fn main() -> ! {
     let mut stop_req = false;
     let mut stop_ack = false;
 
+    // スペクトログラムは棒グラフと同じ位置・同じレベルの範囲で表示する
+    const WATERFALL_ROWS: usize = (SCREEN_HEIGHT - wio_waterfall::AXIS_HEIGHT) as usize;
+    let mut waterfall: Waterfall<NUMBER_OF_BARS, WATERFALL_ROWS> = Waterfall::new(
+        Point::new((SCREEN_WIDTH - DRAW_AREA_WIDTH) / 2, 0),
+        BAR_WIDTH as u32,
+        Colormap::Viridis,
+    );
+    waterfall.set_range(BOTTOM_DBFS, TOP_DBFS);
+    let mut mode = DisplayMode::Bars;
+    let mut mode_pressed = false;
+
     loop {
-        // TODO: `processing_buffer`が埋まっていれば、FFTを実行しスペクトラムを描画する
+        // TODO: ブロックが書き終わっていれば、FFTを実行しスペクトラムを描画する
         //       停止ボタンが押された場合は、棒グラフを表示する
-        if button_stop.is_low().unwrap() {
+        if board.is_pressed(Button::Button2) {
             stop_req = true;
         }
-        let processing_buffer = unsafe {
-            let ctx = CTX.as_mut().unwrap();
-            ctx.processing_buffer.as_mut().unwrap()
-        };
-        let len = processing_buffer.len();
-        let cap = processing_buffer.capacity(); // TODO: capacity?
-        if len == cap {
-            for i in 0..FFT_POINTS {
-                processing_buffer[i] *= hann_factor[i];
+        // 棒グラフ → スペクトログラム (色を順に切り替え) → 棒グラフ の順に切り替える
+        let pressed = board.is_pressed(Button::Button3);
+        if pressed && !mode_pressed {
+            mode = match mode {
+                DisplayMode::Bars => {
+                    waterfall.set_colormap(Colormap::Viridis);
+                    DisplayMode::Waterfall
+                }
+                DisplayMode::Waterfall if waterfall.colormap() == Colormap::Grayscale => {
+                    DisplayMode::Bars
+                }
+                DisplayMode::Waterfall => {
+                    waterfall.next_colormap();
+                    DisplayMode::Waterfall
+                }
+            };
+            clear_screen(board.display()).unwrap();
+            waterfall.clear();
+            if mode == DisplayMode::Waterfall {
+                waterfall.draw_axis(board.display(), SAMPLING_RATE).unwrap();
             }
-            let result = microfft::real::rfft_256(processing_buffer.as_mut());
+        }
+        mode_pressed = pressed;
+        // 処理が間に合わずに捨てたブロックがあれば知らせる
+        let overruns = samples.overruns();
+        if overruns != reported_overruns {
+            writeln!(&mut serial, "overruns: {}", overruns).ok();
+            reported_overruns = overruns;
+        }
+
+        // 割り込みハンドラが書き終えたブロックがあれば、その場でFFTする
+        let mut processed = false;
+        if let Some(mut processing_buffer) = samples.read() {
+            analyzer.process(processing_buffer.samples_mut());
 
-            let offset_top = 0;
-            let offset_left = (SCREEN_WIDTH - DRAW_AREA_WIDTH) / 2;
-            let area_height = SCREEN_HEIGHT;
-            for (step, spectrum) in result.iter().enumerate() {
-                let power = spectrum.norm_sqr() / (FFT_POINTS * FFT_POINTS) as f32;
-                let relative_power = if power <= 0.0 {
-                    core::f32::NEG_INFINITY
-                } else {
-                    power.log10() * 10.0
-                };
-                let height = -(((relative_power + 50.0) * 5.0).round().max(-area_height as f32).min(0.0) as i32);
-                let intensity = (height * 255) / (SCREEN_HEIGHT / 2);
-                let red = if height < SCREEN_HEIGHT / 2 {
-                    255 - intensity
-                } else {
-                    0
-                };
-                let green = if height < SCREEN_HEIGHT / 2 {
-                    intensity
-                } else {
-                    511 - intensity
-                };
-                let blue = if height < SCREEN_HEIGHT / 2 {
-                    0
-                } else {
-                    intensity - 256
-                };
+            match mode {
+                DisplayMode::Bars => {
+                    let offset_top = 0;
+                    let offset_left = (SCREEN_WIDTH - DRAW_AREA_WIDTH) / 2;
+                    let area_height = SCREEN_HEIGHT;
+                    for (step, level) in analyzer.levels().enumerate() {
+                        let ratio = spectrum::scale(level, BOTTOM_DBFS, TOP_DBFS);
+                        let height = ((1.0 - ratio) * area_height as f32).round() as i32;
+                        let intensity = (height * 255) / (SCREEN_HEIGHT / 2);
+                        let red = if height < SCREEN_HEIGHT / 2 {
+                            255 - intensity
+                        } else {
+                            0
+                        };
+                        let green = if height < SCREEN_HEIGHT / 2 {
+                            intensity
+                        } else {
+                            511 - intensity
+                        };
+                        let blue = if height < SCREEN_HEIGHT / 2 {
+                            0
+                        } else {
+                            intensity - 256
+                        };
 
-                let start_x = offset_left + step as i32 * BAR_WIDTH;
-                let end_x = offset_left + (step + 1) as i32 * BAR_WIDTH;
-                let prev_y = prev_bar_position[step] as i32;
-                egrectangle!(
-                    top_left = (start_x, prev_y),
-                    bottom_right = (end_x, (prev_y + 2).min(area_height - 1)),
-                    style = primitive_style!(fill_color = Rgb565::BLACK)
-                )
-                .draw(&mut display)
-                .unwrap();
-                if stop_req {
-                    egrectangle!(
-                        top_left = (start_x, offset_top + height),
-                        bottom_right = (end_x, area_height - 1),
-                         style = primitive_style!(
-                             fill_color = Rgb888::new(
-                                 red as u8,
-                                 green as u8,
-                                 blue as u8
-                             )
-                             .into()
-                         )
-                    )
-                    .draw(&mut display)
-                    .unwrap();
-                } else {
-                    egrectangle!(
-                        top_left = (start_x, offset_top + height),
-                        bottom_right = (
-                            end_x,
-                            (offset_top + height + 2).min(area_height - 1)
-                        ),
-                        style = primitive_style!(
-                             fill_color = Rgb888::new(
-                                 red as u8,
-                                 green as u8,
-                                 blue as u8
-                             )
-                             .into()
+                        let start_x = offset_left + step as i32 * BAR_WIDTH;
+                        let end_x = offset_left + (step + 1) as i32 * BAR_WIDTH;
+                        let prev_y = prev_bar_position[step] as i32;
+                        egrectangle!(
+                            top_left = (start_x, prev_y),
+                            bottom_right = (end_x, (prev_y + 2).min(area_height - 1)),
+                            style = primitive_style!(fill_color = Rgb565::BLACK)
                         )
-                    )
-                    .draw(&mut display)
-                    .unwrap();
+                        .draw(board.display())
+                        .unwrap();
+                        if stop_req {
+                            egrectangle!(
+                                top_left = (start_x, offset_top + height),
+                                bottom_right = (end_x, area_height - 1),
+                                 style = primitive_style!(
+                                     fill_color = Rgb888::new(
+                                         red as u8,
+                                         green as u8,
+                                         blue as u8
+                                     )
+                                     .into()
+                                 )
+                            )
+                            .draw(board.display())
+                            .unwrap();
+                        } else {
+                            egrectangle!(
+                                top_left = (start_x, offset_top + height),
+                                bottom_right = (
+                                    end_x,
+                                    (offset_top + height + 2).min(area_height - 1)
+                                ),
+                                style = primitive_style!(
+                                     fill_color = Rgb888::new(
+                                         red as u8,
+                                         green as u8,
+                                         blue as u8
+                                     )
+                                     .into()
+                                )
+                            )
+                            .draw(board.display())
+                            .unwrap();
+                        }
+                        prev_bar_position[step] = (offset_top + height) as u8;
+                    }
+                }
+                DisplayMode::Waterfall => {
+                    waterfall.push(analyzer.levels());
+                    waterfall.draw(board.display()).unwrap();
                 }
-                prev_bar_position[step] = (offset_top + height) as u8;
             }
 
-            processing_buffer.clear();
+            processed = true;
+        }
+        if processed {
+            // FFTと描画の間にたまった古いブロックは捨てて、最新のサンプルを表示する
+            // (読み出し中のブロックを返してから捨てる)
+            samples.discard_all();
             stop_ack = stop_req;
         }
 
         if stop_ack {
             stop_req = false;
             stop_ack = false;
-            while !button_restart.is_low().unwrap() {}
-            clear_screen(&mut display).unwrap();
+            while !board.is_pressed(Button::Button1) {}
+            clear_screen(board.display()).unwrap();
+            if mode == DisplayMode::Waterfall {
+                waterfall.clear();
+                waterfall.draw_axis(board.display(), SAMPLING_RATE).unwrap();
+            }
         }
 
     }
@@ -291,31 +320,21 @@ fn main() -> ! {
 
 #[interrupt]
 fn ADC1_RESRDY() {
-    // TODO: データをサンプリングし、`sampling_buffer`を埋める。
-    //       `sampling_buffer`がいっぱいになったら`processing_buffer`と入れ替える。
-    static mut AVERAGE: f32 = 0.0;
-    static mut AVERAGE_COUNT: u32 = 0;
-    unsafe {
-        let ctx = CTX.as_mut().unwrap();
+    // TODO: データをサンプリングし、間引いたサンプルを`SAMPLES`に書き込む。
+    ADC_CTX.lock(|ctx| {
         if let Some(sample) = ctx.adc.service_interrupt_ready() {
-            *AVERAGE += sample as f32;
-            *AVERAGE_COUNT += 1;
-            if *AVERAGE_COUNT == AVERAGING_FACTOR {
-                let sampling_buffer = 
-                    ctx.sampling_buffer.as_mut().unwrap();
-                if sampling_buffer.len() == sampling_buffer.capacity() {
-                    if ctx.processing_buffer.as_mut().unwrap().len() == 0  {
-                        core::mem::swap(
-                            &mut ctx.processing_buffer,
-                            &mut ctx.sampling_buffer,
-                        );
-                    }
-                } else {
-                    let _ = sampling_buffer.push(*AVERAGE / (AVERAGING_FACTOR as f32));
-                }
-                *AVERAGE_COUNT = 0;
-                *AVERAGE = 0.0;
+            // DECIMATION_FACTOR 個ごとにフィルタの出力が得られる
+            if let Some(sample) = ctx.decimator.push(sample as f32) {
+                // 空きバッファがないときはブロック単位で捨てられ、オーバーランとして数えられる
+                let now = ctx.timebase.now();
+                ctx.samples.push(sample, now);
             }
         }
-    }
+    });
+}
+
+// TC2の割り込みハンドラ (時刻を数える32bitカウンタがオーバーフローしたときに呼ばれる)
+#[interrupt]
+fn TC2() {
+    timebase::on_interrupt();
 }
@@ -0,0 +1,204 @@
+//! 8-3 チューナーをつくる
+//! マイクから入力した音の基本周波数をYIN法で求め、最も近い音名とずれをメーターで表示します
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example 8-3-tuner --features app --release
+//! ```
+
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+use wio_terminal as wio;
+
+use core::fmt::Write;
+use cortex_m::peripheral::NVIC;
+use wio::entry;
+use wio::hal::adc::{FreeRunning, InterruptAdc};
+use wio::hal::clock::GenericClockController;
+use wio::hal::delay::Delay;
+use wio::hal::time::Hertz;
+use wio::pac::{interrupt, CorePeripherals, Peripherals, ADC1};
+use wio::prelude::*;
+use wio::Pins;
+use wio_dsp::Decimator;
+use wio_examples::acquisition::{Acquisition, Writer};
+use wio_examples::shared::Shared;
+use wio_examples::timebase::{self, Instant, TimeBase};
+use wio_tuner::{nearest_note, Gauge, Yin};
+
+use eg::{egrectangle, pixelcolor::Rgb565, prelude::*, primitive_style};
+use embedded_graphics as eg;
+
+// ADCの割り込みハンドラが使うリソース
+struct AdcCtx {
+    adc: InterruptAdc<ADC1, FreeRunning>,
+    samples: Writer<'static, f32, Instant, BLOCK_SIZE, 2>, // 間引いたサンプルの書き込み側
+    timebase: TimeBase,                           // ブロックの時刻を記録する
+    decimator: Decimator<DECIMATOR_TAPS>,         // 折り返しを防ぎながら間引く
+}
+
+static ADC_CTX: Shared<AdcCtx> = Shared::new();
+static SAMPLES: Acquisition<f32, Instant, BLOCK_SIZE, 2> =
+    Acquisition::new(0.0, Instant::from_ticks(0));
+
+const DECIMATION_FACTOR: usize = 4; // ADCのサンプルを間引く割合
+const DECIMATOR_TAPS: usize = 32; // 間引く前にかける低域通過フィルタのタップ数
+// 1回に処理するサンプル数 (約49[ms]分)
+// 最低周波数の周期の2倍以上が必要
+const BLOCK_SIZE: usize = 1024;
+const ADC_SAMPLING_RATE: f32 = 83333.0; // ADCのサンプリングレート
+// 間引いた後のサンプリングレート
+const SAMPLING_RATE: f32 = ADC_SAMPLING_RATE / DECIMATION_FACTOR as f32;
+// 検出する周波数の範囲 [Hz] (ギターの6弦 E2 = 82[Hz] より少し低いところから)
+const MIN_FREQUENCY: f32 = 60.0;
+const MAX_FREQUENCY: f32 = 1500.0;
+// これより小さい音は無音とみなす (ADCの値の実効値)
+const MIN_RMS: f32 = 8.0;
+const A4_FREQUENCY: f32 = 440.0; // 基準音
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let core = CorePeripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+
+    let mut sets = Pins::new(peripherals.PORT).split();
+    let mut delay = Delay::new(core.SYST, &mut clocks);
+
+    // フリーランニングモードでADCを動かす
+    let (microphone_adc, mut microphone_pin) = sets.microphone.init(
+        peripherals.ADC1,
+        &mut clocks,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+    let mut microphone_adc: InterruptAdc<_, FreeRunning> = InterruptAdc::from(microphone_adc);
+    microphone_adc.start_conversion(&mut microphone_pin);
+
+    // デバッグ用UARTを初期化する
+    let mut serial = sets.uart.init(
+        &mut clocks,
+        Hertz(115200u32),
+        peripherals.SERCOM2,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+
+    // 画面を初期化する
+    // 光センサがつながっているADC1はマイクの音の取り込みに使うので、バックライトは点灯したままにする
+    let (mut display, _backlight) = sets
+        .display
+        .init(
+            &mut clocks,
+            peripherals.SERCOM7,
+            &mut peripherals.MCLK,
+            &mut sets.port,
+            60.mhz(),
+            &mut delay,
+        )
+        .unwrap();
+    egrectangle!(
+        top_left = (0, 0),
+        bottom_right = (319, 239),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(&mut display)
+    .unwrap();
+
+    // ブロックの時刻を記録するために、XOSC32Kを基準にしたGCLK6でTC2/TC3を動かす
+    let gclk6 = clocks
+        .configure_gclk_divider_and_source(
+            wio::pac::gclk::pchctrl::GEN_A::GCLK6,
+            1,
+            wio::pac::gclk::genctrl::SRC_A::XOSC32K,
+            false,
+        )
+        .unwrap();
+    let timer_clock = clocks.tc2_tc3(&gclk6).unwrap();
+    let timebase = TimeBase::new(
+        peripherals.TC2,
+        peripherals.TC3,
+        &mut peripherals.MCLK,
+        &timer_clock,
+    );
+
+    let (writer, mut samples) = SAMPLES.split().unwrap();
+    ADC_CTX.init(AdcCtx {
+        adc: microphone_adc,
+        samples: writer,
+        timebase,
+        decimator: Decimator::lowpass(DECIMATION_FACTOR),
+    });
+
+    // 周期は最大で BLOCK_SIZE / 2 サンプルまで調べられる
+    let mut yin: Yin<{ BLOCK_SIZE / 2 }> = Yin::new(SAMPLING_RATE, MIN_FREQUENCY, MAX_FREQUENCY);
+    yin.set_min_rms(MIN_RMS);
+    let gauge = Gauge::new(Point::new(160, 200), 130);
+    gauge.draw(&mut display, None, 0.0).unwrap();
+
+    writeln!(&mut serial, "start").unwrap();
+    unsafe {
+        NVIC::unmask(interrupt::ADC1_RESRDY);
+    }
+
+    let mut last_midi = None;
+    loop {
+        let block = match samples.read() {
+            Some(block) => block,
+            None => continue,
+        };
+        let pitch = yin.detect(block.samples());
+        drop(block);
+
+        let reading = pitch.and_then(|pitch| nearest_note(pitch.frequency, A4_FREQUENCY));
+        let frequency = pitch.map(|pitch| pitch.frequency).unwrap_or(0.0);
+        gauge.draw(&mut display, reading.as_ref(), frequency).unwrap();
+
+        // 音が変わったらUARTにも出力する
+        let midi = reading.map(|reading| reading.midi);
+        if midi != last_midi {
+            if let Some(reading) = reading {
+                writeln!(
+                    &mut serial,
+                    "{}{} {:.1}Hz",
+                    reading.name(),
+                    reading.octave(),
+                    frequency
+                )
+                .ok();
+            }
+            last_midi = midi;
+        }
+
+        // 計算と描画の間にたまった古いブロックは捨てて、最新の音を表示する
+        samples.discard_all();
+    }
+}
+
+#[interrupt]
+fn ADC1_RESRDY() {
+    ADC_CTX.lock(|ctx| {
+        if let Some(sample) = ctx.adc.service_interrupt_ready() {
+            // DECIMATION_FACTOR 個ごとにフィルタの出力が得られる
+            if let Some(sample) = ctx.decimator.push(sample as f32) {
+                // 空きバッファがないときはブロック単位で捨てられ、オーバーランとして数えられる
+                let now = ctx.timebase.now();
+                ctx.samples.push(sample, now);
+            }
+        }
+    });
+}
+
+// TC2の割り込みハンドラ (時刻を数える32bitカウンタがオーバーフローしたときに呼ばれる)
+#[interrupt]
+fn TC2() {
+    timebase::on_interrupt();
+}
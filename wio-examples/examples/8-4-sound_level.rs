@@ -0,0 +1,342 @@
+//! 8-4 騒音計をつくる
+//! マイクから入力した音にA/C特性の重み付けをして、音の大きさを表示します
+//! ボタン3で重み付け (A → C → Z) を切り替え、ボタン1で Leq をリセットします
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example 8-4-sound_level --features app --release
+//! ```
+
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+use wio_terminal as wio;
+
+use core::fmt::Write;
+use cortex_m::peripheral::NVIC;
+use eg::{
+    egline, egrectangle, egtext,
+    fonts::{Font12x16, Font24x32, Font6x8},
+    pixelcolor::Rgb565,
+    prelude::*,
+    primitive_style, text_style,
+};
+use embedded_graphics as eg;
+use heapless::consts::*;
+use heapless::String;
+use wio::entry;
+use wio::hal::adc::{FreeRunning, InterruptAdc};
+use wio::hal::clock::GenericClockController;
+use wio::hal::delay::Delay;
+use wio::hal::time::Hertz;
+use wio::pac::{interrupt, CorePeripherals, Peripherals, ADC1};
+use wio::prelude::*;
+use wio::Pins;
+use wio_dsp::{Decimator, LevelMeter, LevelReading, Weighting, WeightingFilter};
+use wio_examples::acquisition::{Acquisition, Writer};
+use wio_examples::shared::Shared;
+use wio_examples::timebase::{self, Instant, TimeBase};
+
+// ADCの割り込みハンドラが使うリソース
+struct AdcCtx {
+    adc: InterruptAdc<ADC1, FreeRunning>,
+    samples: Writer<'static, f32, Instant, BLOCK_SIZE, 4>, // 間引いたサンプルの書き込み側
+    timebase: TimeBase,                           // ブロックの時刻を記録する
+    decimator: Decimator<DECIMATOR_TAPS>,         // 折り返しを防ぎながら間引く
+}
+
+static ADC_CTX: Shared<AdcCtx> = Shared::new();
+// 騒音計はサンプルを欠かさずに処理したいので、バッファを多めに用意する
+static SAMPLES: Acquisition<f32, Instant, BLOCK_SIZE, 4> =
+    Acquisition::new(0.0, Instant::from_ticks(0));
+
+const DECIMATION_FACTOR: usize = 4; // ADCのサンプルを間引く割合
+const DECIMATOR_TAPS: usize = 32; // 間引く前にかける低域通過フィルタのタップ数
+const BLOCK_SIZE: usize = 256;
+const ADC_SAMPLING_RATE: f32 = 83333.0; // ADCのサンプリングレート
+// 間引いた後のサンプリングレート
+const SAMPLING_RATE: f32 = ADC_SAMPLING_RATE / DECIMATION_FACTOR as f32;
+const ADC_CENTER: f32 = 2048.0; // 無音のときのADCの値
+// ADCのフルスケールを音圧レベルに換算する較正値 [dB]
+// 実際の騒音計と並べて同じ値になるように調整してください
+const CALIBRATION_DB: f32 = 100.0;
+const WINDOW_MS: u32 = 125; // 騒音計の FAST 特性に相当する
+
+// 棒グラフの範囲 [dB]
+const BAR_MIN_DB: f32 = 30.0;
+const BAR_MAX_DB: f32 = 110.0;
+const BAR_LEFT: i32 = 10;
+const BAR_TOP: i32 = 150;
+const BAR_WIDTH: i32 = 300;
+const BAR_HEIGHT: i32 = 24;
+// ピークホールドを保持する窓の数と、その後に下げる量 [dB/窓]
+const PEAK_HOLD_WINDOWS: u32 = 12;
+const PEAK_DECAY_DB: f32 = 2.5;
+
+// レベル [dB] に対応する棒グラフの x 座標
+fn bar_x(level_db: f32) -> i32 {
+    let ratio = (level_db - BAR_MIN_DB) / (BAR_MAX_DB - BAR_MIN_DB);
+    let ratio = if ratio < 0.0 {
+        0.0
+    } else if ratio > 1.0 {
+        1.0
+    } else {
+        ratio
+    };
+    BAR_LEFT + (ratio * BAR_WIDTH as f32) as i32
+}
+
+// 棒グラフの目盛りを描画する
+fn draw_scale<D: DrawTarget<Rgb565>>(display: &mut D) -> Result<(), D::Error> {
+    let mut text = String::<U8>::new();
+    let mut level = BAR_MIN_DB as i32;
+    while level <= BAR_MAX_DB as i32 {
+        let x = bar_x(level as f32);
+        egline!(
+            start = (x, BAR_TOP + BAR_HEIGHT + 2),
+            end = (x, BAR_TOP + BAR_HEIGHT + 6),
+            style = primitive_style!(stroke_color = Rgb565::WHITE, stroke_width = 1)
+        )
+        .draw(display)?;
+        text.clear();
+        write!(text, "{}", level).ok();
+        egtext!(
+            text = text.as_str(),
+            top_left = (x - text.len() as i32 * 3, BAR_TOP + BAR_HEIGHT + 8),
+            style = text_style!(font = Font6x8, text_color = Rgb565::WHITE)
+        )
+        .draw(display)?;
+        level += 10;
+    }
+    Ok(())
+}
+
+// 数値と棒グラフを描画する
+fn draw_reading<D: DrawTarget<Rgb565>>(
+    display: &mut D,
+    reading: &LevelReading,
+    peak_hold_db: f32,
+    weighting: Weighting,
+) -> Result<(), D::Error> {
+    // 大きな数値 "62.3 dB(A)"
+    egrectangle!(
+        top_left = (0, 30),
+        bottom_right = (319, 120),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(display)?;
+    let mut text = String::<U32>::new();
+    write!(text, "{:5.1}", reading.rms_db).ok();
+    egtext!(
+        text = text.as_str(),
+        top_left = (40, 40),
+        style = text_style!(font = Font24x32, text_color = Rgb565::WHITE)
+    )
+    .draw(display)?;
+    text.clear();
+    write!(text, "dB({})", weighting.name()).ok();
+    egtext!(
+        text = text.as_str(),
+        top_left = (170, 56),
+        style = text_style!(font = Font12x16, text_color = Rgb565::WHITE)
+    )
+    .draw(display)?;
+    text.clear();
+    write!(text, "Leq {:.1}  Peak {:.1}", reading.leq_db, reading.peak_db).ok();
+    egtext!(
+        text = text.as_str(),
+        top_left = (20, 96),
+        style = text_style!(font = Font12x16, text_color = Rgb565::YELLOW)
+    )
+    .draw(display)?;
+
+    // 実効値の棒グラフとピークホールド
+    let level_x = bar_x(reading.rms_db);
+    let peak_x = bar_x(peak_hold_db);
+    egrectangle!(
+        top_left = (BAR_LEFT, BAR_TOP),
+        bottom_right = (level_x, BAR_TOP + BAR_HEIGHT),
+        style = primitive_style!(fill_color = Rgb565::GREEN)
+    )
+    .draw(display)?;
+    egrectangle!(
+        top_left = (level_x + 1, BAR_TOP),
+        bottom_right = (BAR_LEFT + BAR_WIDTH, BAR_TOP + BAR_HEIGHT),
+        style = primitive_style!(fill_color = Rgb565::new(4, 8, 4))
+    )
+    .draw(display)?;
+    egrectangle!(
+        top_left = (peak_x - 1, BAR_TOP),
+        bottom_right = (peak_x + 1, BAR_TOP + BAR_HEIGHT),
+        style = primitive_style!(fill_color = Rgb565::RED)
+    )
+    .draw(display)?;
+    Ok(())
+}
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let core = CorePeripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+
+    let mut sets = Pins::new(peripherals.PORT).split();
+    let mut delay = Delay::new(core.SYST, &mut clocks);
+
+    // フリーランニングモードでADCを動かす
+    let (microphone_adc, mut microphone_pin) = sets.microphone.init(
+        peripherals.ADC1,
+        &mut clocks,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+    let mut microphone_adc: InterruptAdc<_, FreeRunning> = InterruptAdc::from(microphone_adc);
+    microphone_adc.start_conversion(&mut microphone_pin);
+
+    // デバッグ用UARTを初期化する
+    let mut serial = sets.uart.init(
+        &mut clocks,
+        Hertz(115200u32),
+        peripherals.SERCOM2,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+
+    // 画面を初期化する
+    // 光センサがつながっているADC1はマイクの音の取り込みに使うので、バックライトは点灯したままにする
+    let (mut display, _backlight) = sets
+        .display
+        .init(
+            &mut clocks,
+            peripherals.SERCOM7,
+            &mut peripherals.MCLK,
+            &mut sets.port,
+            60.mhz(),
+            &mut delay,
+        )
+        .unwrap();
+    egrectangle!(
+        top_left = (0, 0),
+        bottom_right = (319, 239),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(&mut display)
+    .unwrap();
+    draw_scale(&mut display).unwrap();
+
+    // ブロックの時刻を記録するために、XOSC32Kを基準にしたGCLK6でTC2/TC3を動かす
+    let gclk6 = clocks
+        .configure_gclk_divider_and_source(
+            wio::pac::gclk::pchctrl::GEN_A::GCLK6,
+            1,
+            wio::pac::gclk::genctrl::SRC_A::XOSC32K,
+            false,
+        )
+        .unwrap();
+    let timer_clock = clocks.tc2_tc3(&gclk6).unwrap();
+    let timebase = TimeBase::new(
+        peripherals.TC2,
+        peripherals.TC3,
+        &mut peripherals.MCLK,
+        &timer_clock,
+    );
+
+    let (writer, mut samples) = SAMPLES.split().unwrap();
+    ADC_CTX.init(AdcCtx {
+        adc: microphone_adc,
+        samples: writer,
+        timebase,
+        decimator: Decimator::lowpass(DECIMATION_FACTOR),
+    });
+
+    let button_reset = sets.buttons.button1.into_floating_input(&mut sets.port);
+    let button_weighting = sets.buttons.button3.into_floating_input(&mut sets.port);
+
+    let mut filter = WeightingFilter::new(Weighting::A, SAMPLING_RATE);
+    let mut meter = LevelMeter::new(SAMPLING_RATE, WINDOW_MS);
+    meter.set_calibration(CALIBRATION_DB);
+    let mut peak_hold_db = BAR_MIN_DB;
+    let mut peak_hold_windows = 0;
+    let mut weighting_pressed = false;
+    let mut reported_overruns = 0;
+
+    writeln!(&mut serial, "start").unwrap();
+    unsafe {
+        NVIC::unmask(interrupt::ADC1_RESRDY);
+    }
+
+    loop {
+        // 重み付けを切り替えたら、フィルタの状態と Leq をやり直す
+        let pressed = button_weighting.is_low().unwrap();
+        if pressed && !weighting_pressed {
+            let index = Weighting::ALL
+                .iter()
+                .position(|&w| w == filter.weighting())
+                .unwrap_or(0);
+            let weighting = Weighting::ALL[(index + 1) % Weighting::ALL.len()];
+            filter = WeightingFilter::new(weighting, SAMPLING_RATE);
+            meter.reset_leq();
+        }
+        weighting_pressed = pressed;
+        if button_reset.is_low().unwrap() {
+            meter.reset_leq();
+        }
+
+        let overruns = samples.overruns();
+        if overruns != reported_overruns {
+            writeln!(&mut serial, "overruns: {}", overruns).ok();
+            reported_overruns = overruns;
+        }
+
+        let mut block = match samples.read() {
+            Some(block) => block,
+            None => continue,
+        };
+        // ADCの値を -1.0〜1.0 にしてから重み付けする (直流分はフィルタで除かれる)
+        for sample in block.iter_mut() {
+            *sample = (*sample - ADC_CENTER) / ADC_CENTER;
+        }
+        filter.process_block(block.samples_mut());
+        let reading = meter.process(block.samples());
+        drop(block);
+
+        if let Some(reading) = reading {
+            if reading.peak_db >= peak_hold_db {
+                peak_hold_db = reading.peak_db;
+                peak_hold_windows = PEAK_HOLD_WINDOWS;
+            } else if peak_hold_windows > 0 {
+                peak_hold_windows -= 1;
+            } else {
+                peak_hold_db -= PEAK_DECAY_DB;
+            }
+            draw_reading(&mut display, &reading, peak_hold_db, filter.weighting()).unwrap();
+        }
+    }
+}
+
+#[interrupt]
+fn ADC1_RESRDY() {
+    ADC_CTX.lock(|ctx| {
+        if let Some(sample) = ctx.adc.service_interrupt_ready() {
+            // DECIMATION_FACTOR 個ごとにフィルタの出力が得られる
+            if let Some(sample) = ctx.decimator.push(sample as f32) {
+                // 空きバッファがないときはブロック単位で捨てられ、オーバーランとして数えられる
+                let now = ctx.timebase.now();
+                ctx.samples.push(sample, now);
+            }
+        }
+    });
+}
+
+// TC2の割り込みハンドラ (時刻を数える32bitカウンタがオーバーフローしたときに呼ばれる)
+#[interrupt]
+fn TC2() {
+    timebase::on_interrupt();
+}
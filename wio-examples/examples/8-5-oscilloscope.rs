@@ -0,0 +1,473 @@
+//! 8-5 オシロスコープをつくる
+//! マイク・光センサ・Groveの A0 ピンのどれかをADCで読み、トリガをかけて波形を表示します
+//!
+//! - ボタン3: 入力を切り替える (MIC → LIGHT → A0)
+//! - ボタン2: トリガのエッジを切り替える (立ち上がり / 立ち下がり)
+//! - ボタン1: トリガのモードを切り替える (AUTO → NORMAL → SINGLE)
+//! - 5方向スイッチの左右: 調整する項目を選ぶ (時間軸 / 電圧軸 / トリガレベル / ホールドオフ)
+//! - 5方向スイッチの上下: 選んだ項目を調整する
+//! - 5方向スイッチの押し込み: SINGLE モードでもう一度取り込む
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example 8-5-oscilloscope --features app --release
+//! ```
+
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+use wio_terminal as wio;
+
+use core::fmt::Write;
+use cortex_m::peripheral::NVIC;
+use eg::{
+    egrectangle, egtext, fonts::Font6x8, pixelcolor::Rgb565, prelude::*, primitive_style,
+    text_style,
+};
+use embedded_graphics as eg;
+use heapless::consts::*;
+use heapless::String;
+use wio::entry;
+use wio::hal::adc::{FreeRunning, InterruptAdc};
+use wio::hal::clock::GenericClockController;
+use wio::hal::delay::Delay;
+use wio::hal::time::Hertz;
+use wio::pac::{interrupt, CorePeripherals, Peripherals, ADC1};
+use wio::prelude::*;
+use wio::Pins;
+use wio_examples::shared::Shared;
+use wio_examples::timebase::{self, TimeBase};
+use wio_examples::{Button, ButtonEvent};
+use wio_scope::{Capture, CaptureState, Edge, Screen, TriggerMode, VerticalScale};
+
+// ADCの割り込みハンドラが使うリソース
+struct AdcCtx {
+    adc: InterruptAdc<ADC1, FreeRunning>,
+    capture: Capture<FRAME_LENGTH>, // トリガを待って1画面分を取り込む
+}
+
+static ADC_CTX: Shared<AdcCtx> = Shared::new();
+
+const FRAME_LENGTH: usize = 300; // 1画面分のサンプル数 (横10マス)
+const ADC_SAMPLING_RATE: u32 = 83333; // ADCのサンプリングレート
+// 時間軸の切り替え (ADCのサンプルを何個に1個取り込むか)
+// 1マス30サンプルなので、0.36[ms/div]〜360[ms/div]
+const DECIMATIONS: [u32; 10] = [1, 2, 4, 10, 20, 40, 100, 200, 400, 1000];
+// ホールドオフの切り替え [ms]
+const HOLDOFFS_MS: [u32; 8] = [0, 1, 2, 5, 10, 20, 50, 100];
+// オートモードでトリガがかからないときに表示する間隔 [ms]
+const AUTO_TIMEOUT_MS: u32 = 100;
+const LEVEL_STEP: u16 = 32; // トリガレベルを1回に動かす量 (ADCの値)
+
+const TEXT_COLOR: Rgb565 = Rgb565::WHITE;
+const SELECTED_COLOR: Rgb565 = Rgb565::new(0, 20, 16);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Input {
+    Microphone,
+    LightSensor,
+    GroveA0,
+}
+
+impl Input {
+    fn next(self) -> Self {
+        match self {
+            Input::Microphone => Input::LightSensor,
+            Input::LightSensor => Input::GroveA0,
+            Input::GroveA0 => Input::Microphone,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Input::Microphone => "MIC",
+            Input::LightSensor => "LIGHT",
+            Input::GroveA0 => "A0",
+        }
+    }
+}
+
+// 5方向スイッチの上下で調整する項目
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Setting {
+    Timebase,
+    Volts,
+    Level,
+    Holdoff,
+}
+
+impl Setting {
+    const ALL: [Setting; 4] = [
+        Setting::Timebase,
+        Setting::Volts,
+        Setting::Level,
+        Setting::Holdoff,
+    ];
+}
+
+// 画面に表示する設定
+struct Settings {
+    input: Input,
+    mode: TriggerMode,
+    edge: Edge,
+    decimation: usize, // DECIMATIONS の添字
+    holdoff: usize,    // HOLDOFFS_MS の添字
+    level: u16,
+    scale: VerticalScale,
+    selected: usize, // Setting::ALL の添字
+}
+
+impl Settings {
+    // 設定を取り込みに反映する
+    fn apply(&self, capture: &mut Capture<FRAME_LENGTH>) {
+        let decimation = DECIMATIONS[self.decimation];
+        let rate = ADC_SAMPLING_RATE / decimation; // 取り込むサンプルのレート
+        if capture.decimation() != decimation {
+            capture.set_decimation(decimation);
+        }
+        capture.set_level(self.level);
+        capture.set_holdoff(HOLDOFFS_MS[self.holdoff] * rate / 1000);
+        capture.set_auto_timeout((AUTO_TIMEOUT_MS * rate / 1000).max(FRAME_LENGTH as u32));
+        if capture.edge() != self.edge {
+            capture.set_edge(self.edge);
+        }
+        if capture.mode() != self.mode {
+            capture.set_mode(self.mode);
+        }
+    }
+
+    // 選んでいる項目を step だけ動かす
+    fn adjust(&mut self, step: i32) {
+        match Setting::ALL[self.selected] {
+            Setting::Timebase => {
+                self.decimation = offset_index(self.decimation, step, DECIMATIONS.len())
+            }
+            Setting::Volts => self.scale.step(step),
+            Setting::Level => {
+                let level = self.level as i32 + step * LEVEL_STEP as i32;
+                self.level = level.max(0).min(4095) as u16;
+            }
+            Setting::Holdoff => self.holdoff = offset_index(self.holdoff, step, HOLDOFFS_MS.len()),
+        }
+    }
+}
+
+fn offset_index(index: usize, step: i32, len: usize) -> usize {
+    (index as i32 + step).max(0).min(len as i32 - 1) as usize
+}
+
+// 1マスあたりの時間を "360us/div" "1.2ms/div" のような文字列にする
+fn write_time_per_div(text: &mut String<U16>, decimation: u32) {
+    let samples = (FRAME_LENGTH as u32 / Screen::DIVISIONS_X * decimation) as u64;
+    let micros = samples * 1_000_000 / ADC_SAMPLING_RATE as u64;
+    if micros < 1000 {
+        write!(text, "{}us/div", micros).ok();
+    } else if micros < 10_000 {
+        write!(text, "{}.{}ms/div", micros / 1000, micros % 1000 / 100).ok();
+    } else {
+        write!(text, "{}ms/div", micros / 1000).ok();
+    }
+}
+
+// 上部に入力・モード・状態、下部に調整する項目を表示する
+fn draw_status<D: DrawTarget<Rgb565>>(
+    display: &mut D,
+    settings: &Settings,
+    state: CaptureState,
+    forced: bool,
+) -> Result<(), D::Error> {
+    let mut text = String::<U16>::new();
+    let mode = match settings.mode {
+        TriggerMode::Auto => "AUTO",
+        TriggerMode::Normal => "NORMAL",
+        TriggerMode::Single => "SINGLE",
+    };
+    let edge = match settings.edge {
+        Edge::Rising => "RISE",
+        Edge::Falling => "FALL",
+    };
+    // オートモードでトリガがかからずに表示しているときは知らせる
+    let status = if state == CaptureState::Stopped {
+        "STOP"
+    } else if forced {
+        "NO TRIG"
+    } else {
+        "RUN"
+    };
+    egrectangle!(
+        top_left = (0, 0),
+        bottom_right = (319, 15),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(display)?;
+    for (i, label) in [settings.input.name(), mode, edge, status].iter().enumerate() {
+        egtext!(
+            text = label,
+            top_left = (9 + i as i32 * 80, 4),
+            style = text_style!(font = Font6x8, text_color = TEXT_COLOR)
+        )
+        .draw(display)?;
+    }
+
+    for (i, setting) in Setting::ALL.iter().enumerate() {
+        text.clear();
+        match setting {
+            Setting::Timebase => write_time_per_div(&mut text, DECIMATIONS[settings.decimation]),
+            Setting::Volts => {
+                write!(text, "{}mV/div", settings.scale.millivolts_per_div).ok();
+            }
+            Setting::Level => {
+                write!(text, "T {}mV", settings.scale.millivolts(settings.level)).ok();
+            }
+            Setting::Holdoff => {
+                write!(text, "HO {}ms", HOLDOFFS_MS[settings.holdoff]).ok();
+            }
+        }
+        let x = i as i32 * 80;
+        let background = if i == settings.selected {
+            SELECTED_COLOR
+        } else {
+            Rgb565::BLACK
+        };
+        egrectangle!(
+            top_left = (x, 222),
+            bottom_right = (x + 79, 239),
+            style = primitive_style!(fill_color = background)
+        )
+        .draw(display)?;
+        egtext!(
+            text = text.as_str(),
+            top_left = (x + 9, 227),
+            style = text_style!(font = Font6x8, text_color = TEXT_COLOR)
+        )
+        .draw(display)?;
+    }
+    Ok(())
+}
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let core = CorePeripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+
+    let mut sets = Pins::new(peripherals.PORT).split();
+    let mut delay = Delay::new(core.SYST, &mut clocks);
+
+    // 光センサとマイクとGroveの A0 (PB08) はどれもADC1につながっている
+    // 変換するピンを切り替えて使う
+    let (adc, mut light_pin) = sets.light_sensor.init(
+        peripherals.ADC1,
+        &mut clocks,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+    let mut microphone_pin = sets.microphone.mic.into_function_b(&mut sets.port);
+    let mut grove_pin = sets.header_pins.a0_d0.into_function_b(&mut sets.port);
+    let mut adc: InterruptAdc<_, FreeRunning> = InterruptAdc::from(adc);
+    adc.start_conversion(&mut microphone_pin);
+
+    // デバッグ用UARTを初期化する
+    let mut serial = sets.uart.init(
+        &mut clocks,
+        Hertz(115200u32),
+        peripherals.SERCOM2,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+
+    // 画面を初期化する
+    // 光センサがつながっているADC1は波形の取り込みに使うので、バックライトは点灯したままにする
+    let (mut display, _backlight) = sets
+        .display
+        .init(
+            &mut clocks,
+            peripherals.SERCOM7,
+            &mut peripherals.MCLK,
+            &mut sets.port,
+            60.mhz(),
+            &mut delay,
+        )
+        .unwrap();
+    egrectangle!(
+        top_left = (0, 0),
+        bottom_right = (319, 239),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(&mut display)
+    .unwrap();
+
+    // ボタンの時刻を数えるために、XOSC32Kを基準にしたGCLK6でTC2/TC3を動かす
+    let gclk6 = clocks
+        .configure_gclk_divider_and_source(
+            wio::pac::gclk::pchctrl::GEN_A::GCLK6,
+            1,
+            wio::pac::gclk::genctrl::SRC_A::XOSC32K,
+            false,
+        )
+        .unwrap();
+    let timer_clock = clocks.tc2_tc3(&gclk6).unwrap();
+    let timebase = TimeBase::new(
+        peripherals.TC2,
+        peripherals.TC3,
+        &mut peripherals.MCLK,
+        &timer_clock,
+    );
+
+    let port = &mut sets.port;
+    let mut button_mode = Button::new(sets.buttons.button1.into_floating_input(port));
+    let mut button_edge = Button::new(sets.buttons.button2.into_floating_input(port));
+    let mut button_input = Button::new(sets.buttons.button3.into_floating_input(port));
+    let mut switch_up = Button::new(sets.buttons.switch_x.into_floating_input(port));
+    let mut switch_down = Button::new(sets.buttons.switch_u.into_floating_input(port));
+    let mut switch_left = Button::new(sets.buttons.switch_y.into_floating_input(port));
+    let mut switch_right = Button::new(sets.buttons.switch_z.into_floating_input(port));
+    let mut switch_press = Button::new(sets.buttons.switch_b.into_floating_input(port));
+
+    let mut settings = Settings {
+        input: Input::Microphone,
+        mode: TriggerMode::Auto,
+        edge: Edge::Rising,
+        decimation: 2,
+        holdoff: 0,
+        level: 2048,
+        scale: VerticalScale::new(200),
+        selected: 0,
+    };
+    let mut capture = Capture::new(settings.level);
+    settings.apply(&mut capture);
+    ADC_CTX.init(AdcCtx { adc, capture });
+
+    let screen = Screen::new(Point::new(9, 19), Size::new(30, 25));
+    let mut frame = [0u16; FRAME_LENGTH];
+    let mut trigger_index = None;
+    let mut forced = false;
+    let mut changed = true;
+
+    writeln!(&mut serial, "start").unwrap();
+    unsafe {
+        NVIC::unmask(interrupt::ADC1_RESRDY);
+    }
+
+    loop {
+        let now = timebase.now().since_start().as_millis() as u32;
+        button_mode.update(now);
+        button_edge.update(now);
+        button_input.update(now);
+        switch_up.update(now);
+        switch_down.update(now);
+        switch_left.update(now);
+        switch_right.update(now);
+        switch_press.update(now);
+
+        let mut rearm = false;
+        if button_mode.next_event() == Some(ButtonEvent::Pressed) {
+            settings.mode = match settings.mode {
+                TriggerMode::Auto => TriggerMode::Normal,
+                TriggerMode::Normal => TriggerMode::Single,
+                TriggerMode::Single => TriggerMode::Auto,
+            };
+            changed = true;
+        }
+        if button_edge.next_event() == Some(ButtonEvent::Pressed) {
+            settings.edge = settings.edge.toggled();
+            changed = true;
+        }
+        if switch_press.next_event() == Some(ButtonEvent::Pressed) {
+            rearm = true;
+            changed = true;
+        }
+        if switch_left.next_event() == Some(ButtonEvent::Pressed) {
+            settings.selected = offset_index(settings.selected, -1, Setting::ALL.len());
+            changed = true;
+        }
+        if switch_right.next_event() == Some(ButtonEvent::Pressed) {
+            settings.selected = offset_index(settings.selected, 1, Setting::ALL.len());
+            changed = true;
+        }
+        // 上下は押し続けると繰り返し動かす
+        match switch_up.next_event() {
+            Some(ButtonEvent::Pressed) | Some(ButtonEvent::Repeat) => {
+                settings.adjust(1);
+                changed = true;
+            }
+            _ => {}
+        }
+        match switch_down.next_event() {
+            Some(ButtonEvent::Pressed) | Some(ButtonEvent::Repeat) => {
+                settings.adjust(-1);
+                changed = true;
+            }
+            _ => {}
+        }
+
+        let input_changed = button_input.next_event() == Some(ButtonEvent::Pressed);
+        if input_changed {
+            settings.input = settings.input.next();
+            changed = true;
+        }
+
+        let (taken, state) = ADC_CTX.lock(|ctx| {
+            if input_changed {
+                // 変換するピンを切り替えて、取り込みをやり直す
+                ctx.adc.stop_conversion();
+                match settings.input {
+                    Input::Microphone => ctx.adc.start_conversion(&mut microphone_pin),
+                    Input::LightSensor => ctx.adc.start_conversion(&mut light_pin),
+                    Input::GroveA0 => ctx.adc.start_conversion(&mut grove_pin),
+                }
+                ctx.capture.arm();
+            }
+            if changed {
+                settings.apply(&mut ctx.capture);
+            }
+            if rearm {
+                ctx.capture.arm();
+            }
+            let taken = ctx.capture.take_frame(&mut frame);
+            (taken, ctx.capture.state())
+        });
+
+        if let Some(info) = taken {
+            trigger_index = Some(info.trigger_index);
+            forced = info.forced;
+        }
+        if taken.is_some() || changed {
+            // SINGLE モードで止まっている間は、最後に取り込んだ波形を表示し続ける
+            screen
+                .draw(
+                    &mut display,
+                    &frame,
+                    &settings.scale,
+                    trigger_index,
+                    Some(settings.level),
+                )
+                .unwrap();
+            draw_status(&mut display, &settings, state, forced).unwrap();
+            changed = false;
+        }
+    }
+}
+
+#[interrupt]
+fn ADC1_RESRDY() {
+    ADC_CTX.lock(|ctx| {
+        if let Some(sample) = ctx.adc.service_interrupt_ready() {
+            // 1画面分そろうと、取り出されるまでは読み捨てる
+            ctx.capture.push(sample);
+        }
+    });
+}
+
+// TC2の割り込みハンドラ (時刻を数える32bitカウンタがオーバーフローしたときに呼ばれる)
+#[interrupt]
+fn TC2() {
+    timebase::on_interrupt();
+}
@@ -0,0 +1,315 @@
+//! 8-6 歩数計をつくる
+//! 加速度センサの値から歩数を数え、歩数・ピッチ・歩いた距離を画面に表示します。
+//! Wio Terminalを手に持つか、ポケットに入れて歩いてください。
+//! 6-9 で保存した較正値があれば、それをかけた値を使います。
+//! 画面の明るさは、光センサの値に合わせて自動で変えます (6-10 と同じ)。
+//! 加速度と光センサの値はTC4の割り込みで一定間隔で読み、8-2 のマイクと同じ Acquisition で main() に渡します。
+//!
+//! - ボタン1: 歩数を0に戻す
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example 8-6-pedometer --features app --release
+//! ```
+
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+use wio_terminal as wio;
+
+use accelerometer::{vector::F32x3, Accelerometer};
+use core::fmt::Write;
+use cortex_m::peripheral::NVIC;
+use eg::{
+    egrectangle, egtext,
+    fonts::{Font12x16, Font24x32},
+    pixelcolor::Rgb565,
+    prelude::*,
+    primitive_style, text_style,
+};
+use embedded_graphics as eg;
+use heapless::consts::*;
+use heapless::String;
+use lis3dh::Lis3dh;
+use wio::entry;
+use wio::hal::adc::Adc;
+use wio::hal::clock::GenericClockController;
+use wio::hal::delay::Delay;
+use wio::hal::gpio::{Pa12, Pa13, Pd1, PfB, PfD};
+use wio::hal::sercom::{I2CMaster4, Sercom4Pad0, Sercom4Pad1};
+use wio::hal::time::Hertz;
+use wio::hal::timer::TimerCounter;
+use wio::pac::{interrupt, CorePeripherals, Peripherals, ADC1, TC4};
+use wio::prelude::*;
+use wio::Pins;
+use wio_backlight::{AutoBrightness, Curve};
+use wio_examples::accel_calibration;
+use wio_examples::acquisition::{Acquisition, Writer};
+use wio_examples::backlight::Backlight;
+use wio_examples::clock::CycleClock;
+use wio_examples::shared::Shared;
+use wio_examples::storage::Storage;
+use wio_examples::{Button, ButtonEvent};
+use wio_motion::{Activity, CalibratedAccelerometer, Pedometer};
+
+static BACKLIGHT: Backlight = Backlight::new();
+
+const SAMPLE_INTERVAL_MS: u32 = 20; // 加速度を読む間隔 (50[Hz])
+const LIGHT_INTERVAL_MS: u32 = 100; // 光センサを読む間隔
+const ACCEL_BLOCK: usize = 5; // 加速度は5個 (100[ms]分) ずつ main() に渡す
+
+type Accel =
+    CalibratedAccelerometer<Lis3dh<I2CMaster4<Sercom4Pad0<Pa13<PfD>>, Sercom4Pad1<Pa12<PfD>>>>>;
+
+// TC4の割り込みハンドラが使うリソース
+struct SensorCtx {
+    timer: TimerCounter<TC4>,
+    accel: Accel,
+    adc: Adc<ADC1>,
+    light: Pd1<PfB>,
+    accel_samples: Writer<'static, F32x3, u32, ACCEL_BLOCK, 2>,
+    light_samples: Writer<'static, u16, u32, 1, 2>,
+    last_accel: F32x3, // 読めなかったときに代わりに使う値
+    now_ms: u32,       // 割り込みの回数から求めた時刻
+}
+
+static SENSOR_CTX: Shared<SensorCtx> = Shared::new();
+// 時刻は割り込みの回数から求めた [ms] なので、時刻の型は u32 にする
+static ACCEL_SAMPLES: Acquisition<F32x3, u32, ACCEL_BLOCK, 2> =
+    Acquisition::new(F32x3 { x: 0.0, y: 0.0, z: 0.0 }, 0);
+// 光センサは読むたびに渡す (1個ずつのブロック)
+static LIGHT_SAMPLES: Acquisition<u16, u32, 1, 2> = Acquisition::new(0, 0);
+const REFRESH_INTERVAL_MS: u32 = 1000; // 歩数が変わらなくても表示を更新する間隔
+const STRIDE_CM: u32 = 70; // 歩幅 (距離の目安に使う)
+
+const SCREEN_WIDTH: i32 = 320;
+
+// 歩数を大きく、その下にピッチ・状態・距離を描画する
+fn draw<T>(display: &mut T, steps: u32, cadence: f32, activity: Activity) -> Result<(), T::Error>
+where
+    T: DrawTarget<Rgb565>,
+{
+    const FONT_WIDTH: i32 = 24;
+    const SMALL_FONT_WIDTH: i32 = 12;
+    egrectangle!(
+        top_left = (0, 60),
+        bottom_right = (SCREEN_WIDTH - 1, 199),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(display)?;
+
+    // 歩数は中央寄せで描画する
+    let mut text = String::<U32>::new();
+    write!(text, "{}", steps).ok();
+    let left = (SCREEN_WIDTH - text.len() as i32 * FONT_WIDTH) / 2;
+    egtext!(
+        text = text.as_str(),
+        top_left = (left, 60),
+        style = text_style!(font = Font24x32, text_color = Rgb565::WHITE)
+    )
+    .draw(display)?;
+    let label = "STEPS";
+    egtext!(
+        text = label,
+        top_left = ((SCREEN_WIDTH - label.len() as i32 * SMALL_FONT_WIDTH) / 2, 100),
+        style = text_style!(font = Font12x16, text_color = Rgb565::CYAN)
+    )
+    .draw(display)?;
+
+    let color = match activity {
+        Activity::Still => Rgb565::WHITE,
+        Activity::Walking => Rgb565::GREEN,
+        Activity::Running => Rgb565::YELLOW,
+    };
+    egtext!(
+        text = activity.name(),
+        top_left = (20, 140),
+        style = text_style!(font = Font12x16, text_color = color)
+    )
+    .draw(display)?;
+
+    text.clear();
+    write!(text, "{} steps/min", cadence as u32).ok();
+    egtext!(
+        text = text.as_str(),
+        top_left = (SCREEN_WIDTH - 20 - text.len() as i32 * SMALL_FONT_WIDTH, 140),
+        style = text_style!(font = Font12x16, text_color = Rgb565::WHITE)
+    )
+    .draw(display)?;
+
+    // 距離は歩数に歩幅をかけた目安
+    let meters = steps * STRIDE_CM / 100;
+    text.clear();
+    write!(text, "{}.{:02} km", meters / 1000, meters % 1000 / 10).ok();
+    egtext!(
+        text = text.as_str(),
+        top_left = (SCREEN_WIDTH - 20 - text.len() as i32 * SMALL_FONT_WIDTH, 170),
+        style = text_style!(font = Font12x16, text_color = Rgb565::WHITE)
+    )
+    .draw(display)?;
+    Ok(())
+}
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let mut core = CorePeripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+    let core_frequency: Hertz = clocks.gclk0().into();
+    let mut clock = CycleClock::new(&mut core.DCB, &mut core.DWT, core_frequency);
+
+    let mut sets = Pins::new(peripherals.PORT).split();
+    let mut delay = Delay::new(core.SYST, &mut clocks);
+    let mut button_reset = Button::new(sets.buttons.button1.into_floating_input(&mut sets.port));
+
+    // 加速度センサを 6-7 と同じように初期化して、保存してある較正値をかける
+    let accel = sets.accelerometer.init(
+        &mut clocks,
+        peripherals.SERCOM4,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+    let storage = Storage::new(peripherals.NVMCTRL);
+    let accel = accel_calibration::calibrated(accel, &storage);
+
+    let (adc, light) = sets.light_sensor.init(
+        peripherals.ADC1,
+        &mut clocks,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+
+    // 画面を初期化する
+    let (mut display, backlight) = sets
+        .display
+        .init(
+            &mut clocks,
+            peripherals.SERCOM7,
+            &mut peripherals.MCLK,
+            &mut sets.port,
+            60.mhz(),
+            &mut delay,
+        )
+        .unwrap();
+    egrectangle!(
+        top_left = (0, 0),
+        bottom_right = (319, 239),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(&mut display)
+    .unwrap();
+    egtext!(
+        text = "PEDOMETER",
+        top_left = (9, 9),
+        style = text_style!(font = Font12x16, text_color = Rgb565::CYAN)
+    )
+    .draw(&mut display)
+    .unwrap();
+
+    // 2MHzのクロックでTC5を動かし、バックライトのピンをPWMで切り替える
+    let gclk5 = clocks
+        .get_gclk(wio::pac::gclk::pchctrl::GEN_A::GCLK5)
+        .unwrap();
+    let timer_clock = clocks.tc4_tc5(&gclk5).unwrap();
+    let tc5 = TimerCounter::tc5_(&timer_clock, peripherals.TC5, &mut peripherals.MCLK);
+    BACKLIGHT.start(backlight, tc5);
+    let mut brightness = AutoBrightness::new(Curve::default());
+
+    // 同じクロックでTC4を動かし、SAMPLE_INTERVAL_MS ごとにセンサを読む
+    let mut tc4 = TimerCounter::tc4_(&timer_clock, peripherals.TC4, &mut peripherals.MCLK);
+    tc4.start((SAMPLE_INTERVAL_MS * 1000).us());
+    tc4.enable_interrupt();
+    let (accel_writer, mut accel_samples) = ACCEL_SAMPLES.split().unwrap();
+    let (light_writer, mut light_samples) = LIGHT_SAMPLES.split().unwrap();
+    SENSOR_CTX.init(SensorCtx {
+        timer: tc4,
+        accel,
+        adc,
+        light,
+        accel_samples: accel_writer,
+        light_samples: light_writer,
+        last_accel: F32x3 { x: 0.0, y: 0.0, z: 1.0 },
+        now_ms: 0,
+    });
+    unsafe {
+        NVIC::unmask(interrupt::TC4);
+    }
+
+    let mut pedometer = Pedometer::new();
+    let mut drawn_at = clock.now_ms();
+    let mut changed = true;
+    loop {
+        let now = clock.now_ms();
+        button_reset.update(now);
+        if button_reset.next_event() == Some(ButtonEvent::Pressed) {
+            pedometer.reset();
+            changed = true;
+        }
+
+        // ブロックの時刻は先頭のサンプルのもので、以降は SAMPLE_INTERVAL_MS ずつ進む
+        while let Some(block) = accel_samples.read() {
+            let started_at = block.timestamp();
+            for (i, sample) in block.iter().enumerate() {
+                let at = started_at.wrapping_add(i as u32 * SAMPLE_INTERVAL_MS);
+                if pedometer.update(*sample, at).is_some() {
+                    changed = true;
+                }
+            }
+        }
+
+        while let Some(block) = light_samples.read() {
+            if let Some(level) = brightness.update(block[0], block.timestamp()) {
+                BACKLIGHT.set_level(level);
+            }
+        }
+
+        // ピッチと状態は歩数が変わらなくても変わるので、ときどき描き直す
+        if changed || now.wrapping_sub(drawn_at) >= REFRESH_INTERVAL_MS {
+            draw(
+                &mut display,
+                pedometer.steps(),
+                pedometer.cadence(),
+                pedometer.activity(),
+            )
+            .unwrap();
+            drawn_at = now;
+            changed = false;
+        }
+    }
+}
+
+// SAMPLE_INTERVAL_MS ごとに加速度を、LIGHT_INTERVAL_MS ごとに光センサを読む
+// 空きバッファがないときはブロック単位で捨てられ、オーバーランとして数えられる
+#[interrupt]
+fn TC4() {
+    SENSOR_CTX.lock(|ctx| {
+        ctx.timer.wait().ok();
+        let now = ctx.now_ms;
+        ctx.now_ms = now.wrapping_add(SAMPLE_INTERVAL_MS);
+
+        // 読めなかったときは直前の値で埋めて、サンプルの間隔を保つ
+        if let Ok(sample) = ctx.accel.accel_norm() {
+            ctx.last_accel = sample;
+        }
+        ctx.accel_samples.push(ctx.last_accel, now);
+
+        if now % LIGHT_INTERVAL_MS == 0 {
+            let value: Result<u16, ()> = nb::block!(ctx.adc.read(&mut ctx.light));
+            if let Ok(value) = value {
+                ctx.light_samples.push(value, now);
+            }
+        }
+    });
+}
+
+#[interrupt]
+fn TC5() {
+    BACKLIGHT.on_interrupt();
+}
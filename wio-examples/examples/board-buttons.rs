@@ -0,0 +1,44 @@
+//! WioBoard トレイトを使ったサンプルコードです。
+//! いずれかのボタンを押している間、ユーザーLEDが点灯してブザーが鳴ります。
+//! 同じ処理はホストPC上の仮想Wio Terminal (wio_board::SimBoard) でも動きます。
+//!
+//! ### 実行方法
+//! ```sh
+//! $ cargo hf2 --example board-buttons --features board
+//! ```
+
+#![no_std]
+#![no_main]
+
+use panic_halt as _;
+use wio_terminal as wio;
+
+use wio::entry;
+use wio::pac::{CorePeripherals, Peripherals};
+use wio_board::{Button, WioBoard};
+use wio_examples::board;
+
+// ボタンの状態をLEDとブザーに反映する
+fn update<B: WioBoard>(board: &mut B, was_pressed: bool) -> bool {
+    let pressed = Button::ALL.iter().any(|b| board.is_pressed(*b));
+    board.set_led(pressed);
+    if pressed && !was_pressed {
+        board.start_tone(880);
+    } else if !pressed && was_pressed {
+        board.stop_tone();
+    }
+    pressed
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let core = CorePeripherals::take().unwrap();
+    let mut board = board::init(peripherals, core);
+
+    let mut pressed = false;
+    loop {
+        pressed = update(&mut board, pressed);
+        board.delay_ms(10);
+    }
+}
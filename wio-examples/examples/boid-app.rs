@@ -1,9 +1,18 @@
-//! 7-4 Wio TerminalのLCDにHello World!のサンプルコードです。
-//! Wio Terminal の LCD に `Hello Wio Terminal!` と表示します。
+//! Wio TerminalのLCDでボイド (鳥の群れ) を動かすサンプルコードです。
+//! Wio Terminalを傾けると、低い方へ風が吹いたように群れが流されます。
+//!
+//! - ボタン1: 次の群れの動き方にする (CALM → SWARM → SCATTER)
+//! - ボタン3: 前の群れの動き方にする
+//! - ボタン2: 群れを最初の位置に戻す
+//! - 5方向スイッチの上下左右: 捕食者 (赤い丸) を動かす。群れは捕食者から逃げる
+//! - 5方向スイッチの押し込み: 捕食者を出す / 消す
+//!
+//! 周辺機能は WioBoard を通して使うので、6-9 で保存した較正値をかけた加速度で傾きを求めます。
+//! 画面の明るさは、光センサの値に合わせて自動で変えます (6-10 と同じ)。
 //!
 //! ### 実行方法
 //! ```sh
-//! $ cargo hf2 --example 7-4-hello_lcd
+//! $ cargo hf2 --example boid-app --features feature_boid --release
 //! ```
 
 #![no_std]
@@ -13,55 +22,159 @@ use embedded_graphics as eg;
 use panic_halt as _;
 use wio_terminal as wio;
 
-use eg::{pixelcolor::*, prelude::*, primitives::*, style::*};
-use wio::hal::clock::GenericClockController;
-use wio::hal::delay::Delay;
-use wio::pac::{CorePeripherals, Peripherals};
-use wio::prelude::*;
-use wio::{entry, Pins};
 use boid::*;
+use eg::{fonts::*, pixelcolor::*, prelude::*, primitives::*, style::*};
+use wio::entry;
+use wio::hal::timer::TimerCounter;
+use wio::pac::{interrupt, CorePeripherals, Peripherals};
+use wio_backlight::{AutoBrightness, Curve};
+use wio_board::{Button, WioBoard};
+use wio_examples::backlight::Backlight;
+use wio_examples::board::{self, BoardButtons, Rest};
+use wio_examples::ButtonEvent;
+use wio_motion::Motion;
+
+static BACKLIGHT: Backlight = Backlight::new();
+
+const LIGHT_INTERVAL_MS: u32 = 100; // 光センサを読む間隔
+
+// 1[G]傾けたときに、群れに加わる力
+const WIND_FORCE: f32 = 0.0008;
+// 机に置いたときのわずかな傾きでは流されないようにする [G]
+const WIND_DEAD_ZONE: f32 = 0.1;
+const PREDATOR_SPEED: f32 = 0.03; // 捕食者が1回の更新で動く距離
+// 捕食者が動ける範囲 (画面の端まで)
+const PREDATOR_LIMIT_X: f32 = 1.2;
+const PREDATOR_LIMIT_Y: f32 = 0.9;
+
+// 傾き (重力の成分 [G]) を風の強さにする
+fn wind(component: f32) -> f32 {
+    if component.abs() < WIND_DEAD_ZONE {
+        0.0
+    } else {
+        component * WIND_FORCE
+    }
+}
+
+fn clamp(value: f32, limit: f32) -> f32 {
+    value.max(-limit).min(limit)
+}
 
 #[entry]
 fn main() -> ! {
-    let mut peripherals = Peripherals::take().unwrap();
+    let peripherals = Peripherals::take().unwrap();
     let core = CorePeripherals::take().unwrap();
-    let mut clocks = GenericClockController::with_external_32kosc(
-        peripherals.GCLK,
-        &mut peripherals.MCLK,
-        &mut peripherals.OSC32KCTRL,
-        &mut peripherals.OSCCTRL,
-        &mut peripherals.NVMCTRL,
-    );
-    let mut delay = Delay::new(core.SYST, &mut clocks);
-    let mut sets = Pins::new(peripherals.PORT).split();
-
-    // TODO: ディスプレイドライバを初期化する
-    let (mut display, _backlight) = sets
-        .display
-        .init(
-            &mut clocks,
-            peripherals.SERCOM7,
-            &mut peripherals.MCLK,
-            &mut sets.port,
-            58.mhz(),
-            &mut delay,
-        )
+    let (mut board, rest) = board::split(peripherals, core);
+    let Rest {
+        mut clocks,
+        mut mclk,
+        backlight,
+        tc5,
+        ..
+    } = rest;
+
+    // 2MHzのクロックでTC5を動かし、バックライトのピンをPWMで切り替える
+    let gclk5 = clocks
+        .get_gclk(wio::pac::gclk::pchctrl::GEN_A::GCLK5)
         .unwrap();
+    let timer_clock = clocks.tc4_tc5(&gclk5).unwrap();
+    let tc5 = TimerCounter::tc5_(&timer_clock, tc5, &mut mclk);
+    BACKLIGHT.start(backlight, tc5);
 
-    // TODO: LCDを黒色で塗りつぶす
+    run(&mut board, |level| BACKLIGHT.set_level(level))
+}
+
+// WioBoard だけに依存させておき、仮想Wio Terminalでも同じように動かせるようにする
+// バックライトの明るさは set_backlight に渡す (バックライトがなければ何もしない関数を渡す)
+fn run<B: WioBoard>(board: &mut B, mut set_backlight: impl FnMut(u8)) -> ! {
     let style = PrimitiveStyleBuilder::new()
         .fill_color(Rgb565::BLACK).build();
     let background = Rectangle::new(Point::new(0, 0), Point::new(319, 239))
         .into_styled(style);
-    background.draw(&mut display).unwrap();
+    background.draw(board.display()).ok();
 
+    let mut buttons = BoardButtons::new();
+    let mut motion = Motion::new();
     let mut boids: Boids = Boids::new();
     boids.init();
+    let mut preset = Preset::Calm;
+    boids.set_preset(preset);
     let mut renderer = BoidRenderer::new();
+    let mut predator: Option<(f32, f32)> = None;
+    let text_style = TextStyle::new(Font6x8, Rgb565::WHITE);
+    let label_area = Rectangle::new(Point::new(0, 0), Point::new(59, 9))
+        .into_styled(style);
+    let mut brightness = AutoBrightness::new(Curve::default());
+    let mut light_read_at = board.uptime_ms();
 
     loop {
+        let now = board.uptime_ms();
+        buttons.update(board, now);
+
+        if now.wrapping_sub(light_read_at) >= LIGHT_INTERVAL_MS {
+            light_read_at = now;
+            if let Some(level) = brightness.update(board.read_light(), now) {
+                set_backlight(level);
+            }
+        }
+
+        if buttons.next_event(Button::Button1) == Some(ButtonEvent::Pressed) {
+            preset = preset.next();
+            boids.set_preset(preset);
+        }
+        if buttons.next_event(Button::Button3) == Some(ButtonEvent::Pressed) {
+            preset = preset.prev();
+            boids.set_preset(preset);
+        }
+        if buttons.next_event(Button::Button2) == Some(ButtonEvent::Pressed) {
+            boids.init();
+        }
+        if buttons.next_event(Button::SwitchPress) == Some(ButtonEvent::Pressed) {
+            predator = match predator {
+                Some(_) => None,
+                None => Some((0.0, 0.0)),
+            };
+        }
+        // 捕食者は押している間ずっと動かす
+        if let Some((x, y)) = predator.as_mut() {
+            if buttons.is_pressed(Button::SwitchLeft) {
+                *x -= PREDATOR_SPEED;
+            }
+            if buttons.is_pressed(Button::SwitchRight) {
+                *x += PREDATOR_SPEED;
+            }
+            if buttons.is_pressed(Button::SwitchUp) {
+                *y -= PREDATOR_SPEED;
+            }
+            if buttons.is_pressed(Button::SwitchDown) {
+                *y += PREDATOR_SPEED;
+            }
+            *x = clamp(*x, PREDATOR_LIMIT_X);
+            *y = clamp(*y, PREDATOR_LIMIT_Y);
+        }
+        boids.set_predator(predator);
+
+        // 加速度センサのy軸は画面の左、x軸は画面の上を向いている
+        // 重力の成分が負になる (低くなった) 方向へ群れを流す
+        motion.update(board.read_acceleration(), now);
+        let gravity = motion.gravity();
+        boids.set_force(wind(gravity.y), wind(gravity.x));
+
         boids.update();
-        renderer.clear(&mut display).unwrap();
-        renderer.draw(&mut display, &boids).unwrap();
+        renderer.clear(board.display()).ok();
+        renderer.draw(board.display(), &boids).ok();
+
+        // 群れが上を通ると消えるので、毎回描き直す
+        label_area.draw(board.display()).ok();
+        Text::new(preset.name(), Point::new(2, 1))
+            .into_styled(text_style)
+            .draw(board.display())
+            .ok();
+        board.refresh();
     }
 }
+
+#[interrupt]
+fn TC5() {
+    BACKLIGHT.on_interrupt();
+}
@@ -0,0 +1,28 @@
+// 加速度センサの較正値をフラッシュに保存し、読み出して適用します
+//
+//   let storage = Storage::new(peripherals.NVMCTRL);
+//   let accel = accel_calibration::calibrated(accel, &storage);
+//   // 以降の accel.accel_norm() には較正値がかかる
+
+use crate::storage::{Storage, StorageError};
+use accelerometer::Accelerometer;
+use wio_motion::calibration::RECORD_SIZE;
+use wio_motion::{CalibratedAccelerometer, Calibration, CalibrationRecord, RecordError};
+
+// 保存されている較正値を読み出します
+pub fn load(storage: &Storage) -> Result<Calibration, RecordError> {
+    let mut bytes = [0u8; RECORD_SIZE];
+    storage.read(&mut bytes);
+    CalibrationRecord::decode(&bytes)
+}
+
+pub fn save(storage: &mut Storage, calibration: &Calibration) -> Result<(), StorageError> {
+    storage.write(&CalibrationRecord::encode(calibration))
+}
+
+// 保存されている較正値をかける加速度センサにします
+// 較正値が保存されていない (読めない) ときは、何も補正しません
+pub fn calibrated<A: Accelerometer>(accel: A, storage: &Storage) -> CalibratedAccelerometer<A> {
+    let calibration = load(storage).unwrap_or(Calibration::IDENTITY);
+    CalibratedAccelerometer::new(accel, calibration)
+}
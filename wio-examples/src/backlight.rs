@@ -0,0 +1,124 @@
+// 画面のバックライト (PC05) の明るさを変えるドライバです
+// PC05 はPWMを出せるタイマにつながっていないので、TC5の割り込みでピンを切り替えてPWMにします
+//
+// 1周期 (PERIOD_US) を点灯と消灯の2つに分け、それぞれの長さでTC5を動かします
+// 明るさが0か最大に近いときは割り込みを止めて、ピンを消灯か点灯のままにします
+//
+// 使い方:
+//   static BACKLIGHT: Backlight = Backlight::new();
+//   let (display, backlight) = sets.display.init(...).unwrap();
+//   BACKLIGHT.start(backlight, tc5);
+//   BACKLIGHT.set_level(128);
+//
+//   #[interrupt]
+//   fn TC5() { BACKLIGHT.on_interrupt(); }
+//
+// 光センサで明るさを自動で変えるサンプルは 6-10、8-1、8-6、boid-app です
+// 8-2〜8-5 はADC1 (光センサとマイクで共用) を音や波形の取り込みに使うので、点灯したままにしています
+
+use crate::shared::Shared;
+use core::sync::atomic::{AtomicU8, Ordering};
+use cortex_m::peripheral::NVIC;
+use embedded_hal::digital::v2::OutputPin;
+use wio::hal::gpio::{Output, Pc5, PushPull};
+use wio::hal::timer::TimerCounter;
+use wio::pac::{interrupt, TC5};
+use wio::prelude::*;
+use wio_terminal as wio;
+
+// PWMの周期 (ちらつきが見えないように500[Hz]にする)
+const PERIOD_US: u32 = 2000;
+// これより短い点灯・消灯は割り込みが間に合わないので、消灯か点灯のままにする
+const MIN_PULSE_US: u32 = 20;
+
+struct Pwm {
+    pin: Pc5<Output<PushPull>>,
+    timer: TimerCounter<TC5>,
+    on: bool, // いま点灯している区間か
+}
+
+impl Pwm {
+    // 周期の先頭 (点灯する区間) からやり直す
+    fn restart(&mut self, level: u8) {
+        self.on = false;
+        self.timer.enable_interrupt();
+        self.next(level);
+    }
+
+    // 次の区間に進む
+    fn next(&mut self, level: u8) {
+        let on_us = PERIOD_US * level as u32 / u8::MAX as u32;
+        if on_us < MIN_PULSE_US {
+            self.timer.disable_interrupt();
+            self.pin.set_low().ok();
+            return;
+        }
+        if PERIOD_US - on_us < MIN_PULSE_US {
+            self.timer.disable_interrupt();
+            self.pin.set_high().ok();
+            return;
+        }
+        self.on = !self.on;
+        if self.on {
+            self.pin.set_high().ok();
+            self.timer.start(on_us.us());
+        } else {
+            self.pin.set_low().ok();
+            self.timer.start((PERIOD_US - on_us).us());
+        }
+    }
+}
+
+pub struct Backlight {
+    pwm: Shared<Pwm>,
+    level: AtomicU8,
+}
+
+impl Backlight {
+    pub const fn new() -> Self {
+        Backlight {
+            pwm: Shared::new(),
+            level: AtomicU8::new(u8::MAX),
+        }
+    }
+
+    // sets.display.init が返したバックライトのピンと、TC5を渡して動かし始めます
+    pub fn start(&self, pin: Pc5<Output<PushPull>>, timer: TimerCounter<TC5>) {
+        self.pwm.init(Pwm {
+            pin,
+            timer,
+            on: false,
+        });
+        unsafe {
+            NVIC::unmask(interrupt::TC5);
+        }
+        let level = self.level();
+        self.pwm.lock(|pwm| pwm.restart(level));
+    }
+
+    // 明るさ (0: 消灯, 255: 最大)
+    pub fn set_level(&self, level: u8) {
+        if self.level.swap(level, Ordering::SeqCst) != level {
+            self.pwm.try_lock(|pwm| pwm.restart(level));
+        }
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level.load(Ordering::SeqCst)
+    }
+
+    // TC5の割り込みハンドラから呼び出してください
+    pub fn on_interrupt(&self) {
+        let level = self.level();
+        self.pwm.try_lock(|pwm| {
+            pwm.timer.wait().ok();
+            pwm.next(level);
+        });
+    }
+}
+
+impl Default for Backlight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
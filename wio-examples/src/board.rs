@@ -0,0 +1,293 @@
+// WioBoard トレイトの実機向け実装です
+// アプリケーションを WioBoard だけに依存させておけば、
+// ホストPC上の仮想Wio Terminal (wio_board::SimBoard) でも同じコードが動きます
+//
+// タイマやバックライトなど WioBoard で扱わない周辺機能は、`split` で `Rest` として受け取れます
+// UARTとブザー、マイクのADCは、アプリケーションが直接使うときに `take_*` で取り出せます
+// (取り出した後は、WioBoard の対応するメソッドは何もしません)
+
+use crate::accel_calibration;
+use crate::clock::CycleClock;
+use crate::melody::{Buzzer, Tone};
+use crate::storage::Storage;
+use crate::{user_led, UserLed};
+use accelerometer::{vector::F32x3, Accelerometer};
+use wio::hal::adc::{Adc, FreeRunning, InterruptAdc};
+use wio::hal::clock::GenericClockController;
+use wio::hal::delay::Delay;
+use wio::hal::gpio::*;
+use wio::hal::sercom::{Sercom2Pad0, Sercom2Pad1, UART2};
+use wio::hal::time::Hertz;
+use wio::pac::{CorePeripherals, Peripherals, ADC1, MCLK, TC2, TC3, TC4, TC5};
+use wio::prelude::*;
+use wio::{Pins, LCD};
+pub use wio_board::BoardButtons;
+use wio_board::{Button, WioBoard};
+use wio_terminal as wio;
+
+// デバッグ用UART
+pub type Uart = UART2<Sercom2Pad1<Pb27<PfC>>, Sercom2Pad0<Pb26<PfC>>, (), ()>;
+
+// ボタンと5方向スイッチのピン
+struct Buttons {
+    button1: Pc26<Input<Floating>>,
+    button2: Pc27<Input<Floating>>,
+    button3: Pc28<Input<Floating>>,
+    switch_up: Pd20<Input<Floating>>,
+    switch_down: Pd8<Input<Floating>>,
+    switch_left: Pd12<Input<Floating>>,
+    switch_right: Pd9<Input<Floating>>,
+    switch_press: Pd10<Input<Floating>>,
+}
+
+impl Buttons {
+    // ボタンは押されている間Lowになる
+    fn is_pressed(&self, button: Button) -> bool {
+        match button {
+            Button::Button1 => self.button1.is_low(),
+            Button::Button2 => self.button2.is_low(),
+            Button::Button3 => self.button3.is_low(),
+            Button::SwitchUp => self.switch_up.is_low(),
+            Button::SwitchDown => self.switch_down.is_low(),
+            Button::SwitchLeft => self.switch_left.is_low(),
+            Button::SwitchRight => self.switch_right.is_low(),
+            Button::SwitchPress => self.switch_press.is_low(),
+        }
+        .unwrap()
+    }
+}
+
+pub struct Wio<A> {
+    display: LCD,
+    buttons: Buttons,
+    led: UserLed,
+    buzzer: Option<Buzzer>,
+    adc: Option<Adc<ADC1>>,
+    light: Pd1<PfB>,
+    microphone: Pc30<PfB>,
+    accelerometer: A,
+    uart: Option<Uart>,
+    delay: Delay,
+    clock: CycleClock,
+}
+
+// WioBoard で扱わない周辺機能
+pub struct Rest {
+    pub clocks: GenericClockController,
+    pub mclk: MCLK,
+    pub backlight: Pc5<Output<PushPull>>,
+    pub tc2: TC2,
+    pub tc3: TC3,
+    pub tc4: TC4,
+    pub tc5: TC5,
+}
+
+// Wio Terminalの周辺機能をまとめて初期化します
+pub fn init(peripherals: Peripherals, core: CorePeripherals) -> Wio<impl Accelerometer> {
+    split(peripherals, core).0
+}
+
+// init と同じように初期化し、WioBoard で扱わない周辺機能も返します
+pub fn split(
+    mut peripherals: Peripherals,
+    mut core: CorePeripherals,
+) -> (Wio<impl Accelerometer>, Rest) {
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+    let core_frequency: Hertz = clocks.gclk0().into();
+    let clock = CycleClock::new(&mut core.DCB, &mut core.DWT, core_frequency);
+    let mut delay = Delay::new(core.SYST, &mut clocks);
+    let mut sets = Pins::new(peripherals.PORT).split();
+
+    let (display, backlight) = sets
+        .display
+        .init(
+            &mut clocks,
+            peripherals.SERCOM7,
+            &mut peripherals.MCLK,
+            &mut sets.port,
+            58.mhz(),
+            &mut delay,
+        )
+        .unwrap();
+
+    let port = &mut sets.port;
+    let buttons = Buttons {
+        button1: sets.buttons.button1.into_floating_input(port),
+        button2: sets.buttons.button2.into_floating_input(port),
+        button3: sets.buttons.button3.into_floating_input(port),
+        switch_up: sets.buttons.switch_u.into_floating_input(port),
+        switch_down: sets.buttons.switch_x.into_floating_input(port),
+        switch_left: sets.buttons.switch_b.into_floating_input(port),
+        switch_right: sets.buttons.switch_y.into_floating_input(port),
+        switch_press: sets.buttons.switch_z.into_floating_input(port),
+    };
+    let led = user_led(sets.user_led, &mut sets.port);
+
+    let buzzer = sets.buzzer.init(
+        &mut clocks,
+        peripherals.TCC0,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+
+    // 光センサとマイクはどちらもADC1につながっている
+    let (adc, light) = sets.light_sensor.init(
+        peripherals.ADC1,
+        &mut clocks,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+    let microphone = sets.microphone.mic.into_function_b(&mut sets.port);
+
+    // 加速度センサには、フラッシュに保存されている較正値をかける
+    let accelerometer = sets.accelerometer.init(
+        &mut clocks,
+        peripherals.SERCOM4,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+    let storage = Storage::new(peripherals.NVMCTRL);
+    let accelerometer = accel_calibration::calibrated(accelerometer, &storage);
+
+    let uart = sets.uart.init(
+        &mut clocks,
+        115200.hz(),
+        peripherals.SERCOM2,
+        &mut peripherals.MCLK,
+        &mut sets.port,
+    );
+
+    let wio = Wio {
+        display,
+        buttons,
+        led,
+        buzzer: Some(Buzzer::new(buzzer)),
+        adc: Some(adc),
+        light,
+        microphone,
+        accelerometer,
+        uart: Some(uart),
+        delay,
+        clock,
+    };
+    let rest = Rest {
+        clocks,
+        mclk: peripherals.MCLK,
+        backlight,
+        tc2: peripherals.TC2,
+        tc3: peripherals.TC3,
+        tc4: peripherals.TC4,
+        tc5: peripherals.TC5,
+    };
+    (wio, rest)
+}
+
+impl<A> Wio<A> {
+    // UARTを取り出します (パニックハンドラから使う場合など)
+    pub fn take_uart(&mut self) -> Option<Uart> {
+        self.uart.take()
+    }
+
+    // ブザーを取り出します (割り込みハンドラからメロディを鳴らす場合など)
+    pub fn take_buzzer(&mut self) -> Option<Buzzer> {
+        self.buzzer.take()
+    }
+
+    // ADCをフリーランニングモードにして、マイクの値を連続して変換させます
+    // 変換が終わるたびに ADC1_RESRDY 割り込みが発生します
+    // 以降、read_light と read_microphone は 0 を返します
+    pub fn start_microphone_sampling(&mut self) -> Option<InterruptAdc<ADC1, FreeRunning>> {
+        let adc = self.adc.take()?;
+        let mut adc: InterruptAdc<_, FreeRunning> = InterruptAdc::from(adc);
+        adc.start_conversion(&mut self.microphone);
+        Some(adc)
+    }
+}
+
+impl<A: Accelerometer> WioBoard for Wio<A> {
+    type Display = LCD;
+
+    fn display(&mut self) -> &mut Self::Display {
+        &mut self.display
+    }
+
+    // 実機のLCDは描画した時点で反映されているので何もしない
+    fn refresh(&mut self) -> bool {
+        true
+    }
+
+    fn is_pressed(&mut self, button: Button) -> bool {
+        self.buttons.is_pressed(button)
+    }
+
+    fn set_led(&mut self, on: bool) {
+        if on {
+            self.led.turn_on();
+        } else {
+            self.led.turn_off();
+        }
+    }
+
+    fn start_tone(&mut self, frequency_hz: u32) {
+        if let Some(buzzer) = self.buzzer.as_mut() {
+            Tone::start_tone(buzzer, frequency_hz);
+        }
+    }
+
+    fn stop_tone(&mut self) {
+        if let Some(buzzer) = self.buzzer.as_mut() {
+            Tone::stop_tone(buzzer);
+        }
+    }
+
+    fn read_light(&mut self) -> u16 {
+        let adc = match self.adc.as_mut() {
+            Some(adc) => adc,
+            None => return 0,
+        };
+        let value: Result<u16, ()> = nb::block!(adc.read(&mut self.light));
+        value.unwrap_or(0)
+    }
+
+    fn read_acceleration(&mut self) -> F32x3 {
+        match self.accelerometer.accel_norm() {
+            Ok(acceleration) => acceleration,
+            Err(_) => F32x3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    fn read_microphone(&mut self) -> u16 {
+        let adc = match self.adc.as_mut() {
+            Some(adc) => adc,
+            None => return 0,
+        };
+        let value: Result<u16, ()> = nb::block!(adc.read(&mut self.microphone));
+        value.unwrap_or(0)
+    }
+
+    fn write_uart(&mut self, bytes: &[u8]) {
+        if let Some(uart) = self.uart.as_mut() {
+            for byte in bytes.iter() {
+                nb::block!(uart.write(*byte)).ok();
+            }
+        }
+    }
+
+    fn read_uart(&mut self) -> Option<u8> {
+        self.uart.as_mut()?.read().ok()
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay.delay_ms(ms);
+    }
+
+    fn uptime_ms(&mut self) -> u32 {
+        self.clock.now_ms()
+    }
+}
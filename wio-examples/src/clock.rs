@@ -0,0 +1,35 @@
+// DWTのサイクルカウンタから経過時間 [ms] を求める時計です
+// サイクルカウンタは約35秒 (120MHz) で一周するので、それより短い間隔で now_ms を呼び出す必要があります
+
+use cortex_m::peripheral::{DCB, DWT};
+use wio::hal::time::Hertz;
+use wio_terminal as wio;
+
+pub struct CycleClock {
+    cycles_per_ms: u32,
+    last_cycles: u32,
+    elapsed_ms: u32,
+}
+
+impl CycleClock {
+    // サイクルカウンタを有効にして、時計を0から動かし始めます
+    pub fn new(dcb: &mut DCB, dwt: &mut DWT, core_frequency: Hertz) -> Self {
+        dcb.enable_trace();
+        dwt.enable_cycle_counter();
+        CycleClock {
+            cycles_per_ms: core_frequency.0 / 1000,
+            last_cycles: DWT::cycle_count(),
+            elapsed_ms: 0,
+        }
+    }
+
+    // 時計を動かし始めてからの経過時間 [ms]
+    // u32の範囲 (約49日) で一周します
+    pub fn now_ms(&mut self) -> u32 {
+        let elapsed = DWT::cycle_count().wrapping_sub(self.last_cycles);
+        let ms = elapsed / self.cycles_per_ms;
+        self.last_cycles = self.last_cycles.wrapping_add(ms * self.cycles_per_ms);
+        self.elapsed_ms = self.elapsed_ms.wrapping_add(ms);
+        self.elapsed_ms
+    }
+}
@@ -0,0 +1,31 @@
+// ユーザーLEDのドライバです
+// LEDドライバ (Led, PwmLed) と点滅パターンは wio_util クレートにあり、
+// embedded-hal の OutputPin / PwmPin ならどのピンにも使えます
+
+use wio::hal::gpio::*;
+use wio::hal::time::Hertz;
+use wio_terminal as wio;
+pub use wio_util::led::{Led, Pattern, PwmLed, Sequencer};
+
+// ユーザーLED (PA15) のドライバ
+pub type UserLed = Led<Pa15<Output<PushPull>>>;
+
+// デフォルトモードのPA15ピン (ユーザーLED) を、出力モードに移行してドライバを作ります
+pub fn user_led(pin: Pa15<Input<Floating>>, port: &mut Port) -> UserLed {
+    Led::new(pin.into_push_pull_output(port))
+}
+
+// パニックしたときなどに、割り込みもタイマも使わずにユーザーLEDでSOSを送り続けます
+// 待ち時間はCPUのサイクル数で数えるので、CycleClock と同じようにCPUクロックを渡してください
+pub fn sos_forever(core_frequency: Hertz) -> ! {
+    let cycles_per_ms = core_frequency.0 / 1000;
+    // パニックハンドラからはピンの所有権を得られないので、PORTを借用し直す
+    let peripherals = unsafe { wio::pac::Peripherals::steal() };
+    let mut pins = wio::Pins::new(peripherals.PORT);
+    let mut led = user_led(pins.user_led, &mut pins.port);
+    led.set_pattern(Pattern::SOS);
+    loop {
+        cortex_m::asm::delay(cycles_per_ms);
+        led.tick(1);
+    }
+}
@@ -0,0 +1,38 @@
+// Wio Terminalのブザーでメロディを鳴らすモジュールです
+// RTTTL と MML の解釈と再生 (Player) は wio_util クレートにあり、
+// ここではブザー (TCC0のチャネル4) を Tone として使えるようにします
+
+use wio::hal::pwm::{Channel, Tcc0Pwm};
+use wio::prelude::*;
+use wio_terminal as wio;
+pub use wio_util::melody::*;
+
+// Wio Terminalのブザー
+pub struct Buzzer {
+    pwm: Tcc0Pwm,
+}
+
+impl Buzzer {
+    // sets.buzzer.init が返したPWMから作ります
+    pub fn new(pwm: Tcc0Pwm) -> Self {
+        Buzzer { pwm }
+    }
+
+    pub fn free(self) -> Tcc0Pwm {
+        self.pwm
+    }
+}
+
+impl Tone for Buzzer {
+    fn start_tone(&mut self, frequency_hz: u32) {
+        self.pwm.set_period(frequency_hz.hz());
+        // 周期を変えるとデューティ比の最大値も変わるので、毎回設定し直す
+        let max_duty = self.pwm.get_max_duty();
+        self.pwm.set_duty(Channel::_4, max_duty / 2);
+        self.pwm.enable(Channel::_4);
+    }
+
+    fn stop_tone(&mut self) {
+        self.pwm.disable(Channel::_4);
+    }
+}
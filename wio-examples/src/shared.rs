@@ -0,0 +1,79 @@
+// main() と割り込みハンドラとでデータを安全に共有するための型です
+//
+// `static mut CTX: Option<Ctx>` を `unsafe` で読み書きすると、main() が読み書きしている途中で
+// 割り込みハンドラが同じデータを書き換えてもコンパイラは検出できません
+//
+// Shared<T> は一度だけ初期化できるセルで、中身には `lock` に渡したクロージャの中でだけ
+// (割り込みを禁止した状態で) 触れます
+//
+//   static CTX: Shared<Ctx> = Shared::new();
+//   CTX.init(Ctx { ... });                  // main() で1回だけ
+//   CTX.lock(|ctx| ctx.counter += 1);       // main() からも割り込みハンドラからも使える
+//
+// SpscQueue<T, N> は書き込み側と読み出し側が1つずつのキューで、割り込みを禁止せずに使えます
+// (ホストPCでテストできるように wio_util::spsc にあります)
+//
+//   static SAMPLES: SpscQueue<u16, 256> = SpscQueue::new();
+//   let (producer, consumer) = SAMPLES.split().unwrap();
+
+use core::cell::RefCell;
+use cortex_m::interrupt::{self, Mutex};
+
+pub use wio_util::spsc::{Consumer, Producer, SpscQueue};
+
+pub struct Shared<T> {
+    inner: Mutex<RefCell<Option<T>>>,
+}
+
+impl<T> Shared<T> {
+    pub const fn new() -> Self {
+        Shared {
+            inner: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    // 中身を設定します
+    // 2回目以降の呼び出しはパニックします
+    pub fn init(&self, value: T) {
+        interrupt::free(|cs| {
+            let mut inner = self.inner.borrow(cs).borrow_mut();
+            if inner.is_some() {
+                panic!("Shared is already initialized");
+            }
+            *inner = Some(value);
+        });
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        interrupt::free(|cs| self.inner.borrow(cs).borrow().is_some())
+    }
+
+    // 割り込みを禁止した状態で中身を操作します
+    // 初期化前に呼び出したとき、lock の中で同じ Shared を lock したときはパニックします
+    pub fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        interrupt::free(|cs| {
+            let mut inner = self.inner.borrow(cs).borrow_mut();
+            f(inner.as_mut().expect("Shared is not initialized"))
+        })
+    }
+
+    // lock と同じですが、初期化前や lock の中から呼び出したときは None を返します
+    // パニックハンドラのように、どこから呼ばれるかわからない場所で使います
+    pub fn try_lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        interrupt::free(|cs| {
+            let mut inner = self.inner.borrow(cs).try_borrow_mut().ok()?;
+            inner.as_mut().map(f)
+        })
+    }
+
+    // 中身を取り出して、初期化前の状態に戻します
+    pub fn take(&self) -> Option<T> {
+        interrupt::free(|cs| self.inner.borrow(cs).borrow_mut().take())
+    }
+}
+
+impl<T> Default for Shared<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,98 @@
+// 1つのハードウェアタイマ (TC4) を共有して、多数のワンショット/周期タイマを動かすモジュールです
+//
+// TimerWheel はハードウェアに依存しないタイマホイールです (wio_util::soft_timer)
+// TimerService は TimerWheel を割り込みハンドラと main() とで安全に共有するためのものです
+// 満了の通知はフラグ (main() で `take_fired` を呼んで確認する) か、
+// コールバック (割り込みハンドラの中で呼ばれる) で受け取れます
+// コールバックは TimerWheel の借用を返してから呼ぶので、その中で TIMERS.start_once などを呼べます
+//
+// 使い方:
+//   static TIMERS: TimerService = TimerService::new();
+//   TIMERS.start_hardware(tc4, 1.ms());
+//   let blink = TIMERS.start_periodic(500, Action::Flag).unwrap();
+//   loop { if TIMERS.take_fired(blink) { led.toggle(); } }
+//
+//   #[interrupt]
+//   fn TC4() { TIMERS.on_interrupt(); }
+
+use crate::shared::Shared;
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::NVIC;
+use wio::hal::time::Nanoseconds;
+use wio::hal::timer::TimerCounter;
+use wio::pac::{interrupt, TC4};
+use wio::prelude::*;
+use wio_terminal as wio;
+
+pub use wio_util::soft_timer::{Action, Callbacks, TimerError, TimerId, TimerWheel, MAX_TIMERS};
+
+// TimerWheel をTC4の割り込みで進めるサービスです
+// `static` に置いて、main() と割り込みハンドラの両方から使います
+pub struct TimerService {
+    wheel: Mutex<RefCell<TimerWheel>>,
+    hardware: Shared<TimerCounter<TC4>>,
+}
+
+impl TimerService {
+    pub const fn new() -> Self {
+        TimerService {
+            wheel: Mutex::new(RefCell::new(TimerWheel::new())),
+            hardware: Shared::new(),
+        }
+    }
+
+    // TC4を tick 周期で動かし始めます
+    // タイマの長さはすべてこの周期を単位にして指定します
+    pub fn start_hardware<T: Into<Nanoseconds>>(&self, mut tc4: TimerCounter<TC4>, tick: T) {
+        tc4.start(tick);
+        tc4.enable_interrupt();
+        self.hardware.init(tc4);
+        unsafe {
+            NVIC::unmask(interrupt::TC4);
+        }
+    }
+
+    pub fn start_once(&self, delay_ticks: u32, action: Action) -> Result<TimerId, TimerError> {
+        self.with_wheel(|wheel| wheel.start_once(delay_ticks, action))
+    }
+
+    pub fn start_periodic(&self, period_ticks: u32, action: Action) -> Result<TimerId, TimerError> {
+        self.with_wheel(|wheel| wheel.start_periodic(period_ticks, action))
+    }
+
+    pub fn cancel(&self, id: TimerId) -> bool {
+        self.with_wheel(|wheel| wheel.cancel(id))
+    }
+
+    pub fn is_active(&self, id: TimerId) -> bool {
+        self.with_wheel(|wheel| wheel.is_active(id))
+    }
+
+    // タイマが満了していればフラグを下ろして true を返します
+    // 前回の確認から何回満了していても、true を返すのは1回だけです
+    pub fn take_fired(&self, id: TimerId) -> bool {
+        self.with_wheel(|wheel| wheel.take_fired(id))
+    }
+
+    // 割り込みを止めた状態で TimerWheel を操作します
+    // f の中から TimerService のメソッドを呼ぶと、借用が重なってパニックします
+    pub fn with_wheel<R>(&self, f: impl FnOnce(&mut TimerWheel) -> R) -> R {
+        cortex_m::interrupt::free(|cs| f(&mut self.wheel.borrow(cs).borrow_mut()))
+    }
+
+    // TC4の割り込みハンドラから呼び出してください
+    pub fn on_interrupt(&self) {
+        self.hardware.try_lock(|tc4| tc4.wait().ok());
+        let callbacks = self.with_wheel(|wheel| wheel.tick());
+        for callback in callbacks {
+            callback();
+        }
+    }
+}
+
+impl Default for TimerService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
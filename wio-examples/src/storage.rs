@@ -0,0 +1,139 @@
+// 内蔵フラッシュの最後のブロックに、小さな設定値を保存するドライバです
+// 電源を切ったりリセットしたりしても値が残ります
+//
+// ATSAMD51P19A のフラッシュは 512[KB] で、消去は8[KB]のブロック単位、書き込みは512[B]のページ単位です
+// プログラムはフラッシュの先頭から配置されるので、最後のブロックは使われていません
+// (プログラムが大きくなって最後のブロックに届いたときは、Storage::new でパニックします)
+//
+//   let mut storage = Storage::new(peripherals.NVMCTRL);
+//   storage.write(&record)?;
+//   let mut buffer = [0u8; 32];
+//   storage.read(&mut buffer);
+
+use core::ptr;
+use wio::pac::nvmctrl::ctrlb;
+use wio::pac::NVMCTRL;
+use wio_terminal as wio;
+
+const FLASH_SIZE: usize = 512 * 1024;
+const BLOCK_SIZE: usize = 8 * 1024;
+const PAGE_SIZE: usize = 512;
+// 保存に使うブロックの先頭アドレス
+const STORAGE_ADDRESS: usize = FLASH_SIZE - BLOCK_SIZE;
+
+extern "C" {
+    // cortex-m-rt のリンカスクリプトで定義される
+    static __sidata: u32; // フラッシュに置かれた .data の初期値の先頭
+    static __sdata: u32;
+    static __edata: u32;
+}
+
+// プログラム (.text, .rodata と .data の初期値) が使っているフラッシュの終わりのアドレス
+fn image_end() -> usize {
+    unsafe {
+        let start = &__sdata as *const u32 as usize;
+        let end = &__edata as *const u32 as usize;
+        &__sidata as *const u32 as usize + (end - start)
+    }
+}
+
+// 書き込み・消去の失敗 (NVMCTRL の INTFLAG で通知される)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageError {
+    Locked,      // 書き込みが禁止された領域だった (LOCKE)
+    Programming, // コマンドの使い方が正しくなかった (PROGE)
+}
+
+pub struct Storage {
+    nvmctrl: NVMCTRL,
+}
+
+impl Storage {
+    // 保存できる最大のバイト数
+    pub const CAPACITY: usize = PAGE_SIZE;
+
+    pub fn new(nvmctrl: NVMCTRL) -> Self {
+        // 保存に使うブロックにプログラムが置かれていたら、消去するとプログラムが壊れる
+        assert!(
+            image_end() <= STORAGE_ADDRESS,
+            "program overlaps the storage block"
+        );
+        // ページバッファの内容を、コマンドを発行したときだけ書き込む
+        nvmctrl.ctrla.modify(|_, w| w.wmode().man());
+        Storage { nvmctrl }
+    }
+
+    // 保存されている内容を buffer に読み出します
+    // 一度も書き込んでいない (消去された) ところは 0xFF になります
+    pub fn read(&self, buffer: &mut [u8]) {
+        let length = buffer.len().min(Self::CAPACITY);
+        for (i, byte) in buffer[..length].iter_mut().enumerate() {
+            *byte = unsafe { ptr::read_volatile((STORAGE_ADDRESS + i) as *const u8) };
+        }
+    }
+
+    // ブロックを消去して data を書き込みます (CAPACITY を超える分は書き込みません)
+    pub fn write(&mut self, data: &[u8]) -> Result<(), StorageError> {
+        let length = data.len().min(Self::CAPACITY);
+
+        self.erase()?;
+        self.execute(|w| w.cmd().pbc())?;
+
+        // ページバッファへは32bit単位で書き込む (足りない分は 0xFF で埋める)
+        for offset in (0..length).step_by(4) {
+            let mut word = [0xFFu8; 4];
+            let end = (offset + 4).min(length);
+            word[..end - offset].copy_from_slice(&data[offset..end]);
+            unsafe {
+                ptr::write_volatile(
+                    (STORAGE_ADDRESS + offset) as *mut u32,
+                    u32::from_le_bytes(word),
+                );
+            }
+        }
+
+        self.nvmctrl
+            .addr
+            .write(|w| unsafe { w.addr().bits(STORAGE_ADDRESS as u32) });
+        self.execute(|w| w.cmd().wp())
+    }
+
+    // 保存されている内容を消去します
+    pub fn erase(&mut self) -> Result<(), StorageError> {
+        self.wait_ready();
+        self.nvmctrl
+            .addr
+            .write(|w| unsafe { w.addr().bits(STORAGE_ADDRESS as u32) });
+        self.execute(|w| w.cmd().eb())
+    }
+
+    // コマンドを発行して終わるのを待ち、エラーのフラグを確かめます
+    fn execute(
+        &mut self,
+        command: impl FnOnce(&mut ctrlb::W) -> &mut ctrlb::W,
+    ) -> Result<(), StorageError> {
+        // 前のコマンドのフラグが残っていれば下ろしておく (1を書くと0になる)
+        self.nvmctrl
+            .intflag
+            .write(|w| w.proge().set_bit().locke().set_bit());
+        self.nvmctrl.ctrlb.write(|w| command(w.cmdex().key()));
+        self.wait_ready();
+
+        let flags = self.nvmctrl.intflag.read();
+        if flags.locke().bit_is_set() {
+            Err(StorageError::Locked)
+        } else if flags.proge().bit_is_set() {
+            Err(StorageError::Programming)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn wait_ready(&self) {
+        while self.nvmctrl.status.read().ready().bit_is_clear() {}
+    }
+
+    pub fn free(self) -> NVMCTRL {
+        self.nvmctrl
+    }
+}
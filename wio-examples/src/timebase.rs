@@ -0,0 +1,112 @@
+// 単調増加する時刻を提供するモジュールです
+// XOSC32K (外部32.768[kHz]水晶発振器) を基準にしたGCLK6で、TC2とTC3を組にした
+// 32bitカウンタをフリーランで動かし、オーバーフローの回数と合わせて64bitに拡張します
+//
+// タイマを周期ごとに再スタートしないので、割り込みの遅れで誤差が積み重なることはありません
+// 分解能は 1/32768[s] (約30.5[us]) で、64bitのカウンタは事実上一周しません
+//
+// 使い方:
+//   let timebase = TimeBase::new(peripherals.TC2, peripherals.TC3, &mut peripherals.MCLK, &timer_clock);
+//   let start = timebase.now();
+//   ...
+//   let elapsed = timebase.now() - start;
+//
+// オーバーフローを数えるために、アプリケーション側でTC2の割り込みハンドラから
+// `timebase::on_interrupt()` を呼び出してください
+//
+// Duration と Instant の計算はハードウェアに依存しないので、wio_util::timebase にあります
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::peripheral::NVIC;
+use wio::hal::clock::Tc2Tc3Clock;
+use wio::pac::{interrupt, MCLK, TC2, TC3};
+use wio_terminal as wio;
+use wio_util::timebase;
+
+pub use wio_util::timebase::{Duration, Instant, TICK_HZ};
+
+// 32bitカウンタがオーバーフローした回数 (64bitカウンタの上位32bit)
+static OVERFLOWS: AtomicU32 = AtomicU32::new(0);
+
+pub struct TimeBase {
+    tc2: TC2,
+    tc3: TC3, // 32bitモードではTC3はTC2の上位16bitとして使われる
+}
+
+impl TimeBase {
+    // TC2/TC3を32bitのフリーランカウンタとして動かし始めます
+    // clock にはGCLK6 (XOSC32K, 分周なし) から作ったクロックを渡してください
+    pub fn new(tc2: TC2, tc3: TC3, mclk: &mut MCLK, _clock: &Tc2Tc3Clock) -> Self {
+        // TC2とTC3へのバスクロックを有効にする
+        mclk.apbbmask
+            .modify(|_, w| w.tc2_().set_bit().tc3_().set_bit());
+
+        let count = tc2.count32();
+        count.ctrla.write(|w| w.swrst().set_bit());
+        while count.syncbusy.read().swrst().bit_is_set() {}
+
+        // 32bitモード、分周なし、0xFFFF_FFFFの次は0に戻る
+        count
+            .ctrla
+            .modify(|_, w| w.mode().count32().prescaler().div1());
+        count.wave.write(|w| w.wavegen().nfrq());
+        count.intflag.write(|w| w.ovf().set_bit());
+        count.intenset.write(|w| w.ovf().set_bit());
+
+        OVERFLOWS.store(0, Ordering::SeqCst);
+        count.ctrla.modify(|_, w| w.enable().set_bit());
+        while count.syncbusy.read().enable().bit_is_set() {}
+
+        unsafe {
+            NVIC::unmask(interrupt::TC2);
+        }
+
+        TimeBase { tc2, tc3 }
+    }
+
+    // 現在時刻
+    // 割り込み禁止中に呼び出しても正しい値を返します
+    pub fn now(&self) -> Instant {
+        cortex_m::interrupt::free(|_| {
+            let count = self.tc2.count32();
+            let overflows = OVERFLOWS.load(Ordering::SeqCst);
+            let low = read_count(&self.tc2);
+            // オーバーフローしたがまだ割り込みハンドラで数えていない場合は、
+            // オーバーフローの後の値になるように読み直す
+            let pending = count.intflag.read().ovf().bit_is_set();
+            let low = if pending { read_count(&self.tc2) } else { low };
+            timebase::extend(overflows, pending, low)
+        })
+    }
+
+    // タイマを止めてTCを返します
+    pub fn free(self) -> (TC2, TC3) {
+        let count = self.tc2.count32();
+        count.intenclr.write(|w| w.ovf().set_bit());
+        count.ctrla.modify(|_, w| w.enable().clear_bit());
+        while count.syncbusy.read().enable().bit_is_set() {}
+        NVIC::mask(interrupt::TC2);
+        (self.tc2, self.tc3)
+    }
+}
+
+// COUNTレジスタはREADSYNCコマンドで同期してから読む
+fn read_count(tc2: &TC2) -> u32 {
+    let count = tc2.count32();
+    count.ctrlbset.write(|w| w.cmd().readsync());
+    while count.ctrlbset.read().cmd().bits() != 0 {}
+    while count.syncbusy.read().count().bit_is_set() {}
+    count.count.read().bits()
+}
+
+// TC2の割り込みハンドラから呼び出してください
+pub fn on_interrupt() {
+    // TimeBase の所有権は割り込みハンドラにないので、レジスタを直接参照する
+    let count = unsafe { &*TC2::ptr() }.count32();
+    cortex_m::interrupt::free(|_| {
+        if count.intflag.read().ovf().bit_is_set() {
+            count.intflag.write(|w| w.ovf().set_bit());
+            OVERFLOWS.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+}
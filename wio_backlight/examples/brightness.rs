@@ -0,0 +1,45 @@
+// 折れ線とヒステリシスの動きをホストPCで確かめるサンプルです
+// 光センサの値を暗い部屋 → 明るい部屋 → 手をかざす → 暗い部屋 と変えて、明るさの変化を表示します
+// `cargo run --example brightness`
+
+use wio_backlight::{AutoBrightness, Curve, Mode};
+
+const UPDATE_INTERVAL_MS: u32 = 100;
+
+fn main() {
+    let curve = Curve::default();
+    println!("curve:");
+    for light in [0u16, 25, 50, 100, 300, 600, 1000, 1500, 2000, 4095].iter() {
+        println!("  light {:4} -> level {:3}", light, curve.level(*light as f32));
+    }
+
+    // (光センサの値, 続く時間 [ms])
+    let scenes = [
+        ("dark room", 30u16, 5000u32),
+        ("bright room", 800, 8000),
+        ("hand over sensor", 10, 300),
+        ("bright room", 800, 3000),
+        ("dark room", 30, 20000),
+    ];
+    let mut brightness = AutoBrightness::new(curve);
+    let mut now = 0;
+    let mut noise: u32 = 1;
+    for (name, light, duration) in scenes.iter() {
+        println!("{}:", name);
+        for _ in 0..duration / UPDATE_INTERVAL_MS {
+            // 光センサの値には少し揺らぎを加える
+            noise = noise.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let jitter = (noise >> 16) % 21;
+            let value = (*light + jitter as u16).saturating_sub(10);
+            if let Some(level) = brightness.update(value, now) {
+                println!("  {:6}ms: level {:3}", now, level);
+            }
+            now += UPDATE_INTERVAL_MS;
+        }
+    }
+
+    // 手動にすると光センサの値によらずその明るさになり、自動に戻すといまの明るさに合わせる
+    println!("manual 200 -> {}", brightness.set_mode(Mode::Manual(200)));
+    brightness.update(800, now);
+    println!("auto -> {}", brightness.set_mode(Mode::Auto));
+}
@@ -0,0 +1,244 @@
+// 光センサの値からバックライトの明るさを決めます
+//
+//   1. 光センサの値を低域通過フィルタでならす (手や影が一瞬かかっただけで変わらないように)
+//   2. いまの明るさを決めたときの値から hysteresis の割合 (暗いところでは min_light_change)
+//      以上変わったときだけ、明るさを決め直す (境目でちらつかないように)
+//   3. 折れ線 (Curve) で明るさを求める
+//
+// 手動の明るさ (Mode::Manual) にしている間は、光センサの値によらずその明るさにします
+// その間も光センサの値は追いかけているので、自動に戻すとすぐにいまの明るさに合わせます
+//
+//   let mut brightness = AutoBrightness::new(Curve::default());
+//   if let Some(level) = brightness.update(light, now_ms) {
+//       backlight.set_level(level);
+//   }
+
+use crate::curve::Curve;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Auto,
+    Manual(u8), // 明るさ (0: 消灯, 255: 最大)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BrightnessConfig {
+    pub smoothing_time_constant_ms: u32, // 光センサの値をならす低域通過フィルタの時定数
+    pub hysteresis: f32,                 // 光センサの値がこの割合以上変わったら明るさを変える
+    pub min_light_change: u16,           // 暗いときは、光センサの値がこれ以上変わったら変える
+}
+
+impl Default for BrightnessConfig {
+    fn default() -> Self {
+        BrightnessConfig {
+            smoothing_time_constant_ms: 3000,
+            hysteresis: 0.12,
+            min_light_change: 10,
+        }
+    }
+}
+
+pub struct AutoBrightness<const N: usize> {
+    curve: Curve<N>,
+    config: BrightnessConfig,
+    mode: Mode,
+    light: Option<f32>, // ならした光センサの値
+    last_update: u32,
+    reference: Option<f32>, // いまの明るさを決めたときの光センサの値
+}
+
+impl<const N: usize> AutoBrightness<N> {
+    pub fn new(curve: Curve<N>) -> Self {
+        Self::with_config(curve, BrightnessConfig::default())
+    }
+
+    pub fn with_config(curve: Curve<N>, config: BrightnessConfig) -> Self {
+        AutoBrightness {
+            curve,
+            config,
+            mode: Mode::Auto,
+            light: None,
+            last_update: 0,
+            reference: None,
+        }
+    }
+
+    pub fn curve(&self) -> &Curve<N> {
+        &self.curve
+    }
+
+    // 折れ線を変えて、変えた後の明るさを返します
+    pub fn set_curve(&mut self, curve: Curve<N>) -> u8 {
+        self.curve = curve;
+        self.reference = self.light;
+        self.level()
+    }
+
+    pub fn config(&self) -> &BrightnessConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: BrightnessConfig) {
+        self.config = config;
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    // モードを変えて、変えた後の明るさを返します
+    pub fn set_mode(&mut self, mode: Mode) -> u8 {
+        self.mode = mode;
+        self.level()
+    }
+
+    // 光センサの値 light (ADCの値) を入力します
+    // 明るさが変わったときは、新しい明るさを返します
+    pub fn update(&mut self, light: u16, now_ms: u32) -> Option<u8> {
+        let previous = self.level();
+        let light = light as f32;
+        let smoothed = match self.light {
+            Some(smoothed) => {
+                let elapsed = now_ms.wrapping_sub(self.last_update) as f32;
+                let time_constant = self.config.smoothing_time_constant_ms as f32;
+                let alpha = elapsed / (time_constant + elapsed).max(1.0);
+                smoothed + alpha * (light - smoothed)
+            }
+            None => light,
+        };
+        self.light = Some(smoothed);
+        self.last_update = now_ms;
+
+        let changed = match self.reference {
+            Some(reference) => {
+                let threshold =
+                    (reference * self.config.hysteresis).max(self.config.min_light_change as f32);
+                (smoothed - reference).abs() >= threshold
+            }
+            None => true,
+        };
+        if changed {
+            self.reference = Some(smoothed);
+        }
+
+        let level = self.level();
+        if level != previous {
+            Some(level)
+        } else {
+            None
+        }
+    }
+
+    // いまの明るさ (自動のときに光センサの値がまだ入力されていなければ最大の明るさ)
+    pub fn level(&self) -> u8 {
+        match (self.mode, self.reference) {
+            (Mode::Manual(level), _) => level,
+            (Mode::Auto, Some(reference)) => self.curve.level(reference),
+            (Mode::Auto, None) => self.curve.last_level(),
+        }
+    }
+
+    // ならした光センサの値 (まだ入力がないときは None)
+    pub fn light(&self) -> Option<f32> {
+        self.light
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ならさずに、入力した値をそのまま使う
+    fn unsmoothed() -> AutoBrightness<5> {
+        AutoBrightness::with_config(
+            Curve::default(),
+            BrightnessConfig {
+                smoothing_time_constant_ms: 0,
+                ..BrightnessConfig::default()
+            },
+        )
+    }
+
+    #[test]
+    fn first_reading_sets_level() {
+        let mut brightness = unsmoothed();
+        // 入力がなければ最大の明るさ
+        assert_eq!(brightness.level(), 255);
+        assert_eq!(brightness.light(), None);
+        assert_eq!(brightness.update(300, 0), Some(120));
+        assert_eq!(brightness.level(), 120);
+        assert_eq!(brightness.light(), Some(300.0));
+    }
+
+    #[test]
+    fn no_change_inside_hysteresis() {
+        let mut brightness = unsmoothed();
+        brightness.update(300, 0);
+        // 300 の 12% (36) 未満の変化では変えない
+        for (i, &light) in [335, 265, 320, 280, 335].iter().enumerate() {
+            assert_eq!(brightness.update(light, 100 * i as u32), None, "{}", light);
+            assert_eq!(brightness.level(), 120);
+        }
+        // すぐ外に出たら変える
+        assert_eq!(brightness.update(336, 1000), Some(125));
+        // 新しい値を基準にする
+        assert_eq!(brightness.update(300, 1100), None);
+        assert_eq!(brightness.update(295, 1200), Some(118));
+    }
+
+    #[test]
+    fn min_light_change_when_dark() {
+        let mut brightness = unsmoothed();
+        brightness.update(20, 0);
+        assert_eq!(brightness.level(), 26);
+        // 20 の 12% は 2.4 だが、min_light_change (10) までは変えない
+        assert_eq!(brightness.update(29, 100), None);
+        assert_eq!(brightness.update(11, 200), None);
+        assert_eq!(brightness.update(30, 300), Some(30));
+        assert_eq!(brightness.update(20, 400), Some(26));
+    }
+
+    #[test]
+    fn short_shadow_is_smoothed() {
+        let mut brightness = AutoBrightness::new(Curve::default());
+        brightness.update(1000, 0);
+        assert_eq!(brightness.level(), 220);
+        // 0.3[s] だけ手をかざしても変わらない
+        for t in (100..=300).step_by(100) {
+            assert_eq!(brightness.update(0, t), None);
+        }
+        assert!(brightness.light().unwrap() > 880.0);
+        // 暗いままなら、時定数の数倍で暗いときの明るさに近づく
+        // (min_light_change より小さい変化では変えないので、0 の明るさまでは下がりきらない)
+        for t in (400..=30_000).step_by(100) {
+            brightness.update(0, t);
+        }
+        assert!(brightness.light().unwrap() < 1.0);
+        let level = brightness.level();
+        assert!((16..=21).contains(&level), "{}", level);
+    }
+
+    #[test]
+    fn manual_override_then_auto() {
+        let mut brightness = unsmoothed();
+        brightness.update(300, 0);
+        assert_eq!(brightness.set_mode(Mode::Manual(50)), 50);
+        assert_eq!(brightness.mode(), Mode::Manual(50));
+        // 手動の間は光センサの値が変わっても明るさを変えない
+        assert_eq!(brightness.update(2000, 100), None);
+        assert_eq!(brightness.update(1000, 200), None);
+        assert_eq!(brightness.level(), 50);
+        // 自動に戻すと、追いかけていた光センサの値にすぐ合わせる
+        assert_eq!(brightness.set_mode(Mode::Auto), 220);
+        assert_eq!(brightness.level(), 220);
+    }
+
+    #[test]
+    fn changing_curve_applies_immediately() {
+        let mut brightness = unsmoothed();
+        brightness.update(300, 0);
+        let curve = Curve::new([(0, 0), (300, 30), (1000, 100), (2000, 200), (3000, 255)]);
+        assert_eq!(brightness.set_curve(curve.unwrap()), 30);
+        assert_eq!(brightness.update(1000, 100), Some(100));
+    }
+}
@@ -0,0 +1,123 @@
+// 光センサの値 (ADCの値) から明るさ (0: 消灯, 255: 最大) を求める折れ線です
+// 点と点の間は直線で補間し、最初の点より暗いときは最初の点の明るさ、
+// 最後の点より明るいときは最後の点の明るさにします
+//
+//   let curve = Curve::new([(0, 20), (300, 120), (2000, 255)]).unwrap();
+//   let level = curve.level(500.0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveError {
+    Empty,         // 点がない
+    NotIncreasing, // 光センサの値が小さい順に並んでいない
+}
+
+// 部屋の明るさで読みやすく、暗いところでまぶしくないようにした折れ線
+pub const DEFAULT_POINTS: [(u16, u8); 5] =
+    [(0, 16), (50, 40), (300, 120), (1000, 220), (2000, 255)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Curve<const N: usize> {
+    points: [(u16, u8); N], // (光センサの値, 明るさ)
+}
+
+impl<const N: usize> Curve<N> {
+    // 光センサの値が小さい順に並んだ点を渡します
+    pub fn new(points: [(u16, u8); N]) -> Result<Self, CurveError> {
+        if N == 0 {
+            return Err(CurveError::Empty);
+        }
+        if points.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(CurveError::NotIncreasing);
+        }
+        Ok(Curve { points })
+    }
+
+    pub fn points(&self) -> &[(u16, u8); N] {
+        &self.points
+    }
+
+    // 光センサの値 light のときの明るさ
+    pub fn level(&self, light: f32) -> u8 {
+        let (first_light, first_level) = self.points[0];
+        if light <= first_light as f32 {
+            return first_level;
+        }
+        for pair in self.points.windows(2) {
+            let (x0, y0) = pair[0];
+            let (x1, y1) = pair[1];
+            if light < x1 as f32 {
+                let t = (light - x0 as f32) / (x1 - x0) as f32;
+                return (y0 as f32 + t * (y1 as f32 - y0 as f32) + 0.5) as u8;
+            }
+        }
+        self.points[N - 1].1
+    }
+
+    // 一番暗いときと一番明るいときの明るさ
+    pub fn first_level(&self) -> u8 {
+        self.points[0].1
+    }
+
+    pub fn last_level(&self) -> u8 {
+        self.points[N - 1].1
+    }
+}
+
+impl Default for Curve<5> {
+    fn default() -> Self {
+        Curve {
+            points: DEFAULT_POINTS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_invalid_points() {
+        assert_eq!(Curve::new([]), Err(CurveError::Empty));
+        assert_eq!(
+            Curve::new([(0, 10), (0, 20)]),
+            Err(CurveError::NotIncreasing)
+        );
+        assert_eq!(
+            Curve::new([(0, 10), (200, 20), (100, 30)]),
+            Err(CurveError::NotIncreasing)
+        );
+        assert!(Curve::new([(100, 10)]).is_ok());
+    }
+
+    #[test]
+    fn interpolates_between_points() {
+        let curve = Curve::default();
+        assert_eq!(curve.level(0.0), 16);
+        assert_eq!(curve.level(25.0), 28);
+        assert_eq!(curve.level(50.0), 40);
+        assert_eq!(curve.level(175.0), 80);
+        assert_eq!(curve.level(650.0), 170);
+        assert_eq!(curve.level(2000.0), 255);
+
+        // 明るさが下がっていく折れ線でもよい
+        let curve = Curve::new([(0, 255), (100, 0)]).unwrap();
+        assert_eq!(curve.level(50.0), 128);
+        assert_eq!(curve.level(99.0), 3);
+    }
+
+    #[test]
+    fn clamps_outside_points() {
+        let curve = Curve::new([(100, 10), (200, 20)]).unwrap();
+        assert_eq!(curve.level(50.0), 10);
+        assert_eq!(curve.level(-1.0), 10);
+        assert_eq!(curve.level(250.0), 20);
+        assert_eq!(curve.level(4095.0), 20);
+        assert_eq!(curve.first_level(), 10);
+        assert_eq!(curve.last_level(), 20);
+
+        // 点が1つなら、いつも同じ明るさ
+        let curve = Curve::new([(100, 60)]).unwrap();
+        assert_eq!(curve.level(0.0), 60);
+        assert_eq!(curve.level(1000.0), 60);
+    }
+}
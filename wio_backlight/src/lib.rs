@@ -0,0 +1,14 @@
+#![cfg_attr(not(test), no_std)]
+
+// 周囲の明るさに合わせて画面のバックライトの明るさを決めます
+//
+// curve:      光センサの値から明るさを求める折れ線
+// brightness: 光センサの値をならし、ヒステリシスをもたせて明るさを決める (手動で決めることもできる)
+//
+// 光センサの値 (ADCの値) と時刻 [ms] を渡すだけなので、ホストPCでも同じように動かせます
+
+pub mod brightness;
+pub mod curve;
+
+pub use brightness::{AutoBrightness, BrightnessConfig, Mode};
+pub use curve::{Curve, CurveError, DEFAULT_POINTS};
@@ -0,0 +1,62 @@
+// 仮想Wio Terminalで入力とセンサの値を表示するサンプルです
+// `cargo run --example sim --features sim-window`
+
+use core::fmt::Write;
+use embedded_graphics::{
+    egrectangle, egtext, fonts::Font8x16, pixelcolor::Rgb565, prelude::*, primitive_style,
+    text_style,
+};
+use wio_board::{Button, SimBoard, WioBoard};
+
+// WioBoard だけに依存しているので、実機でも同じように動く
+fn draw_status<B: WioBoard>(board: &mut B) -> Result<(), <B::Display as DrawTarget<Rgb565>>::Error> {
+    let mut pressed = String::new();
+    for button in Button::ALL.iter() {
+        if board.is_pressed(*button) {
+            write!(pressed, "{:?} ", button).unwrap();
+        }
+    }
+    let accel = board.read_acceleration();
+    let light = board.read_light();
+    let lines = [
+        format!("buttons: {}", pressed),
+        format!("light: {}", light),
+        format!("accel: {:.2} {:.2} {:.2}", accel.x, accel.y, accel.z),
+    ];
+
+    let display = board.display();
+    egrectangle!(
+        top_left = (0, 0),
+        bottom_right = (319, 239),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(display)?;
+    for (i, line) in lines.iter().enumerate() {
+        egtext!(
+            text = line.as_str(),
+            top_left = (8, 8 + i as i32 * 20),
+            style = text_style!(font = Font8x16, text_color = Rgb565::GREEN)
+        )
+        .draw(display)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut board = SimBoard::new();
+    board.script_light(|t| (2048.0 + 2000.0 * (t.as_secs_f32()).sin()) as u16);
+    board.script_acceleration(|t| {
+        let theta = t.as_secs_f32() * 0.5;
+        accelerometer::vector::F32x3::new(theta.sin(), 0.0, theta.cos())
+    });
+
+    loop {
+        draw_status(&mut board).unwrap();
+        let any_pressed = Button::ALL.iter().any(|b| board.is_pressed(*b));
+        board.set_led(any_pressed);
+        if !board.refresh() {
+            break;
+        }
+        board.delay_ms(30);
+    }
+}
@@ -0,0 +1,58 @@
+// WioBoard のボタンと5方向スイッチのチャタリングを取り除いて、イベントを発生させます
+// ピンの代わりに WioBoard::is_pressed で状態を読みます
+
+use crate::{Button, WioBoard};
+use wio_util::{ButtonConfig, ButtonEvent, ButtonState};
+
+pub struct BoardButtons {
+    states: [ButtonState; 8],
+}
+
+impl BoardButtons {
+    pub fn new() -> Self {
+        Self::with_config(ButtonConfig::default())
+    }
+
+    pub fn with_config(config: ButtonConfig) -> Self {
+        BoardButtons {
+            states: [
+                ButtonState::new(config),
+                ButtonState::new(config),
+                ButtonState::new(config),
+                ButtonState::new(config),
+                ButtonState::new(config),
+                ButtonState::new(config),
+                ButtonState::new(config),
+                ButtonState::new(config),
+            ],
+        }
+    }
+
+    // すべてのボタンの状態を読み、必要ならイベントを発生させます
+    pub fn update<B: WioBoard>(&mut self, board: &mut B, now_ms: u32) {
+        for (state, button) in self.states.iter_mut().zip(Button::ALL.iter()) {
+            state.update(board.is_pressed(*button), now_ms);
+        }
+    }
+
+    // ボタンにたまっているイベントを古い順に取り出します
+    pub fn next_event(&mut self, button: Button) -> Option<ButtonEvent> {
+        self.states[index(button)].next_event()
+    }
+
+    // チャタリング除去後の状態
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.states[index(button)].is_pressed()
+    }
+}
+
+impl Default for BoardButtons {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Button::ALL の中の位置
+fn index(button: Button) -> usize {
+    Button::ALL.iter().position(|b| *b == button).unwrap()
+}
@@ -0,0 +1,88 @@
+#![cfg_attr(not(any(test, feature = "sim")), no_std)]
+
+// Wio Terminalのハードウェアを抽象化するトレイトです
+// 実機向けの実装は wio-examples の `board` モジュールに、
+// ホストPC向けの実装 (SimBoard) は `sim` フィーチャにあります
+//
+// buttons:   WioBoard のボタンのチャタリングを取り除いてイベントにする
+// stopwatch: 8-1 のストップウォッチ (WioBoard だけで動く部分)
+
+use accelerometer::vector::F32x3;
+use embedded_graphics::{pixelcolor::Rgb565, DrawTarget};
+
+mod buttons;
+#[cfg(feature = "sim")]
+mod sim;
+pub mod stopwatch;
+
+pub use buttons::BoardButtons;
+#[cfg(feature = "sim")]
+pub use sim::SimBoard;
+
+pub const SCREEN_WIDTH: u32 = 320;
+pub const SCREEN_HEIGHT: u32 = 240;
+
+// 上部の3つのボタンと5方向スイッチ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Button1, // 一番右のボタン
+    Button2,
+    Button3, // 一番左のボタン
+    SwitchUp,
+    SwitchDown,
+    SwitchLeft,
+    SwitchRight,
+    SwitchPress,
+}
+
+impl Button {
+    pub const ALL: [Button; 8] = [
+        Button::Button1,
+        Button::Button2,
+        Button::Button3,
+        Button::SwitchUp,
+        Button::SwitchDown,
+        Button::SwitchLeft,
+        Button::SwitchRight,
+        Button::SwitchPress,
+    ];
+}
+
+pub trait WioBoard {
+    type Display: DrawTarget<Rgb565>;
+
+    // LCD
+    fn display(&mut self) -> &mut Self::Display;
+
+    // 画面の内容を反映し、入力を処理します
+    // 終了が要求された (シミュレータのウィンドウが閉じられた) ときは false を返します
+    fn refresh(&mut self) -> bool;
+
+    // ボタンが押されているかどうか
+    fn is_pressed(&mut self, button: Button) -> bool;
+
+    // ユーザーLED
+    fn set_led(&mut self, on: bool);
+
+    // ブザーを指定した周波数で鳴らします
+    fn start_tone(&mut self, frequency_hz: u32);
+    fn stop_tone(&mut self);
+
+    // 光センサのADC値 (0-4095)
+    fn read_light(&mut self) -> u16;
+
+    // 加速度センサの値 [G]
+    fn read_acceleration(&mut self) -> F32x3;
+
+    // マイクのADC値 (0-4095)
+    fn read_microphone(&mut self) -> u16;
+
+    // UART
+    fn write_uart(&mut self, bytes: &[u8]);
+    fn read_uart(&mut self) -> Option<u8>;
+
+    fn delay_ms(&mut self, ms: u32);
+
+    // 起動してからの経過時間 [ms]
+    fn uptime_ms(&mut self) -> u32;
+}
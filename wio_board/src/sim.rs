@@ -0,0 +1,304 @@
+// embedded-graphics-simulator を使った仮想Wio Terminalです
+//
+// `SimBoard::headless` はウィンドウを開かず、時間も delay_ms で進めた分だけ進むので、
+// テストからボタンを操作して描画された内容を確かめられます
+// `sim-window` フィーチャを有効にすると、`SimBoard::new` でウィンドウに表示します
+//
+// キーボード操作 (sim-window):
+//   Z / X / C     : ボタン3 / ボタン2 / ボタン1 (左から順)
+//   矢印キー      : 5方向スイッチの上下左右
+//   Enter / Space : 5方向スイッチの押し込み
+//
+// センサの値は `script_*` で経過時間の関数として与えます
+
+use crate::{Button, WioBoard, SCREEN_HEIGHT, SCREEN_WIDTH};
+use accelerometer::vector::F32x3;
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use embedded_graphics_simulator::SimulatorDisplay;
+#[cfg(feature = "sim-window")]
+use embedded_graphics_simulator::{OutputSettings, OutputSettingsBuilder, SimulatorEvent, Window};
+#[cfg(feature = "sim-window")]
+use sdl2::keyboard::Keycode;
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    time::Duration,
+};
+#[cfg(feature = "sim-window")]
+use std::{thread, time::Instant};
+
+type Script<T> = Box<dyn FnMut(Duration) -> T>;
+
+const ADC_MIDPOINT: u16 = 2048;
+
+#[cfg(feature = "sim-window")]
+fn button_for_key(keycode: Keycode) -> Option<Button> {
+    let button = match keycode {
+        Keycode::C => Button::Button1,
+        Keycode::X => Button::Button2,
+        Keycode::Z => Button::Button3,
+        Keycode::Up => Button::SwitchUp,
+        Keycode::Down => Button::SwitchDown,
+        Keycode::Left => Button::SwitchLeft,
+        Keycode::Right => Button::SwitchRight,
+        Keycode::Return | Keycode::Space => Button::SwitchPress,
+        _ => return None,
+    };
+    Some(button)
+}
+
+// 経過時間の測り方
+enum Clock {
+    #[cfg(feature = "sim-window")]
+    Real(Instant), // 実際の時間 (ウィンドウに表示するとき)
+    Virtual(Duration), // delay_ms で進めた分だけ進む (テスト用)
+}
+
+pub struct SimBoard {
+    display: SimulatorDisplay<Rgb565>,
+    #[cfg(feature = "sim-window")]
+    window: Option<Window>,
+    clock: Clock,
+    pressed: [bool; 8],
+    led: bool,
+    tone: Option<u32>,
+    light: Script<u16>,
+    acceleration: Script<F32x3>,
+    microphone: Script<u16>,
+    uart_input: VecDeque<u8>,
+}
+
+impl SimBoard {
+    #[cfg(feature = "sim-window")]
+    pub fn new() -> Self {
+        Self::with_output_settings(&OutputSettingsBuilder::new().scale(2).build())
+    }
+
+    #[cfg(feature = "sim-window")]
+    pub fn with_output_settings(output_settings: &OutputSettings) -> Self {
+        SimBoard {
+            window: Some(Window::new("Wio Terminal", output_settings)),
+            clock: Clock::Real(Instant::now()),
+            ..Self::headless()
+        }
+    }
+
+    // ウィンドウを開かない SimBoard を作ります
+    // 経過時間は0から始まり、delay_ms を呼んだときだけ進みます
+    pub fn headless() -> Self {
+        SimBoard {
+            display: SimulatorDisplay::new(Size::new(SCREEN_WIDTH, SCREEN_HEIGHT)),
+            #[cfg(feature = "sim-window")]
+            window: None,
+            clock: Clock::Virtual(Duration::from_millis(0)),
+            pressed: [false; 8],
+            led: false,
+            tone: None,
+            light: Box::new(|_| ADC_MIDPOINT),
+            acceleration: Box::new(|_| F32x3::new(0.0, 0.0, 1.0)),
+            microphone: Box::new(|_| ADC_MIDPOINT),
+            uart_input: VecDeque::new(),
+        }
+    }
+
+    // 光センサの値を経過時間の関数として与えます
+    pub fn script_light<F>(&mut self, script: F)
+    where
+        F: FnMut(Duration) -> u16 + 'static,
+    {
+        self.light = Box::new(script);
+    }
+
+    pub fn script_acceleration<F>(&mut self, script: F)
+    where
+        F: FnMut(Duration) -> F32x3 + 'static,
+    {
+        self.acceleration = Box::new(script);
+    }
+
+    pub fn script_microphone<F>(&mut self, script: F)
+    where
+        F: FnMut(Duration) -> u16 + 'static,
+    {
+        self.microphone = Box::new(script);
+    }
+
+    // UARTの受信データを追加します
+    pub fn push_uart_input(&mut self, bytes: &[u8]) {
+        self.uart_input.extend(bytes.iter().copied());
+    }
+
+    // キーボードを使わずにボタンの状態を変えます
+    pub fn set_pressed(&mut self, button: Button, pressed: bool) {
+        self.pressed[button as usize] = pressed;
+    }
+
+    pub fn led(&self) -> bool {
+        self.led
+    }
+
+    pub fn tone(&self) -> Option<u32> {
+        self.tone
+    }
+
+    fn elapsed(&self) -> Duration {
+        match self.clock {
+            #[cfg(feature = "sim-window")]
+            Clock::Real(started) => Instant::now() - started,
+            Clock::Virtual(elapsed) => elapsed,
+        }
+    }
+}
+
+#[cfg(feature = "sim-window")]
+impl Default for SimBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WioBoard for SimBoard {
+    type Display = SimulatorDisplay<Rgb565>;
+
+    fn display(&mut self) -> &mut Self::Display {
+        &mut self.display
+    }
+
+    #[cfg(not(feature = "sim-window"))]
+    fn refresh(&mut self) -> bool {
+        true
+    }
+
+    #[cfg(feature = "sim-window")]
+    fn refresh(&mut self) -> bool {
+        let window = match &mut self.window {
+            Some(window) => window,
+            None => return true,
+        };
+        window.update(&self.display);
+        for event in window.events() {
+            match event {
+                SimulatorEvent::Quit => return false,
+                SimulatorEvent::KeyDown { keycode, .. } => {
+                    if let Some(button) = button_for_key(keycode) {
+                        self.pressed[button as usize] = true;
+                    }
+                }
+                SimulatorEvent::KeyUp { keycode, .. } => {
+                    if let Some(button) = button_for_key(keycode) {
+                        self.pressed[button as usize] = false;
+                    }
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+
+    fn is_pressed(&mut self, button: Button) -> bool {
+        self.pressed[button as usize]
+    }
+
+    fn set_led(&mut self, on: bool) {
+        if self.led != on {
+            eprintln!("[led] {}", if on { "on" } else { "off" });
+        }
+        self.led = on;
+    }
+
+    fn start_tone(&mut self, frequency_hz: u32) {
+        eprintln!("[buzzer] {} Hz", frequency_hz);
+        self.tone = Some(frequency_hz);
+    }
+
+    fn stop_tone(&mut self) {
+        if self.tone.is_some() {
+            eprintln!("[buzzer] off");
+        }
+        self.tone = None;
+    }
+
+    fn read_light(&mut self) -> u16 {
+        let t = self.elapsed();
+        (self.light)(t)
+    }
+
+    fn read_acceleration(&mut self) -> F32x3 {
+        let t = self.elapsed();
+        (self.acceleration)(t)
+    }
+
+    fn read_microphone(&mut self) -> u16 {
+        let t = self.elapsed();
+        (self.microphone)(t)
+    }
+
+    fn write_uart(&mut self, bytes: &[u8]) {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        stdout.write_all(bytes).ok();
+        stdout.flush().ok();
+    }
+
+    fn read_uart(&mut self) -> Option<u8> {
+        self.uart_input.pop_front()
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        let duration = Duration::from_millis(ms as u64);
+        match &mut self.clock {
+            #[cfg(feature = "sim-window")]
+            Clock::Real(_) => thread::sleep(duration),
+            Clock::Virtual(elapsed) => *elapsed += duration,
+        }
+    }
+
+    fn uptime_ms(&mut self) -> u32 {
+        self.elapsed().as_millis() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headless_clock_advances_only_by_delay() {
+        let mut board = SimBoard::headless();
+        assert_eq!(board.uptime_ms(), 0);
+        board.delay_ms(250);
+        assert!(board.refresh());
+        assert_eq!(board.uptime_ms(), 250);
+    }
+
+    #[test]
+    fn scripts_see_elapsed_time() {
+        let mut board = SimBoard::headless();
+        board.script_light(|t| t.as_millis() as u16);
+        board.script_acceleration(|t| F32x3::new(t.as_secs_f32(), 0.0, 1.0));
+        board.delay_ms(1500);
+        assert_eq!(board.read_light(), 1500);
+        assert_eq!(board.read_acceleration().x, 1.5);
+        assert_eq!(board.read_microphone(), ADC_MIDPOINT);
+    }
+
+    #[test]
+    fn buttons_led_tone_and_uart() {
+        let mut board = SimBoard::headless();
+        board.set_pressed(Button::SwitchLeft, true);
+        assert!(board.is_pressed(Button::SwitchLeft));
+        assert!(!board.is_pressed(Button::SwitchRight));
+
+        board.set_led(true);
+        board.start_tone(440);
+        assert!(board.led());
+        assert_eq!(board.tone(), Some(440));
+        board.stop_tone();
+        assert_eq!(board.tone(), None);
+
+        board.push_uart_input(b"ok");
+        assert_eq!(board.read_uart(), Some(b'o'));
+        assert_eq!(board.read_uart(), Some(b'k'));
+        assert_eq!(board.read_uart(), None);
+    }
+}
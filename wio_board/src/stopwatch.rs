@@ -0,0 +1,245 @@
+// 8-1 のストップウォッチのうち、ボタン操作と描画の部分です
+// WioBoard だけに依存しているので、SimBoard でも動かせます
+// 時刻の取得と操作音の再生は、呼び出し側で行います
+//
+// ボタン3で開始、ボタン2で停止、ボタン1でクリア (計測中はラップを記録する)
+
+use crate::{BoardButtons, Button, WioBoard, SCREEN_WIDTH};
+use core::fmt::Write;
+use embedded_graphics::{
+    egrectangle, egtext,
+    fonts::{Font12x16, Font24x32},
+    pixelcolor::Rgb565,
+    prelude::*,
+    primitive_style, text_style, DrawTarget,
+};
+use heapless::consts::*;
+use heapless::String;
+use wio_util::stopwatch::{Lap, MinSecCenti, Stopwatch, StopwatchState};
+use wio_util::ButtonEvent;
+
+const FONT_WIDTH: i32 = 24;
+const FONT_HEIGHT: i32 = 32;
+const LAP_FONT_WIDTH: i32 = 12;
+const LAP_FONT_HEIGHT: i32 = 16;
+
+// 鳴らす操作音の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    Start, // 開始・ラップ
+    Stop,  // 停止
+    Clear, // クリア
+}
+
+type DisplayError<B> = <<B as WioBoard>::Display as DrawTarget<Rgb565>>::Error;
+
+pub struct StopwatchApp {
+    stopwatch: Stopwatch,
+    buttons: BoardButtons,
+}
+
+impl StopwatchApp {
+    pub fn new() -> Self {
+        StopwatchApp {
+            stopwatch: Stopwatch::new(),
+            buttons: BoardButtons::new(),
+        }
+    }
+
+    pub fn stopwatch(&self) -> &Stopwatch {
+        &self.stopwatch
+    }
+
+    // ボタンを読んで状態を進め、表示を更新します
+    // 操作音を鳴らすときはその種類を返します
+    pub fn update<B: WioBoard>(
+        &mut self,
+        board: &mut B,
+        now_ms: u32,
+    ) -> Result<Option<Sound>, DisplayError<B>> {
+        self.buttons.update(board, now_ms);
+        let start_pressed = self.buttons.next_event(Button::Button3) == Some(ButtonEvent::Pressed);
+        let stop_pressed = self.buttons.next_event(Button::Button2) == Some(ButtonEvent::Pressed);
+        let clear_pressed = self.buttons.next_event(Button::Button1) == Some(ButtonEvent::Pressed);
+
+        let mut sound = None;
+        match self.stopwatch.state() {
+            StopwatchState::Idle | StopwatchState::Stopped => {
+                if start_pressed {
+                    self.stopwatch.start(now_ms);
+                    sound = Some(Sound::Start);
+                } else if clear_pressed {
+                    self.stopwatch.reset();
+                    sound = Some(Sound::Clear);
+                    draw(board.display(), 0, None)?;
+                }
+            }
+            StopwatchState::Running => {
+                if stop_pressed {
+                    self.stopwatch.stop(now_ms);
+                    sound = Some(Sound::Stop);
+                } else if clear_pressed {
+                    self.stopwatch.lap(now_ms);
+                    sound = Some(Sound::Start);
+                }
+                // 停止したときも、止めた時点の時間を表示する
+                draw(
+                    board.display(),
+                    self.stopwatch.elapsed_ms(now_ms),
+                    self.stopwatch.laps().last(),
+                )?;
+            }
+        }
+        Ok(sound)
+    }
+}
+
+impl Default for StopwatchApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 計測時間と直前のラップを描画する
+pub fn draw<T>(display: &mut T, elapsed_ms: u32, last_lap: Option<&Lap>) -> Result<(), T::Error>
+where
+    T: DrawTarget<Rgb565>,
+{
+    let screen_width = SCREEN_WIDTH as i32;
+    // カウント表示エリアとラップ表示エリアをクリアする
+    egrectangle!(
+        top_left = (0, 0),
+        bottom_right = (screen_width - 1, FONT_HEIGHT + LAP_FONT_HEIGHT),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(display)?;
+
+    // 計測時間を "mm:ss.cc" の形式で右寄せで描画する
+    let mut textbuffer = String::<U256>::new();
+    write!(&mut textbuffer, "{}", MinSecCenti(elapsed_ms)).unwrap();
+    let left = screen_width - (textbuffer.len() as i32) * FONT_WIDTH;
+    egtext!(
+        text = textbuffer.as_str(),
+        top_left = (left, 0),
+        style = text_style!(font = Font24x32, text_color = Rgb565::WHITE)
+    )
+    .draw(display)?;
+
+    if let Some(lap) = last_lap {
+        let mut textbuffer = String::<U256>::new();
+        write!(
+            &mut textbuffer,
+            "LAP{} {}",
+            lap.number,
+            MinSecCenti(lap.lap_ms)
+        )
+        .unwrap();
+        let left = screen_width - (textbuffer.len() as i32) * LAP_FONT_WIDTH;
+        egtext!(
+            text = textbuffer.as_str(),
+            top_left = (left, FONT_HEIGHT),
+            style = text_style!(font = Font12x16, text_color = Rgb565::YELLOW)
+        )
+        .draw(display)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::{SimBoard, SCREEN_HEIGHT};
+    use embedded_graphics_simulator::SimulatorDisplay;
+    use std::vec::Vec;
+
+    const FRAME_MS: u32 = 10;
+
+    // ms の間、FRAME_MS ごとに update を呼び、鳴らした操作音を返す
+    fn run(app: &mut StopwatchApp, board: &mut SimBoard, ms: u32) -> Vec<Sound> {
+        let mut sounds = Vec::new();
+        for _ in 0..ms / FRAME_MS {
+            let now = board.uptime_ms();
+            if let Some(sound) = app.update(board, now).unwrap() {
+                sounds.push(sound);
+            }
+            board.delay_ms(FRAME_MS);
+        }
+        sounds
+    }
+
+    // チャタリング除去の時間より長く押してから離す
+    fn click(app: &mut StopwatchApp, board: &mut SimBoard, button: Button) -> Vec<Sound> {
+        board.set_pressed(button, true);
+        let mut sounds = run(app, board, 100);
+        board.set_pressed(button, false);
+        sounds.extend(run(app, board, 100));
+        sounds
+    }
+
+    // 画面の上部 (計測時間とラップ) が draw で描いたものと同じか
+    fn assert_shows(board: &mut SimBoard, elapsed_ms: u32, last_lap: Option<&Lap>) {
+        let mut expected = SimulatorDisplay::new(Size::new(SCREEN_WIDTH, SCREEN_HEIGHT));
+        draw(&mut expected, elapsed_ms, last_lap).unwrap();
+        let display = board.display();
+        for y in 0..=FONT_HEIGHT + LAP_FONT_HEIGHT {
+            for x in 0..SCREEN_WIDTH as i32 {
+                let point = Point::new(x, y);
+                assert_eq!(
+                    display.get_pixel(point),
+                    expected.get_pixel(point),
+                    "pixel at {:?} for {} ms",
+                    point,
+                    elapsed_ms
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn start_stop_and_clear() {
+        let mut board = SimBoard::headless();
+        let mut app = StopwatchApp::new();
+
+        // 計測していないときは停止ボタンを押しても何も起きない
+        assert_eq!(click(&mut app, &mut board, Button::Button2), []);
+        assert_eq!(app.stopwatch().state(), StopwatchState::Idle);
+
+        assert_eq!(click(&mut app, &mut board, Button::Button3), [Sound::Start]);
+        assert!(app.stopwatch().is_running());
+        run(&mut app, &mut board, 1000);
+        assert_eq!(click(&mut app, &mut board, Button::Button2), [Sound::Stop]);
+        assert_eq!(app.stopwatch().state(), StopwatchState::Stopped);
+
+        // 押してから離すまでの時間も含めて、約1.2秒計測している
+        let elapsed = app.stopwatch().elapsed_ms(board.uptime_ms());
+        assert!((1100..=1300).contains(&elapsed), "elapsed {}", elapsed);
+        assert_shows(&mut board, elapsed, None);
+
+        // 停止中は時間が進まない
+        run(&mut app, &mut board, 500);
+        assert_eq!(app.stopwatch().elapsed_ms(board.uptime_ms()), elapsed);
+
+        assert_eq!(click(&mut app, &mut board, Button::Button1), [Sound::Clear]);
+        assert_eq!(app.stopwatch().state(), StopwatchState::Idle);
+        assert_shows(&mut board, 0, None);
+    }
+
+    #[test]
+    fn lap_is_drawn_while_running() {
+        let mut board = SimBoard::headless();
+        let mut app = StopwatchApp::new();
+
+        click(&mut app, &mut board, Button::Button3);
+        run(&mut app, &mut board, 700);
+        assert_eq!(click(&mut app, &mut board, Button::Button1), [Sound::Start]);
+        assert_eq!(app.stopwatch().laps().len(), 1);
+        assert!(app.stopwatch().is_running());
+
+        // 最後の update で描画した時刻の表示とラップが残っている
+        let now = board.uptime_ms() - FRAME_MS;
+        let elapsed = app.stopwatch().elapsed_ms(now);
+        let lap = *app.stopwatch().laps().last().unwrap();
+        assert_shows(&mut board, elapsed, Some(&lap));
+    }
+}
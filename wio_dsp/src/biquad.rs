@@ -0,0 +1,275 @@
+// 2次のIIRフィルタ (双2次フィルタ)
+//
+//   y[n] = b0 x[n] + b1 x[n-1] + b2 x[n-2] - a1 y[n-1] - a2 y[n-2]
+//
+// 転置直接形IIで計算するので、状態は2つだけです
+// 係数は遮断周波数とQから設計するか、アナログフィルタの伝達関数から双一次変換で作れます
+// (設計式は R. Bristow-Johnson "Audio EQ Cookbook" によります)
+
+use micromath::F32Ext;
+
+// フィルタの係数 (a0 = 1 に正規化したもの)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coefficients {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+impl Coefficients {
+    // 入力をそのまま出力する係数
+    pub const IDENTITY: Coefficients = Coefficients {
+        b0: 1.0,
+        b1: 0.0,
+        b2: 0.0,
+        a1: 0.0,
+        a2: 0.0,
+    };
+
+    // 低域通過 (cutoff [Hz] で -3[dB]、Q = 0.707 でバターワース特性)
+    pub fn lowpass(cutoff: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = design_terms(cutoff, q, sample_rate);
+        let b1 = 1.0 - cos;
+        normalize(
+            [b1 / 2.0, b1, b1 / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    // 高域通過
+    pub fn highpass(cutoff: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = design_terms(cutoff, q, sample_rate);
+        let b1 = -(1.0 + cos);
+        normalize(
+            [-b1 / 2.0, b1, -b1 / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    // 帯域通過 (center [Hz] でゲイン1、帯域幅は center / q)
+    pub fn bandpass(center: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = design_terms(center, q, sample_rate);
+        normalize([alpha, 0.0, -alpha], [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    // 帯域阻止 (center [Hz] を取り除く、電源ノイズの除去など)
+    pub fn notch(center: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = design_terms(center, q, sample_rate);
+        normalize(
+            [1.0, -2.0 * cos, 1.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    // アナログフィルタ H(s) = (b[0] s^2 + b[1] s + b[2]) / (a[0] s^2 + a[1] s + a[2]) を
+    // 双一次変換して、サンプリングレート sample_rate [Hz] のデジタルフィルタにします
+    // ナイキスト周波数に近づくほど周波数軸が縮むことに注意してください
+    pub fn from_analog(b: [f64; 3], a: [f64; 3], sample_rate: f32) -> Self {
+        // s = k (1 - z^-1) / (1 + z^-1)
+        let k = 2.0 * sample_rate as f64;
+        let k2 = k * k;
+        let transform = |c: [f64; 3]| {
+            [
+                c[0] * k2 + c[1] * k + c[2],
+                2.0 * (c[2] - c[0] * k2),
+                c[0] * k2 - c[1] * k + c[2],
+            ]
+        };
+        let b = transform(b);
+        let a = transform(a);
+        Coefficients {
+            b0: (b[0] / a[0]) as f32,
+            b1: (b[1] / a[0]) as f32,
+            b2: (b[2] / a[0]) as f32,
+            a1: (a[1] / a[0]) as f32,
+            a2: (a[2] / a[0]) as f32,
+        }
+    }
+
+    // ゲインを gain 倍した係数
+    pub fn scaled(&self, gain: f32) -> Self {
+        Coefficients {
+            b0: self.b0 * gain,
+            b1: self.b1 * gain,
+            b2: self.b2 * gain,
+            ..*self
+        }
+    }
+
+    // 周波数 frequency [Hz] でのゲイン (振幅の比)
+    pub fn magnitude(&self, frequency: f32, sample_rate: f32) -> f32 {
+        use core::f32::consts::PI;
+        let w = 2.0 * PI * frequency / sample_rate;
+        let (cos1, sin1) = (w.cos(), w.sin());
+        let (cos2, sin2) = ((2.0 * w).cos(), (2.0 * w).sin());
+        // e^{-jw} と e^{-2jw} を代入した分子と分母
+        let num_re = self.b0 + self.b1 * cos1 + self.b2 * cos2;
+        let num_im = -(self.b1 * sin1 + self.b2 * sin2);
+        let den_re = 1.0 + self.a1 * cos1 + self.a2 * cos2;
+        let den_im = -(self.a1 * sin1 + self.a2 * sin2);
+        ((num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im)).sqrt()
+    }
+}
+
+// 設計式で使う cos(w0) と alpha
+fn design_terms(frequency: f32, q: f32, sample_rate: f32) -> (f32, f32) {
+    use core::f32::consts::PI;
+    let w0 = 2.0 * PI * frequency / sample_rate;
+    (w0.cos(), w0.sin() / (2.0 * q))
+}
+
+fn normalize(b: [f32; 3], a: [f32; 3]) -> Coefficients {
+    Coefficients {
+        b0: b[0] / a[0],
+        b1: b[1] / a[0],
+        b2: b[2] / a[0],
+        a1: a[1] / a[0],
+        a2: a[2] / a[0],
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Biquad {
+    coefficients: Coefficients,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    pub const fn new(coefficients: Coefficients) -> Self {
+        Biquad {
+            coefficients,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn coefficients(&self) -> &Coefficients {
+        &self.coefficients
+    }
+
+    // 係数を変えます (状態はそのまま)
+    pub fn set_coefficients(&mut self, coefficients: Coefficients) {
+        self.coefficients = coefficients;
+    }
+
+    // 状態を0に戻します
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    // 1サンプル処理します
+    pub fn process(&mut self, x: f32) -> f32 {
+        let c = &self.coefficients;
+        let y = c.b0 * x + self.z1;
+        self.z1 = c.b1 * x - c.a1 * y + self.z2;
+        self.z2 = c.b2 * x - c.a2 * y;
+        y
+    }
+
+    // samples をその場でフィルタします
+    pub fn process_block(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const NYQUIST: f32 = SAMPLE_RATE / 2.0;
+    const BUTTERWORTH_Q: f32 = core::f32::consts::FRAC_1_SQRT_2;
+
+    fn gain(coefficients: &Coefficients, frequency: f32) -> f32 {
+        coefficients.magnitude(frequency, SAMPLE_RATE)
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn lowpass() {
+        let c = Coefficients::lowpass(1000.0, BUTTERWORTH_Q, SAMPLE_RATE);
+        assert!(close(gain(&c, 0.0), 1.0));
+        assert!(close(gain(&c, 1000.0), BUTTERWORTH_Q));
+        assert!(gain(&c, NYQUIST) < 1e-3);
+    }
+
+    #[test]
+    fn highpass() {
+        let c = Coefficients::highpass(1000.0, BUTTERWORTH_Q, SAMPLE_RATE);
+        assert!(gain(&c, 0.0) < 1e-3);
+        assert!(close(gain(&c, 1000.0), BUTTERWORTH_Q));
+        assert!(close(gain(&c, NYQUIST), 1.0));
+    }
+
+    #[test]
+    fn bandpass() {
+        let c = Coefficients::bandpass(1000.0, 2.0, SAMPLE_RATE);
+        assert!(gain(&c, 0.0) < 1e-3);
+        assert!(close(gain(&c, 1000.0), 1.0));
+        assert!(gain(&c, NYQUIST) < 1e-3);
+        // 帯域幅 center / q の両端で -3[dB]
+        let edge = gain(&c, 1000.0 * 1.28);
+        assert!((edge - BUTTERWORTH_Q).abs() < 0.02, "{}", edge);
+    }
+
+    #[test]
+    fn notch() {
+        let c = Coefficients::notch(1000.0, 10.0, SAMPLE_RATE);
+        assert!(close(gain(&c, 0.0), 1.0));
+        assert!(gain(&c, 1000.0) < 1e-3);
+        assert!(close(gain(&c, 5000.0), 1.0));
+        assert!(close(gain(&c, NYQUIST), 1.0));
+    }
+
+    #[test]
+    fn from_analog_first_order_lowpass() {
+        // H(s) = w / (s + w) は遮断周波数で -3[dB]
+        let w = 2.0 * core::f64::consts::PI * 1000.0;
+        let c = Coefficients::from_analog([0.0, 0.0, w], [0.0, 1.0, w], SAMPLE_RATE);
+        assert!(close(gain(&c, 0.0), 1.0));
+        assert!((gain(&c, 1000.0) - BUTTERWORTH_Q).abs() < 0.01);
+        assert!(close(c.scaled(0.5).magnitude(0.0, SAMPLE_RATE), 0.5));
+    }
+
+    #[test]
+    fn identity() {
+        let mut filter = Biquad::new(Coefficients::IDENTITY);
+        assert_eq!(filter.process(0.25), 0.25);
+        assert_eq!(filter.process(-1.0), -1.0);
+    }
+
+    #[test]
+    fn step_response_settles_to_dc_gain() {
+        let mut filter = Biquad::new(Coefficients::lowpass(1000.0, BUTTERWORTH_Q, SAMPLE_RATE));
+        let mut samples = [1.0; 480];
+        filter.process_block(&mut samples);
+        assert!(close(samples[479], 1.0));
+        filter.reset();
+        assert!(filter.process(0.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sine_response_matches_magnitude() {
+        use core::f32::consts::PI;
+        let coefficients = Coefficients::bandpass(1000.0, 2.0, SAMPLE_RATE);
+        let mut filter = Biquad::new(coefficients);
+        let mut peak: f32 = 0.0;
+        for i in 0..4800 {
+            let x = (2.0 * PI * 2000.0 * i as f32 / SAMPLE_RATE).sin();
+            let y = filter.process(x);
+            if i >= 2400 {
+                peak = peak.max(y.abs());
+            }
+        }
+        assert!((peak - gain(&coefficients, 2000.0)).abs() < 0.01);
+    }
+}
@@ -0,0 +1,176 @@
+// 間引き器 (デシメータ)
+//
+// 単純に FACTOR 個ごとに1個取り出すと、新しいナイキスト周波数より高い成分が低い周波数に折り返します
+// 先にFIRの低域通過フィルタをかけてから間引き、出力するサンプルだけを計算します
+// (ポリフェーズ構成と同じ計算量で、入力1サンプルあたりの処理は書き込みだけです)
+//
+//   let mut decimator: Decimator<32> = Decimator::lowpass(4);
+//   // 割り込みハンドラで
+//   if let Some(y) = decimator.push(sample) { ... }
+
+use crate::fir::{convolve, lowpass_coefficients, push_history};
+
+pub struct Decimator<const TAPS: usize> {
+    coefficients: [f32; TAPS],
+    history: [f32; TAPS],
+    index: usize,
+    factor: usize,
+    phase: usize, // 前回出力してから入力したサンプル数
+}
+
+impl<const TAPS: usize> Decimator<TAPS> {
+    // 係数 coefficients のFIRフィルタをかけて、factor 個に1個を出力します
+    pub fn new(coefficients: [f32; TAPS], factor: usize) -> Self {
+        Decimator {
+            coefficients,
+            history: [0.0; TAPS],
+            index: 0,
+            factor: factor.max(1),
+            phase: 0,
+        }
+    }
+
+    // 出力のナイキスト周波数の80%を遮断周波数にした低域通過フィルタで、サンプリングレートを 1/factor にします
+    pub fn lowpass(factor: usize) -> Self {
+        let factor = factor.max(1);
+        // 入力のサンプリングレートを1としたときの遮断周波数
+        let cutoff = 0.8 * 0.5 / factor as f32;
+        Decimator::new(lowpass_coefficients(cutoff, 1.0), factor)
+    }
+
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    pub fn coefficients(&self) -> &[f32; TAPS] {
+        &self.coefficients
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; TAPS];
+        self.index = 0;
+        self.phase = 0;
+    }
+
+    // 1サンプル入力します
+    // factor 個入力するごとに1個出力します
+    pub fn push(&mut self, x: f32) -> Option<f32> {
+        push_history(&mut self.history, &mut self.index, x);
+        self.phase += 1;
+        if self.phase < self.factor {
+            return None;
+        }
+        self.phase = 0;
+        Some(convolve(&self.coefficients, &self.history, self.index))
+    }
+
+    // input を入力し、出力を output の先頭から書き込みます
+    // (入力した数, 書き込んだ数) を返します
+    // output が足りなくなったときは、次に出力するサンプルの手前で入力をやめるので、
+    // 残りの input[consumed..] は次の呼び出しで渡してください
+    pub fn process_block(&mut self, input: &[f32], output: &mut [f32]) -> (usize, usize) {
+        let mut consumed = 0;
+        let mut written = 0;
+        for &x in input {
+            if written == output.len() && self.phase + 1 == self.factor {
+                break;
+            }
+            if let Some(y) = self.push(x) {
+                output[written] = y;
+                written += 1;
+            }
+            consumed += 1;
+        }
+        (consumed, written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::PI;
+    use micromath::F32Ext;
+    use std::vec::Vec;
+
+    // 8-2, 8-3, 8-4 と同じ、32タップで1/4に間引く
+    const TAPS: usize = 32;
+    const FACTOR: usize = 4;
+
+    // 入力のサンプリングレートを1とした周波数 frequency の正弦波を間引き、
+    // 落ち着いた後の出力の振幅 [dB] を求める
+    fn output_gain_db(frequency: f32) -> f32 {
+        let mut decimator: Decimator<TAPS> = Decimator::lowpass(FACTOR);
+        let output: Vec<f32> = (0..8192)
+            .filter_map(|i| decimator.push((2.0 * PI * frequency * i as f32).sin()))
+            .skip(TAPS)
+            .collect();
+        let power = output.iter().map(|y| y * y).sum::<f32>() / output.len() as f32;
+        10.0 * (2.0 * power).log10()
+    }
+
+    #[test]
+    fn passes_low_frequencies() {
+        for &frequency in [0.005, 0.01, 0.02, 0.03].iter() {
+            let gain = output_gain_db(frequency);
+            assert!(gain.abs() < 0.2, "{}: {} dB", frequency, gain);
+        }
+    }
+
+    #[test]
+    fn rejects_aliases() {
+        // 間引いた後のナイキスト周波数 (0.125) を超える成分は、折り返す前に取り除く
+        // (遷移帯域は約 5.5 / 32 = 0.17 なので、0.1 + 0.17 より上を調べる)
+        for &frequency in [0.3, 0.35, 0.4, 0.45].iter() {
+            let gain = output_gain_db(frequency);
+            assert!(gain < -60.0, "{}: {} dB", frequency, gain);
+        }
+        // 間引くだけだと、0.35 は 0.1 に折り返して減衰しない
+        let aliased: f32 = (0..1024)
+            .step_by(FACTOR)
+            .map(|i| (2.0 * PI * 0.35 * i as f32).sin().abs())
+            .fold(0.0, f32::max);
+        assert!(aliased > 0.9);
+    }
+
+    #[test]
+    fn outputs_every_factor_samples() {
+        let mut decimator: Decimator<TAPS> = Decimator::lowpass(FACTOR);
+        let outputs: Vec<bool> = (0..8).map(|_| decimator.push(1.0).is_some()).collect();
+        assert_eq!(
+            outputs,
+            [false, false, false, true, false, false, false, true]
+        );
+        decimator.reset();
+        assert_eq!(decimator.push(1.0), None);
+    }
+
+    #[test]
+    fn process_block_matches_push() {
+        let input: Vec<f32> = (0..100).map(|i| (i as f32 * 0.37).sin()).collect();
+        let mut a: Decimator<TAPS> = Decimator::lowpass(FACTOR);
+        let expected: Vec<f32> = input.iter().filter_map(|&x| a.push(x)).collect();
+
+        let mut b: Decimator<TAPS> = Decimator::lowpass(FACTOR);
+        let mut output = [0.0; 64];
+        let (consumed, written) = b.process_block(&input, &mut output);
+        assert_eq!((consumed, written), (100, 25));
+        assert_eq!(&output[..written], &expected[..]);
+    }
+
+    #[test]
+    fn process_block_stops_when_output_is_full() {
+        let input: Vec<f32> = (0..100).map(|i| (i as f32 * 0.37).sin()).collect();
+        let mut a: Decimator<TAPS> = Decimator::lowpass(FACTOR);
+        let expected: Vec<f32> = input.iter().filter_map(|&x| a.push(x)).collect();
+
+        // 出力が足りないときは、次の出力の手前で止まり、残りは続きから渡せる
+        let mut decimator: Decimator<TAPS> = Decimator::lowpass(FACTOR);
+        let mut output = [0.0; 10];
+        let (consumed, written) = decimator.process_block(&input, &mut output);
+        assert_eq!((consumed, written), (43, 10));
+        assert_eq!(&output[..], &expected[..10]);
+        let (rest, written) = decimator.process_block(&input[consumed..], &mut output);
+        assert_eq!((rest, written), (40, 10));
+        assert_eq!(&output[..], &expected[10..20]);
+    }
+}
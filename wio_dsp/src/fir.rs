@@ -0,0 +1,184 @@
+// FIRフィルタ
+//
+//   y[n] = h[0] x[n] + h[1] x[n-1] + ... + h[TAPS-1] x[n-TAPS+1]
+//
+// 係数が左右対称なら位相が周波数によらず一定 (直線位相) になり、波形の形が崩れません
+// 窓関数法 (sinc関数にBlackman窓をかける) で低域通過フィルタの係数を設計できます
+
+use micromath::F32Ext;
+
+// 遮断周波数 cutoff [Hz] の低域通過フィルタの係数を設計します
+// 直流でのゲインが1になるように正規化します (cutoff でのゲインは約 -6[dB])
+// TAPS が多いほど遮断特性が急になります (遷移帯域の幅はおよそ 5.5 * sample_rate / TAPS)
+pub fn lowpass_coefficients<const TAPS: usize>(cutoff: f32, sample_rate: f32) -> [f32; TAPS] {
+    use core::f32::consts::PI;
+    let fc = cutoff / sample_rate;
+    let center = (TAPS - 1) as f32 / 2.0;
+    let mut coefficients = [0.0; TAPS];
+    let mut sum = 0.0;
+    for (i, coefficient) in coefficients.iter_mut().enumerate() {
+        let t = i as f32 - center;
+        let sinc = if t == 0.0 {
+            2.0 * fc
+        } else {
+            (2.0 * PI * fc * t).sin() / (PI * t)
+        };
+        // 両端が0になるBlackman窓
+        let x = 2.0 * PI * i as f32 / (TAPS - 1) as f32;
+        let window = 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos();
+        *coefficient = sinc * window;
+        sum += *coefficient;
+    }
+    for coefficient in coefficients.iter_mut() {
+        *coefficient /= sum;
+    }
+    coefficients
+}
+
+// 係数 coefficients の周波数 frequency [Hz] でのゲイン (振幅の比)
+pub fn magnitude(coefficients: &[f32], frequency: f32, sample_rate: f32) -> f32 {
+    use core::f32::consts::PI;
+    let w = 2.0 * PI * frequency / sample_rate;
+    let (mut re, mut im) = (0.0, 0.0);
+    for (i, coefficient) in coefficients.iter().enumerate() {
+        re += coefficient * (w * i as f32).cos();
+        im -= coefficient * (w * i as f32).sin();
+    }
+    (re * re + im * im).sqrt()
+}
+
+pub struct Fir<const TAPS: usize> {
+    coefficients: [f32; TAPS],
+    history: [f32; TAPS], // 過去の入力 (リングバッファ)
+    index: usize,         // 次に書き込む位置
+}
+
+impl<const TAPS: usize> Fir<TAPS> {
+    pub fn new(coefficients: [f32; TAPS]) -> Self {
+        Fir {
+            coefficients,
+            history: [0.0; TAPS],
+            index: 0,
+        }
+    }
+
+    // 遮断周波数 cutoff [Hz] の低域通過フィルタ
+    pub fn lowpass(cutoff: f32, sample_rate: f32) -> Self {
+        Fir::new(lowpass_coefficients(cutoff, sample_rate))
+    }
+
+    pub fn coefficients(&self) -> &[f32; TAPS] {
+        &self.coefficients
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; TAPS];
+        self.index = 0;
+    }
+
+    // 1サンプル処理します
+    pub fn process(&mut self, x: f32) -> f32 {
+        push_history(&mut self.history, &mut self.index, x);
+        convolve(&self.coefficients, &self.history, self.index)
+    }
+
+    // samples をその場でフィルタします
+    pub fn process_block(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+}
+
+// リングバッファに x を書き込み、書き込み位置を進める
+pub(crate) fn push_history(history: &mut [f32], index: &mut usize, x: f32) {
+    history[*index] = x;
+    *index += 1;
+    if *index == history.len() {
+        *index = 0;
+    }
+}
+
+// 最新の入力から順に係数をかけて足す
+// (index は次に書き込む位置なので、その1つ前が最新の入力)
+pub(crate) fn convolve(coefficients: &[f32], history: &[f32], index: usize) -> f32 {
+    // 添字の剰余計算を避けるため、リングバッファを2つの区間に分けて計算する
+    // [0, index) は新しい入力、[index, TAPS) はそれより古い入力
+    let (recent, old) = history.split_at(index);
+    let mut sum = 0.0;
+    for (x, h) in recent
+        .iter()
+        .rev()
+        .chain(old.iter().rev())
+        .zip(coefficients)
+    {
+        sum += x * h;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use micromath::F32Ext;
+
+    const SAMPLE_RATE: f32 = 8000.0;
+    const TAPS: usize = 32;
+
+    fn gain_db(coefficients: &[f32], frequency: f32) -> f32 {
+        20.0 * magnitude(coefficients, frequency, SAMPLE_RATE).log10()
+    }
+
+    #[test]
+    fn lowpass_dc_gain_is_one() {
+        let coefficients: [f32; TAPS] = lowpass_coefficients(1000.0, SAMPLE_RATE);
+        let sum: f32 = coefficients.iter().sum();
+        assert!((sum - 1.0).abs() < 1e-6);
+        assert!(gain_db(&coefficients, 0.0).abs() < 1e-3);
+        // 遮断周波数では約 -6[dB]
+        assert!((gain_db(&coefficients, 1000.0) + 6.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn lowpass_is_symmetric() {
+        let coefficients: [f32; TAPS] = lowpass_coefficients(1000.0, SAMPLE_RATE);
+        for i in 0..TAPS / 2 {
+            assert!((coefficients[i] - coefficients[TAPS - 1 - i]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn lowpass_stopband() {
+        let coefficients: [f32; TAPS] = lowpass_coefficients(1000.0, SAMPLE_RATE);
+        // 遷移帯域 (約 5.5 * 8000 / 32 = 1375[Hz]) より先は Blackman窓で大きく減衰する
+        let mut frequency = 1000.0 + 5.5 * SAMPLE_RATE / TAPS as f32;
+        while frequency <= SAMPLE_RATE / 2.0 {
+            let gain = gain_db(&coefficients, frequency);
+            assert!(gain < -60.0, "{} Hz: {} dB", frequency, gain);
+            frequency += 50.0;
+        }
+        // 通過域はほぼ平坦
+        assert!(gain_db(&coefficients, 300.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn impulse_response_is_coefficients() {
+        let mut fir = Fir::<TAPS>::lowpass(1000.0, SAMPLE_RATE);
+        let coefficients = *fir.coefficients();
+        assert_eq!(fir.process(1.0), coefficients[0]);
+        for coefficient in coefficients.iter().skip(1) {
+            assert_eq!(fir.process(0.0), *coefficient);
+        }
+        assert_eq!(fir.process(0.0), 0.0);
+    }
+
+    #[test]
+    fn step_response_and_reset() {
+        let mut fir = Fir::<TAPS>::lowpass(1000.0, SAMPLE_RATE);
+        let mut samples = [1.0; TAPS * 2];
+        fir.process_block(&mut samples);
+        assert!((samples[TAPS * 2 - 1] - 1.0).abs() < 1e-5);
+        fir.reset();
+        assert_eq!(fir.process(0.0), 0.0);
+    }
+}
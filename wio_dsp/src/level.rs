@@ -0,0 +1,245 @@
+// 音の大きさの計測
+//
+// サンプルを一定の長さ (窓) ごとに区切り、窓ごとの実効値とピークを dB で求めます
+// 等価騒音レベル (Leq) は、計測を始めてからの (または一定時間ごとの) エネルギーの平均です
+//
+// レベルは振幅1の直流を0[dB]とした値で、較正値を足して返します
+// (マイクを騒音計と比べて較正値を決めれば、音圧レベル [dB SPL] として読めます)
+//
+//   let mut meter = LevelMeter::new(SAMPLING_RATE, 125);
+//   if let Some(reading) = meter.process(&samples) { ... }
+
+use micromath::F32Ext;
+
+// 値が0のときなどに返すレベルの下限 (較正値を足す前)
+pub const FLOOR_DB: f32 = -150.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelReading {
+    pub rms_db: f32,  // 窓の実効値
+    pub peak_db: f32, // 窓の中の最大の振幅
+    pub leq_db: f32,  // 等価騒音レベル
+}
+
+pub struct LevelMeter {
+    sample_rate: f32,
+    window_samples: u32,
+    calibration_db: f32,
+    // 今の窓
+    sum_squares: f32,
+    peak: f32,
+    count: u32,
+    // Leq (長時間積算するので f64 で足す)
+    leq_sum: f64,
+    leq_count: u64,
+    leq_period_samples: u64, // 0なら reset_leq まで積算を続ける
+}
+
+impl LevelMeter {
+    // window_ms [ms] ごとにレベルを求めます
+    // (騒音計の時定数に合わせるなら、FAST は 125[ms]、SLOW は 1000[ms])
+    pub fn new(sample_rate: f32, window_ms: u32) -> Self {
+        let mut meter = LevelMeter {
+            sample_rate,
+            window_samples: 1,
+            calibration_db: 0.0,
+            sum_squares: 0.0,
+            peak: 0.0,
+            count: 0,
+            leq_sum: 0.0,
+            leq_count: 0,
+            leq_period_samples: 0,
+        };
+        meter.set_window_ms(window_ms);
+        meter
+    }
+
+    // 窓の長さを変えます (計測中の窓は捨てます)
+    pub fn set_window_ms(&mut self, window_ms: u32) {
+        let samples = (self.sample_rate * window_ms as f32 / 1000.0) as u32;
+        self.window_samples = samples.max(1);
+        self.sum_squares = 0.0;
+        self.peak = 0.0;
+        self.count = 0;
+    }
+
+    // Leq を period_s [s] ごとに計算し直します (0なら reset_leq まで積算を続ける)
+    pub fn set_leq_period_s(&mut self, period_s: u32) {
+        self.leq_period_samples = (self.sample_rate as f64 * period_s as f64) as u64;
+        self.reset_leq();
+    }
+
+    // すべてのレベルに offset_db を足します
+    pub fn set_calibration(&mut self, offset_db: f32) {
+        self.calibration_db = offset_db;
+    }
+
+    pub fn calibration(&self) -> f32 {
+        self.calibration_db
+    }
+
+    // Leq の積算をやり直します
+    pub fn reset_leq(&mut self) {
+        self.leq_sum = 0.0;
+        self.leq_count = 0;
+    }
+
+    // Leq を積算している時間 [s]
+    pub fn leq_duration_s(&self) -> f32 {
+        self.leq_count as f32 / self.sample_rate
+    }
+
+    // 1サンプル加えます
+    // 窓が埋まったときにその窓のレベルを返します
+    pub fn push(&mut self, x: f32) -> Option<LevelReading> {
+        let square = x * x;
+        self.sum_squares += square;
+        let magnitude = x.abs();
+        if magnitude > self.peak {
+            self.peak = magnitude;
+        }
+        self.count += 1;
+
+        if self.leq_period_samples > 0 && self.leq_count >= self.leq_period_samples {
+            self.reset_leq();
+        }
+        self.leq_sum += square as f64;
+        self.leq_count += 1;
+
+        if self.count < self.window_samples {
+            return None;
+        }
+        let reading = LevelReading {
+            rms_db: self.to_db(self.sum_squares / self.count as f32),
+            peak_db: self.to_db(self.peak * self.peak),
+            leq_db: self.to_db((self.leq_sum / self.leq_count as f64) as f32),
+        };
+        self.sum_squares = 0.0;
+        self.peak = 0.0;
+        self.count = 0;
+        Some(reading)
+    }
+
+    // samples をすべて加えます
+    // 途中で埋まった窓のうち、最後の窓のレベルを返します
+    pub fn process(&mut self, samples: &[f32]) -> Option<LevelReading> {
+        samples.iter().fold(None, |last, &x| self.push(x).or(last))
+    }
+
+    // 振幅の2乗を dB にする
+    fn to_db(&self, power: f32) -> f32 {
+        let level = if power > 0.0 {
+            10.0 * power.log10()
+        } else {
+            FLOOR_DB
+        };
+        let level = if level < FLOOR_DB { FLOOR_DB } else { level };
+        level + self.calibration_db
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::PI;
+    use std::vec::Vec;
+
+    const SAMPLE_RATE: f32 = 8000.0;
+
+    // 1[kHz] の正弦波 (1周期がちょうど8サンプルなので、ピークの値も含まれる)
+    fn sine(amplitude: f32, seconds: f32) -> Vec<f32> {
+        let n = (SAMPLE_RATE * seconds) as usize;
+        (0..n)
+            .map(|i| amplitude * (2.0 * PI * 1000.0 * i as f32 / SAMPLE_RATE).sin())
+            .collect()
+    }
+
+    fn silence(seconds: f32) -> Vec<f32> {
+        std::vec![0.0; (SAMPLE_RATE * seconds) as usize]
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.02
+    }
+
+    #[test]
+    fn full_scale_sine() {
+        let mut meter = LevelMeter::new(SAMPLE_RATE, 125);
+        let reading = meter.process(&sine(1.0, 0.125)).unwrap();
+        assert!(close(reading.rms_db, -3.01), "{:?}", reading);
+        assert!(close(reading.peak_db, 0.0), "{:?}", reading);
+        assert!(close(reading.leq_db, -3.01), "{:?}", reading);
+    }
+
+    #[test]
+    fn window_length() {
+        let mut meter = LevelMeter::new(SAMPLE_RATE, 125);
+        let samples = sine(0.5, 0.125);
+        // 窓が埋まるまでは何も返さない
+        assert_eq!(meter.process(&samples[..999]), None);
+        let reading = meter.push(samples[999]).unwrap();
+        assert!(close(reading.rms_db, -9.03), "{:?}", reading);
+        assert!(close(reading.peak_db, -6.02), "{:?}", reading);
+    }
+
+    #[test]
+    fn silence_is_floor() {
+        let mut meter = LevelMeter::new(SAMPLE_RATE, 125);
+        let reading = meter.process(&silence(0.125)).unwrap();
+        assert_eq!(reading.rms_db, FLOOR_DB);
+        assert_eq!(reading.peak_db, FLOOR_DB);
+    }
+
+    #[test]
+    fn leq_averages_energy() {
+        // 1秒の正弦波と1秒の無音の Leq は、正弦波の実効値より3[dB]小さい
+        let mut meter = LevelMeter::new(SAMPLE_RATE, 125);
+        meter.process(&sine(1.0, 1.0));
+        let reading = meter.process(&silence(1.0)).unwrap();
+        assert_eq!(reading.rms_db, FLOOR_DB);
+        assert!(close(reading.leq_db, -6.02), "{:?}", reading);
+        assert!(close(meter.leq_duration_s(), 2.0));
+
+        meter.reset_leq();
+        assert_eq!(meter.leq_duration_s(), 0.0);
+        let reading = meter.process(&sine(0.5, 0.125)).unwrap();
+        assert!(close(reading.leq_db, -9.03), "{:?}", reading);
+    }
+
+    #[test]
+    fn leq_period_reset() {
+        let mut meter = LevelMeter::new(SAMPLE_RATE, 500);
+        meter.set_leq_period_s(1);
+        meter.process(&sine(1.0, 1.0));
+        assert!(close(meter.leq_duration_s(), 1.0));
+        // 次の期間の最初のサンプルで積算をやり直す
+        let reading = meter.process(&silence(0.5)).unwrap();
+        assert_eq!(reading.leq_db, FLOOR_DB);
+        assert!(close(meter.leq_duration_s(), 0.5));
+        let reading = meter.process(&sine(1.0, 0.5)).unwrap();
+        assert!(close(reading.leq_db, -6.02), "{:?}", reading);
+    }
+
+    #[test]
+    fn calibration_offset() {
+        let mut meter = LevelMeter::new(SAMPLE_RATE, 125);
+        meter.set_calibration(94.0);
+        assert_eq!(meter.calibration(), 94.0);
+        let reading = meter.process(&sine(1.0, 0.125)).unwrap();
+        assert!(close(reading.rms_db, 90.99), "{:?}", reading);
+        assert!(close(reading.peak_db, 94.0), "{:?}", reading);
+        assert!(close(reading.leq_db, 90.99), "{:?}", reading);
+        // 下限にも較正値を足す
+        let reading = meter.process(&silence(0.125)).unwrap();
+        assert_eq!(reading.rms_db, FLOOR_DB + 94.0);
+    }
+
+    #[test]
+    fn process_returns_last_window() {
+        let mut meter = LevelMeter::new(SAMPLE_RATE, 125);
+        let mut samples = sine(1.0, 0.125);
+        samples.extend(sine(0.5, 0.125));
+        let reading = meter.process(&samples).unwrap();
+        assert!(close(reading.rms_db, -9.03), "{:?}", reading);
+    }
+}
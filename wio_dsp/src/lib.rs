@@ -0,0 +1,27 @@
+#![cfg_attr(not(test), no_std)]
+// テストでは std の f32 のメソッドが使われるので、micromath::F32Ext が未使用になる
+#![cfg_attr(test, allow(unused_imports))]
+
+// マイクやセンサの信号を処理するためのフィルタと計測器です
+// サンプルは f32 で、ハードウェアに依存しないのでホストPCでも動きます
+//
+// biquad:    2次のIIRフィルタ (双2次フィルタ)
+// fir:       FIRフィルタ
+// decimator: 低域通過フィルタをかけてサンプリングレートを下げる間引き器
+// weighting: 騒音計のA特性/C特性の聴感補正フィルタ
+// level:     実効値・ピーク・等価騒音レベル (Leq) の計測
+// spectrum:  窓関数をかけたFFTで求めるスペクトラム (dBFS)
+
+pub mod biquad;
+pub mod decimator;
+pub mod fir;
+pub mod level;
+pub mod spectrum;
+pub mod weighting;
+
+pub use biquad::{Biquad, Coefficients};
+pub use decimator::Decimator;
+pub use fir::Fir;
+pub use level::{LevelMeter, LevelReading};
+pub use spectrum::{Peak, Spectrum, Window};
+pub use weighting::{Weighting, WeightingFilter};
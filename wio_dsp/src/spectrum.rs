@@ -0,0 +1,420 @@
+// サンプル列からスペクトラムを求めるモジュールです
+//
+// 窓関数をかけてFFTし、各周波数成分 (ビン) の大きさを dBFS (フルスケールの正弦波を0[dB]とした値) で返します
+// 窓関数によるゲインの違いは補正するので、窓関数を変えても正弦波のレベルはほぼ同じになります
+// 複数フレームの平均化と、ピークホールドにも対応しています
+//
+// FFTの点数 N は 64〜2048 の2のべき乗で、ビンの数 BINS は N/2 です
+// (型の別名 Spectrum64〜Spectrum2048 を使うと BINS を書かずに済みます)
+//
+//   let mut spectrum = Spectrum256::new(Window::Hann, SAMPLING_RATE, AMPLITUDE);
+//   spectrum.process(&mut samples);   // samples は作業領域として書き換えられる
+//   let level = spectrum.level_dbfs(10);
+//   let frequency = spectrum.bin_frequency(10);
+
+use micromath::F32Ext;
+
+// レベルが0のときなどに返す dBFS の下限
+pub const FLOOR_DBFS: f32 = -150.0;
+
+pub type Spectrum64 = Spectrum<64, 32>;
+pub type Spectrum128 = Spectrum<128, 64>;
+pub type Spectrum256 = Spectrum<256, 128>;
+pub type Spectrum512 = Spectrum<512, 256>;
+pub type Spectrum1024 = Spectrum<1024, 512>;
+pub type Spectrum2048 = Spectrum<2048, 1024>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    Rectangular, // 窓関数なし
+    Hann,        // 汎用
+    Hamming,     // Hann より近くの漏れが小さい
+    Blackman,    // 遠くの漏れが小さい
+    FlatTop,     // 周波数分解能は低いがレベルの誤差が小さい
+}
+
+impl Window {
+    // N 点の窓関数の i 番目の係数
+    // 連続して処理するサンプルに使うので、周期的な (両端が対称でない) 形にする
+    pub fn coefficient(&self, i: usize, n: usize) -> f32 {
+        use core::f32::consts::PI;
+        let x = 2.0 * PI * i as f32 / n as f32;
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * x.cos(),
+            Window::Hamming => 0.54 - 0.46 * x.cos(),
+            Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+            Window::FlatTop => {
+                0.215_578_95 - 0.416_631_58 * x.cos() + 0.277_263_16 * (2.0 * x).cos()
+                    - 0.083_578_95 * (3.0 * x).cos()
+                    + 0.006_947_37 * (4.0 * x).cos()
+            }
+        }
+    }
+
+    // メインローブの片側の幅 [ビン]
+    // DCや大きな成分の漏れがこの範囲に広がる
+    pub fn main_lobe_bins(&self) -> usize {
+        match self {
+            Window::Rectangular => 1,
+            Window::Hann | Window::Hamming => 2,
+            Window::Blackman => 3,
+            Window::FlatTop => 5,
+        }
+    }
+}
+
+// 最も大きいビン
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Peak {
+    pub bin: usize,
+    pub frequency: f32,  // 両隣のビンから補間した周波数 [Hz]
+    pub level_dbfs: f32, // ビンのレベル
+}
+
+pub struct Spectrum<const N: usize, const BINS: usize> {
+    window: Window,
+    coefficients: [f32; N],
+    window_sum: f32, // 窓関数のゲインの補正に使う
+    sample_rate: f32,
+    full_scale: f32,
+    averaging: u32,
+    frames: u32,
+    power: [f32; BINS], // 平均化した振幅の2乗 (フルスケールを1とする)
+    peak_dbfs: [f32; BINS],
+    peak_decay_db: f32,
+}
+
+impl<const N: usize, const BINS: usize> Spectrum<N, BINS> {
+    // sample_rate はサンプリングレート [Hz]
+    // full_scale は 0[dBFS] とする正弦波の振幅 (サンプルと同じ単位)
+    pub fn new(window: Window, sample_rate: f32, full_scale: f32) -> Self {
+        assert!(
+            matches!(N, 64 | 128 | 256 | 512 | 1024 | 2048) && BINS * 2 == N,
+            "unsupported FFT size"
+        );
+        let mut spectrum = Spectrum {
+            window,
+            coefficients: [0.0; N],
+            window_sum: 0.0,
+            sample_rate,
+            full_scale,
+            averaging: 1,
+            frames: 0,
+            power: [0.0; BINS],
+            peak_dbfs: [FLOOR_DBFS; BINS],
+            peak_decay_db: 0.0,
+        };
+        spectrum.set_window(window);
+        spectrum
+    }
+
+    pub fn window(&self) -> Window {
+        self.window
+    }
+
+    pub fn set_window(&mut self, window: Window) {
+        self.window = window;
+        self.window_sum = 0.0;
+        for (i, coefficient) in self.coefficients.iter_mut().enumerate() {
+            *coefficient = window.coefficient(i, N);
+            self.window_sum += *coefficient;
+        }
+        self.reset();
+    }
+
+    // 直近 frames フレームを指数移動平均で平均化します (1なら平均化しない)
+    pub fn set_averaging(&mut self, frames: u32) {
+        self.averaging = frames.max(1);
+    }
+
+    // ピークホールドの値を1フレームごとに decay_db ずつ下げます (0なら reset まで保持する)
+    pub fn set_peak_decay(&mut self, decay_db: f32) {
+        self.peak_decay_db = decay_db;
+    }
+
+    // 平均化とピークホールドをやり直します
+    pub fn reset(&mut self) {
+        self.frames = 0;
+        self.power = [0.0; BINS];
+        self.peak_dbfs = [FLOOR_DBFS; BINS];
+    }
+
+    pub const fn fft_size(&self) -> usize {
+        N
+    }
+
+    pub const fn bins(&self) -> usize {
+        BINS
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    // ビンの中心周波数 [Hz]
+    pub fn bin_frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.sample_rate / N as f32
+    }
+
+    // 周波数 [Hz] に最も近いビン
+    pub fn frequency_bin(&self, frequency: f32) -> usize {
+        let bin = (frequency * N as f32 / self.sample_rate).round();
+        if bin <= 0.0 {
+            0
+        } else if bin >= (BINS - 1) as f32 {
+            BINS - 1
+        } else {
+            bin as usize
+        }
+    }
+
+    // N 個のサンプルを1フレームとして処理します
+    // samples はFFTの作業領域として書き換えられます
+    pub fn process(&mut self, samples: &mut [f32; N]) {
+        for (sample, coefficient) in samples.iter_mut().zip(self.coefficients.iter()) {
+            *sample *= coefficient;
+        }
+        let result = fft(samples);
+
+        // 振幅 = 2|X| / (窓関数の総和) (DCだけは |X| / 総和)
+        let scale = 2.0 / (self.window_sum * self.full_scale);
+        let frames = (self.frames + 1).min(self.averaging);
+        let weight = 1.0 / frames as f32;
+        for (bin, spectrum) in result.iter().enumerate().take(BINS) {
+            let amplitude = if bin == 0 {
+                spectrum.re.abs() * scale / 2.0
+            } else {
+                spectrum.norm_sqr().sqrt() * scale
+            };
+            let power = amplitude * amplitude;
+            self.power[bin] += (power - self.power[bin]) * weight;
+
+            let level = power_to_dbfs(self.power[bin]);
+            let held = self.peak_dbfs[bin] - self.peak_decay_db;
+            self.peak_dbfs[bin] = if level > held { level } else { held };
+        }
+        self.frames = frames;
+    }
+
+    // ビンのレベル [dBFS] (平均化した値)
+    pub fn level_dbfs(&self, bin: usize) -> f32 {
+        power_to_dbfs(self.power[bin])
+    }
+
+    // ビンのピークホールドの値 [dBFS]
+    pub fn peak_hold_dbfs(&self, bin: usize) -> f32 {
+        self.peak_dbfs[bin]
+    }
+
+    // 全ビンのレベル [dBFS]
+    pub fn levels(&self) -> impl Iterator<Item = f32> + '_ {
+        self.power.iter().map(|&power| power_to_dbfs(power))
+    }
+
+    // DCとその漏れを除いて最も大きいビン
+    pub fn peak(&self) -> Option<Peak> {
+        let (bin, _) = self
+            .power
+            .iter()
+            .enumerate()
+            .skip(self.window.main_lobe_bins())
+            .fold(None, |max: Option<(usize, f32)>, (bin, &power)| match max {
+                Some((_, max_power)) if max_power >= power => max,
+                _ => Some((bin, power)),
+            })?;
+        if self.power[bin] <= 0.0 {
+            return None;
+        }
+
+        // 両隣のレベルを放物線で補間して、ビンの間の周波数を求める
+        let mut offset = 0.0;
+        if bin + 1 < BINS {
+            let left = self.level_dbfs(bin - 1);
+            let center = self.level_dbfs(bin);
+            let right = self.level_dbfs(bin + 1);
+            let denominator = left - 2.0 * center + right;
+            if denominator < 0.0 {
+                offset = 0.5 * (left - right) / denominator;
+            }
+        }
+        Some(Peak {
+            bin,
+            frequency: (bin as f32 + offset) * self.sample_rate / N as f32,
+            level_dbfs: self.level_dbfs(bin),
+        })
+    }
+}
+
+// レベル [dBFS] を floor_dbfs〜ceiling_dbfs の範囲で 0.0〜1.0 に変換します (棒グラフの高さなどに使う)
+pub fn scale(level_dbfs: f32, floor_dbfs: f32, ceiling_dbfs: f32) -> f32 {
+    let ratio = (level_dbfs - floor_dbfs) / (ceiling_dbfs - floor_dbfs);
+    ratio.clamp(0.0, 1.0)
+}
+
+fn power_to_dbfs(power: f32) -> f32 {
+    if power <= 0.0 {
+        return FLOOR_DBFS;
+    }
+    let level = 10.0 * power.log10();
+    if level < FLOOR_DBFS {
+        FLOOR_DBFS
+    } else {
+        level
+    }
+}
+
+fn fft(samples: &mut [f32]) -> &mut [microfft::Complex32] {
+    use microfft::real::*;
+    match samples.len() {
+        64 => rfft_64(samples),
+        128 => rfft_128(samples),
+        256 => rfft_256(samples),
+        512 => rfft_512(samples),
+        1024 => rfft_1024(samples),
+        2048 => rfft_2048(samples),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::PI;
+
+    const SAMPLE_RATE: f32 = 8000.0;
+    const FULL_SCALE: f32 = 2048.0;
+    // Spectrum256 の1ビンの幅 [Hz]
+    const BIN_WIDTH: f32 = SAMPLE_RATE / 256.0;
+
+    fn sine(frequency: f32, amplitude: f32) -> [f32; 256] {
+        let mut samples = [0.0; 256];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample = amplitude * (2.0 * PI * frequency * i as f32 / SAMPLE_RATE).sin();
+        }
+        samples
+    }
+
+    fn analyze(window: Window, frequency: f32, amplitude: f32) -> Spectrum256 {
+        let mut spectrum = Spectrum256::new(window, SAMPLE_RATE, FULL_SCALE);
+        spectrum.process(&mut sine(frequency, amplitude));
+        spectrum
+    }
+
+    #[test]
+    fn bins() {
+        let spectrum = Spectrum256::new(Window::Hann, SAMPLE_RATE, FULL_SCALE);
+        assert_eq!(spectrum.bins(), 128);
+        assert_eq!(spectrum.bin_frequency(32), 1000.0);
+        assert_eq!(spectrum.frequency_bin(1010.0), 32);
+        assert_eq!(spectrum.frequency_bin(-5.0), 0);
+        assert_eq!(spectrum.frequency_bin(SAMPLE_RATE), 127);
+    }
+
+    #[test]
+    fn peak_at_sine_frequency() {
+        for &window in [Window::Rectangular, Window::Hann, Window::Blackman].iter() {
+            let peak = analyze(window, 1000.0, FULL_SCALE / 2.0).peak().unwrap();
+            assert_eq!(peak.bin, 32, "{:?}", window);
+            assert!((peak.frequency - 1000.0).abs() < 0.5, "{:?}", peak);
+        }
+    }
+
+    #[test]
+    fn full_scale_sine_is_zero_dbfs() {
+        // ビンの中心の周波数なら、どの窓関数でも 0[dBFS]
+        for &window in [
+            Window::Rectangular,
+            Window::Hann,
+            Window::Hamming,
+            Window::Blackman,
+            Window::FlatTop,
+        ]
+        .iter()
+        {
+            let spectrum = analyze(window, 1000.0, FULL_SCALE);
+            assert!(spectrum.level_dbfs(32).abs() < 0.05, "{:?}", window);
+        }
+        // 半分の振幅なら -6[dB]
+        let spectrum = analyze(Window::Hann, 1000.0, FULL_SCALE / 2.0);
+        assert!((spectrum.level_dbfs(32) + 6.02).abs() < 0.05);
+    }
+
+    #[test]
+    fn flat_top_keeps_level_between_bins() {
+        // ビンの間の周波数では Hann は約1.4[dB]下がるが、FlatTop はほとんど下がらない
+        let frequency = 1000.0 + BIN_WIDTH / 2.0;
+        let hann = analyze(Window::Hann, frequency, FULL_SCALE).peak().unwrap();
+        let flat_top = analyze(Window::FlatTop, frequency, FULL_SCALE)
+            .peak()
+            .unwrap();
+        assert!((hann.level_dbfs + 1.42).abs() < 0.1, "{:?}", hann);
+        assert!(flat_top.level_dbfs.abs() < 0.1, "{:?}", flat_top);
+    }
+
+    #[test]
+    fn window_reduces_leakage() {
+        // ビンの間の周波数で、ピークから離れたビンへの漏れを比べる
+        let frequency = 1000.0 + BIN_WIDTH / 2.0;
+        let rectangular = analyze(Window::Rectangular, frequency, FULL_SCALE);
+        let hann = analyze(Window::Hann, frequency, FULL_SCALE);
+        let blackman = analyze(Window::Blackman, frequency, FULL_SCALE);
+        let far = 32 + 12;
+        assert!(rectangular.level_dbfs(far) > -40.0);
+        assert!(hann.level_dbfs(far) < -65.0);
+        assert!(blackman.level_dbfs(far) < -78.0);
+    }
+
+    #[test]
+    fn interpolated_frequency() {
+        // 放物線補間でビンの幅より細かく周波数を求められる
+        for &offset in [0.1, 0.25, 0.4, -0.3].iter() {
+            let frequency = 1000.0 + offset * BIN_WIDTH;
+            let peak = analyze(Window::Hann, frequency, FULL_SCALE).peak().unwrap();
+            let error = (peak.frequency - frequency).abs();
+            assert!(error < 0.1 * BIN_WIDTH, "{} Hz: {:?}", frequency, peak);
+        }
+    }
+
+    #[test]
+    fn silence_has_no_peak() {
+        let spectrum = analyze(Window::Hann, 1000.0, 0.0);
+        assert_eq!(spectrum.peak(), None);
+        assert_eq!(spectrum.level_dbfs(32), FLOOR_DBFS);
+    }
+
+    #[test]
+    fn dc_is_ignored_by_peak() {
+        let mut samples = sine(2000.0, FULL_SCALE / 10.0);
+        for sample in samples.iter_mut() {
+            *sample += FULL_SCALE / 2.0;
+        }
+        let mut spectrum = Spectrum256::new(Window::Hann, SAMPLE_RATE, FULL_SCALE);
+        spectrum.process(&mut samples);
+        assert!((spectrum.level_dbfs(0) + 6.02).abs() < 0.05);
+        assert_eq!(spectrum.peak().unwrap().bin, 64);
+    }
+
+    #[test]
+    fn averaging_and_peak_hold() {
+        let mut spectrum = Spectrum256::new(Window::Hann, SAMPLE_RATE, FULL_SCALE);
+        spectrum.set_averaging(2);
+        spectrum.set_peak_decay(3.0);
+        spectrum.process(&mut sine(1000.0, FULL_SCALE));
+        spectrum.process(&mut sine(1000.0, 0.0));
+        // 2フレームの平均で電力は半分 (-3[dB])
+        assert!((spectrum.level_dbfs(32) + 3.01).abs() < 0.05);
+        // ピークホールドは1フレームで 3[dB] 下がる
+        assert!((spectrum.peak_hold_dbfs(32) + 3.0).abs() < 0.05);
+        spectrum.reset();
+        assert_eq!(spectrum.level_dbfs(32), FLOOR_DBFS);
+        assert_eq!(spectrum.peak_hold_dbfs(32), FLOOR_DBFS);
+    }
+
+    #[test]
+    fn scale_to_ratio() {
+        assert_eq!(scale(-60.0, -80.0, -40.0), 0.5);
+        assert_eq!(scale(-100.0, -80.0, -40.0), 0.0);
+        assert_eq!(scale(0.0, -80.0, -40.0), 1.0);
+    }
+}
@@ -0,0 +1,255 @@
+// 騒音計の周波数重み付け (IEC 61672-1)
+//
+// A特性は小さな音に対する耳の感度に近く、低音と高音を弱めます
+// C特性は大きな音向けで、ほぼ平坦です
+// アナログの伝達関数を双一次変換して2次のフィルタを縦続に並べ、1[kHz]でのゲインを0[dB]に合わせます
+//
+// サンプリングレートが低いとナイキスト周波数の近くで規格からずれます
+// (20.8[kHz]では 5[kHz] 以上で誤差が大きくなります)
+
+use crate::biquad::{Biquad, Coefficients};
+
+// 規格の極の周波数 [Hz]
+const F1: f64 = 20.598_997;
+const F2: f64 = 107.652_65;
+const F3: f64 = 737.862_23;
+const F4: f64 = 12_194.217;
+// Z特性で取り除く直流付近の周波数 [Hz]
+const DC_CUTOFF: f64 = 10.0;
+const SECTIONS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weighting {
+    A,
+    C,
+    Z, // 重み付けなし (直流だけ取り除く)
+}
+
+impl Weighting {
+    pub const ALL: [Weighting; 3] = [Weighting::A, Weighting::C, Weighting::Z];
+
+    // 表示用の名前 ("dB(A)" などに使う)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Weighting::A => "A",
+            Weighting::C => "C",
+            Weighting::Z => "Z",
+        }
+    }
+}
+
+pub struct WeightingFilter {
+    weighting: Weighting,
+    sample_rate: f32,
+    sections: [Biquad; SECTIONS],
+}
+
+impl WeightingFilter {
+    pub fn new(weighting: Weighting, sample_rate: f32) -> Self {
+        use core::f64::consts::PI;
+        let w = |f: f64| 2.0 * PI * f;
+        // (s + p)^2 と (s + p)(s + q) の係数
+        let double = |p: f64| [1.0, 2.0 * p, p * p];
+        let pair = |p: f64, q: f64| [1.0, p + q, p * q];
+        let highpass = [1.0, 0.0, 0.0]; // s^2
+        let lowpass = [0.0, 0.0, 1.0]; // 1
+
+        let mut coefficients = [Coefficients::IDENTITY; SECTIONS];
+        match weighting {
+            // s^4 / ((s + w1)^2 (s + w2)(s + w3)(s + w4)^2)
+            Weighting::A => {
+                coefficients[0] = Coefficients::from_analog(highpass, double(w(F1)), sample_rate);
+                coefficients[1] =
+                    Coefficients::from_analog(highpass, pair(w(F2), w(F3)), sample_rate);
+                coefficients[2] = Coefficients::from_analog(lowpass, double(w(F4)), sample_rate);
+            }
+            // s^2 / ((s + w1)^2 (s + w4)^2)
+            Weighting::C => {
+                coefficients[0] = Coefficients::from_analog(highpass, double(w(F1)), sample_rate);
+                coefficients[1] = Coefficients::from_analog(lowpass, double(w(F4)), sample_rate);
+            }
+            Weighting::Z => {
+                coefficients[0] =
+                    Coefficients::from_analog(highpass, double(w(DC_CUTOFF)), sample_rate);
+            }
+        }
+
+        // 1[kHz] でのゲインが1になるように最初の段で補正する
+        let gain: f32 = coefficients
+            .iter()
+            .map(|c| c.magnitude(1000.0, sample_rate))
+            .product();
+        coefficients[0] = coefficients[0].scaled(1.0 / gain);
+
+        WeightingFilter {
+            weighting,
+            sample_rate,
+            sections: [
+                Biquad::new(coefficients[0]),
+                Biquad::new(coefficients[1]),
+                Biquad::new(coefficients[2]),
+            ],
+        }
+    }
+
+    pub fn weighting(&self) -> Weighting {
+        self.weighting
+    }
+
+    pub fn reset(&mut self) {
+        for section in self.sections.iter_mut() {
+            section.reset();
+        }
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.sections
+            .iter_mut()
+            .fold(x, |x, section| section.process(x))
+    }
+
+    pub fn process_block(&mut self, samples: &mut [f32]) {
+        for section in self.sections.iter_mut() {
+            section.process_block(samples);
+        }
+    }
+
+    // 周波数 frequency [Hz] でのゲイン (振幅の比)
+    pub fn magnitude(&self, frequency: f32) -> f32 {
+        self.sections
+            .iter()
+            .map(|section| {
+                section
+                    .coefficients()
+                    .magnitude(frequency, self.sample_rate)
+            })
+            .product()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use micromath::F32Ext;
+
+    // 8-4-sound_level と同じ、83.3[kHz] を1/4に間引いたサンプリングレート
+    const DECIMATED_RATE: f32 = 83333.0 / 4.0;
+
+    fn gain_db(filter: &WeightingFilter, frequency: f32) -> f32 {
+        20.0 * filter.magnitude(frequency).log10()
+    }
+
+    // (公称周波数 [Hz], IEC 61672-1 の規格値 [dB])
+    // 規格値は公称周波数で0.1[dB]に丸めてあるので、0.2[dB]まで許す
+    const A_TABLE: [(f32, f32); 7] = [
+        (31.5, -39.4),
+        (63.0, -26.2),
+        (100.0, -19.1),
+        (250.0, -8.6),
+        (1000.0, 0.0),
+        (2000.0, 1.2),
+        (4000.0, 1.0),
+    ];
+    const C_TABLE: [(f32, f32); 5] = [
+        (31.5, -3.0),
+        (100.0, -0.3),
+        (1000.0, 0.0),
+        (2000.0, -0.2),
+        (4000.0, -0.8),
+    ];
+
+    #[test]
+    fn a_weighting_matches_standard() {
+        let filter = WeightingFilter::new(Weighting::A, 48000.0);
+        for &(frequency, expected) in A_TABLE.iter() {
+            let gain = gain_db(&filter, frequency);
+            assert!(
+                (gain - expected).abs() < 0.2,
+                "{} Hz: {} dB",
+                frequency,
+                gain
+            );
+        }
+    }
+
+    #[test]
+    fn c_weighting_matches_standard() {
+        let filter = WeightingFilter::new(Weighting::C, 48000.0);
+        for &(frequency, expected) in C_TABLE.iter() {
+            let gain = gain_db(&filter, frequency);
+            assert!(
+                (gain - expected).abs() < 0.2,
+                "{} Hz: {} dB",
+                frequency,
+                gain
+            );
+        }
+    }
+
+    #[test]
+    fn a_weighting_at_decimated_rate() {
+        // 双一次変換の周波数のずれは 2[kHz] までは小さい
+        let filter = WeightingFilter::new(Weighting::A, DECIMATED_RATE);
+        for &(frequency, expected) in A_TABLE.iter().take(6) {
+            let gain = gain_db(&filter, frequency);
+            assert!(
+                (gain - expected).abs() < 0.2,
+                "{} Hz: {} dB",
+                frequency,
+                gain
+            );
+        }
+    }
+
+    #[test]
+    fn z_weighting_is_flat() {
+        let filter = WeightingFilter::new(Weighting::Z, DECIMATED_RATE);
+        for &frequency in [100.0, 1000.0, 5000.0].iter() {
+            assert!(gain_db(&filter, frequency).abs() < 0.2, "{} Hz", frequency);
+        }
+        // 直流は取り除く
+        assert!(filter.magnitude(0.0) < 1e-3);
+    }
+
+    // 正弦波を通して、落ち着いた後の振幅の比 [dB] を求める
+    fn measured_gain_db(weighting: Weighting, frequency: f32) -> f32 {
+        use core::f32::consts::PI;
+        let sample_rate = 48000.0;
+        let mut filter = WeightingFilter::new(weighting, sample_rate);
+        let mut samples = [0.0; 9600];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample = (2.0 * PI * frequency * i as f32 / sample_rate).sin();
+        }
+        filter.process_block(&mut samples);
+        // 最初の 0.1[s] は過渡応答なので捨てる
+        let settled = &samples[4800..];
+        let power = settled.iter().map(|x| x * x).sum::<f32>() / settled.len() as f32;
+        10.0 * (power * 2.0).log10()
+    }
+
+    #[test]
+    fn time_domain_gain() {
+        assert!(measured_gain_db(Weighting::A, 1000.0).abs() < 0.1);
+        assert!((measured_gain_db(Weighting::A, 100.0) + 19.1).abs() < 0.2);
+        assert!((measured_gain_db(Weighting::A, 2000.0) - 1.2).abs() < 0.1);
+    }
+
+    #[test]
+    fn process_matches_process_block() {
+        let mut a = WeightingFilter::new(Weighting::A, DECIMATED_RATE);
+        let mut b = WeightingFilter::new(Weighting::A, DECIMATED_RATE);
+        let mut block = [0.0; 64];
+        block[0] = 1.0;
+        let single: [f32; 64] = {
+            let mut out = [0.0; 64];
+            for (y, x) in out.iter_mut().zip(block.iter()) {
+                *y = a.process(*x);
+            }
+            out
+        };
+        b.process_block(&mut block);
+        for (x, y) in single.iter().zip(block.iter()) {
+            assert!((x - y).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+// BDFフォントを読み込み、必要な文字だけを抜き出したグリフテーブルを生成します。
+//
+// - `WIO_FONT_BDF`: 変換するBDFファイル (既定値: fonts/sample8.bdf)
+// - `WIO_FONT_CHARSET`: ASCIIとかな以外に取り込む文字の一覧 (既定値: fonts/charset.txt)
+//
+// BDFは ISO10646 (Unicode) エンコーディングである必要があります。
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+#[path = "build/bdf.rs"]
+mod bdf;
+
+const DEFAULT_BDF: &str = "fonts/sample8.bdf";
+const DEFAULT_CHARSET: &str = "fonts/charset.txt";
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=WIO_FONT_BDF");
+    println!("cargo:rerun-if-env-changed=WIO_FONT_CHARSET");
+
+    let bdf_path = PathBuf::from(env::var("WIO_FONT_BDF").unwrap_or_else(|_| DEFAULT_BDF.into()));
+    let charset_path =
+        PathBuf::from(env::var("WIO_FONT_CHARSET").unwrap_or_else(|_| DEFAULT_CHARSET.into()));
+    println!("cargo:rerun-if-changed={}", bdf_path.display());
+    println!("cargo:rerun-if-changed={}", charset_path.display());
+
+    let charset = bdf::parse_charset(
+        &fs::read_to_string(&charset_path)
+            .unwrap_or_else(|e| panic!("{}: {}", charset_path.display(), e)),
+    );
+    let source =
+        fs::read_to_string(&bdf_path).unwrap_or_else(|e| panic!("{}: {}", bdf_path.display(), e));
+    let font = bdf::parse_bdf(&bdf_path, &source, &charset);
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("font.rs");
+    fs::write(&out, bdf::generate(&font)).unwrap();
+}
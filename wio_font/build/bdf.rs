@@ -0,0 +1,344 @@
+// BDFフォントの読み込みと、グリフテーブルのコード生成です
+// build.rs から使い、テストのときは lib.rs からも取り込んで確かめます
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+pub struct Glyph {
+    code: u32,
+    width: u32,
+    height: u32,
+    x_offset: i32,
+    y_offset: i32,
+    advance: u32,
+    rows: Vec<u8>,
+}
+
+pub struct Font {
+    ascent: u32,
+    descent: u32,
+    glyphs: Vec<Glyph>,
+}
+
+fn is_always_included(code: u32) -> bool {
+    match code {
+        0x20..=0x7e => true,     // ASCII
+        0x3000..=0x30ff => true, // 和文記号、ひらがな、カタカナ
+        0xff61..=0xff9f => true, // 半角カナ
+        _ => false,
+    }
+}
+
+fn parse_numbers(path: &Path, line_no: usize, args: &str, count: usize) -> Vec<i32> {
+    let numbers: Vec<i32> = args
+        .split_whitespace()
+        .map(|s| {
+            s.parse().unwrap_or_else(|_| {
+                panic!("{}:{}: invalid number `{}`", path.display(), line_no, s)
+            })
+        })
+        .collect();
+    if numbers.len() < count {
+        panic!(
+            "{}:{}: expected {} numbers, found `{}`",
+            path.display(),
+            line_no,
+            count,
+            args
+        );
+    }
+    numbers
+}
+
+pub fn parse_bdf(path: &Path, source: &str, charset: &BTreeSet<u32>) -> Font {
+    let mut ascent = None;
+    let mut descent = None;
+    let mut registry = None;
+    let mut default_bbx = None;
+    let mut glyphs = Vec::new();
+
+    let mut lines = source.lines().enumerate().map(|(i, l)| (i + 1, l.trim()));
+    while let Some((line_no, line)) = lines.next() {
+        let (keyword, args) = match line.find(' ') {
+            Some(i) => (&line[..i], line[i + 1..].trim()),
+            None => (line, ""),
+        };
+        match keyword {
+            "FONTBOUNDINGBOX" => default_bbx = Some(parse_numbers(path, line_no, args, 4)),
+            "FONT_ASCENT" => ascent = Some(parse_numbers(path, line_no, args, 1)[0]),
+            "FONT_DESCENT" => descent = Some(parse_numbers(path, line_no, args, 1)[0]),
+            "CHARSET_REGISTRY" => registry = Some(args.trim_matches('"').to_uppercase()),
+            "STARTCHAR" => {
+                let mut code = None;
+                let mut advance = None;
+                let mut bbx = default_bbx.clone();
+                let mut rows = Vec::new();
+                let mut in_bitmap = false;
+                loop {
+                    let (line_no, line) = lines.next().unwrap_or_else(|| {
+                        panic!("{}:{}: missing ENDCHAR", path.display(), line_no)
+                    });
+                    if line == "ENDCHAR" {
+                        break;
+                    }
+                    if in_bitmap {
+                        for i in (0..line.len()).step_by(2) {
+                            let byte = line
+                                .get(i..i + 2)
+                                .and_then(|s| u8::from_str_radix(s, 16).ok());
+                            rows.push(byte.unwrap_or_else(|| {
+                                panic!(
+                                    "{}:{}: invalid bitmap row `{}`",
+                                    path.display(),
+                                    line_no,
+                                    line
+                                )
+                            }));
+                        }
+                        continue;
+                    }
+                    let (keyword, args) = match line.find(' ') {
+                        Some(i) => (&line[..i], line[i + 1..].trim()),
+                        None => (line, ""),
+                    };
+                    match keyword {
+                        "ENCODING" => code = Some(parse_numbers(path, line_no, args, 1)[0]),
+                        "DWIDTH" => advance = Some(parse_numbers(path, line_no, args, 1)[0]),
+                        "BBX" => bbx = Some(parse_numbers(path, line_no, args, 4)),
+                        "BITMAP" => in_bitmap = true,
+                        _ => {}
+                    }
+                }
+                let code = match code {
+                    Some(code) if code >= 0 => code as u32,
+                    _ => continue, // エンコーディングのないグリフは使えない
+                };
+                if !is_always_included(code) && !charset.contains(&code) {
+                    continue;
+                }
+                let bbx = bbx.unwrap_or_else(|| {
+                    panic!("{}: glyph U+{:04X} has no BBX", path.display(), code)
+                });
+                let (width, height) = (bbx[0] as u32, bbx[1] as u32);
+                let expected = (width.div_ceil(8) * height) as usize;
+                if rows.len() != expected {
+                    panic!(
+                        "{}: glyph U+{:04X} has {} bitmap bytes, expected {}",
+                        path.display(),
+                        code,
+                        rows.len(),
+                        expected
+                    );
+                }
+                glyphs.push(Glyph {
+                    code,
+                    width,
+                    height,
+                    x_offset: bbx[2],
+                    y_offset: bbx[3],
+                    advance: advance.unwrap_or(bbx[0]) as u32,
+                    rows,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    match registry.as_deref() {
+        Some("ISO10646") => {}
+        other => panic!(
+            "{}: CHARSET_REGISTRY must be \"ISO10646\" (found {:?}); convert the font to Unicode first",
+            path.display(),
+            other
+        ),
+    }
+    let bbx = default_bbx.unwrap_or_else(|| panic!("{}: missing FONTBOUNDINGBOX", path.display()));
+    let ascent = ascent.unwrap_or(bbx[1] + bbx[3]);
+    let descent = descent.unwrap_or(-bbx[3]);
+
+    glyphs.sort_by_key(|g| g.code);
+    glyphs.dedup_by_key(|g| g.code);
+    Font {
+        ascent: ascent.max(0) as u32,
+        descent: descent.max(0) as u32,
+        glyphs,
+    }
+}
+
+pub fn parse_charset(source: &str) -> BTreeSet<u32> {
+    source
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(|line| line.chars())
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c as u32)
+        .collect()
+}
+
+pub fn generate(font: &Font) -> String {
+    let mut glyphs = String::new();
+    let mut bitmap = String::new();
+    let mut offset = 0;
+    for g in font.glyphs.iter() {
+        writeln!(
+            glyphs,
+            "        Glyph {{ code: 0x{:04x}, width: {}, height: {}, x_offset: {}, y_offset: {}, advance: {}, offset: {} }},",
+            g.code, g.width, g.height, g.x_offset, g.y_offset, g.advance, offset
+        )
+        .unwrap();
+        bitmap.push_str("        ");
+        for byte in g.rows.iter() {
+            write!(bitmap, "0x{:02x}, ", byte).unwrap();
+        }
+        bitmap.push('\n');
+        offset += g.rows.len();
+    }
+    format!(
+        "pub static DEFAULT_FONT: BitmapFont = BitmapFont {{\n    \
+         ascent: {},\n    descent: {},\n    glyphs: &[\n{}    ],\n    bitmap: &[\n{}    ],\n}};\n",
+        font.ascent, font.descent, glyphs, bitmap
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "STARTFONT 2.1
+FONTBOUNDINGBOX 8 8 0 -1
+STARTPROPERTIES 3
+CHARSET_REGISTRY \"ISO10646\"
+FONT_ASCENT 7
+FONT_DESCENT 1
+ENDPROPERTIES
+";
+
+    fn parse(glyphs: &str, charset: &str) -> Font {
+        let source = format!("{}{}ENDFONT\n", HEADER, glyphs);
+        parse_bdf(Path::new("test.bdf"), &source, &parse_charset(charset))
+    }
+
+    #[test]
+    fn parses_glyphs() {
+        let font = parse(
+            "STARTCHAR B
+ENCODING 66
+DWIDTH 6 0
+BBX 5 2 1 0
+BITMAP
+F8
+88
+ENDCHAR
+STARTCHAR A
+ENCODING 65
+BITMAP
+00
+18
+24
+42
+7E
+42
+42
+00
+ENDCHAR
+",
+            "",
+        );
+        assert_eq!((font.ascent, font.descent), (7, 1));
+        // コード順に並び替えられる
+        let codes: Vec<u32> = font.glyphs.iter().map(|g| g.code).collect();
+        assert_eq!(codes, [0x41, 0x42]);
+
+        // BBX と DWIDTH がないときは FONTBOUNDINGBOX を使う
+        let a = &font.glyphs[0];
+        assert_eq!((a.width, a.height, a.x_offset, a.y_offset), (8, 8, 0, -1));
+        assert_eq!(a.advance, 8);
+        assert_eq!(a.rows[1..5], [0x18, 0x24, 0x42, 0x7e]);
+
+        let b = &font.glyphs[1];
+        assert_eq!((b.width, b.height, b.x_offset, b.y_offset), (5, 2, 1, 0));
+        assert_eq!(b.advance, 6);
+        assert_eq!(b.rows, [0xf8, 0x88]);
+    }
+
+    #[test]
+    fn wide_glyph_rows_use_two_bytes() {
+        let font = parse(
+            "STARTCHAR U+4E2D
+ENCODING 20013
+BBX 12 2 0 0
+BITMAP
+FFF0
+8010
+ENDCHAR
+",
+            "中",
+        );
+        assert_eq!(font.glyphs[0].rows, [0xff, 0xf0, 0x80, 0x10]);
+    }
+
+    #[test]
+    fn keeps_only_needed_glyphs() {
+        let glyph = |code: u32| {
+            format!(
+                "STARTCHAR x\nENCODING {}\nBITMAP\n00\n00\n00\n00\n00\n00\n00\n00\nENDCHAR\n",
+                code
+            )
+        };
+        let glyphs = [0x41, 0x1f, 0x3042, 0xff71, 0x4e2d, 0x4e00]
+            .iter()
+            .map(|code| glyph(*code))
+            .collect::<String>()
+            + "STARTCHAR unencoded\nENCODING -1\nBITMAP\nENDCHAR\n";
+        let font = parse(&glyphs, "# 漢字\n中 \n");
+        let codes: Vec<u32> = font.glyphs.iter().map(|g| g.code).collect();
+        // ASCII とかなは常に、漢字は charset にあるものだけ取り込む
+        assert_eq!(codes, [0x41, 0x3042, 0x4e2d, 0xff71]);
+    }
+
+    #[test]
+    fn charset_ignores_comments_and_whitespace() {
+        let charset = parse_charset("# 中 は無視される\n日 本\n\t語\n");
+        assert_eq!(
+            charset.into_iter().collect::<Vec<u32>>(),
+            ['日' as u32, '本' as u32, '語' as u32]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "glyph U+0041 has 1 bitmap bytes, expected 8")]
+    fn rejects_short_bitmap() {
+        parse("STARTCHAR A\nENCODING 65\nBITMAP\n00\nENDCHAR\n", "");
+    }
+
+    #[test]
+    #[should_panic(expected = "test.bdf:9: invalid number `x`")]
+    fn reports_line_of_bad_number() {
+        parse("STARTCHAR A\nENCODING x\nENDCHAR\n", "");
+    }
+
+    #[test]
+    #[should_panic(expected = "CHARSET_REGISTRY must be \"ISO10646\"")]
+    fn rejects_non_unicode_font() {
+        let source = HEADER.replace("ISO10646", "JISX0208.1983");
+        parse_bdf(Path::new("test.bdf"), &source, &BTreeSet::new());
+    }
+
+    #[test]
+    fn generates_glyph_table() {
+        let font = parse(
+            "STARTCHAR A\nENCODING 65\nBBX 2 1 0 0\nBITMAP\nC0\nENDCHAR\n\
+             STARTCHAR B\nENCODING 66\nBBX 2 2 0 0\nBITMAP\n40\n80\nENDCHAR\n",
+            "",
+        );
+        let code = generate(&font);
+        assert!(code.contains("ascent: 7,\n    descent: 1,"));
+        assert!(code.contains(
+            "Glyph { code: 0x0041, width: 2, height: 1, x_offset: 0, y_offset: 0, advance: 2, offset: 0 },"
+        ));
+        // ビットマップは前のグリフの続きに置かれる
+        assert!(code.contains("advance: 2, offset: 1 },"));
+        assert!(code.contains("        0xc0, \n        0x40, 0x80, \n"));
+    }
+}
@@ -0,0 +1,335 @@
+#![cfg_attr(not(test), no_std)]
+
+use embedded_graphics::{
+    pixelcolor::Rgb565,
+    prelude::*,
+    primitives::Rectangle,
+    style::PrimitiveStyle,
+};
+
+// フォントに含まれない文字の代わりに描画する文字
+const REPLACEMENT_CHARACTER: char = '?';
+
+// 1文字分のグリフ情報
+// 座標系はBDFと同じで、(x_offset, y_offset) はベースライン上の原点から見た
+// ビットマップ左下の位置です
+#[derive(Debug, Clone, Copy)]
+pub struct Glyph {
+    code: u32,
+    width: u8,
+    height: u8,
+    x_offset: i8,
+    y_offset: i8,
+    advance: u8,
+    offset: u32,
+}
+
+impl Glyph {
+    pub fn advance(&self) -> u32 {
+        self.advance as u32
+    }
+}
+
+// ビルド時にBDFから生成されるビットマップフォント
+#[derive(Debug)]
+pub struct BitmapFont {
+    ascent: u8,
+    descent: u8,
+    glyphs: &'static [Glyph],
+    bitmap: &'static [u8],
+}
+
+// build.rs が生成する `DEFAULT_FONT` を取り込みます
+include!(concat!(env!("OUT_DIR"), "/font.rs"));
+
+impl BitmapFont {
+    // 文字に対応するグリフを探します
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        let code = c as u32;
+        self.glyphs
+            .binary_search_by_key(&code, |g| g.code)
+            .ok()
+            .map(|i| &self.glyphs[i])
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.glyph(c).is_some()
+    }
+
+    pub fn line_height(&self) -> u32 {
+        self.ascent as u32 + self.descent as u32
+    }
+
+    // 文字列を描画したときの幅 (複数行の場合は最も長い行の幅) を返します
+    pub fn text_width(&self, text: &str) -> u32 {
+        text.lines()
+            .map(|line| {
+                line.chars()
+                    .filter(|c| *c != '\r')
+                    .map(|c| self.advance(c))
+                    .sum::<u32>()
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn advance(&self, c: char) -> u32 {
+        self.glyph(c)
+            .or_else(|| self.glyph(REPLACEMENT_CHARACTER))
+            .map(|g| g.advance())
+            .unwrap_or(self.line_height() / 2)
+    }
+
+    fn is_set(&self, glyph: &Glyph, x: u32, y: u32) -> bool {
+        let stride = (glyph.width as u32).div_ceil(8);
+        let byte = self.bitmap[(glyph.offset + y * stride + x / 8) as usize];
+        byte & (0x80 >> (x % 8)) != 0
+    }
+}
+
+// UTF-8文字列をビットマップフォントで描画します
+pub struct BitmapText<'a> {
+    text: &'a str,
+    top_left: Point,
+    font: &'a BitmapFont,
+    text_color: Rgb565,
+    background_color: Option<Rgb565>,
+}
+
+impl<'a> BitmapText<'a> {
+    pub fn new(
+        text: &'a str,
+        top_left: Point,
+        font: &'a BitmapFont,
+        text_color: Rgb565,
+    ) -> Self {
+        Self {
+            text,
+            top_left,
+            font,
+            text_color,
+            background_color: None,
+        }
+    }
+
+    pub fn with_background(mut self, background_color: Rgb565) -> Self {
+        self.background_color = Some(background_color);
+        self
+    }
+
+    fn draw_glyph<D>(
+        &self,
+        display: &mut D,
+        glyph: &Glyph,
+        origin: Point,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        // origin はベースライン上のペン位置
+        let left = origin.x + glyph.x_offset as i32;
+        let top = origin.y - (glyph.y_offset as i32 + glyph.height as i32);
+        for y in 0..glyph.height as u32 {
+            for x in 0..glyph.width as u32 {
+                if self.font.is_set(glyph, x, y) {
+                    let point = Point::new(left + x as i32, top + y as i32);
+                    display.draw_pixel(Pixel(point, self.text_color))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_missing<D>(
+        &self,
+        display: &mut D,
+        origin: Point,
+        advance: u32,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        // 代替文字もない場合は豆腐 (四角) を描く
+        let top = origin.y - self.font.ascent as i32 + 1;
+        Rectangle::new(
+            Point::new(origin.x, top),
+            Point::new(origin.x + advance as i32 - 2, origin.y),
+        )
+        .into_styled(PrimitiveStyle::with_stroke(self.text_color, 1))
+        .draw(display)
+    }
+}
+
+impl<'a> Drawable<Rgb565> for BitmapText<'a> {
+    fn draw<D>(self, display: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        let line_height = self.font.line_height() as i32;
+        let mut origin = Point::new(
+            self.top_left.x,
+            self.top_left.y + self.font.ascent as i32,
+        );
+        for c in self.text.chars() {
+            if c == '\n' {
+                origin = Point::new(self.top_left.x, origin.y + line_height);
+                continue;
+            }
+            // 改行コードが CRLF のときの CR は描画しない
+            if c == '\r' {
+                continue;
+            }
+            let advance = self.font.advance(c);
+            if let Some(color) = self.background_color {
+                let top = origin.y - self.font.ascent as i32;
+                Rectangle::new(
+                    Point::new(origin.x, top),
+                    Point::new(origin.x + advance as i32 - 1, top + line_height - 1),
+                )
+                .into_styled(PrimitiveStyle::with_fill(color))
+                .draw(display)?;
+            }
+            match self
+                .font
+                .glyph(c)
+                .or_else(|| self.font.glyph(REPLACEMENT_CHARACTER))
+            {
+                Some(glyph) => self.draw_glyph(display, glyph, origin)?,
+                None => self.draw_missing(display, origin, advance)?,
+            }
+            origin.x += advance as i32;
+        }
+        Ok(())
+    }
+}
+
+// build.rs と同じBDFの読み込み処理をテストします
+#[cfg(test)]
+#[path = "../build/bdf.rs"]
+mod bdf;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    // 描画されたピクセルを記録する
+    struct Recorder {
+        pixels: Vec<Point>,
+    }
+
+    impl DrawTarget<Rgb565> for Recorder {
+        type Error = core::convert::Infallible;
+
+        fn draw_pixel(&mut self, pixel: Pixel<Rgb565>) -> Result<(), Self::Error> {
+            self.pixels.push(pixel.0);
+            Ok(())
+        }
+
+        fn size(&self) -> Size {
+            Size::new(320, 240)
+        }
+    }
+
+    fn draw(text: &str, font: &BitmapFont) -> Vec<Point> {
+        let mut recorder = Recorder { pixels: Vec::new() };
+        BitmapText::new(text, Point::zero(), font, Rgb565::WHITE)
+            .draw(&mut recorder)
+            .unwrap();
+        recorder.pixels.sort_by_key(|p| (p.y, p.x));
+        recorder.pixels
+    }
+
+    // first の後に、offset だけずらした second を描いたときのピクセル
+    fn followed_by(first: Vec<Point>, second: Vec<Point>, offset: Point) -> Vec<Point> {
+        let mut pixels = first;
+        pixels.extend(second.into_iter().map(|p| p + offset));
+        pixels.sort_by_key(|p| (p.y, p.x));
+        pixels
+    }
+
+    #[test]
+    fn default_font_has_printable_ascii() {
+        for c in ' '..='~' {
+            assert!(DEFAULT_FONT.contains(c), "{:?}", c);
+        }
+        assert!(DEFAULT_FONT.contains('こ'));
+        assert!(DEFAULT_FONT.contains('中'));
+        assert_eq!(DEFAULT_FONT.line_height(), 8);
+    }
+
+    #[test]
+    fn draws_glyph_bitmap() {
+        // 'T' の横棒は一番上の行に、縦棒はその下に並ぶ
+        let pixels = draw("T", &DEFAULT_FONT);
+        let top: Vec<i32> = pixels.iter().filter(|p| p.y == 0).map(|p| p.x).collect();
+        assert_eq!(top, [1, 2, 3, 4, 5]);
+        assert!(pixels.iter().filter(|p| p.y > 0).all(|p| p.x == 3));
+        assert_eq!(pixels.len(), 11);
+    }
+
+    #[test]
+    fn decodes_utf8() {
+        // マルチバイト文字も1文字ずつ進む
+        assert_eq!(DEFAULT_FONT.text_width("Aこ中"), 24);
+        assert_eq!(
+            draw("こA", &DEFAULT_FONT),
+            followed_by(
+                draw("こ", &DEFAULT_FONT),
+                draw("A", &DEFAULT_FONT),
+                Point::new(8, 0)
+            )
+        );
+    }
+
+    #[test]
+    fn newlines() {
+        assert_eq!(DEFAULT_FONT.text_width("ab\ncde"), 24);
+        assert_eq!(
+            draw("A\nB", &DEFAULT_FONT),
+            followed_by(
+                draw("A", &DEFAULT_FONT),
+                draw("B", &DEFAULT_FONT),
+                Point::new(0, 8)
+            )
+        );
+        // CR は描画せず、幅にも含めない
+        assert_eq!(draw("A\r\nB", &DEFAULT_FONT), draw("A\nB", &DEFAULT_FONT));
+        assert_eq!(draw("A\rB", &DEFAULT_FONT), draw("AB", &DEFAULT_FONT));
+        assert_eq!(DEFAULT_FONT.text_width("ab\r\ncd\r"), 16);
+    }
+
+    #[test]
+    fn missing_glyph_uses_replacement() {
+        assert!(!DEFAULT_FONT.contains('☺'));
+        assert_eq!(draw("☺", &DEFAULT_FONT), draw("?", &DEFAULT_FONT));
+        assert_eq!(DEFAULT_FONT.text_width("☺☺"), 16);
+    }
+
+    #[test]
+    fn missing_replacement_draws_box() {
+        // '?' もないフォントでは、行の高さの半分の幅の四角を描く
+        static GLYPHS: [Glyph; 1] = [Glyph {
+            code: 0x41,
+            width: 1,
+            height: 1,
+            x_offset: 0,
+            y_offset: 0,
+            advance: 2,
+            offset: 0,
+        }];
+        let font = BitmapFont {
+            ascent: 3,
+            descent: 1,
+            glyphs: &GLYPHS,
+            bitmap: &[0x80],
+        };
+        assert_eq!(font.text_width("AB"), 4);
+        assert_eq!(draw("A", &font), [Point::new(0, 2)]);
+        let pixels = draw("B", &font);
+        assert_eq!(
+            pixels,
+            [Point::new(0, 1), Point::new(0, 2), Point::new(0, 3)]
+        );
+    }
+}
@@ -0,0 +1,45 @@
+// 記録した加速度 (traces/synthetic_*.csv など) を歩数計に通して、数えた歩数を表示するサンプルです
+// CSVの "# steps: N" の行に書いた歩数と比べます
+// `cargo run --example pedometer_trace [CSVファイル...]`
+
+use accelerometer::vector::F32x3;
+use std::{env, fs};
+use wio_motion::Pedometer;
+
+fn main() {
+    let mut paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/traces");
+        for name in ["walking", "running", "jolts"].iter() {
+            paths.push(format!("{}/synthetic_{}.csv", dir, name));
+        }
+    }
+
+    for path in paths {
+        let text = fs::read_to_string(&path).expect("cannot read trace");
+        let mut pedometer = Pedometer::new();
+        let mut expected = None;
+        let mut max_cadence: f32 = 0.0;
+        for line in text.lines() {
+            if let Some(steps) = line.strip_prefix("# steps:") {
+                expected = steps.trim().parse::<u32>().ok();
+                continue;
+            }
+            let fields: Vec<f32> = line.split(',').filter_map(|f| f.parse().ok()).collect();
+            // コメントと見出しの行は読み飛ばす
+            if fields.len() != 4 {
+                continue;
+            }
+            let accel = F32x3::new(fields[1], fields[2], fields[3]);
+            pedometer.update(accel, fields[0] as u32);
+            max_cadence = max_cadence.max(pedometer.cadence());
+        }
+        println!(
+            "{}: {} steps (expected {:?}), max cadence {:.0} steps/min",
+            path,
+            pedometer.steps(),
+            expected,
+            max_cadence
+        );
+    }
+}
@@ -0,0 +1,584 @@
+// 加速度センサの較正
+//
+// センサの値には軸ごとにずれ (オフセット) と感度の誤差があります
+//
+//   測定値 = オフセット + 感度 * 真の値
+//
+// 6つの向き (各軸を真上と真下に向ける) で静止させて平均をとると、各軸の真の値は +1, -1, 0 [G] のどれかです
+// 軸ごとに6点の (真の値, 測定値) に直線を最小二乗法で当てはめて、オフセットと感度を求めます
+//
+// 求めた値は CalibrationRecord でバイト列にして保存し、
+// CalibratedAccelerometer で包むと accel_norm() の値に自動でかかります
+
+use accelerometer::{vector::F32x3, Accelerometer, Error};
+use micromath::F32Ext;
+
+// 較正値 (補正後の値 = (測定値 - offset) * scale)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub offset: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+impl Calibration {
+    // 何も補正しない較正値
+    pub const IDENTITY: Calibration = Calibration {
+        offset: [0.0; 3],
+        scale: [1.0; 3],
+    };
+
+    pub fn apply(&self, raw: F32x3) -> F32x3 {
+        F32x3::new(
+            (raw.x - self.offset[0]) * self.scale[0],
+            (raw.y - self.offset[1]) * self.scale[1],
+            (raw.z - self.offset[2]) * self.scale[2],
+        )
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Calibration::IDENTITY
+    }
+}
+
+// 較正のときに置く向き (どの軸を上に向けるか)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    XUp,
+    XDown,
+    YUp,
+    YDown,
+    ZUp, // 画面を上にして水平に置く
+    ZDown,
+}
+
+impl Position {
+    pub const ALL: [Position; 6] = [
+        Position::ZUp,
+        Position::ZDown,
+        Position::XUp,
+        Position::XDown,
+        Position::YUp,
+        Position::YDown,
+    ];
+
+    // この向きで静止しているときの真の値 [G]
+    // 上に向けた軸は +1[G] になる (センサは重力と逆向きの加速度を受けている)
+    pub fn expected(self) -> [f32; 3] {
+        match self {
+            Position::XUp => [1.0, 0.0, 0.0],
+            Position::XDown => [-1.0, 0.0, 0.0],
+            Position::YUp => [0.0, 1.0, 0.0],
+            Position::YDown => [0.0, -1.0, 0.0],
+            Position::ZUp => [0.0, 0.0, 1.0],
+            Position::ZDown => [0.0, 0.0, -1.0],
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Position::XUp => "X UP",
+            Position::XDown => "X DOWN",
+            Position::YUp => "Y UP",
+            Position::YDown => "Y DOWN",
+            Position::ZUp => "Z UP (FACE UP)",
+            Position::ZDown => "Z DOWN (FACE DOWN)",
+        }
+    }
+
+    fn index(self) -> usize {
+        Position::ALL.iter().position(|&p| p == self).unwrap_or(0)
+    }
+}
+
+// 1つの向きの測定をやり直す理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionError {
+    Moving,        // 測定中に動いた
+    WrongPosition, // 指示と違う向きに置かれている
+}
+
+// 当てはめの結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fit {
+    pub calibration: Calibration,
+    pub residual: f32, // 当てはめた直線からのずれの二乗平均平方根 [G]
+}
+
+// 6つの向きの測定値を集めて較正値を求めます
+//
+//   let mut calibrator = Calibrator::new(200);
+//   while let Some(position) = calibrator.next_position() {
+//       // position の向きに置くように指示し、静止したら値を入れる
+//       calibrator.add_sample(accel.accel_norm()?);
+//       if calibrator.is_position_complete() { calibrator.finish_position()?; }
+//   }
+//   let fit = calibrator.fit();
+pub struct Calibrator {
+    samples_per_position: u32,
+    max_noise: f32, // 静止しているとみなす標準偏差 [G]
+    means: [Option<[f32; 3]>; 6],
+    count: u32,
+    sum: [f32; 3],
+    sum_of_squares: [f32; 3],
+}
+
+impl Calibrator {
+    pub fn new(samples_per_position: u32) -> Self {
+        Calibrator {
+            samples_per_position: samples_per_position.max(1),
+            max_noise: 0.05,
+            means: [None; 6],
+            count: 0,
+            sum: [0.0; 3],
+            sum_of_squares: [0.0; 3],
+        }
+    }
+
+    // 静止しているとみなす標準偏差 [G] を変えます
+    pub fn set_max_noise(&mut self, max_noise: f32) {
+        self.max_noise = max_noise;
+    }
+
+    // 最初からやり直します
+    pub fn reset(&mut self) {
+        self.means = [None; 6];
+        self.discard_samples();
+    }
+
+    // 次に測定する向き (すべて終わったら None)
+    pub fn next_position(&self) -> Option<Position> {
+        Position::ALL
+            .iter()
+            .copied()
+            .find(|position| self.means[position.index()].is_none())
+    }
+
+    // 測定が終わった向きの数
+    pub fn completed(&self) -> usize {
+        self.means.iter().filter(|mean| mean.is_some()).count()
+    }
+
+    // 測定中の向きのサンプル数
+    pub fn sample_count(&self) -> u32 {
+        self.count
+    }
+
+    // 測定中の向きの値を1つ入れます
+    pub fn add_sample(&mut self, sample: F32x3) {
+        if self.is_position_complete() {
+            return;
+        }
+        for (axis, value) in [sample.x, sample.y, sample.z].iter().enumerate() {
+            self.sum[axis] += value;
+            self.sum_of_squares[axis] += value * value;
+        }
+        self.count += 1;
+    }
+
+    pub fn is_position_complete(&self) -> bool {
+        self.count >= self.samples_per_position
+    }
+
+    // 測定中の向きの値を捨てて、その向きを測り直します
+    pub fn discard_samples(&mut self) {
+        self.count = 0;
+        self.sum = [0.0; 3];
+        self.sum_of_squares = [0.0; 3];
+    }
+
+    // 集めた値の平均をその向きの測定値として確定し、次の向きに進みます
+    // 動いていたり向きが違ったりしたときは、値を捨ててエラーを返します
+    pub fn finish_position(&mut self) -> Result<Position, PositionError> {
+        let position = match self.next_position() {
+            Some(position) => position,
+            None => return Err(PositionError::WrongPosition),
+        };
+        let n = self.count.max(1) as f32;
+        let mut mean = [0.0; 3];
+        let mut result = Ok(position);
+        for (axis, value) in mean.iter_mut().enumerate() {
+            *value = self.sum[axis] / n;
+            let variance = (self.sum_of_squares[axis] / n - *value * *value).max(0.0);
+            if variance.sqrt() > self.max_noise {
+                result = Err(PositionError::Moving);
+            }
+        }
+        // 上に向けるはずの軸が、最も大きく正しい向きになっているか
+        let expected = position.expected();
+        let main_axis = expected.iter().position(|&v| v != 0.0).unwrap_or(2);
+        let main = mean[main_axis] * expected[main_axis];
+        if result.is_ok() && (main < 0.5 || mean.iter().any(|v| v.abs() > main)) {
+            result = Err(PositionError::WrongPosition);
+        }
+
+        if result.is_ok() {
+            self.means[position.index()] = Some(mean);
+        }
+        self.discard_samples();
+        result
+    }
+
+    // 各向きの測定値 (未測定なら None)
+    pub fn measurement(&self, position: Position) -> Option<F32x3> {
+        self.means[position.index()].map(|mean| F32x3::new(mean[0], mean[1], mean[2]))
+    }
+
+    // 6つの向きがそろっていれば較正値を求めます
+    pub fn fit(&self) -> Option<Fit> {
+        let mut points = [([0.0; 3], [0.0; 3]); 6];
+        for (i, position) in Position::ALL.iter().enumerate() {
+            points[i] = (position.expected(), self.means[position.index()]?);
+        }
+        fit(&points)
+    }
+}
+
+// (真の値, 測定値) の組から、軸ごとに 測定値 = offset + gain * 真の値 を最小二乗法で当てはめます
+// 各軸の真の値に2種類以上の値がないと求まらないので None を返します
+pub fn fit(points: &[([f32; 3], [f32; 3])]) -> Option<Fit> {
+    let mut calibration = Calibration::IDENTITY;
+    let mut squared_error = 0.0;
+    for axis in 0..3 {
+        let n = points.len() as f32;
+        let (mut sx, mut sy, mut sxx, mut sxy) = (0.0, 0.0, 0.0, 0.0);
+        for (expected, measured) in points {
+            let (x, y) = (expected[axis], measured[axis]);
+            sx += x;
+            sy += y;
+            sxx += x * x;
+            sxy += x * y;
+        }
+        let denominator = n * sxx - sx * sx;
+        if denominator.abs() < 1e-6 {
+            return None;
+        }
+        let gain = (n * sxy - sx * sy) / denominator;
+        let offset = (sy - gain * sx) / n;
+        if gain.abs() < 1e-3 {
+            return None;
+        }
+        calibration.offset[axis] = offset;
+        calibration.scale[axis] = 1.0 / gain;
+
+        for (expected, measured) in points {
+            let error = measured[axis] - (offset + gain * expected[axis]);
+            squared_error += error * error;
+        }
+    }
+    let residual = (squared_error / (3 * points.len().max(1)) as f32).sqrt();
+    Some(Fit {
+        calibration,
+        residual,
+    })
+}
+
+// 較正値を保存するためのバイト列
+//
+//   0..4   "ACAL"
+//   4      バージョン
+//   5      予約 (0)
+//   6..8   CRC-16 (8..32 に対するもの、リトルエンディアン)
+//   8..20  offset x, y, z (f32、リトルエンディアン)
+//   20..32 scale x, y, z
+pub const RECORD_SIZE: usize = 32;
+const RECORD_MAGIC: [u8; 4] = *b"ACAL";
+const RECORD_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordError {
+    NotFound,               // 何も保存されていない (消去された状態など)
+    UnsupportedVersion(u8), // 読めないバージョン
+    Corrupted,              // CRCが合わない
+}
+
+pub struct CalibrationRecord;
+
+impl CalibrationRecord {
+    pub fn encode(calibration: &Calibration) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0u8; RECORD_SIZE];
+        bytes[0..4].copy_from_slice(&RECORD_MAGIC);
+        bytes[4] = RECORD_VERSION;
+        let values = calibration.offset.iter().chain(calibration.scale.iter());
+        for (i, value) in values.enumerate() {
+            bytes[8 + i * 4..12 + i * 4].copy_from_slice(&value.to_le_bytes());
+        }
+        let crc = crc16(&bytes[8..]);
+        bytes[6..8].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Calibration, RecordError> {
+        if bytes.len() < RECORD_SIZE || bytes[0..4] != RECORD_MAGIC {
+            return Err(RecordError::NotFound);
+        }
+        if bytes[4] != RECORD_VERSION {
+            return Err(RecordError::UnsupportedVersion(bytes[4]));
+        }
+        let crc = u16::from_le_bytes([bytes[6], bytes[7]]);
+        if crc != crc16(&bytes[8..RECORD_SIZE]) {
+            return Err(RecordError::Corrupted);
+        }
+        let value = |i: usize| {
+            let mut word = [0u8; 4];
+            word.copy_from_slice(&bytes[8 + i * 4..12 + i * 4]);
+            f32::from_le_bytes(word)
+        };
+        Ok(Calibration {
+            offset: [value(0), value(1), value(2)],
+            scale: [value(3), value(4), value(5)],
+        })
+    }
+}
+
+// CRC-16/CCITT-FALSE
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in bytes {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+// accel_norm() の値に較正値をかける加速度センサ
+// 元の加速度センサの代わりに使えます
+pub struct CalibratedAccelerometer<A> {
+    inner: A,
+    calibration: Calibration,
+}
+
+impl<A: Accelerometer> CalibratedAccelerometer<A> {
+    pub fn new(inner: A, calibration: Calibration) -> Self {
+        CalibratedAccelerometer { inner, calibration }
+    }
+
+    pub fn calibration(&self) -> &Calibration {
+        &self.calibration
+    }
+
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    // 較正前の値を読みます
+    pub fn accel_uncalibrated(&mut self) -> Result<F32x3, Error<A::Error>> {
+        self.inner.accel_norm()
+    }
+
+    pub fn inner(&mut self) -> &mut A {
+        &mut self.inner
+    }
+
+    pub fn free(self) -> A {
+        self.inner
+    }
+}
+
+impl<A: Accelerometer> Accelerometer for CalibratedAccelerometer<A> {
+    type Error = A::Error;
+
+    fn accel_norm(&mut self) -> Result<F32x3, Error<Self::Error>> {
+        Ok(self.calibration.apply(self.inner.accel_norm()?))
+    }
+
+    fn sample_rate(&mut self) -> Result<f32, Error<Self::Error>> {
+        self.inner.sample_rate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFFSET: [f32; 3] = [0.04, -0.06, 0.1];
+    const GAIN: [f32; 3] = [1.05, 0.97, 1.02];
+
+    // 真の値 expected [G] のときにセンサが返す、ずれのある値
+    fn biased(expected: [f32; 3], noise: f32) -> F32x3 {
+        let value = |axis: usize| OFFSET[axis] + GAIN[axis] * expected[axis] + noise;
+        F32x3::new(value(0), value(1), value(2))
+    }
+
+    // 6つの向きを順に測る
+    fn calibrate(calibrator: &mut Calibrator) {
+        while let Some(position) = calibrator.next_position() {
+            for i in 0..10 {
+                // ±0.005[G] の雑音
+                let noise = if i % 2 == 0 { 0.005 } else { -0.005 };
+                calibrator.add_sample(biased(position.expected(), noise));
+            }
+            assert!(calibrator.is_position_complete());
+            assert_eq!(calibrator.finish_position(), Ok(position));
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn recovers_offset_and_scale() {
+        let mut calibrator = Calibrator::new(10);
+        calibrate(&mut calibrator);
+        assert_eq!(calibrator.completed(), 6);
+
+        let fit = calibrator.fit().unwrap();
+        for axis in 0..3 {
+            assert_close(fit.calibration.offset[axis], OFFSET[axis], 1e-4);
+            assert_close(fit.calibration.scale[axis], 1.0 / GAIN[axis], 1e-4);
+        }
+        assert!(fit.residual < 1e-4, "{}", fit.residual);
+
+        // 較正値をかけると真の値に戻る
+        for position in Position::ALL.iter() {
+            let expected = position.expected();
+            let corrected = fit.calibration.apply(biased(expected, 0.0));
+            assert_close(corrected.x, expected[0], 1e-4);
+            assert_close(corrected.y, expected[1], 1e-4);
+            assert_close(corrected.z, expected[2], 1e-4);
+        }
+    }
+
+    #[test]
+    fn fit_needs_all_positions() {
+        let mut calibrator = Calibrator::new(1);
+        assert_eq!(calibrator.fit(), None);
+        calibrator.add_sample(biased(Position::ZUp.expected(), 0.0));
+        calibrator.finish_position().unwrap();
+        assert_eq!(calibrator.next_position(), Some(Position::ZDown));
+        assert_eq!(calibrator.fit(), None);
+
+        // 真の値が1種類しかない軸は求まらない
+        let points = [([0.0, 0.0, 1.0], [0.0, 0.0, 1.0]); 6];
+        assert_eq!(fit(&points), None);
+    }
+
+    #[test]
+    fn rejects_moving_samples() {
+        let mut calibrator = Calibrator::new(10);
+        for i in 0..10 {
+            let noise = if i % 2 == 0 { 0.2 } else { -0.2 };
+            calibrator.add_sample(biased(Position::ZUp.expected(), noise));
+        }
+        assert_eq!(calibrator.finish_position(), Err(PositionError::Moving));
+        // 値は捨てられ、同じ向きを測り直す
+        assert_eq!(calibrator.sample_count(), 0);
+        assert_eq!(calibrator.next_position(), Some(Position::ZUp));
+    }
+
+    #[test]
+    fn rejects_wrong_position() {
+        let mut calibrator = Calibrator::new(10);
+        // ZUp を測るはずが、x軸が上を向いている
+        for _ in 0..10 {
+            calibrator.add_sample(biased(Position::XUp.expected(), 0.0));
+        }
+        assert_eq!(
+            calibrator.finish_position(),
+            Err(PositionError::WrongPosition)
+        );
+        assert_eq!(calibrator.completed(), 0);
+        assert_eq!(calibrator.measurement(Position::ZUp), None);
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let calibration = Calibration {
+            offset: OFFSET,
+            scale: [1.0 / GAIN[0], 1.0 / GAIN[1], 1.0 / GAIN[2]],
+        };
+        let bytes = CalibrationRecord::encode(&calibration);
+        assert_eq!(&bytes[0..4], b"ACAL");
+        assert_eq!(CalibrationRecord::decode(&bytes), Ok(calibration));
+    }
+
+    #[test]
+    fn corrupted_record() {
+        let bytes = CalibrationRecord::encode(&Calibration::IDENTITY);
+        for i in (6..RECORD_SIZE).step_by(3) {
+            let mut corrupted = bytes;
+            corrupted[i] ^= 0x10;
+            assert_eq!(
+                CalibrationRecord::decode(&corrupted),
+                Err(RecordError::Corrupted),
+                "{}",
+                i
+            );
+        }
+        let mut other_version = bytes;
+        other_version[4] = 2;
+        assert_eq!(
+            CalibrationRecord::decode(&other_version),
+            Err(RecordError::UnsupportedVersion(2))
+        );
+    }
+
+    #[test]
+    fn erased_flash_is_not_found() {
+        assert_eq!(
+            CalibrationRecord::decode(&[0xFF; RECORD_SIZE]),
+            Err(RecordError::NotFound)
+        );
+        assert_eq!(
+            CalibrationRecord::decode(&[0xFF; 512]),
+            Err(RecordError::NotFound)
+        );
+        // 短すぎる
+        let bytes = CalibrationRecord::encode(&Calibration::IDENTITY);
+        assert_eq!(
+            CalibrationRecord::decode(&bytes[..RECORD_SIZE - 1]),
+            Err(RecordError::NotFound)
+        );
+    }
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    struct FixedAccelerometer(F32x3);
+
+    impl Accelerometer for FixedAccelerometer {
+        type Error = ();
+
+        fn accel_norm(&mut self) -> Result<F32x3, Error<Self::Error>> {
+            Ok(self.0)
+        }
+
+        fn sample_rate(&mut self) -> Result<f32, Error<Self::Error>> {
+            Ok(100.0)
+        }
+    }
+
+    #[test]
+    fn calibrated_accelerometer() {
+        let raw = biased([0.0, 0.0, 1.0], 0.0);
+        let calibration = Calibration {
+            offset: OFFSET,
+            scale: [1.0 / GAIN[0], 1.0 / GAIN[1], 1.0 / GAIN[2]],
+        };
+        let mut accel = CalibratedAccelerometer::new(FixedAccelerometer(raw), calibration);
+        let corrected = accel.accel_norm().unwrap();
+        assert_close(corrected.x, 0.0, 1e-6);
+        assert_close(corrected.y, 0.0, 1e-6);
+        assert_close(corrected.z, 1.0, 1e-6);
+        assert_eq!(accel.accel_uncalibrated().unwrap(), raw);
+        assert_eq!(accel.sample_rate().unwrap(), 100.0);
+
+        accel.set_calibration(Calibration::IDENTITY);
+        assert_eq!(accel.accel_norm().unwrap(), raw);
+    }
+}
@@ -0,0 +1,568 @@
+// タップ・ダブルタップ・シェイクと画面の向きの変化を検出します
+//
+// 加速度を低域通過フィルタにかけたものを重力とみなし、残りを動きによる加速度として扱います
+//
+//   タップ:   静かな状態から、短い (tap_max_duration_ms 以内の) 大きな加速度が1回だけ来る
+//   シェイク: shake_window_ms の間に、大きな加速度が shake_count 回以上来る
+//
+// シェイクの1回目の振りはタップと区別できないので、タップは tap_confirm_ms の間
+// 長い動きやシェイクが続かないことを確かめてから発生させます
+//
+// `update` に加速度 [G] と時刻 [ms] を渡して定期的に (10msごと程度以上の頻度で) 呼び出すと、
+// 発生したイベントがキューにたまり、`next_event` で取り出せます
+//
+//   let mut motion = Motion::new();
+//   motion.update(accel.accel_norm().unwrap(), now_ms);
+//   while let Some(event) = motion.next_event() { ... }
+
+use accelerometer::vector::F32x3;
+use heapless::consts::*;
+use heapless::spsc::Queue;
+use micromath::F32Ext;
+
+use crate::tilt::{Orientation, Tilt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionEvent {
+    OrientationChanged(Orientation), // 画面の向きが変わった
+    Tap,                             // 軽くたたかれた
+    DoubleTap,                       // 直前のタップから double_tap_ms 以内に再びたたかれた
+    Shake,                           // 振られた
+}
+
+// 各検出に使うしきい値 (加速度は [G]、時間は [ms])
+#[derive(Debug, Clone, Copy)]
+pub struct MotionConfig {
+    pub gravity_time_constant_ms: u32, // 重力を求める低域通過フィルタの時定数
+    pub orientation_threshold: f32,    // 向きを決める軸が重力に占める割合 (0〜1)
+    pub orientation_hold_ms: u32,      // 向きが変わったとみなすまでに同じ向きが続く時間
+    pub tap_threshold: f32,            // タップとみなす動きの加速度
+    pub tap_max_duration_ms: u32,      // これより長く続く動きはタップとみなさない
+    pub tap_quiet_ms: u32,             // タップの前に静かな状態が続いている時間
+    pub tap_confirm_ms: u32,           // タップの後、これだけ長い動きがなければタップと確定する
+    pub double_tap_ms: u32,            // 0のときはDoubleTapを発生させない
+    pub shake_threshold: f32,          // シェイクの1回の振りとみなす動きの加速度
+    pub shake_count: u32,              // シェイクとみなす振りの回数
+    pub shake_window_ms: u32,          // 振りを数える時間
+    pub shake_cooldown_ms: u32,        // シェイクを検出した後、次を検出しない時間
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        MotionConfig {
+            gravity_time_constant_ms: 300,
+            orientation_threshold: 0.8,
+            orientation_hold_ms: 300,
+            tap_threshold: 0.5,
+            tap_max_duration_ms: 40,
+            tap_quiet_ms: 150,
+            tap_confirm_ms: 200,
+            double_tap_ms: 400,
+            shake_threshold: 1.2,
+            shake_count: 4,
+            shake_window_ms: 1000,
+            shake_cooldown_ms: 1000,
+        }
+    }
+}
+
+pub struct Motion {
+    config: MotionConfig,
+    last_update: Option<u32>, // 前回 update を呼ばれた時刻
+    gravity: F32x3,           // 低域通過フィルタで求めた重力
+    activity: f32,            // 重力を除いた動きの加速度の大きさ
+    orientation: Option<Orientation>,
+    pending_orientation: Option<(Orientation, u32)>, // 向きが変わりかけている (新しい向き, 変わり始めた時刻)
+    spike: Option<(u32, bool)>, // タップのしきい値を超えている (超えた時刻, タップの候補か)
+    quiet_since: u32,           // 動きがタップのしきい値を下回った時刻
+    pending_tap: Option<u32>,   // 確定を待っているタップの時刻
+    last_tap_at: Option<u32>,   // 直前のタップ (ダブルタップの1回目になりうるもの) の時刻
+    shaking: bool,              // シェイクのしきい値を超えている
+    swings: u32,                // 数えている振りの回数
+    first_swing_at: u32,        // 数え始めた振りの時刻
+    shake_cooldown_until: Option<u32>,
+    events: Queue<MotionEvent, U8>,
+}
+
+impl Motion {
+    pub fn new() -> Self {
+        Self::with_config(MotionConfig::default())
+    }
+
+    pub fn with_config(config: MotionConfig) -> Self {
+        Motion {
+            config,
+            last_update: None,
+            gravity: F32x3::new(0.0, 0.0, 0.0),
+            activity: 0.0,
+            orientation: None,
+            pending_orientation: None,
+            spike: None,
+            quiet_since: 0,
+            pending_tap: None,
+            last_tap_at: None,
+            shaking: false,
+            swings: 0,
+            first_swing_at: 0,
+            shake_cooldown_until: None,
+            events: Queue::new(),
+        }
+    }
+
+    pub fn config(&self) -> &MotionConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: MotionConfig) {
+        self.config = config;
+    }
+
+    // 状態を最初に戻します (たまっているイベントも捨てます)
+    pub fn reset(&mut self) {
+        *self = Self::with_config(self.config);
+    }
+
+    // 加速度 accel [G] を入力し、必要ならイベントを発生させます
+    // now_ms は単調増加する時刻で、u32の範囲で一周しても構いません
+    pub fn update(&mut self, accel: F32x3, now_ms: u32) {
+        let elapsed = match self.last_update {
+            Some(last) => now_ms.wrapping_sub(last),
+            None => {
+                // 最初の値をそのまま重力とし、静かな状態から始める
+                self.gravity = accel;
+                self.quiet_since = now_ms;
+                0
+            }
+        };
+        self.last_update = Some(now_ms);
+
+        // 1次の低域通過フィルタ (サンプルの間隔が変わっても時定数が変わらないようにする)
+        let time_constant = self.config.gravity_time_constant_ms as f32;
+        let alpha = elapsed as f32 / (time_constant + elapsed as f32).max(1.0);
+        self.gravity = F32x3::new(
+            self.gravity.x + alpha * (accel.x - self.gravity.x),
+            self.gravity.y + alpha * (accel.y - self.gravity.y),
+            self.gravity.z + alpha * (accel.z - self.gravity.z),
+        );
+        let (dx, dy, dz) = (
+            accel.x - self.gravity.x,
+            accel.y - self.gravity.y,
+            accel.z - self.gravity.z,
+        );
+        self.activity = (dx * dx + dy * dy + dz * dz).sqrt();
+
+        self.update_orientation(now_ms);
+        self.update_tap(now_ms);
+        self.update_shake(now_ms);
+    }
+
+    fn update_orientation(&mut self, now_ms: u32) {
+        // 動いている間は重力の向きが正しくないので判定しない
+        if self.activity >= self.config.tap_threshold {
+            return;
+        }
+        let candidate =
+            match Orientation::from_gravity(self.gravity, self.config.orientation_threshold) {
+                Some(candidate) if Some(candidate) != self.orientation => candidate,
+                _ => {
+                    self.pending_orientation = None;
+                    return;
+                }
+            };
+        match self.pending_orientation {
+            Some((pending, since)) if pending == candidate => {
+                if now_ms.wrapping_sub(since) >= self.config.orientation_hold_ms {
+                    self.orientation = Some(candidate);
+                    self.pending_orientation = None;
+                    self.push(MotionEvent::OrientationChanged(candidate));
+                }
+            }
+            _ => self.pending_orientation = Some((candidate, now_ms)),
+        }
+    }
+
+    fn update_tap(&mut self, now_ms: u32) {
+        let config = self.config;
+        if self.activity >= config.tap_threshold {
+            match self.spike {
+                None => {
+                    // 直前まで静かだったときだけタップの候補にする
+                    let quiet = now_ms.wrapping_sub(self.quiet_since) >= config.tap_quiet_ms;
+                    self.spike = Some((now_ms, quiet));
+                }
+                // タップより長い動きが続いたら、保留中のタップは大きな動きの始まりとみなして捨てる
+                Some((started_at, _))
+                    if now_ms.wrapping_sub(started_at) > config.tap_max_duration_ms =>
+                {
+                    self.pending_tap = None;
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some((started_at, candidate)) = self.spike.take() {
+            self.quiet_since = now_ms;
+            if candidate && now_ms.wrapping_sub(started_at) <= config.tap_max_duration_ms {
+                // 前のタップが残っていれば先に確定させる
+                if let Some(tapped_at) = self.pending_tap.take() {
+                    self.on_tap(tapped_at);
+                }
+                self.pending_tap = Some(now_ms);
+            }
+        }
+        if let Some(tapped_at) = self.pending_tap {
+            if now_ms.wrapping_sub(tapped_at) >= config.tap_confirm_ms {
+                self.pending_tap = None;
+                self.on_tap(tapped_at);
+            }
+        }
+    }
+
+    // tapped_at はタップの動きが収まった時刻
+    fn on_tap(&mut self, tapped_at: u32) {
+        self.push(MotionEvent::Tap);
+        match self.last_tap_at.take() {
+            Some(last)
+                if self.config.double_tap_ms > 0
+                    && tapped_at.wrapping_sub(last) <= self.config.double_tap_ms =>
+            {
+                self.push(MotionEvent::DoubleTap);
+            }
+            _ => self.last_tap_at = Some(tapped_at),
+        }
+    }
+
+    fn update_shake(&mut self, now_ms: u32) {
+        let config = self.config;
+        // しきい値の7割を下回るまでは同じ振りとして数える
+        if self.shaking {
+            if self.activity < config.shake_threshold * 0.7 {
+                self.shaking = false;
+            }
+            return;
+        }
+        if self.activity < config.shake_threshold {
+            return;
+        }
+        self.shaking = true;
+
+        if self.swings == 0 || now_ms.wrapping_sub(self.first_swing_at) > config.shake_window_ms {
+            self.swings = 0;
+            self.first_swing_at = now_ms;
+        }
+        self.swings += 1;
+
+        if let Some(until) = self.shake_cooldown_until {
+            if (now_ms.wrapping_sub(until) as i32) < 0 {
+                return;
+            }
+            self.shake_cooldown_until = None;
+        }
+        if self.swings >= config.shake_count {
+            self.swings = 0;
+            self.shake_cooldown_until = Some(now_ms.wrapping_add(config.shake_cooldown_ms));
+            // シェイクの1回目の振りをタップとして保留していたら捨てる
+            self.pending_tap = None;
+            self.last_tap_at = None;
+            self.push(MotionEvent::Shake);
+        }
+    }
+
+    // キューがいっぱいのときは新しいイベントを捨てる
+    fn push(&mut self, event: MotionEvent) {
+        self.events.enqueue(event).ok();
+    }
+
+    // たまっているイベントを古い順に取り出します
+    pub fn next_event(&mut self) -> Option<MotionEvent> {
+        self.events.dequeue()
+    }
+
+    // 重力の推定値 [G]
+    pub fn gravity(&self) -> F32x3 {
+        self.gravity
+    }
+
+    // 重力を除いた動きの加速度の大きさ [G]
+    pub fn activity(&self) -> f32 {
+        self.activity
+    }
+
+    pub fn tilt(&self) -> Tilt {
+        Tilt::from_gravity(self.gravity)
+    }
+
+    // 確定している画面の向き (まだ決まっていなければ None)
+    pub fn orientation(&self) -> Option<Orientation> {
+        self.orientation
+    }
+}
+
+impl Default for Motion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    const PERIOD_MS: u32 = 10; // 100[Hz] で読む
+    const FACE_UP: F32x3 = F32x3 {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    };
+
+    // start_ms から duration_ms の間、accel(経過時間) を入力して発生したイベントを返す
+    fn run(
+        motion: &mut Motion,
+        start_ms: u32,
+        duration_ms: u32,
+        accel: impl Fn(u32) -> F32x3,
+    ) -> Vec<MotionEvent> {
+        let mut events = Vec::new();
+        for t in (0..duration_ms).step_by(PERIOD_MS as usize) {
+            motion.update(accel(t), start_ms.wrapping_add(t));
+            while let Some(event) = motion.next_event() {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    // 画面を上にして置いたまま、taps_ms の時刻に 20[ms] の衝撃を受ける
+    fn taps(taps_ms: &'static [u32]) -> impl Fn(u32) -> F32x3 {
+        move |t| {
+            if taps_ms.iter().any(|&tap| (tap..tap + 20).contains(&t)) {
+                F32x3::new(0.0, 0.0, 2.0)
+            } else {
+                FACE_UP
+            }
+        }
+    }
+
+    // from_ms から 1[s] の間、x方向に 4[Hz]、振幅 2[G] で振る
+    fn shake(t: u32, from_ms: u32) -> F32x3 {
+        if (from_ms..from_ms + 1000).contains(&t) {
+            let phase = 2.0 * core::f32::consts::PI * (t - from_ms) as f32 / 250.0;
+            F32x3::new(2.0 * phase.sin(), 0.0, 1.0)
+        } else {
+            FACE_UP
+        }
+    }
+
+    #[test]
+    fn settles_to_initial_orientation() {
+        let mut motion = Motion::new();
+        let events = run(&mut motion, 0, 500, |_| FACE_UP);
+        assert_eq!(
+            events,
+            [MotionEvent::OrientationChanged(Orientation::FaceUp)]
+        );
+        assert_eq!(motion.orientation(), Some(Orientation::FaceUp));
+        assert!(motion.activity() < 1e-6);
+    }
+
+    #[test]
+    fn single_tap() {
+        let mut motion = Motion::new();
+        let events = run(&mut motion, 0, 1500, taps(&[1000]));
+        assert_eq!(
+            events,
+            [
+                MotionEvent::OrientationChanged(Orientation::FaceUp),
+                MotionEvent::Tap
+            ]
+        );
+    }
+
+    #[test]
+    fn tap_waits_for_confirmation() {
+        let mut motion = Motion::new();
+        run(&mut motion, 0, 1000, |_| FACE_UP);
+        // 衝撃が収まってから tap_confirm_ms 経つまでは発生しない
+        let events = run(&mut motion, 1000, 200, taps(&[0]));
+        assert_eq!(events, []);
+        let events = run(&mut motion, 1200, 30, |_| FACE_UP);
+        assert_eq!(events, [MotionEvent::Tap]);
+    }
+
+    #[test]
+    fn double_tap() {
+        let mut motion = Motion::new();
+        let events = run(&mut motion, 0, 2000, taps(&[1000, 1300]));
+        assert_eq!(
+            events,
+            [
+                MotionEvent::OrientationChanged(Orientation::FaceUp),
+                MotionEvent::Tap,
+                MotionEvent::Tap,
+                MotionEvent::DoubleTap
+            ]
+        );
+
+        // 間が double_tap_ms より長ければ別々のタップ
+        let mut motion = Motion::new();
+        let events = run(&mut motion, 0, 2500, taps(&[1000, 1600]));
+        assert_eq!(
+            events,
+            [
+                MotionEvent::OrientationChanged(Orientation::FaceUp),
+                MotionEvent::Tap,
+                MotionEvent::Tap
+            ]
+        );
+    }
+
+    #[test]
+    fn tap_needs_quiet_before() {
+        // 直前のタップから tap_quiet_ms 以内の衝撃はタップにしない
+        let mut motion = Motion::new();
+        let events = run(&mut motion, 0, 2000, taps(&[1000, 1100]));
+        assert_eq!(
+            events,
+            [
+                MotionEvent::OrientationChanged(Orientation::FaceUp),
+                MotionEvent::Tap
+            ]
+        );
+    }
+
+    #[test]
+    fn long_push_is_not_tap() {
+        let mut motion = Motion::new();
+        let events = run(&mut motion, 0, 2000, |t| {
+            if (1000..1100).contains(&t) {
+                F32x3::new(0.0, 0.0, 2.0)
+            } else {
+                FACE_UP
+            }
+        });
+        assert_eq!(
+            events,
+            [MotionEvent::OrientationChanged(Orientation::FaceUp)]
+        );
+    }
+
+    #[test]
+    fn shake_once() {
+        let mut motion = Motion::new();
+        let events = run(&mut motion, 0, 3000, |t| shake(t, 1000));
+        assert_eq!(
+            events,
+            [
+                MotionEvent::OrientationChanged(Orientation::FaceUp),
+                MotionEvent::Shake
+            ]
+        );
+        // 2回目は shake_cooldown_ms が過ぎてから
+        let events = run(&mut motion, 3000, 3000, |t| shake(t, 0));
+        assert_eq!(events, [MotionEvent::Shake]);
+    }
+
+    #[test]
+    fn shake_starting_with_sharp_swing_is_not_tap() {
+        // 1回目の振りがタップと同じくらい短く鋭い
+        let trace = |t| {
+            if (1000..1020).contains(&t) {
+                F32x3::new(2.0, 0.0, 1.0)
+            } else {
+                shake(t, 1100)
+            }
+        };
+        let mut motion = Motion::new();
+        let events = run(&mut motion, 0, 3000, trace);
+        assert_eq!(
+            events,
+            [
+                MotionEvent::OrientationChanged(Orientation::FaceUp),
+                MotionEvent::Shake
+            ]
+        );
+
+        // 確定を待たなければ、1回目の振りがタップになってしまう
+        let mut motion = Motion::with_config(MotionConfig {
+            tap_confirm_ms: 0,
+            ..MotionConfig::default()
+        });
+        let events = run(&mut motion, 0, 3000, trace);
+        assert_eq!(
+            events,
+            [
+                MotionEvent::OrientationChanged(Orientation::FaceUp),
+                MotionEvent::Tap,
+                MotionEvent::Shake
+            ]
+        );
+    }
+
+    #[test]
+    fn orientation_changes() {
+        // 1[s] かけて画面を上から +x が上になるまで回し、さらに -y が上になるまで回す
+        let trace = |t: u32| {
+            let angle = |from: u32| {
+                let progress = (t.saturating_sub(from) as f32 / 1000.0).min(1.0);
+                progress * core::f32::consts::FRAC_PI_2
+            };
+            let (a, b) = (angle(1000), angle(3000));
+            F32x3::new(a.sin() * b.cos(), -b.sin(), a.cos() * b.cos())
+        };
+        let mut motion = Motion::new();
+        let events = run(&mut motion, 0, 5000, trace);
+        assert_eq!(
+            events,
+            [
+                MotionEvent::OrientationChanged(Orientation::FaceUp),
+                MotionEvent::OrientationChanged(Orientation::Landscape),
+                MotionEvent::OrientationChanged(Orientation::PortraitFlipped)
+            ]
+        );
+        assert_eq!(motion.orientation(), Some(Orientation::PortraitFlipped));
+    }
+
+    #[test]
+    fn short_tilt_does_not_change_orientation() {
+        // orientation_hold_ms より短い間だけ横に向ける
+        let mut motion = Motion::new();
+        run(&mut motion, 0, 1000, |_| FACE_UP);
+        for t in (1000..6000).step_by(PERIOD_MS as usize) {
+            let accel = if (3000..3250).contains(&t) {
+                F32x3::new(1.0, 0.0, 0.0)
+            } else {
+                FACE_UP
+            };
+            motion.update(accel, t);
+            assert_eq!(motion.next_event(), None, "{}", t);
+        }
+    }
+
+    #[test]
+    fn time_wraps_around() {
+        let mut motion = Motion::new();
+        let start = u32::MAX - 1200;
+        let events = run(&mut motion, start, 2000, taps(&[1000, 1300]));
+        assert_eq!(
+            events,
+            [
+                MotionEvent::OrientationChanged(Orientation::FaceUp),
+                MotionEvent::Tap,
+                MotionEvent::Tap,
+                MotionEvent::DoubleTap
+            ]
+        );
+    }
+
+    #[test]
+    fn reset_drops_events() {
+        let mut motion = Motion::new();
+        run(&mut motion, 0, 1000, |_| FACE_UP);
+        motion.update(FACE_UP, 1000);
+        motion.reset();
+        assert_eq!(motion.next_event(), None);
+        assert_eq!(motion.orientation(), None);
+    }
+}
@@ -0,0 +1,26 @@
+#![cfg_attr(not(test), no_std)]
+// テストでは std の f32 のメソッドが使われるので、micromath::F32Ext が未使用になる
+#![cfg_attr(test, allow(unused_imports))]
+
+// 加速度センサの値から傾きや動きを求めます
+//
+// tilt:        重力の向きから傾き (ピッチ・ロール) と画面の向きを求める
+// gesture:     タップ・ダブルタップ・シェイクを検出してイベントを発生させる
+// calibration: 6つの向きの測定値からオフセットと感度を求め、保存・適用する
+// pedometer:   加速度の山を数えて歩数とピッチを求める
+//
+// accelerometer クレートの F32x3 [G] と時刻 [ms] を渡すだけなので、
+// 記録しておいた値を使ってホストPCでも同じように動かせます
+
+pub mod calibration;
+pub mod gesture;
+pub mod pedometer;
+pub mod tilt;
+
+pub use calibration::{
+    CalibratedAccelerometer, Calibration, CalibrationRecord, Calibrator, Position, PositionError,
+    RecordError,
+};
+pub use gesture::{Motion, MotionConfig, MotionEvent};
+pub use pedometer::{Activity, Pedometer, PedometerConfig};
+pub use tilt::{Orientation, Tilt};
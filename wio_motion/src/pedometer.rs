@@ -0,0 +1,231 @@
+// 歩数計
+//
+// 歩くと、一歩ごとに加速度の大きさが山を描きます
+//
+//   1. 加速度の大きさから重力分 (ゆっくり変わる成分) を引き、細かい揺れをならす
+//   2. 最近の最大値と最小値の中間をしきい値にして、それを越える山を一歩の候補とする
+//      (歩き方や持ち方で振れ幅が変わっても追従する)
+//   3. 間隔が min_step_interval_ms〜max_step_interval_ms の候補が confirm_steps 回続いたら歩いているとみなし、
+//      それまでの候補もまとめて歩数に加える (手に取ったときなどの単発の揺れを数えないため)
+//
+// 50[Hz]程度で `update` を呼び出してください
+//
+//   let mut pedometer = Pedometer::new();
+//   pedometer.update(accel.accel_norm().unwrap(), now_ms);
+//   let steps = pedometer.steps();
+
+use accelerometer::vector::F32x3;
+use micromath::F32Ext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    Still,
+    Walking,
+    Running,
+}
+
+impl Activity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Activity::Still => "STILL",
+            Activity::Walking => "WALKING",
+            Activity::Running => "RUNNING",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PedometerConfig {
+    pub gravity_time_constant_ms: u32,   // 重力分を求める低域通過フィルタの時定数
+    pub smoothing_time_constant_ms: u32, // 細かい揺れをならす低域通過フィルタの時定数
+    pub envelope_time_constant_ms: u32,  // 最大値・最小値が信号に戻っていく時定数
+    pub min_amplitude: f32,              // 一歩とみなす最小の振れ幅 [G]
+    pub min_step_interval_ms: u32,       // これより短い間隔の山は同じ一歩とみなす
+    pub max_step_interval_ms: u32,       // これより間が空いたら止まったとみなす
+    pub confirm_steps: u32,              // 歩き始めとみなすまでに続く候補の数
+    pub running_cadence: f32,            // これ以上のピッチ [歩/分] なら走っているとみなす
+}
+
+impl Default for PedometerConfig {
+    fn default() -> Self {
+        PedometerConfig {
+            gravity_time_constant_ms: 1000,
+            smoothing_time_constant_ms: 40,
+            envelope_time_constant_ms: 1500,
+            min_amplitude: 0.15,
+            min_step_interval_ms: 250,
+            max_step_interval_ms: 2000,
+            confirm_steps: 4,
+            running_cadence: 140.0,
+        }
+    }
+}
+
+pub struct Pedometer {
+    config: PedometerConfig,
+    last_update: Option<u32>,
+    gravity: f32,  // 加速度の大きさの平均 (ほぼ1[G])
+    smoothed: f32, // 重力分を引いてならした値
+    maximum: f32,  // 最近の最大値
+    minimum: f32,  // 最近の最小値
+    above: bool,   // しきい値を越えている (山の途中)
+    steps: u32,
+    candidates: u32,           // 歩き始めとみなす前に数えた候補
+    walking: bool,             // 歩いている (候補を1つずつ歩数に加える)
+    last_step_at: Option<u32>, // 直前の候補の時刻
+    interval: f32,             // 一歩の間隔の平均 [ms]
+}
+
+impl Pedometer {
+    pub fn new() -> Self {
+        Self::with_config(PedometerConfig::default())
+    }
+
+    pub fn with_config(config: PedometerConfig) -> Self {
+        Pedometer {
+            config,
+            last_update: None,
+            gravity: 1.0,
+            smoothed: 0.0,
+            maximum: 0.0,
+            minimum: 0.0,
+            above: false,
+            steps: 0,
+            candidates: 0,
+            walking: false,
+            last_step_at: None,
+            interval: 0.0,
+        }
+    }
+
+    pub fn config(&self) -> &PedometerConfig {
+        &self.config
+    }
+
+    // 歩数を0に戻します
+    pub fn reset(&mut self) {
+        *self = Self::with_config(self.config);
+    }
+
+    // 加速度 accel [G] を入力します
+    // 歩数が増えたときは、増えた歩数を返します
+    pub fn update(&mut self, accel: F32x3, now_ms: u32) -> Option<u32> {
+        let magnitude = (accel.x * accel.x + accel.y * accel.y + accel.z * accel.z).sqrt();
+        let elapsed = match self.last_update {
+            Some(last) => now_ms.wrapping_sub(last) as f32,
+            None => {
+                self.gravity = magnitude;
+                0.0
+            }
+        };
+        self.last_update = Some(now_ms);
+
+        let config = self.config;
+        let alpha = |time_constant: u32| elapsed / (time_constant as f32 + elapsed).max(1.0);
+        self.gravity += alpha(config.gravity_time_constant_ms) * (magnitude - self.gravity);
+        let dynamic = magnitude - self.gravity;
+        self.smoothed += alpha(config.smoothing_time_constant_ms) * (dynamic - self.smoothed);
+        let x = self.smoothed;
+
+        // 最大値・最小値は越えたらすぐに追従し、越えなければゆっくり信号に戻る
+        let envelope = alpha(config.envelope_time_constant_ms);
+        self.maximum = if x > self.maximum {
+            x
+        } else {
+            self.maximum + envelope * (x - self.maximum)
+        };
+        self.minimum = if x < self.minimum {
+            x
+        } else {
+            self.minimum + envelope * (x - self.minimum)
+        };
+
+        // 止まってから時間がたったら、次の一歩は歩き始めとして扱う
+        if let Some(last) = self.last_step_at {
+            if now_ms.wrapping_sub(last) > config.max_step_interval_ms {
+                self.walking = false;
+                self.candidates = 0;
+                self.last_step_at = None;
+                self.interval = 0.0;
+            }
+        }
+
+        let amplitude = self.maximum - self.minimum;
+        let threshold = (self.maximum + self.minimum) / 2.0;
+        // しきい値のまわりで振れ幅の1割のヒステリシスをもたせる
+        let hysteresis = amplitude * 0.1;
+        if !self.above {
+            // 谷が山より深いと、しきい値は0より下になる
+            // 谷から0に戻っただけで数えないように、山は0より上でなければならない
+            if x > threshold.max(0.0) + hysteresis && amplitude >= config.min_amplitude {
+                self.above = true;
+                return self.on_candidate(now_ms);
+            }
+        } else if x < threshold - hysteresis {
+            self.above = false;
+        }
+        None
+    }
+
+    fn on_candidate(&mut self, now_ms: u32) -> Option<u32> {
+        let config = self.config;
+        if let Some(last) = self.last_step_at {
+            let interval = now_ms.wrapping_sub(last);
+            if interval < config.min_step_interval_ms {
+                return None;
+            }
+            // ピッチは最近の間隔を重くした平均から求める
+            self.interval = if self.interval == 0.0 {
+                interval as f32
+            } else {
+                self.interval * 0.75 + interval as f32 * 0.25
+            };
+        }
+        self.last_step_at = Some(now_ms);
+
+        if self.walking {
+            self.steps += 1;
+            return Some(1);
+        }
+        self.candidates += 1;
+        if self.candidates >= config.confirm_steps {
+            self.walking = true;
+            self.steps += self.candidates;
+            let added = self.candidates;
+            self.candidates = 0;
+            return Some(added);
+        }
+        None
+    }
+
+    // 歩数
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    // ピッチ [歩/分] (歩いていないときは0)
+    pub fn cadence(&self) -> f32 {
+        if self.walking && self.interval > 0.0 {
+            60_000.0 / self.interval
+        } else {
+            0.0
+        }
+    }
+
+    pub fn activity(&self) -> Activity {
+        let cadence = self.cadence();
+        if cadence <= 0.0 {
+            Activity::Still
+        } else if cadence >= self.config.running_cadence {
+            Activity::Running
+        } else {
+            Activity::Walking
+        }
+    }
+}
+
+impl Default for Pedometer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
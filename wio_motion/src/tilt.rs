@@ -0,0 +1,82 @@
+// 傾きと画面の向き
+//
+// 静止しているときの加速度は重力 (大きさ1[G]) だけなので、その向きから傾きがわかります
+//
+//   ピッチ: x軸の傾き (x軸が水平なら0度、真上を向くと90度)
+//   ロール: x軸まわりの回転 (画面を上にして水平なら0度)
+
+use accelerometer::vector::F32x3;
+use micromath::F32Ext;
+
+// 傾き [度]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tilt {
+    pub pitch: f32, // -90〜90
+    pub roll: f32,  // -180〜180
+}
+
+impl Tilt {
+    // 重力の向きから傾きを求めます
+    pub fn from_gravity(gravity: F32x3) -> Self {
+        let F32x3 { x, y, z } = gravity;
+        Tilt {
+            pitch: x.atan2((y * y + z * z).sqrt()).to_degrees(),
+            roll: y.atan2(z).to_degrees(),
+        }
+    }
+}
+
+// 画面の向き (どの軸が真上を向いているか)
+// 静止しているとき、真上を向いた軸の値が +1[G] になります
+//
+//   +x: Landscape        -x: LandscapeFlipped
+//   +y: Portrait         -y: PortraitFlipped
+//   +z: FaceUp (画面が上) -z: FaceDown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Landscape,
+    LandscapeFlipped,
+    Portrait,
+    PortraitFlipped,
+    FaceUp,
+    FaceDown,
+}
+
+impl Orientation {
+    // 重力のうち threshold (0〜1) 以上の割合を占める軸があれば、その向きを返します
+    // どの軸も傾きの途中でどちらともいえないときは None を返します
+    pub fn from_gravity(gravity: F32x3, threshold: f32) -> Option<Self> {
+        let F32x3 { x, y, z } = gravity;
+        let magnitude = (x * x + y * y + z * z).sqrt();
+        if magnitude < 0.1 {
+            // 落下中などで重力の向きがわからない
+            return None;
+        }
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+        let (component, positive, negative) = if ax >= ay && ax >= az {
+            (x, Orientation::Landscape, Orientation::LandscapeFlipped)
+        } else if ay >= az {
+            (y, Orientation::Portrait, Orientation::PortraitFlipped)
+        } else {
+            (z, Orientation::FaceUp, Orientation::FaceDown)
+        };
+        if component.abs() < threshold * magnitude {
+            None
+        } else if component > 0.0 {
+            Some(positive)
+        } else {
+            Some(negative)
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Orientation::Landscape => "LANDSCAPE",
+            Orientation::LandscapeFlipped => "LANDSCAPE (FLIPPED)",
+            Orientation::Portrait => "PORTRAIT",
+            Orientation::PortraitFlipped => "PORTRAIT (FLIPPED)",
+            Orientation::FaceUp => "FACE UP",
+            Orientation::FaceDown => "FACE DOWN",
+        }
+    }
+}
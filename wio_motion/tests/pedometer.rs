@@ -0,0 +1,167 @@
+// 合成した加速度の記録 (traces/synthetic_*.csv) を歩数計に通して確かめます
+// 記録の作り方は traces/synthesize.py を見てください
+//
+// 実機で記録したもの (traces/recorded_*.csv、traces/capture.py で作る) があれば、それも確かめます
+// まだ実機の記録はリポジトリに入っていません
+
+use accelerometer::vector::F32x3;
+use std::{fs, ops::Range, path::Path};
+use wio_motion::{Activity, Pedometer};
+
+const WALKING: &str = include_str!("../traces/synthetic_walking.csv");
+const RUNNING: &str = include_str!("../traces/synthetic_running.csv");
+const JOLTS: &str = include_str!("../traces/synthetic_jolts.csv");
+
+struct Trace {
+    steps: u32, // "# steps: N" の行に書いた、実際の歩数
+    samples: Vec<(u32, F32x3)>,
+}
+
+fn parse(text: &str) -> Trace {
+    let mut steps = None;
+    let mut samples = Vec::new();
+    for line in text.lines() {
+        if let Some(value) = line.strip_prefix("# steps:") {
+            steps = Some(value.trim().parse().unwrap());
+            continue;
+        }
+        let fields: Vec<f32> = line.split(',').filter_map(|f| f.parse().ok()).collect();
+        // コメントと見出しの行は読み飛ばす
+        if fields.len() != 4 {
+            continue;
+        }
+        let accel = F32x3::new(fields[1], fields[2], fields[3]);
+        samples.push((fields[0] as u32, accel));
+    }
+    Trace {
+        steps: steps.unwrap(),
+        samples,
+    }
+}
+
+// 記録の時刻が range に入る値を、時刻を start_ms ずらして入力する
+fn run(pedometer: &mut Pedometer, trace: &Trace, start_ms: u32, range: Range<u32>) {
+    for &(t, accel) in trace.samples.iter() {
+        if range.contains(&t) {
+            pedometer.update(accel, start_ms.wrapping_add(t));
+        }
+    }
+}
+
+fn assert_steps(actual: u32, expected: u32) {
+    // 歩き始めと止まるときに1歩ずつまで
+    let tolerance = 2;
+    assert!(
+        (actual as i64 - expected as i64).abs() <= tolerance,
+        "{} steps (expected {})",
+        actual,
+        expected
+    );
+}
+
+fn assert_cadence(pedometer: &Pedometer, expected: f32) {
+    let cadence = pedometer.cadence();
+    assert!(
+        (cadence - expected).abs() <= expected * 0.05,
+        "{} steps/min (expected {})",
+        cadence,
+        expected
+    );
+}
+
+#[test]
+fn walking() {
+    let trace = parse(WALKING);
+    assert_eq!(trace.steps, 36);
+    let mut pedometer = Pedometer::new();
+    // 歩き出す前の揺れ (2〜3[s]) は数えない
+    run(&mut pedometer, &trace, 0, 0..5000);
+    assert_eq!(pedometer.steps(), 0);
+    assert_eq!(pedometer.activity(), Activity::Still);
+
+    // 歩いている途中 (5〜25[s])
+    run(&mut pedometer, &trace, 0, 5000..24_000);
+    assert_eq!(pedometer.activity(), Activity::Walking);
+    assert_cadence(&pedometer, 1.8 * 60.0);
+
+    run(&mut pedometer, &trace, 0, 24_000..u32::MAX);
+    assert_steps(pedometer.steps(), trace.steps);
+    // 止まって max_step_interval_ms 以上たっている
+    assert_eq!(pedometer.activity(), Activity::Still);
+    assert_eq!(pedometer.cadence(), 0.0);
+}
+
+#[test]
+fn running() {
+    let trace = parse(RUNNING);
+    assert_eq!(trace.steps, 56);
+    let mut pedometer = Pedometer::new();
+    // 1回目の走り (2〜17[s])
+    run(&mut pedometer, &trace, 0, 0..16_000);
+    assert_eq!(pedometer.activity(), Activity::Running);
+    assert_cadence(&pedometer, 2.8 * 60.0);
+
+    // 2回目の走り (19〜24[s])
+    run(&mut pedometer, &trace, 0, 16_000..23_500);
+    assert_eq!(pedometer.activity(), Activity::Running);
+    assert_cadence(&pedometer, 2.7 * 60.0);
+
+    run(&mut pedometer, &trace, 0, 23_500..u32::MAX);
+    assert_steps(pedometer.steps(), trace.steps);
+    assert_eq!(pedometer.activity(), Activity::Still);
+}
+
+#[test]
+fn isolated_jolts_are_not_steps() {
+    let trace = parse(JOLTS);
+    assert_eq!(trace.steps, 0);
+    let mut pedometer = Pedometer::new();
+    for &(t, accel) in trace.samples.iter() {
+        assert_eq!(pedometer.update(accel, t), None, "{}", t);
+    }
+    assert_eq!(pedometer.steps(), 0);
+    assert_eq!(pedometer.activity(), Activity::Still);
+}
+
+#[test]
+fn time_wraps_around() {
+    let trace = parse(WALKING);
+    let mut pedometer = Pedometer::new();
+    run(&mut pedometer, &trace, u32::MAX - 10_000, 0..u32::MAX);
+    assert_steps(pedometer.steps(), trace.steps);
+}
+
+#[test]
+fn reset_clears_steps() {
+    let trace = parse(RUNNING);
+    let mut pedometer = Pedometer::new();
+    run(&mut pedometer, &trace, 0, 0..16_000);
+    assert!(pedometer.steps() > 0);
+    pedometer.reset();
+    assert_eq!(pedometer.steps(), 0);
+    assert_eq!(pedometer.activity(), Activity::Still);
+}
+
+#[test]
+fn recorded_traces() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("traces");
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with("recorded_") && name.ends_with(".csv")
+        })
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        eprintln!("no recorded traces in {}", dir.display());
+    }
+    for path in paths {
+        let trace = parse(&fs::read_to_string(&path).unwrap());
+        let mut pedometer = Pedometer::new();
+        run(&mut pedometer, &trace, 0, 0..u32::MAX);
+        println!("{}: {} steps", path.display(), pedometer.steps());
+        assert_steps(pedometer.steps(), trace.steps);
+    }
+}
@@ -0,0 +1,59 @@
+# 6-7-accelerometer_serial のシリアル出力を、歩数計のテストに使う加速度の記録 (CSV) にするスクリプトです
+# 実機で記録したものは recorded_*.csv という名前で保存すると、tests/pedometer.rs のテストで使われます
+#
+# 6-7 の出力には時刻がないので、受け取った時刻 (ホストPCの時計) を付け、RATE[Hz] に間引きます
+# 歩数は記録しながら自分で数えて、--steps で指定してください
+#
+#   $ cargo hf2 --example 6-7-accelerometer_serial --features motion
+#   $ stty -F /dev/ttyACM0 115200 raw
+#   $ python3 capture.py /dev/ttyACM0 recorded_walking.csv --steps 40 --title "歩行、胸ポケットに入れて"
+#
+# Ctrl-C で記録を終わります
+
+import argparse
+import re
+import time
+
+RATE = 50  # [Hz]
+LINE = re.compile(r'X:\s*(-?[0-9.]+),\s*Y:\s*(-?[0-9.]+),\s*Z:\s*(-?[0-9.]+)')
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument('device', help='シリアルポート (/dev/ttyACM0 など)')
+    parser.add_argument('output')
+    parser.add_argument('--steps', type=int, required=True, help='記録中に数えた歩数')
+    parser.add_argument('--title', default='実機で記録したもの')
+    args = parser.parse_args()
+
+    lines = []
+    start = None
+    next_ms = 0
+    try:
+        with open(args.device, errors='replace') as fp:
+            for line in fp:
+                m = LINE.search(line)
+                if not m:
+                    continue
+                now = time.monotonic()
+                if start is None:
+                    start = now
+                t_ms = round((now - start) * 1000)
+                # 6-7 は待たずに読み続けるので、RATE[Hz] より細かい値は捨てる
+                if t_ms < next_ms:
+                    continue
+                lines.append('%d,%s,%s,%s' % (t_ms, m.group(1), m.group(2), m.group(3)))
+                next_ms = t_ms + 1000 // RATE
+    except KeyboardInterrupt:
+        pass
+
+    with open(args.output, 'w') as fp:
+        fp.write('# %s\n' % args.title)
+        fp.write('# 約%d[Hz]、6-7-accelerometer_serial の出力を capture.py で記録したもの\n' % RATE)
+        fp.write('# steps: %d\n' % args.steps)
+        fp.write('t_ms,x,y,z\n')
+        fp.write('\n'.join(lines) + '\n')
+    print('%s: %d samples' % (args.output, len(lines)))
+
+
+main()
@@ -0,0 +1,70 @@
+# 歩数計のテストに使う加速度の記録 (synthetic_*.csv) を合成するスクリプトです
+# 実機で記録したものではなく、傾けて持ったときの重力に、一歩ごとの山や単発の揺れと雑音を足しています
+# 乱数の種を固定しているので、何度実行しても同じファイルができます
+#
+#   python3 synthesize.py
+
+import math
+import random
+
+RATE = 50  # [Hz]
+
+
+def synthesize(path, title, segments, seed):
+    random.seed(seed)
+    g = [0.2, -0.3, 0.93]
+    n = math.sqrt(sum(c * c for c in g))
+    g = [c / n for c in g]
+    lines = []
+    t = 0.0
+    phase = 0.0
+    steps = 0
+    # segments: (種類, 長さ [s], 歩数/秒, 振れ幅 [G])
+    for kind, duration, frequency, amplitude in segments:
+        end = t + duration
+        while t < end - 1e-9:
+            noise = [random.gauss(0, 0.015) for _ in range(3)]
+            v = 0.0
+            side = 0.0
+            if kind == 'step':
+                f = frequency * (1 + random.gauss(0, 0.01))
+                previous = phase
+                phase += 2 * math.pi * f / RATE
+                # 位相が π/2 (山) を越えた回数を一歩と数える
+                if math.floor((previous - math.pi / 2) / (2 * math.pi)) < math.floor(
+                        (phase - math.pi / 2) / (2 * math.pi)):
+                    steps += 1
+                a = amplitude * (1 + 0.1 * math.sin(phase / 7))
+                v = a * math.sin(phase) + 0.35 * a * math.sin(2 * phase + 1.0)
+                side = 0.3 * a * math.sin(phase / 2)
+            elif kind == 'bump':
+                # 手に取ったときなどの単発の揺れ (区間の真ん中に1回)
+                c = t - (end - duration) - duration / 2
+                v = amplitude * math.exp(-(c / 0.08) ** 2) * math.cos(c * 20)
+            x = g[0] * (1 + v) + side + noise[0]
+            y = g[1] * (1 + v) + noise[1]
+            z = g[2] * (1 + v) - 0.5 * side + noise[2]
+            lines.append("%d,%.3f,%.3f,%.3f" % (round(t * 1000), x, y, z))
+            t += 1.0 / RATE
+        if kind != 'step':
+            phase = 0.0
+    with open(path, 'w') as fp:
+        fp.write("# %s\n" % title)
+        fp.write("# 50[Hz]、synthesize.py で合成したもの (実機で記録した値ではありません)\n")
+        fp.write("# steps: %d\n" % steps)
+        fp.write("t_ms,x,y,z\n")
+        fp.write("\n".join(lines) + "\n")
+
+
+synthesize('synthetic_walking.csv', '歩行 (約1.8歩/秒)',
+           [('still', 2, 0, 0), ('bump', 1, 0, 0.8), ('still', 2, 0, 0),
+            ('step', 20, 1.8, 0.3), ('still', 3, 0, 0)], 1)
+synthesize('synthetic_running.csv', '走行 (約2.8歩/秒)',
+           [('still', 2, 0, 0), ('step', 15, 2.8, 0.9), ('still', 2, 0, 0),
+            ('step', 5, 2.7, 0.8), ('still', 3, 0, 0)], 2)
+# 歩いていないときの揺れ (2つ続けて揺れても、歩き始めとみなす4回には届かない)
+synthesize('synthetic_jolts.csv', '置いたまま、ときどき揺らす',
+           [('still', 2, 0, 0), ('bump', 1, 0, 0.8), ('still', 2, 0, 0),
+            ('bump', 1, 0, 1.2), ('still', 1.5, 0, 0), ('bump', 0.6, 0, 0.6),
+            ('bump', 0.6, 0, 0.9), ('still', 3, 0, 0), ('bump', 1, 0, 0.5),
+            ('still', 2.5, 0, 0), ('bump', 1, 0, 1.0), ('still', 3, 0, 0)], 3)
@@ -0,0 +1,57 @@
+// ノイズの乗った波形をトリガをかけて表示するオシロスコープをシミュレータで動かすサンプルです
+// ウィンドウをクリックするとトリガのエッジが切り替わります
+// `cargo run --example sim --features sim`
+
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use embedded_graphics_simulator::*;
+use std::{thread, time};
+use wio_scope::{Capture, Screen, VerticalScale};
+
+const FRAME_LENGTH: usize = 301; // 横幅1[px]に1サンプル
+const SAMPLES_PER_UPDATE: usize = 2000;
+
+fn main() {
+    let mut display: SimulatorDisplay<Rgb565> = SimulatorDisplay::new(Size::new(320, 240));
+    let output_settings = OutputSettingsBuilder::new().build();
+    let mut window = Window::new("Scope", &output_settings);
+
+    let screen = Screen::new(Point::new(9, 19), Size::new(30, 25));
+    let scale = VerticalScale::new(500);
+    let mut capture: Capture<FRAME_LENGTH> = Capture::new(scale.sample(1900));
+    let mut frame = [0u16; FRAME_LENGTH];
+
+    let interval = time::Duration::from_millis(30);
+    let mut n = 0u32;
+    'running: loop {
+        for event in window.events() {
+            match event {
+                SimulatorEvent::Quit => break 'running,
+                SimulatorEvent::MouseButtonUp { .. } => capture.set_edge(capture.edge().toggled()),
+                _ => {}
+            }
+        }
+
+        // 基本波と3倍波を重ねたものにノイズを加える (周期は約97サンプル)
+        for _ in 0..SAMPLES_PER_UPDATE {
+            let t = n as f32 * 2.0 * core::f32::consts::PI / 97.0;
+            let noise = ((n as f32 * 12.9898).sin() * 43758.545).fract() * 40.0;
+            let value = 2048.0 + 900.0 * t.sin() + 300.0 * (3.0 * t).sin() + noise;
+            capture.push(value as u16);
+            n = n.wrapping_add(1);
+        }
+
+        if let Some(info) = capture.take_frame(&mut frame) {
+            screen
+                .draw(
+                    &mut display,
+                    &frame,
+                    &scale,
+                    Some(info.trigger_index),
+                    Some(capture.level()),
+                )
+                .unwrap();
+        }
+        window.update(&display);
+        thread::sleep(interval);
+    }
+}
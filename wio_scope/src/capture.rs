@@ -0,0 +1,507 @@
+// トリガつきの波形の取り込み
+//
+// 割り込みハンドラから1サンプルずつ push し、1画面分 (N サンプル) がそろったら取り出します
+//
+//   Filling   : トリガ位置より前の分 (プリトリガ) をためる
+//   Armed     : トリガ条件を待つ
+//   Triggered : トリガ位置より後の分をためる
+//   Ready     : 1画面分そろった (取り出すまで書き込まない)
+//   Holdoff   : 次のトリガを待ち受けるまで待つ
+//   Stopped   : シングルモードで1回取り込んだ後、再び arm するまで止まる
+//
+// トリガにはヒステリシスがあり、一度レベルから hysteresis 以上離れてからでないと再びトリガしません
+// (ノイズでレベル付近を行ったり来たりしても、何度もトリガしないようにするため)
+
+// トリガをかける向き
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Rising,  // 立ち上がり
+    Falling, // 立ち下がり
+}
+
+impl Edge {
+    pub fn toggled(self) -> Self {
+        match self {
+            Edge::Rising => Edge::Falling,
+            Edge::Falling => Edge::Rising,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerMode {
+    Auto,   // トリガがかからなくても一定時間ごとに表示する
+    Normal, // トリガがかかったときだけ表示する
+    Single, // 1回取り込んだら止まる
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptureState {
+    Filling,
+    Armed,
+    Triggered,
+    Ready,
+    Holdoff,
+    Stopped,
+}
+
+// 取り出した1画面分の情報
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameInfo {
+    pub trigger_index: usize, // トリガがかかったサンプルの位置
+    pub forced: bool,         // オートモードでトリガを待たずに取り込んだか
+}
+
+pub struct Capture<const N: usize> {
+    buffer: [u16; N], // リングバッファ
+    index: usize,     // 次に書き込む位置
+    state: CaptureState,
+    mode: TriggerMode,
+    edge: Edge,
+    level: u16,
+    hysteresis: u16,
+    primed: bool,       // トリガの前にレベルの反対側へ十分離れたか
+    pre_trigger: usize, // トリガ位置より前のサンプル数
+    holdoff: u32,       // 取り込み後、次に待ち受けるまでのサンプル数
+    auto_timeout: u32,  // オートモードでトリガを待つ最大のサンプル数
+    decimation: u32,    // decimation 個に1個を取り込む
+    decimation_count: u32,
+    count: u32, // 状態ごとに数えるサンプル数
+    forced: bool,
+}
+
+impl<const N: usize> Capture<N> {
+    // 中央でトリガする、オートモード、立ち上がりエッジの設定で作ります
+    pub fn new(level: u16) -> Self {
+        Capture {
+            buffer: [0; N],
+            index: 0,
+            state: CaptureState::Filling,
+            mode: TriggerMode::Auto,
+            edge: Edge::Rising,
+            level,
+            hysteresis: 16,
+            primed: false,
+            pre_trigger: N / 2,
+            holdoff: 0,
+            auto_timeout: N as u32 * 2,
+            decimation: 1,
+            decimation_count: 0,
+            count: 0,
+            forced: false,
+        }
+    }
+
+    pub fn state(&self) -> CaptureState {
+        self.state
+    }
+
+    pub fn mode(&self) -> TriggerMode {
+        self.mode
+    }
+
+    pub fn edge(&self) -> Edge {
+        self.edge
+    }
+
+    pub fn level(&self) -> u16 {
+        self.level
+    }
+
+    pub fn holdoff(&self) -> u32 {
+        self.holdoff
+    }
+
+    pub fn decimation(&self) -> u32 {
+        self.decimation
+    }
+
+    pub fn pre_trigger(&self) -> usize {
+        self.pre_trigger
+    }
+
+    // モードを変えて取り込みをやり直します
+    pub fn set_mode(&mut self, mode: TriggerMode) {
+        self.mode = mode;
+        self.arm();
+    }
+
+    pub fn set_edge(&mut self, edge: Edge) {
+        self.edge = edge;
+        self.primed = false;
+    }
+
+    pub fn set_level(&mut self, level: u16) {
+        self.level = level;
+        self.primed = false;
+    }
+
+    pub fn set_hysteresis(&mut self, hysteresis: u16) {
+        self.hysteresis = hysteresis;
+    }
+
+    // ホールドオフ (取り込んだ後、次のトリガを待ち受けるまでのサンプル数)
+    // 間引いた後のサンプル数で数えます
+    pub fn set_holdoff(&mut self, holdoff: u32) {
+        self.holdoff = holdoff;
+    }
+
+    // オートモードで、トリガがかからないまま取り込むまでのサンプル数
+    pub fn set_auto_timeout(&mut self, timeout: u32) {
+        self.auto_timeout = timeout.max(1);
+    }
+
+    // トリガ位置より前に表示するサンプル数 (N - 1 まで)
+    pub fn set_pre_trigger(&mut self, pre_trigger: usize) {
+        self.pre_trigger = pre_trigger.min(N - 1);
+        self.arm();
+    }
+
+    // decimation 個に1個を取り込みます (時間軸を伸ばす)
+    pub fn set_decimation(&mut self, decimation: u32) {
+        self.decimation = decimation.max(1);
+        self.arm();
+    }
+
+    // 取り込みを最初からやり直します (シングルモードで止まっているときも再開します)
+    pub fn arm(&mut self) {
+        self.state = CaptureState::Filling;
+        self.count = 0;
+        self.decimation_count = 0;
+        self.primed = false;
+        self.forced = false;
+    }
+
+    // 取り込みを止めます
+    pub fn stop(&mut self) {
+        self.state = CaptureState::Stopped;
+    }
+
+    // 1サンプル入力します
+    // 1画面分がそろったときに true を返します
+    pub fn push(&mut self, sample: u16) -> bool {
+        if matches!(self.state, CaptureState::Ready | CaptureState::Stopped) {
+            return false;
+        }
+        self.decimation_count += 1;
+        if self.decimation_count < self.decimation {
+            return false;
+        }
+        self.decimation_count = 0;
+
+        if self.state == CaptureState::Holdoff {
+            self.count += 1;
+            if self.count >= self.holdoff {
+                self.state = CaptureState::Filling;
+                self.count = 0;
+                self.primed = false;
+            }
+            return false;
+        }
+
+        self.buffer[self.index] = sample;
+        self.index += 1;
+        if self.index == N {
+            self.index = 0;
+        }
+
+        match self.state {
+            CaptureState::Filling => {
+                self.update_primed(sample);
+                self.count += 1;
+                if self.count as usize >= self.pre_trigger {
+                    self.state = CaptureState::Armed;
+                    self.count = 0;
+                }
+                false
+            }
+            CaptureState::Armed => {
+                if self.is_trigger(sample) {
+                    self.forced = false;
+                    self.trigger()
+                } else {
+                    self.update_primed(sample);
+                    self.count += 1;
+                    if self.mode == TriggerMode::Auto && self.count >= self.auto_timeout {
+                        self.forced = true;
+                        self.trigger()
+                    } else {
+                        false
+                    }
+                }
+            }
+            CaptureState::Triggered => {
+                self.count -= 1;
+                if self.count == 0 {
+                    self.state = CaptureState::Ready;
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+
+    // 1画面分がそろっていれば、古い順に frame へコピーして次の取り込みを始めます
+    pub fn take_frame(&mut self, frame: &mut [u16; N]) -> Option<FrameInfo> {
+        if self.state != CaptureState::Ready {
+            return None;
+        }
+        // 最後に書き込んだ次の位置が最も古いサンプル
+        let (recent, old) = self.buffer.split_at(self.index);
+        frame[..old.len()].copy_from_slice(old);
+        frame[old.len()..].copy_from_slice(recent);
+        let info = FrameInfo {
+            trigger_index: self.pre_trigger,
+            forced: self.forced,
+        };
+
+        self.count = 0;
+        self.primed = false;
+        self.state = if self.mode == TriggerMode::Single {
+            CaptureState::Stopped
+        } else if self.holdoff > 0 {
+            CaptureState::Holdoff
+        } else {
+            CaptureState::Filling
+        };
+        Some(info)
+    }
+
+    // トリガ位置のサンプルは書き込み済みなので、残りの N - pre_trigger - 1 個をためる
+    fn trigger(&mut self) -> bool {
+        let remaining = N - self.pre_trigger - 1;
+        if remaining == 0 {
+            self.state = CaptureState::Ready;
+            true
+        } else {
+            self.state = CaptureState::Triggered;
+            self.count = remaining as u32;
+            false
+        }
+    }
+
+    fn is_trigger(&self, sample: u16) -> bool {
+        self.primed
+            && match self.edge {
+                Edge::Rising => sample >= self.level,
+                Edge::Falling => sample <= self.level,
+            }
+    }
+
+    // レベルから十分離れたら準備完了、レベルを越えている間は準備をやり直す
+    // (プリトリガをためている間に越えた分でトリガしないように)
+    fn update_primed(&mut self, sample: u16) {
+        let (away, beyond) = match self.edge {
+            Edge::Rising => (
+                sample < self.level.saturating_sub(self.hysteresis),
+                sample >= self.level,
+            ),
+            Edge::Falling => (
+                sample > self.level.saturating_add(self.hysteresis),
+                sample <= self.level,
+            ),
+        };
+        if away {
+            self.primed = true;
+        } else if beyond {
+            self.primed = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    const N: usize = 64;
+    const LEVEL: u16 = 2048;
+
+    // 周期 period の矩形波 (最初の半周期が Low)
+    fn square(i: usize, period: usize) -> u16 {
+        if i % period < period / 2 {
+            1000
+        } else {
+            3000
+        }
+    }
+
+    fn sine(i: usize, period: usize, amplitude: f32) -> u16 {
+        let phase = 2.0 * core::f32::consts::PI * i as f32 / period as f32;
+        (LEVEL as f32 + amplitude * phase.sin()) as u16
+    }
+
+    // 1画面分そろうまで入力し、そろったときのサンプルの番号を返す
+    fn capture_until_ready(
+        capture: &mut Capture<N>,
+        signal: impl Fn(usize) -> u16,
+        start: usize,
+        limit: usize,
+    ) -> Option<usize> {
+        (start..start + limit).find(|&i| capture.push(signal(i)))
+    }
+
+    fn normal_capture() -> Capture<N> {
+        let mut capture = Capture::new(LEVEL);
+        capture.set_mode(TriggerMode::Normal);
+        capture
+    }
+
+    #[test]
+    fn rising_edge_of_square_wave() {
+        let mut capture = normal_capture();
+        capture_until_ready(&mut capture, |i| square(i, 20), 0, 1000).unwrap();
+        let mut frame = [0; N];
+        let info = capture.take_frame(&mut frame).unwrap();
+        assert_eq!(info.trigger_index, N / 2);
+        assert!(!info.forced);
+        assert_eq!(frame[info.trigger_index - 1], 1000);
+        assert_eq!(frame[info.trigger_index], 3000);
+    }
+
+    #[test]
+    fn falling_edge_of_square_wave() {
+        let mut capture = normal_capture();
+        capture.set_edge(Edge::Rising.toggled());
+        assert_eq!(capture.edge(), Edge::Falling);
+        capture_until_ready(&mut capture, |i| square(i, 20), 0, 1000).unwrap();
+        let mut frame = [0; N];
+        let info = capture.take_frame(&mut frame).unwrap();
+        assert_eq!(frame[info.trigger_index - 1], 3000);
+        assert_eq!(frame[info.trigger_index], 1000);
+    }
+
+    #[test]
+    fn sine_trigger_position() {
+        let mut capture = normal_capture();
+        capture.set_pre_trigger(10);
+        let signal = |i| sine(i, 50, 1000.0);
+        let ready = capture_until_ready(&mut capture, signal, 0, 1000).unwrap();
+        let mut frame = [0; N];
+        let info = capture.take_frame(&mut frame).unwrap();
+        assert_eq!(info.trigger_index, 10);
+        // トリガ位置でレベルを下から上へ越える
+        assert!(
+            frame[9] < LEVEL && frame[10] >= LEVEL,
+            "{:?}",
+            &frame[8..12]
+        );
+        // トリガ位置の後に N - pre_trigger - 1 サンプルある
+        let trigger = ready - (N - 10 - 1);
+        assert_eq!(trigger % 50, 0);
+        assert_eq!(frame[N - 1], signal(ready));
+    }
+
+    #[test]
+    fn hysteresis_suppresses_noise_retriggers() {
+        // ゆっくりした正弦波に ±20 の雑音を乗せる
+        let noisy = |i: usize| {
+            let noise = [0i32, 20, -20, 10, -10, 15, -5][i % 7];
+            (sine(i, 400, 1000.0) as i32 + noise) as u16
+        };
+        // 正弦波の立ち上がりの半周期 (位相 -90°〜+90°) か
+        let rising = |i: usize| {
+            let phase = (i % 400) as f32 / 400.0;
+            !(0.25..0.75).contains(&phase)
+        };
+        let triggers = |hysteresis: u16| {
+            let mut capture = normal_capture();
+            capture.set_hysteresis(hysteresis);
+            let mut frame = [0; N];
+            let mut triggers = Vec::new();
+            for i in 0..20_000 {
+                if capture.push(noisy(i)) {
+                    triggers.push(i - (N - N / 2 - 1));
+                    capture.take_frame(&mut frame);
+                }
+            }
+            triggers
+        };
+        let with_hysteresis = triggers(64);
+        assert!(!with_hysteresis.is_empty());
+        assert!(with_hysteresis.iter().all(|&i| rising(i)));
+        // ヒステリシスがないと、立ち下がりの途中の雑音でもトリガする
+        let without = triggers(0);
+        assert!(without.iter().any(|&i| !rising(i)), "{:?}", without);
+    }
+
+    #[test]
+    fn holdoff_skips_samples() {
+        let mut capture = normal_capture();
+        capture.set_holdoff(100);
+        let ready = capture_until_ready(&mut capture, |i| square(i, 20), 0, 1000).unwrap();
+        let mut frame = [0; N];
+        capture.take_frame(&mut frame).unwrap();
+        assert_eq!(capture.state(), CaptureState::Holdoff);
+        for i in 1..100 {
+            capture.push(square(ready + i, 20));
+            assert_eq!(capture.state(), CaptureState::Holdoff, "{}", i);
+        }
+        capture.push(square(ready + 100, 20));
+        assert_eq!(capture.state(), CaptureState::Filling);
+    }
+
+    #[test]
+    fn auto_mode_forces_capture() {
+        let mut capture: Capture<N> = Capture::new(LEVEL);
+        capture.set_auto_timeout(50);
+        // トリガがかからない直流
+        let ready = capture_until_ready(&mut capture, |_| 1500, 0, 1000).unwrap();
+        // プリトリガ + タイムアウト + トリガの後の分
+        assert_eq!(ready + 1, N / 2 + 50 + (N - N / 2 - 1));
+        let mut frame = [0; N];
+        let info = capture.take_frame(&mut frame).unwrap();
+        assert!(info.forced);
+        assert!(frame.iter().all(|&sample| sample == 1500));
+
+        // ノーマルモードでは取り込まない
+        let mut capture = normal_capture();
+        assert_eq!(capture_until_ready(&mut capture, |_| 1500, 0, 1000), None);
+        assert_eq!(capture.state(), CaptureState::Armed);
+    }
+
+    #[test]
+    fn single_mode_stops() {
+        let mut capture: Capture<N> = Capture::new(LEVEL);
+        capture.set_mode(TriggerMode::Single);
+        let ready = capture_until_ready(&mut capture, |i| square(i, 20), 0, 1000).unwrap();
+        assert_eq!(capture.state(), CaptureState::Ready);
+        // 取り出すまでは上書きしない
+        assert!(!capture.push(0));
+        let mut frame = [0; N];
+        assert!(capture.take_frame(&mut frame).is_some());
+        assert_eq!(capture.state(), CaptureState::Stopped);
+        assert_eq!(
+            capture_until_ready(&mut capture, |i| square(i, 20), ready + 1, 1000),
+            None
+        );
+        assert_eq!(capture.take_frame(&mut frame), None);
+        capture.arm();
+        assert_eq!(capture.state(), CaptureState::Filling);
+        assert!(capture_until_ready(&mut capture, |i| square(i, 20), 0, 1000).is_some());
+    }
+
+    #[test]
+    fn decimation() {
+        let mut capture = normal_capture();
+        capture.set_decimation(2);
+        // 2個に1個を取り込むので、周期40の矩形波が周期20で記録される
+        capture_until_ready(&mut capture, |i| square(i, 40), 0, 2000).unwrap();
+        let mut frame = [0; N];
+        let info = capture.take_frame(&mut frame).unwrap();
+        let t = info.trigger_index;
+        assert_eq!(&frame[t - 10..t], &[1000; 10]);
+        assert_eq!(&frame[t..t + 10], &[3000; 10]);
+    }
+
+    #[test]
+    fn take_frame_before_ready() {
+        let mut capture: Capture<N> = Capture::new(LEVEL);
+        let mut frame = [0; N];
+        assert_eq!(capture.take_frame(&mut frame), None);
+        capture.stop();
+        assert!(!capture.push(3000));
+    }
+}
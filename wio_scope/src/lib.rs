@@ -0,0 +1,14 @@
+#![cfg_attr(not(test), no_std)]
+
+// ADCのサンプルを波形として表示するオシロスコープです
+//
+// capture: トリガを待って1画面分のサンプルを取り込む (割り込みハンドラで1サンプルずつ渡す)
+// render:  取り込んだ波形を目盛りつきで描画する
+//
+// ハードウェアに依存しないので、シミュレータでも同じように動きます
+
+pub mod capture;
+pub mod render;
+
+pub use capture::{Capture, CaptureState, Edge, FrameInfo, TriggerMode};
+pub use render::{Screen, VerticalScale};
@@ -0,0 +1,344 @@
+// 波形の描画
+//
+// 目盛り (横10 x 縦8 マス) の上に、取り込んだサンプルを折れ線で描きます
+// 列ごとに背景・目盛り・トリガの印・波形を決めて1回ずつ書くので、描き直してもちらつきません
+// 1列に複数のサンプルが入るときは、その最小値から最大値までを縦線で描きます
+//
+//   let screen = Screen::new(Point::new(10, 20), Size::new(30, 25));
+//   let scale = VerticalScale::new(500);
+//   screen.draw(&mut display, &frame, &scale, Some(trigger_index), Some(level))?;
+
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+
+const BG_COLOR: Rgb565 = Rgb565::BLACK;
+const GRID_COLOR: Rgb565 = Rgb565::new(10, 20, 10);
+const BORDER_COLOR: Rgb565 = Rgb565::new(16, 32, 16);
+const TRACE_COLOR: Rgb565 = Rgb565::YELLOW;
+const TRIGGER_COLOR: Rgb565 = Rgb565::new(31, 32, 0);
+
+// 縦軸 (電圧) の目盛り
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VerticalScale {
+    pub millivolts_per_div: u32, // 1マスあたりの電圧 [mV]
+    pub center_mv: i32,          // 画面の中央の電圧 [mV]
+    pub reference_mv: u32,       // ADCの基準電圧 [mV]
+    pub full_scale: u32,         // ADCの値の範囲 (12bitなら4096)
+}
+
+impl VerticalScale {
+    // 切り替えられる1マスあたりの電圧 [mV]
+    pub const STEPS_MV: [u32; 6] = [50, 100, 200, 500, 1000, 2000];
+
+    // 3.3[V]、12bitのADCで、中央を1.65[V]にした目盛り
+    pub fn new(millivolts_per_div: u32) -> Self {
+        VerticalScale {
+            millivolts_per_div: millivolts_per_div.max(1),
+            center_mv: 1650,
+            reference_mv: 3300,
+            full_scale: 4096,
+        }
+    }
+
+    // ADCの値を電圧 [mV] に変換します
+    pub fn millivolts(&self, sample: u16) -> i32 {
+        (sample as u32 * self.reference_mv / self.full_scale) as i32
+    }
+
+    // 電圧 [mV] をADCの値に変換します
+    pub fn sample(&self, millivolts: i32) -> u16 {
+        let millivolts = millivolts.max(0).min(self.reference_mv as i32) as u32;
+        (millivolts * self.full_scale / self.reference_mv).min(self.full_scale - 1) as u16
+    }
+
+    // STEPS_MV の中で1つ大きい (step > 0) または小さい (step < 0) 目盛りにします
+    pub fn step(&mut self, step: i32) {
+        let steps = &Self::STEPS_MV;
+        let current = steps
+            .iter()
+            .position(|&mv| mv >= self.millivolts_per_div)
+            .unwrap_or(steps.len() - 1) as i32;
+        let next = (current + step).max(0).min(steps.len() as i32 - 1);
+        self.millivolts_per_div = steps[next as usize];
+    }
+}
+
+pub struct Screen {
+    top_left: Point,
+    division: Size, // 1マスの大きさ [px]
+}
+
+impl Screen {
+    pub const DIVISIONS_X: u32 = 10;
+    pub const DIVISIONS_Y: u32 = 8;
+
+    pub fn new(top_left: Point, division: Size) -> Self {
+        Screen { top_left, division }
+    }
+
+    pub fn top_left(&self) -> Point {
+        self.top_left
+    }
+
+    // 目盛りの外枠を含む描画範囲の大きさ
+    pub fn size(&self) -> Size {
+        Size::new(
+            self.division.width * Self::DIVISIONS_X + 1,
+            self.division.height * Self::DIVISIONS_Y + 1,
+        )
+    }
+
+    // 1マスに入るサンプル数 (samples サンプルを横幅いっぱいに描くとき)
+    pub fn samples_per_division(&self, samples: usize) -> f32 {
+        samples as f32 / Self::DIVISIONS_X as f32
+    }
+
+    // サンプルを描画範囲の上端からのy座標に変換します (範囲外は端に寄せます)
+    pub fn sample_y(&self, sample: u16, scale: &VerticalScale) -> i32 {
+        let height = self.size().height as i32;
+        let offset = scale.millivolts(sample) - scale.center_mv;
+        let pixels = offset * self.division.height as i32 / scale.millivolts_per_div as i32;
+        (height / 2 - pixels).max(0).min(height - 1)
+    }
+
+    // サンプルの番号を描画範囲の左端からのx座標に変換します
+    pub fn sample_x(&self, index: usize, samples: usize) -> i32 {
+        let width = self.size().width as usize;
+        if samples <= 1 {
+            return 0;
+        }
+        (index * (width - 1) / (samples - 1)) as i32
+    }
+
+    // 目盛りと波形を描きます
+    // trigger_index があればその位置に縦の点線、trigger_level があればその高さに横の点線を描きます
+    pub fn draw<D: DrawTarget<Rgb565>>(
+        &self,
+        display: &mut D,
+        samples: &[u16],
+        scale: &VerticalScale,
+        trigger_index: Option<usize>,
+        trigger_level: Option<u16>,
+    ) -> Result<(), D::Error> {
+        let Size { width, height } = self.size();
+        let trigger_x = trigger_index.map(|index| self.sample_x(index, samples.len()));
+        let trigger_y = trigger_level.map(|level| self.sample_y(level, scale));
+        let top_left = self.top_left;
+
+        let pixels = (0..width as i32).flat_map(move |x| {
+            let trace = self.column_span(samples, scale, x);
+            (0..height as i32).map(move |y| {
+                let color = if matches!(trace, Some((top, bottom)) if (top..=bottom).contains(&y)) {
+                    TRACE_COLOR
+                } else if (trigger_y == Some(y) && x % 4 < 2) || (trigger_x == Some(x) && y % 4 < 2)
+                {
+                    TRIGGER_COLOR
+                } else {
+                    self.grid_color(x, y)
+                };
+                Pixel(top_left + Point::new(x, y), color)
+            })
+        });
+        display.draw_iter(pixels)
+    }
+
+    // 列 x に描く波形の上端と下端
+    // 列の左右の境界 (x ± 0.5) での折れ線の高さと、列に入るサンプルの高さの範囲を描く
+    fn column_span(&self, samples: &[u16], scale: &VerticalScale, x: i32) -> Option<(i32, i32)> {
+        let n = samples.len();
+        if n == 0 {
+            return None;
+        }
+        // サンプルの番号を den 分の1単位で表す
+        let den = 2 * (self.size().width as i64 - 1);
+        let end = (n as i64 - 1) * den;
+        let left = ((2 * x as i64 - 1) * (n as i64 - 1)).max(0).min(end);
+        let right = ((2 * x as i64 + 1) * (n as i64 - 1)).max(0).min(end);
+
+        let y_left = self.interpolated_y(samples, scale, left, den);
+        let y_right = self.interpolated_y(samples, scale, right, den);
+        let mut top = y_left.min(y_right);
+        let mut bottom = y_left.max(y_right);
+        let first = ((left + den - 1) / den) as usize;
+        let last = (right / den) as usize;
+        for &sample in samples.iter().take(last + 1).skip(first) {
+            let y = self.sample_y(sample, scale);
+            top = top.min(y);
+            bottom = bottom.max(y);
+        }
+        Some((top, bottom))
+    }
+
+    // 番号 position / den のところの折れ線の高さ
+    fn interpolated_y(
+        &self,
+        samples: &[u16],
+        scale: &VerticalScale,
+        position: i64,
+        den: i64,
+    ) -> i32 {
+        let index = (position / den) as usize;
+        let fraction = position % den;
+        let y0 = self.sample_y(samples[index], scale) as i64;
+        let y1 = self.sample_y(samples[(index + 1).min(samples.len() - 1)], scale) as i64;
+        (y0 + (y1 - y0) * fraction / den) as i32
+    }
+
+    fn grid_color(&self, x: i32, y: i32) -> Rgb565 {
+        let Size { width, height } = self.size();
+        let (dx, dy) = (self.division.width as i32, self.division.height as i32);
+        if x == 0 || y == 0 || x == width as i32 - 1 || y == height as i32 - 1 {
+            return BORDER_COLOR;
+        }
+        // 中央の軸には細かい目盛りをつける
+        let (cx, cy) = (width as i32 / 2, height as i32 / 2);
+        if (x == cx && y % (dy / 5).max(1) == 0) || (y == cy && x % (dx / 5).max(1) == 0) {
+            return BORDER_COLOR;
+        }
+        if (x % dx == 0 && y % 5 == 0) || (y % dy == 0 && x % 5 == 0) {
+            return GRID_COLOR;
+        }
+        BG_COLOR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    // 30 x 25 [px] のマスを 10 x 8 並べた、301 x 201 [px] の画面
+    fn screen() -> Screen {
+        Screen::new(Point::new(5, 7), Size::new(30, 25))
+    }
+
+    #[test]
+    fn voltage_conversion() {
+        let scale = VerticalScale::new(500);
+        assert_eq!(scale.millivolts(0), 0);
+        assert_eq!(scale.millivolts(2048), 1650);
+        assert_eq!(scale.millivolts(4095), 3299);
+        assert_eq!(scale.sample(1650), 2048);
+        assert_eq!(scale.sample(-100), 0);
+        assert_eq!(scale.sample(5000), 4095);
+    }
+
+    #[test]
+    fn scale_steps() {
+        let mut scale = VerticalScale::new(500);
+        scale.step(1);
+        assert_eq!(scale.millivolts_per_div, 1000);
+        scale.step(10);
+        assert_eq!(scale.millivolts_per_div, 2000);
+        scale.step(-10);
+        assert_eq!(scale.millivolts_per_div, 50);
+    }
+
+    #[test]
+    fn sample_y_mapping() {
+        let screen = screen();
+        assert_eq!(screen.size(), Size::new(301, 201));
+        let scale = VerticalScale::new(500);
+        // 中央の電圧が中央の行、1マス (500[mV]) で25[px]
+        // 3072 は 2475[mV]、1024 は 825[mV] で、中央から ±825[mV] = ±41.25[px]
+        assert_eq!(screen.sample_y(2048, &scale), 100);
+        assert_eq!(screen.sample_y(3072, &scale), 59);
+        assert_eq!(screen.sample_y(1024, &scale), 141);
+        assert_eq!(screen.sample_y(3072, &VerticalScale::new(1000)), 80);
+        assert_eq!(screen.sample_y(0, &scale), 182);
+        // 範囲外は端に寄せる
+        let scale = VerticalScale::new(200);
+        assert_eq!(screen.sample_y(4095, &scale), 0);
+        assert_eq!(screen.sample_y(0, &scale), 200);
+    }
+
+    #[test]
+    fn sample_x_mapping() {
+        let screen = screen();
+        assert_eq!(screen.sample_x(0, 320), 0);
+        assert_eq!(screen.sample_x(319, 320), 300);
+        assert_eq!(screen.sample_x(160, 321), 150);
+        assert_eq!(screen.sample_x(0, 1), 0);
+        assert_eq!(screen.samples_per_division(320), 32.0);
+    }
+
+    #[test]
+    fn column_span_one_sample_per_column() {
+        let screen = screen();
+        let scale = VerticalScale::new(500);
+        let samples = [2048; 301];
+        for x in 0..301 {
+            assert_eq!(screen.column_span(&samples, &scale, x), Some((100, 100)));
+        }
+    }
+
+    #[test]
+    fn column_span_covers_samples_in_column() {
+        // 1列に10サンプル入るとき、列の中の最小から最大までを描く
+        let screen = screen();
+        let scale = VerticalScale::new(500);
+        let samples: Vec<u16> = (0..3001)
+            .map(|i| if i % 10 == 5 { 3072 } else { 2048 })
+            .collect();
+        assert_eq!(screen.column_span(&samples, &scale, 150), Some((59, 100)));
+        assert_eq!(screen.column_span(&[], &scale, 0), None);
+    }
+
+    #[test]
+    fn column_span_interpolates_between_samples() {
+        // 2サンプルを横幅いっぱいに描くと、斜めの直線になる
+        let screen = screen();
+        let scale = VerticalScale::new(500);
+        let samples = [3072, 1024];
+        assert_eq!(screen.column_span(&samples, &scale, 0), Some((59, 59)));
+        let (top, bottom) = screen.column_span(&samples, &scale, 150).unwrap();
+        assert!(top <= 100 && 100 <= bottom && bottom - top <= 1);
+        // 右端の列は左の境界 (x - 0.5) までの線分も含む
+        assert_eq!(screen.column_span(&samples, &scale, 300), Some((140, 141)));
+    }
+
+    // 描画されたピクセルを記録する
+    struct Recorder {
+        pixels: Vec<Pixel<Rgb565>>,
+    }
+
+    impl DrawTarget<Rgb565> for Recorder {
+        type Error = core::convert::Infallible;
+
+        fn draw_pixel(&mut self, pixel: Pixel<Rgb565>) -> Result<(), Self::Error> {
+            self.pixels.push(pixel);
+            Ok(())
+        }
+
+        fn size(&self) -> Size {
+            Size::new(320, 240)
+        }
+    }
+
+    #[test]
+    fn draw_trace_and_trigger_marks() {
+        let screen = screen();
+        let scale = VerticalScale::new(500);
+        let samples = [2048; 301];
+        let mut recorder = Recorder { pixels: Vec::new() };
+        screen
+            .draw(&mut recorder, &samples, &scale, Some(150), Some(3000))
+            .unwrap();
+        assert_eq!(recorder.pixels.len(), 301 * 201);
+        let color = |x: i32, y: i32| {
+            recorder
+                .pixels
+                .iter()
+                .find(|Pixel(point, _)| *point == Point::new(5 + x, 7 + y))
+                .map(|Pixel(_, color)| *color)
+                .unwrap()
+        };
+        assert_eq!(color(10, 100), TRACE_COLOR);
+        assert_eq!(color(290, 100), TRACE_COLOR);
+        assert_eq!(color(13, 13), BG_COLOR);
+        assert_eq!(color(0, 50), BORDER_COLOR);
+        // トリガの位置とレベルの点線
+        let level_y = screen.sample_y(3000, &scale);
+        assert_eq!(color(4, level_y), TRIGGER_COLOR);
+        assert_eq!(color(150, 4), TRIGGER_COLOR);
+    }
+}
@@ -0,0 +1,52 @@
+// 音程がゆっくりずれていく音をチューナーに通して、シミュレータで表示するサンプルです
+// `cargo run --example sim --features sim`
+
+use embedded_graphics::{egrectangle, pixelcolor::Rgb565, prelude::*, primitive_style};
+use embedded_graphics_simulator::*;
+use std::{thread, time};
+use wio_tuner::{nearest_note, Gauge, Yin};
+
+const SAMPLE_RATE: f32 = 20833.0;
+const SAMPLES: usize = 1024;
+
+fn main() {
+    let mut display: SimulatorDisplay<Rgb565> = SimulatorDisplay::new(Size::new(320, 240));
+    let output_settings = OutputSettingsBuilder::new().build();
+    let mut window = Window::new("Tuner", &output_settings);
+    egrectangle!(
+        top_left = (0, 0),
+        bottom_right = (319, 239),
+        style = primitive_style!(fill_color = Rgb565::BLACK)
+    )
+    .draw(&mut display)
+    .unwrap();
+
+    let mut yin: Yin<512> = Yin::new(SAMPLE_RATE, 60.0, 1500.0);
+    let gauge = Gauge::new(Point::new(160, 190), 120);
+
+    let interval = time::Duration::from_millis(50);
+    let mut frame = 0u32;
+    'running: loop {
+        if window.events().any(|e| e == SimulatorEvent::Quit) {
+            break 'running;
+        }
+
+        // A2 (110[Hz]) の前後 ±40 セントを行き来する、倍音を含んだ音
+        let cents = 40.0 * (frame as f32 * 0.05).sin();
+        let frequency = 110.0 * 2f32.powf(cents / 1200.0);
+        let mut samples = [0.0f32; SAMPLES];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let phase = 2.0 * std::f32::consts::PI * frequency * i as f32 / SAMPLE_RATE;
+            *sample = phase.sin() + 0.6 * (2.0 * phase).sin() + 0.3 * (3.0 * phase).sin();
+        }
+
+        let pitch = yin.detect(&samples);
+        let reading = pitch.and_then(|pitch| nearest_note(pitch.frequency, 440.0));
+        let detected = pitch.map(|pitch| pitch.frequency).unwrap_or(0.0);
+        gauge.draw(&mut display, reading.as_ref(), detected).unwrap();
+        window.update(&display);
+
+        frame += 1;
+        thread::sleep(interval);
+    }
+}
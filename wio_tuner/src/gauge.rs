@@ -0,0 +1,141 @@
+// チューナーのメーター
+// 音名を大きく表示し、その下に -50〜+50 セントの目盛りと針を描きます
+
+use core::fmt::Write;
+use embedded_graphics::{
+    egcircle, egline, egrectangle, egtext,
+    fonts::{Font24x32, Font8x16},
+    pixelcolor::Rgb565,
+    prelude::*,
+    primitive_style, text_style,
+};
+use heapless::consts::*;
+use heapless::String;
+use micromath::F32Ext;
+
+use crate::note::NoteReading;
+
+const BG_COLOR: Rgb565 = Rgb565::BLACK;
+const SCALE_COLOR: Rgb565 = Rgb565::WHITE;
+const IN_TUNE_COLOR: Rgb565 = Rgb565::GREEN;
+const OUT_OF_TUNE_COLOR: Rgb565 = Rgb565::RED;
+const INACTIVE_COLOR: Rgb565 = Rgb565::new(8, 16, 8);
+// 針が真上から振れる最大の角度 [rad] (±50セントに対応する)
+const MAX_ANGLE: f32 = core::f32::consts::PI / 3.0;
+
+pub struct Gauge {
+    center: Point, // 針の軸の位置
+    radius: i32,
+    in_tune_cents: f32,
+}
+
+impl Gauge {
+    // 描画範囲は center から上に radius + 56 [px]、下に 24 [px] です
+    pub fn new(center: Point, radius: u32) -> Self {
+        Gauge {
+            center,
+            radius: radius as i32,
+            in_tune_cents: 5.0,
+        }
+    }
+
+    // ずれがこの範囲 [セント] なら合っているとみなして緑で表示します
+    pub fn set_in_tune_cents(&mut self, cents: f32) {
+        self.in_tune_cents = cents;
+    }
+
+    // メーターを描き直します
+    // reading が None のとき (無音など) は音名と針を消します
+    pub fn draw<D: DrawTarget<Rgb565>>(
+        &self,
+        display: &mut D,
+        reading: Option<&NoteReading>,
+        frequency: f32,
+    ) -> Result<(), D::Error> {
+        let Point { x: cx, y: cy } = self.center;
+        let r = self.radius;
+        egrectangle!(
+            top_left = (cx - r - 8, cy - r - 56),
+            bottom_right = (cx + r + 8, cy + 24),
+            style = primitive_style!(fill_color = BG_COLOR)
+        )
+        .draw(display)?;
+
+        // 10セントごとの目盛り (0は長く)
+        for cents in (-50..=50).step_by(10) {
+            let (sin, cos) = angle(cents as f32);
+            let inner = if cents == 0 { r - 16 } else { r - 8 };
+            egline!(
+                start = (
+                    cx + (sin * inner as f32) as i32,
+                    cy - (cos * inner as f32) as i32
+                ),
+                end = (cx + (sin * r as f32) as i32, cy - (cos * r as f32) as i32),
+                style = primitive_style!(stroke_color = SCALE_COLOR, stroke_width = 1)
+            )
+            .draw(display)?;
+        }
+
+        let reading = match reading {
+            Some(reading) => reading,
+            None => {
+                return egcircle!(
+                    center = (cx, cy),
+                    radius = 4,
+                    style = primitive_style!(fill_color = INACTIVE_COLOR)
+                )
+                .draw(display);
+            }
+        };
+        let color = if reading.cents.abs() <= self.in_tune_cents {
+            IN_TUNE_COLOR
+        } else {
+            OUT_OF_TUNE_COLOR
+        };
+
+        // 針
+        let (sin, cos) = angle(reading.cents);
+        egline!(
+            start = (cx, cy),
+            end = (
+                cx + (sin * (r - 4) as f32) as i32,
+                cy - (cos * (r - 4) as f32) as i32
+            ),
+            style = primitive_style!(stroke_color = color, stroke_width = 3)
+        )
+        .draw(display)?;
+        egcircle!(
+            center = (cx, cy),
+            radius = 4,
+            style = primitive_style!(fill_color = color)
+        )
+        .draw(display)?;
+
+        // 音名とオクターブ ("A4" など) を中央に
+        let mut text = String::<U16>::new();
+        write!(text, "{}{}", reading.name(), reading.octave()).ok();
+        egtext!(
+            text = text.as_str(),
+            top_left = (cx - text.len() as i32 * 12, cy - r - 48),
+            style = text_style!(font = Font24x32, text_color = color)
+        )
+        .draw(display)?;
+
+        // 周波数とずれ
+        text.clear();
+        write!(text, "{:.1}Hz {:+.0}c", frequency, reading.cents).ok();
+        egtext!(
+            text = text.as_str(),
+            top_left = (cx - text.len() as i32 * 4, cy + 8),
+            style = text_style!(font = Font8x16, text_color = SCALE_COLOR)
+        )
+        .draw(display)?;
+        Ok(())
+    }
+}
+
+// ずれ [セント] に対応する針の向き (真上からの角度の sin, cos)
+fn angle(cents: f32) -> (f32, f32) {
+    let theta = cents.clamp(-50.0, 50.0) / 50.0 * MAX_ANGLE;
+    (theta.sin(), theta.cos())
+}
@@ -0,0 +1,20 @@
+#![cfg_attr(not(test), no_std)]
+// テストやシミュレータでは std がリンクされて std の f32 のメソッドが使われるので、
+// micromath::F32Ext が未使用になる
+#![cfg_attr(any(test, feature = "sim"), allow(unused_imports))]
+
+// マイクの音から音程を求めるチューナーです
+//
+// pitch: YIN法で基本周波数を推定します
+// note:  周波数を最も近い音名とセント (半音の1/100) のずれに変換します
+// gauge: 音名とずれを針のメーターとして描画します
+//
+// ハードウェアに依存しないので、ホストPCでも動きます
+
+pub mod gauge;
+pub mod note;
+pub mod pitch;
+
+pub use gauge::Gauge;
+pub use note::{nearest_note, note_frequency, NoteReading};
+pub use pitch::{Pitch, Yin};
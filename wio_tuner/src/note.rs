@@ -0,0 +1,138 @@
+// 周波数と音名の変換
+// 音はMIDIのノート番号 (A4 = 69、半音ごとに1) で表し、平均律で計算します
+
+use micromath::F32Ext;
+
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+// 最も近い音と、そこからのずれ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteReading {
+    pub midi: i32,             // ノート番号
+    pub cents: f32,            // ずれ [セント] (-50〜+50、高いときが正)
+    pub target_frequency: f32, // 最も近い音の周波数 [Hz]
+}
+
+impl NoteReading {
+    // 音名 ("A", "C#" など)
+    pub fn name(&self) -> &'static str {
+        NOTE_NAMES[self.midi.rem_euclid(12) as usize]
+    }
+
+    // オクターブ (A4 なら4)
+    pub fn octave(&self) -> i32 {
+        self.midi.div_euclid(12) - 1
+    }
+}
+
+// ノート番号の音の周波数 [Hz]
+// a4 は基準にするA4の周波数 (普通は440[Hz])
+pub fn note_frequency(midi: i32, a4: f32) -> f32 {
+    a4 * 2.0f32.powf((midi - 69) as f32 / 12.0)
+}
+
+// frequency [Hz] に最も近い音
+pub fn nearest_note(frequency: f32, a4: f32) -> Option<NoteReading> {
+    if frequency.is_nan() || frequency <= 0.0 {
+        return None;
+    }
+    let semitones = 12.0 * (frequency / a4).ln() / core::f32::consts::LN_2;
+    let midi = 69 + semitones.round() as i32;
+    let target_frequency = note_frequency(midi, a4);
+    Some(NoteReading {
+        midi,
+        cents: 1200.0 * (frequency / target_frequency).ln() / core::f32::consts::LN_2,
+        target_frequency,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A4: f32 = 440.0;
+
+    // frequency から cents だけずれた周波数
+    fn cents_from(frequency: f32, cents: f32) -> f32 {
+        frequency * 2.0f32.powf(cents / 1200.0)
+    }
+
+    #[test]
+    fn note_frequencies() {
+        assert_eq!(note_frequency(69, A4), 440.0);
+        assert!((note_frequency(60, A4) - 261.626).abs() < 0.01);
+        assert!((note_frequency(81, A4) - 880.0).abs() < 0.01);
+        assert!((note_frequency(69, 442.0) - 442.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn exact_notes() {
+        let reading = nearest_note(440.0, A4).unwrap();
+        assert_eq!(reading.midi, 69);
+        assert_eq!(reading.name(), "A");
+        assert_eq!(reading.octave(), 4);
+        assert!(reading.cents.abs() < 0.01);
+
+        let reading = nearest_note(note_frequency(61, A4), A4).unwrap();
+        assert_eq!((reading.name(), reading.octave()), ("C#", 4));
+        // 基準の周波数を変えると、同じ周波数でもずれが変わる
+        let reading = nearest_note(440.0, 442.0).unwrap();
+        assert_eq!(reading.midi, 69);
+        assert!((reading.cents + 7.85).abs() < 0.05);
+    }
+
+    #[test]
+    fn cents() {
+        let reading = nearest_note(445.0, A4).unwrap();
+        assert_eq!(reading.midi, 69);
+        assert!((reading.cents - 19.56).abs() < 0.05);
+        assert!((reading.target_frequency - 440.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn half_semitone_boundary() {
+        // ±50 セントを境に隣の音になる
+        let reading = nearest_note(cents_from(440.0, 49.5), A4).unwrap();
+        assert_eq!(reading.midi, 69);
+        assert!((reading.cents - 49.5).abs() < 0.05);
+        let reading = nearest_note(cents_from(440.0, 50.5), A4).unwrap();
+        assert_eq!(reading.name(), "A#");
+        assert!((reading.cents + 49.5).abs() < 0.05);
+        let reading = nearest_note(cents_from(440.0, -49.5), A4).unwrap();
+        assert_eq!(reading.midi, 69);
+        assert!((reading.cents + 49.5).abs() < 0.05);
+        let reading = nearest_note(cents_from(440.0, -50.5), A4).unwrap();
+        assert_eq!(reading.name(), "G#");
+        assert!((reading.cents - 49.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn octave_boundary() {
+        // オクターブはCで変わる
+        let c4 = note_frequency(60, A4);
+        let reading = nearest_note(cents_from(c4, -40.0), A4).unwrap();
+        assert_eq!((reading.name(), reading.octave()), ("C", 4));
+        let reading = nearest_note(cents_from(c4, -60.0), A4).unwrap();
+        assert_eq!((reading.name(), reading.octave()), ("B", 3));
+    }
+
+    #[test]
+    fn negative_midi_numbers() {
+        let reading = nearest_note(note_frequency(-12, A4), A4).unwrap();
+        assert_eq!(reading.midi, -12);
+        assert_eq!((reading.name(), reading.octave()), ("C", -2));
+        let reading = nearest_note(note_frequency(-1, A4), A4).unwrap();
+        assert_eq!(reading.midi, -1);
+        assert_eq!((reading.name(), reading.octave()), ("B", -2));
+        assert!(reading.cents.abs() < 0.05);
+    }
+
+    #[test]
+    fn invalid_frequencies() {
+        assert_eq!(nearest_note(0.0, A4), None);
+        assert_eq!(nearest_note(-440.0, A4), None);
+        assert_eq!(nearest_note(f32::NAN, A4), None);
+    }
+}
@@ -0,0 +1,236 @@
+// YIN法による基本周波数の推定
+//
+// 信号とそれを τ サンプルずらした信号との差の2乗和 (差分関数) は、τ が周期の整数倍のときに小さくなります
+// 差分関数を τ までの平均で正規化し、しきい値を下回る最初の谷を周期とすることで、
+// 倍音が強い音でも1オクターブ上や下に誤検出しにくくなります
+// FFTのビンの間隔に縛られず、谷の位置を補間するのでセント単位の精度が出ます
+//
+// 参考: A. de Cheveigné, H. Kawahara, "YIN, a fundamental frequency estimator for speech and music" (2002)
+
+use micromath::F32Ext;
+
+// 推定した音程
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pitch {
+    pub frequency: f32, // 基本周波数 [Hz]
+    pub clarity: f32,   // 周期性の強さ (1に近いほど確か)
+}
+
+// MAX_LAG は調べる周期の最大値 [サンプル] で、検出できる最低周波数を決めます
+pub struct Yin<const MAX_LAG: usize> {
+    sample_rate: f32,
+    threshold: f32,
+    min_rms: f32,
+    min_lag: usize,
+    max_lag: usize,
+    difference: [f32; MAX_LAG], // 正規化した差分関数
+}
+
+impl<const MAX_LAG: usize> Yin<MAX_LAG> {
+    // min_frequency〜max_frequency [Hz] の範囲で基本周波数を探します
+    // min_frequency はサンプリングレート / MAX_LAG より低くはできません
+    pub fn new(sample_rate: f32, min_frequency: f32, max_frequency: f32) -> Self {
+        let max_lag = ((sample_rate / min_frequency) as usize + 1).min(MAX_LAG - 1);
+        let min_lag = ((sample_rate / max_frequency) as usize).max(2).min(max_lag);
+        Yin {
+            sample_rate,
+            threshold: 0.15,
+            min_rms: 0.0,
+            min_lag,
+            max_lag,
+            difference: [0.0; MAX_LAG],
+        }
+    }
+
+    // 谷とみなす正規化差分関数のしきい値 (小さいほど厳しい、既定値は0.15)
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    // 直流分を除いた実効値がこれより小さいときは無音とみなします
+    pub fn set_min_rms(&mut self, min_rms: f32) {
+        self.min_rms = min_rms;
+    }
+
+    // 必要なサンプル数 (これより長ければ、差分をとる区間が長くなり安定します)
+    pub fn min_samples(&self) -> usize {
+        self.max_lag * 2
+    }
+
+    // samples の基本周波数を推定します
+    // 無音のときや周期性が見つからないときは None を返します
+    pub fn detect(&mut self, samples: &[f32]) -> Option<Pitch> {
+        if samples.len() < self.min_samples() {
+            return None;
+        }
+        if self.min_rms > 0.0 && rms(samples) < self.min_rms {
+            return None;
+        }
+
+        // 差分関数を求め、τ までの平均で正規化する
+        let window = samples.len() - self.max_lag;
+        self.difference[0] = 1.0;
+        let mut sum = 0.0;
+        for lag in 1..=self.max_lag {
+            let mut difference = 0.0;
+            for (a, b) in samples[..window].iter().zip(samples[lag..].iter()) {
+                let delta = a - b;
+                difference += delta * delta;
+            }
+            sum += difference;
+            self.difference[lag] = if sum > 0.0 {
+                difference * lag as f32 / sum
+            } else {
+                1.0
+            };
+        }
+
+        // しきい値を下回る最初の谷
+        let mut lag = self.min_lag;
+        while lag <= self.max_lag && self.difference[lag] >= self.threshold {
+            lag += 1;
+        }
+        if lag > self.max_lag {
+            return None;
+        }
+        while lag < self.max_lag && self.difference[lag + 1] < self.difference[lag] {
+            lag += 1;
+        }
+
+        // 両隣との放物線補間で、谷の位置をサンプルの間まで求める
+        let mut period = lag as f32;
+        if lag > 1 && lag < self.max_lag {
+            let left = self.difference[lag - 1];
+            let center = self.difference[lag];
+            let right = self.difference[lag + 1];
+            let denominator = left - 2.0 * center + right;
+            if denominator > 0.0 {
+                period += 0.5 * (left - right) / denominator;
+            }
+        }
+        Some(Pitch {
+            frequency: self.sample_rate / period,
+            clarity: 1.0 - self.difference[lag],
+        })
+    }
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    let n = samples.len() as f32;
+    let mean = samples.iter().sum::<f32>() / n;
+    let power = samples
+        .iter()
+        .map(|sample| (sample - mean) * (sample - mean))
+        .sum::<f32>()
+        / n;
+    power.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::PI;
+    use std::vec::Vec;
+
+    // wio-examples の 8-3-tuner と同じ、83.3[kHz] を1/4に間引いたサンプリングレート
+    const SAMPLE_RATE: f32 = 83333.0 / 4.0;
+    const BLOCK_SIZE: usize = 1024;
+
+    fn yin() -> Yin<{ BLOCK_SIZE / 2 }> {
+        Yin::new(SAMPLE_RATE, 60.0, 1500.0)
+    }
+
+    // 倍音ごとの振幅を与えて、ADCの値のように直流分を乗せた音を作る
+    fn tone(frequency: f32, harmonics: &[f32]) -> Vec<f32> {
+        (0..BLOCK_SIZE)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE;
+                let sum: f32 = harmonics
+                    .iter()
+                    .enumerate()
+                    .map(|(n, amplitude)| {
+                        amplitude * (2.0 * PI * frequency * (n + 1) as f32 * t + n as f32).sin()
+                    })
+                    .sum();
+                2048.0 + 500.0 * sum
+            })
+            .collect()
+    }
+
+    fn cents(detected: f32, expected: f32) -> f32 {
+        1200.0 * (detected / expected).log2()
+    }
+
+    fn assert_detects(samples: &[f32], expected: f32) {
+        let pitch = yin().detect(samples).unwrap();
+        let error = cents(pitch.frequency, expected);
+        assert!(
+            error.abs() < 3.0,
+            "{} Hz: {:?} ({} cents)",
+            expected,
+            pitch,
+            error
+        );
+        assert!(pitch.clarity > 0.85, "{:?}", pitch);
+    }
+
+    // E2 (ギターの6弦) から C6 付近まで
+    const FREQUENCIES: [f32; 6] = [82.41, 110.0, 196.0, 440.0, 659.26, 1046.5];
+
+    #[test]
+    fn pure_sines() {
+        for &frequency in FREQUENCIES.iter() {
+            assert_detects(&tone(frequency, &[1.0]), frequency);
+        }
+    }
+
+    #[test]
+    fn strong_second_harmonic() {
+        // 基音より2倍音が強くても1オクターブ上と誤らない
+        for &frequency in FREQUENCIES.iter() {
+            assert_detects(&tone(frequency, &[0.4, 1.0, 0.5, 0.3]), frequency);
+        }
+    }
+
+    #[test]
+    fn harmonic_rich_tone() {
+        // のこぎり波に近い音
+        let harmonics: Vec<f32> = (1..=10).map(|n| 1.0 / n as f32).collect();
+        for &frequency in FREQUENCIES.iter() {
+            assert_detects(&tone(frequency, &harmonics), frequency);
+        }
+    }
+
+    #[test]
+    fn detuned_between_samples() {
+        // 周期がサンプルの整数倍でなくても、補間でセント単位まで求まる
+        for &offset in [-23.0, 7.0, 41.0].iter() {
+            let frequency = 440.0 * 2.0f32.powf(offset / 1200.0);
+            assert_detects(&tone(frequency, &[1.0, 0.5]), frequency);
+        }
+    }
+
+    #[test]
+    fn silence_and_short_input() {
+        let mut yin = yin();
+        yin.set_min_rms(8.0);
+        assert_eq!(yin.detect(&[2048.0; BLOCK_SIZE]), None);
+        let quiet: Vec<f32> = tone(440.0, &[0.01]);
+        assert_eq!(yin.detect(&quiet), None);
+        let short = tone(440.0, &[1.0]);
+        assert_eq!(yin.detect(&short[..yin.min_samples() - 1]), None);
+    }
+
+    #[test]
+    fn noise_has_no_pitch() {
+        // 線形合同法で作った白色雑音
+        let mut state: u32 = 12345;
+        let noise: Vec<f32> = (0..BLOCK_SIZE)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                2048.0 + ((state >> 16) % 1000) as f32 - 500.0
+            })
+            .collect();
+        assert_eq!(yin().detect(&noise), None);
+    }
+}
@@ -0,0 +1,434 @@
+// ADCなどのサンプルをブロック単位で集めて、割り込みハンドラから main() へ渡すモジュールです
+//
+// BUFFERS 面のバッファを順番に使い、割り込みハンドラ (Writer) が BLOCK 個ずつサンプルを書き込み、
+// main() (Reader) が書き終わったブロックを読み出します (BUFFERS = 2 ならピンポンバッファ)
+// どちらの側も割り込みを禁止せずに使えます
+//
+// main() の処理が間に合わず空きバッファがないときは、ブロック1つ分のサンプルを捨てて
+// オーバーラン回数を数えます
+// ブロックには通し番号と先頭サンプルの時刻が付くので、読み出し側で欠落を検出できます
+//
+// 時刻の型 S は呼び出し側で決めます (timebase::Instant や [ms] の u32 など)
+//
+//   static MIC: Acquisition<u16, Instant, 256, 2> = Acquisition::new(0, Instant::from_ticks(0));
+//   let (mut writer, mut reader) = MIC.split().unwrap();
+//   // 割り込みハンドラ
+//   writer.push(sample, timebase.now());
+//   // main()
+//   if let Some(block) = reader.read() { process(block.samples()); }
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+#[derive(Clone, Copy)]
+struct Block<T, S, const BLOCK: usize> {
+    samples: [T; BLOCK],
+    sequence: u32,
+    timestamp: S,
+}
+
+pub struct Acquisition<T, S, const BLOCK: usize, const BUFFERS: usize> {
+    blocks: UnsafeCell<[Block<T, S, BLOCK>; BUFFERS]>,
+    // 書き終わったブロックの数と読み終わったブロックの数 (0..2*BUFFERS で一周する)
+    written: AtomicUsize,
+    read: AtomicUsize,
+    overruns: AtomicU32,
+    split: AtomicBool,
+}
+
+// Writer と Reader は同じブロックに同時に触れないので、別々のコンテキストから使える
+unsafe impl<T: Send, S: Send, const BLOCK: usize, const BUFFERS: usize> Sync
+    for Acquisition<T, S, BLOCK, BUFFERS>
+{
+}
+
+impl<T: Copy, S: Copy, const BLOCK: usize, const BUFFERS: usize> Acquisition<T, S, BLOCK, BUFFERS> {
+    // バッファを initial で、ブロックの時刻を timestamp で埋めて作ります
+    pub const fn new(initial: T, timestamp: S) -> Self {
+        let block = Block {
+            samples: [initial; BLOCK],
+            sequence: 0,
+            timestamp,
+        };
+        Acquisition {
+            blocks: UnsafeCell::new([block; BUFFERS]),
+            written: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            overruns: AtomicU32::new(0),
+            split: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<T, S, const BLOCK: usize, const BUFFERS: usize> Acquisition<T, S, BLOCK, BUFFERS> {
+    // 書き込み側と読み出し側に分けます
+    // 2回目以降の呼び出しは None を返します
+    pub fn split(
+        &self,
+    ) -> Option<(
+        Writer<'_, T, S, BLOCK, BUFFERS>,
+        Reader<'_, T, S, BLOCK, BUFFERS>,
+    )> {
+        if self.split.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        let writer = Writer {
+            acquisition: self,
+            position: 0,
+            skipping: false,
+            sequence: 0,
+        };
+        Some((writer, Reader { acquisition: self }))
+    }
+
+    pub const fn block_size(&self) -> usize {
+        BLOCK
+    }
+
+    // 空きバッファがなくて捨てたブロックの数
+    pub fn overruns(&self) -> u32 {
+        self.overruns.load(Ordering::Relaxed)
+    }
+
+    // 読み出されるのを待っているブロックの数
+    pub fn pending(&self) -> usize {
+        distance(
+            self.read.load(Ordering::Acquire),
+            self.written.load(Ordering::Acquire),
+            BUFFERS,
+        )
+    }
+
+    fn block(&self, position: usize) -> *mut Block<T, S, BLOCK> {
+        unsafe { (*self.blocks.get()).as_mut_ptr().add(position % BUFFERS) }
+    }
+}
+
+fn advance(position: usize, buffers: usize) -> usize {
+    if position + 1 == 2 * buffers {
+        0
+    } else {
+        position + 1
+    }
+}
+
+fn distance(from: usize, to: usize, buffers: usize) -> usize {
+    if to >= from {
+        to - from
+    } else {
+        to + 2 * buffers - from
+    }
+}
+
+// 書き込み側 (割り込みハンドラで使う)
+pub struct Writer<'a, T, S, const BLOCK: usize, const BUFFERS: usize> {
+    acquisition: &'a Acquisition<T, S, BLOCK, BUFFERS>,
+    position: usize, // 今のブロックに書き込んだサンプルの数
+    skipping: bool,  // 今のブロックを捨てているか
+    sequence: u32,   // 今のブロックの通し番号
+}
+
+unsafe impl<'a, T: Send, S: Send, const BLOCK: usize, const BUFFERS: usize> Send
+    for Writer<'a, T, S, BLOCK, BUFFERS>
+{
+}
+
+impl<'a, T, S, const BLOCK: usize, const BUFFERS: usize> Writer<'a, T, S, BLOCK, BUFFERS> {
+    // サンプルを1つ書き込みます
+    // now はブロックの先頭のサンプルのときだけ使われます
+    // ブロックを書き終えたときに true を返します
+    pub fn push(&mut self, sample: T, now: S) -> bool {
+        let acquisition = self.acquisition;
+        let written = acquisition.written.load(Ordering::Relaxed);
+
+        if self.position == 0 {
+            // 新しいブロックを始める前に空きバッファがあるか調べる
+            let read = acquisition.read.load(Ordering::Acquire);
+            self.skipping = distance(read, written, BUFFERS) >= BUFFERS;
+            if self.skipping {
+                acquisition.overruns.fetch_add(1, Ordering::Relaxed);
+            } else {
+                let block = unsafe { &mut *acquisition.block(written) };
+                block.sequence = self.sequence;
+                block.timestamp = now;
+            }
+        }
+
+        if !self.skipping {
+            let block = unsafe { &mut *acquisition.block(written) };
+            block.samples[self.position] = sample;
+        }
+        self.position += 1;
+        if self.position < BLOCK {
+            return false;
+        }
+
+        self.position = 0;
+        self.sequence = self.sequence.wrapping_add(1);
+        if self.skipping {
+            return false;
+        }
+        // ブロックを書き終えてから、読み出し側に見えるようにする
+        acquisition
+            .written
+            .store(advance(written, BUFFERS), Ordering::Release);
+        true
+    }
+}
+
+// 読み出し側 (main() で使う)
+pub struct Reader<'a, T, S, const BLOCK: usize, const BUFFERS: usize> {
+    acquisition: &'a Acquisition<T, S, BLOCK, BUFFERS>,
+}
+
+unsafe impl<'a, T: Send, S: Send, const BLOCK: usize, const BUFFERS: usize> Send
+    for Reader<'a, T, S, BLOCK, BUFFERS>
+{
+}
+
+impl<'a, T, S, const BLOCK: usize, const BUFFERS: usize> Reader<'a, T, S, BLOCK, BUFFERS> {
+    // 書き終わったブロックのうち最も古いものを返します
+    // 返した ReadBlock を破棄するまで、そのバッファには書き込まれません
+    pub fn read(&mut self) -> Option<ReadBlock<'_, T, S, BLOCK, BUFFERS>> {
+        let acquisition = self.acquisition;
+        let read = acquisition.read.load(Ordering::Relaxed);
+        let written = acquisition.written.load(Ordering::Acquire);
+        if read == written {
+            return None;
+        }
+        Some(ReadBlock {
+            acquisition,
+            block: unsafe { &mut *acquisition.block(read) },
+            position: read,
+        })
+    }
+
+    // 書き終わったブロックをすべて捨てます
+    pub fn discard_all(&mut self) {
+        let written = self.acquisition.written.load(Ordering::Acquire);
+        self.acquisition.read.store(written, Ordering::Release);
+    }
+
+    pub fn overruns(&self) -> u32 {
+        self.acquisition.overruns()
+    }
+}
+
+// 読み出し中のブロック
+// サンプルの配列として読み書きでき (その場でFFTするときなど)、破棄するとバッファを書き込み側へ返します
+pub struct ReadBlock<'r, T, S, const BLOCK: usize, const BUFFERS: usize> {
+    acquisition: &'r Acquisition<T, S, BLOCK, BUFFERS>,
+    block: &'r mut Block<T, S, BLOCK>,
+    position: usize,
+}
+
+impl<'r, T, S, const BLOCK: usize, const BUFFERS: usize> ReadBlock<'r, T, S, BLOCK, BUFFERS> {
+    // ブロックの通し番号 (前回読んだブロックから飛んでいれば、その間のブロックは捨てられている)
+    pub fn sequence(&self) -> u32 {
+        self.block.sequence
+    }
+
+    // 先頭のサンプルを書き込んだ時刻
+    pub fn timestamp(&self) -> S
+    where
+        S: Copy,
+    {
+        self.block.timestamp
+    }
+
+    pub fn samples(&self) -> &[T; BLOCK] {
+        &self.block.samples
+    }
+
+    pub fn samples_mut(&mut self) -> &mut [T; BLOCK] {
+        &mut self.block.samples
+    }
+}
+
+impl<'r, T, S, const BLOCK: usize, const BUFFERS: usize> Deref
+    for ReadBlock<'r, T, S, BLOCK, BUFFERS>
+{
+    type Target = [T; BLOCK];
+
+    fn deref(&self) -> &[T; BLOCK] {
+        &self.block.samples
+    }
+}
+
+impl<'r, T, S, const BLOCK: usize, const BUFFERS: usize> DerefMut
+    for ReadBlock<'r, T, S, BLOCK, BUFFERS>
+{
+    fn deref_mut(&mut self) -> &mut [T; BLOCK] {
+        &mut self.block.samples
+    }
+}
+
+impl<'r, T, S, const BLOCK: usize, const BUFFERS: usize> Drop
+    for ReadBlock<'r, T, S, BLOCK, BUFFERS>
+{
+    fn drop(&mut self) {
+        // 読み終えてから、書き込み側が上書きできるようにする
+        self.acquisition
+            .read
+            .store(advance(self.position, BUFFERS), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicBool;
+    use std::thread;
+    use std::vec::Vec;
+
+    // ブロックを1つ書き込む (値は first から1ずつ増える)
+    fn push_block<const BLOCK: usize, const BUFFERS: usize>(
+        writer: &mut Writer<'_, u32, u32, BLOCK, BUFFERS>,
+        first: u32,
+        now: u32,
+    ) -> bool {
+        let mut done = false;
+        for i in 0..BLOCK as u32 {
+            done = writer.push(first + i, now);
+        }
+        done
+    }
+
+    #[test]
+    fn block_carries_samples_sequence_and_timestamp() {
+        let acquisition: Acquisition<u32, u32, 4, 2> = Acquisition::new(0, 0);
+        let (mut writer, mut reader) = acquisition.split().unwrap();
+        assert!(acquisition.split().is_none());
+        assert!(reader.read().is_none());
+
+        // ブロックの途中では読み出せない
+        assert!(!writer.push(10, 100));
+        assert!(!writer.push(11, 101));
+        assert!(reader.read().is_none());
+        assert!(!writer.push(12, 102));
+        assert!(writer.push(13, 103));
+
+        let block = reader.read().unwrap();
+        assert_eq!(block.sequence(), 0);
+        assert_eq!(block.timestamp(), 100);
+        assert_eq!(*block, [10, 11, 12, 13]);
+        drop(block);
+        assert_eq!(acquisition.pending(), 0);
+        assert!(reader.read().is_none());
+    }
+
+    #[test]
+    fn overruns_drop_whole_blocks_and_leave_sequence_gaps() {
+        let acquisition: Acquisition<u32, u32, 4, 2> = Acquisition::new(0, 0);
+        let (mut writer, mut reader) = acquisition.split().unwrap();
+        assert!(push_block(&mut writer, 0, 0));
+        assert!(push_block(&mut writer, 4, 1));
+        assert_eq!(acquisition.pending(), 2);
+        // 空きバッファがないので、3つ目と4つ目のブロックは捨てられる
+        assert!(!push_block(&mut writer, 8, 2));
+        assert!(!push_block(&mut writer, 12, 3));
+        assert_eq!(reader.overruns(), 2);
+        assert_eq!(acquisition.pending(), 2);
+
+        assert_eq!(reader.read().unwrap().sequence(), 0);
+        assert!(push_block(&mut writer, 16, 4));
+        let block = reader.read().unwrap();
+        assert_eq!((block.sequence(), block.timestamp()), (1, 1));
+        assert_eq!(*block, [4, 5, 6, 7]);
+        drop(block);
+        // 捨てられた2つのブロックの分だけ通し番号が飛ぶ
+        let block = reader.read().unwrap();
+        assert_eq!((block.sequence(), block.timestamp()), (4, 4));
+        assert_eq!(*block, [16, 17, 18, 19]);
+    }
+
+    #[test]
+    fn reading_block_is_not_overwritten() {
+        let acquisition: Acquisition<u32, u32, 2, 2> = Acquisition::new(0, 0);
+        let (mut writer, mut reader) = acquisition.split().unwrap();
+        push_block(&mut writer, 0, 0);
+        let mut block = reader.read().unwrap();
+        // 読み出し中でも、もう1つのバッファには書き込める
+        assert!(push_block(&mut writer, 2, 1));
+        assert!(!push_block(&mut writer, 4, 2));
+        block[0] = 100;
+        assert_eq!(*block, [100, 1]);
+        drop(block);
+        assert_eq!(acquisition.overruns(), 1);
+        assert_eq!(*reader.read().unwrap(), [2, 3]);
+    }
+
+    #[test]
+    fn positions_wrap_around() {
+        // 位置は 0..2*BUFFERS で一周するので、書き込むブロックの数を変えながら何周もさせる
+        let acquisition: Acquisition<u32, u32, 3, 3> = Acquisition::new(0, 0);
+        let (mut writer, mut reader) = acquisition.split().unwrap();
+        let mut next = 0;
+        for round in 0..50u32 {
+            let blocks = round % 3 + 1;
+            for sequence in next..next + blocks {
+                assert!(push_block(&mut writer, sequence * 3, sequence));
+            }
+            assert_eq!(acquisition.pending(), blocks as usize);
+            for sequence in next..next + blocks {
+                let block = reader.read().unwrap();
+                assert_eq!((block.sequence(), block.timestamp()), (sequence, sequence));
+                assert_eq!(*block, [sequence * 3, sequence * 3 + 1, sequence * 3 + 2]);
+            }
+            assert!(reader.read().is_none());
+            next += blocks;
+        }
+        assert_eq!(acquisition.overruns(), 0);
+    }
+
+    #[test]
+    fn discard_all_skips_to_latest() {
+        let acquisition: Acquisition<u32, u32, 1, 4> = Acquisition::new(0, 0);
+        let (mut writer, mut reader) = acquisition.split().unwrap();
+        for i in 0..3 {
+            writer.push(i, i);
+        }
+        assert_eq!(acquisition.pending(), 3);
+        reader.discard_all();
+        assert!(reader.read().is_none());
+        writer.push(3, 3);
+        assert_eq!(reader.read().unwrap().sequence(), 3);
+    }
+
+    #[test]
+    fn concurrent_write_read() {
+        const BLOCK: usize = 8;
+        const BLOCKS: u32 = 20_000;
+        let acquisition: Acquisition<u32, u32, BLOCK, 2> = Acquisition::new(0, 0);
+        let (mut writer, mut reader) = acquisition.split().unwrap();
+        let done = AtomicBool::new(false);
+        let received = thread::scope(|scope| {
+            scope.spawn(|| {
+                for sequence in 0..BLOCKS {
+                    push_block(&mut writer, sequence * BLOCK as u32, sequence);
+                }
+                done.store(true, Ordering::Release);
+            });
+            let mut received = Vec::new();
+            loop {
+                let finished = done.load(Ordering::Acquire);
+                match reader.read() {
+                    Some(block) => {
+                        // ブロックの中身は途中で書き換えられていない
+                        let first = block.sequence() * BLOCK as u32;
+                        assert_eq!(block.timestamp(), block.sequence());
+                        assert!(block.iter().copied().eq(first..first + BLOCK as u32));
+                        received.push(block.sequence());
+                    }
+                    None if finished => break,
+                    None => thread::yield_now(),
+                }
+            }
+            received
+        });
+        // 通し番号は増える一方で、読めなかったブロックはすべてオーバーランとして数えられる
+        assert!(received.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(received.len() as u32 + acquisition.overruns(), BLOCKS);
+    }
+}
@@ -0,0 +1,447 @@
+// チャタリングを取り除いてイベントを発生させるボタンドライバです
+// embedded-hal の InputPin に対して実装しているので、
+// 上部の3つのボタンと5方向スイッチのどのピンにも使えます
+//
+// `update` に現在時刻 [ms] を渡して定期的に (数msごとに) 呼び出すと、
+// 発生したイベントがキューにたまり、`next_event` で取り出せます
+//
+// ピン以外 (WioBoard::is_pressed など) から状態を読むときは、
+// 判定の部分だけを持つ `ButtonState` に押されているかどうかを渡してください
+
+use embedded_hal::digital::v2::InputPin;
+use heapless::consts::*;
+use heapless::spsc::Queue;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonEvent {
+    Pressed,     // 押された
+    Released,    // 離された
+    LongPress,   // 長押しされた (押されてから long_press_ms 経過)
+    Repeat,      // 長押しが続いている間、repeat_interval_ms ごとに発生する
+    DoubleClick, // 直前のクリックから double_click_ms 以内に再び押された
+}
+
+// 各イベントの判定に使う時間 [ms]
+#[derive(Debug, Clone, Copy)]
+pub struct ButtonConfig {
+    pub debounce_ms: u32,
+    pub long_press_ms: u32,
+    pub repeat_interval_ms: u32, // 0のときはRepeatを発生させない
+    pub double_click_ms: u32,    // 0のときはDoubleClickを発生させない
+}
+
+impl Default for ButtonConfig {
+    fn default() -> Self {
+        ButtonConfig {
+            debounce_ms: 20,
+            long_press_ms: 800,
+            repeat_interval_ms: 200,
+            double_click_ms: 300,
+        }
+    }
+}
+
+// ピンを持たないボタンの状態です
+// 押されているかどうかを `update` に渡すと、Button と同じイベントを発生させます
+pub struct ButtonState {
+    config: ButtonConfig,
+    raw: bool,                  // 直前に渡された状態 (押されているか)
+    raw_changed_at: u32,        // 渡された状態が最後に変わった時刻
+    pressed: bool,              // チャタリング除去後の状態
+    pressed_at: u32,            // 押された時刻
+    long_pressed: bool,         // 今回の押下でLongPressを発生させたか
+    next_repeat_at: u32,        // 次にRepeatを発生させる時刻
+    last_click_at: Option<u32>, // 直前のクリック (長押しでない押下) を離した時刻
+    events: Queue<ButtonEvent, U8>,
+}
+
+impl ButtonState {
+    pub fn new(config: ButtonConfig) -> Self {
+        ButtonState {
+            config,
+            raw: false,
+            raw_changed_at: 0,
+            pressed: false,
+            pressed_at: 0,
+            long_pressed: false,
+            next_repeat_at: 0,
+            last_click_at: None,
+            events: Queue::new(),
+        }
+    }
+
+    // 押されているかどうかを渡し、必要ならイベントを発生させます
+    // now_ms は単調増加する時刻で、u32の範囲で一周しても構いません
+    pub fn update(&mut self, raw: bool, now_ms: u32) {
+        if raw != self.raw {
+            self.raw = raw;
+            self.raw_changed_at = now_ms;
+        }
+
+        // 一定時間同じ状態が続いたら、状態が変わったとみなす
+        if raw != self.pressed
+            && now_ms.wrapping_sub(self.raw_changed_at) >= self.config.debounce_ms
+        {
+            self.pressed = raw;
+            if raw {
+                self.on_pressed(now_ms);
+            } else {
+                self.on_released(now_ms);
+            }
+        }
+
+        if self.pressed {
+            self.check_long_press(now_ms);
+        }
+    }
+
+    fn on_pressed(&mut self, now_ms: u32) {
+        self.pressed_at = now_ms;
+        self.long_pressed = false;
+        self.push(ButtonEvent::Pressed);
+
+        if let Some(clicked_at) = self.last_click_at.take() {
+            if self.config.double_click_ms > 0
+                && now_ms.wrapping_sub(clicked_at) <= self.config.double_click_ms
+            {
+                self.push(ButtonEvent::DoubleClick);
+            }
+        }
+    }
+
+    fn on_released(&mut self, now_ms: u32) {
+        self.push(ButtonEvent::Released);
+        // 長押しの後はダブルクリックの1回目として数えない
+        self.last_click_at = if self.long_pressed {
+            None
+        } else {
+            Some(now_ms)
+        };
+    }
+
+    fn check_long_press(&mut self, now_ms: u32) {
+        if !self.long_pressed {
+            if now_ms.wrapping_sub(self.pressed_at) >= self.config.long_press_ms {
+                self.long_pressed = true;
+                self.next_repeat_at = now_ms.wrapping_add(self.config.repeat_interval_ms);
+                self.push(ButtonEvent::LongPress);
+            }
+        } else if self.config.repeat_interval_ms > 0 {
+            // wrapping_sub の結果を符号付きで見て、時刻が一周しても比較できるようにする
+            if now_ms.wrapping_sub(self.next_repeat_at) as i32 >= 0 {
+                self.next_repeat_at = self
+                    .next_repeat_at
+                    .wrapping_add(self.config.repeat_interval_ms);
+                self.push(ButtonEvent::Repeat);
+            }
+        }
+    }
+
+    // キューがいっぱいのときは新しいイベントを捨てる
+    fn push(&mut self, event: ButtonEvent) {
+        self.events.enqueue(event).ok();
+    }
+
+    // たまっているイベントを古い順に取り出します
+    pub fn next_event(&mut self) -> Option<ButtonEvent> {
+        self.events.dequeue()
+    }
+
+    // チャタリング除去後の状態
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    pub fn is_released(&self) -> bool {
+        !self.pressed
+    }
+}
+
+pub struct Button<P> {
+    pin: P,
+    active_low: bool,
+    state: ButtonState,
+}
+
+impl<P: InputPin> Button<P> {
+    // Wio Terminalのボタンは押されている間Lowになる
+    pub fn new(pin: P) -> Self {
+        Self::with_config(pin, ButtonConfig::default())
+    }
+
+    pub fn with_config(pin: P, config: ButtonConfig) -> Self {
+        Button {
+            pin,
+            active_low: true,
+            state: ButtonState::new(config),
+        }
+    }
+
+    // 押されている間Highになるピンに使う場合に呼び出します
+    pub fn active_high(mut self) -> Self {
+        self.active_low = false;
+        self
+    }
+
+    // ピンの状態を読み、必要ならイベントを発生させます
+    // now_ms は単調増加する時刻で、u32の範囲で一周しても構いません
+    pub fn update(&mut self, now_ms: u32) {
+        let level = if self.active_low {
+            self.pin.is_low()
+        } else {
+            self.pin.is_high()
+        };
+        // ピンが読めなかったときは、直前の状態が続いているものとする
+        let raw = level.unwrap_or(self.state.raw);
+        self.state.update(raw, now_ms);
+    }
+
+    // たまっているイベントを古い順に取り出します
+    pub fn next_event(&mut self) -> Option<ButtonEvent> {
+        self.state.next_event()
+    }
+
+    // チャタリング除去後の状態
+    pub fn is_pressed(&self) -> bool {
+        self.state.is_pressed()
+    }
+
+    pub fn is_released(&self) -> bool {
+        self.state.is_released()
+    }
+
+    // ピンを返します
+    pub fn free(self) -> P {
+        self.pin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    // テストから状態を変えられるピン (押されている間Low)
+    struct MockPin<'a> {
+        low: &'a Cell<bool>,
+    }
+
+    impl<'a> InputPin for MockPin<'a> {
+        type Error = Infallible;
+
+        fn is_high(&self) -> Result<bool, Self::Error> {
+            Ok(!self.low.get())
+        }
+
+        fn is_low(&self) -> Result<bool, Self::Error> {
+            Ok(self.low.get())
+        }
+    }
+
+    // 1[ms]ごとに update を呼び出す時計
+    struct FakeClock {
+        now: u32,
+    }
+
+    impl FakeClock {
+        fn run<P: InputPin>(&mut self, button: &mut Button<P>, ms: u32) {
+            for _ in 0..ms {
+                self.now = self.now.wrapping_add(1);
+                button.update(self.now);
+            }
+        }
+    }
+
+    fn events<P: InputPin>(button: &mut Button<P>) -> [Option<ButtonEvent>; 4] {
+        let mut events = [None; 4];
+        for event in events.iter_mut() {
+            *event = button.next_event();
+        }
+        events
+    }
+
+    #[test]
+    fn debounce_ignores_short_bounces() {
+        let low = Cell::new(false);
+        let mut button = Button::new(MockPin { low: &low });
+        let mut clock = FakeClock { now: 0 };
+
+        // 20[ms]より短い間だけ状態が変わっても、押されたとはみなさない
+        for _ in 0..5 {
+            low.set(true);
+            clock.run(&mut button, 5);
+            low.set(false);
+            clock.run(&mut button, 5);
+        }
+        assert_eq!(button.next_event(), None);
+        assert!(button.is_released());
+
+        // 変化に気づいてから20[ms]続いたら、1回だけ Pressed になる
+        low.set(true);
+        clock.run(&mut button, 20);
+        assert_eq!(button.next_event(), None);
+        clock.run(&mut button, 1);
+        assert_eq!(button.next_event(), Some(ButtonEvent::Pressed));
+        assert!(button.is_pressed());
+        clock.run(&mut button, 50);
+        assert_eq!(button.next_event(), None);
+
+        low.set(false);
+        clock.run(&mut button, 21);
+        assert_eq!(button.next_event(), Some(ButtonEvent::Released));
+        assert_eq!(button.next_event(), None);
+    }
+
+    #[test]
+    fn long_press_then_repeat() {
+        let low = Cell::new(true);
+        let mut button = Button::new(MockPin { low: &low });
+        let mut clock = FakeClock { now: 1000 };
+
+        // 押されたとみなすのは最初の update から20[ms]後、LongPressはそこから800[ms]後
+        clock.run(&mut button, 21);
+        assert_eq!(button.next_event(), Some(ButtonEvent::Pressed));
+        clock.run(&mut button, 799);
+        assert_eq!(button.next_event(), None);
+        clock.run(&mut button, 1);
+        assert_eq!(button.next_event(), Some(ButtonEvent::LongPress));
+
+        // その後は200[ms]ごとに Repeat
+        clock.run(&mut button, 199);
+        assert_eq!(button.next_event(), None);
+        clock.run(&mut button, 1);
+        assert_eq!(button.next_event(), Some(ButtonEvent::Repeat));
+        clock.run(&mut button, 400);
+        assert_eq!(
+            events(&mut button),
+            [
+                Some(ButtonEvent::Repeat),
+                Some(ButtonEvent::Repeat),
+                None,
+                None
+            ]
+        );
+
+        // 長押しの後に離して押し直しても、ダブルクリックにはならない
+        low.set(false);
+        clock.run(&mut button, 30);
+        low.set(true);
+        clock.run(&mut button, 30);
+        assert_eq!(
+            events(&mut button),
+            [
+                Some(ButtonEvent::Released),
+                Some(ButtonEvent::Pressed),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn double_click() {
+        let low = Cell::new(false);
+        let mut button = Button::new(MockPin { low: &low });
+        let mut clock = FakeClock { now: 0 };
+
+        for _ in 0..2 {
+            low.set(true);
+            clock.run(&mut button, 50);
+            low.set(false);
+            clock.run(&mut button, 100);
+        }
+        assert_eq!(
+            events(&mut button),
+            [
+                Some(ButtonEvent::Pressed),
+                Some(ButtonEvent::Released),
+                Some(ButtonEvent::Pressed),
+                Some(ButtonEvent::DoubleClick),
+            ]
+        );
+        assert_eq!(button.next_event(), Some(ButtonEvent::Released));
+
+        // 離してから300[ms]を過ぎて押したときはダブルクリックにならない
+        clock.run(&mut button, 300);
+        low.set(true);
+        clock.run(&mut button, 50);
+        assert_eq!(
+            events(&mut button),
+            [Some(ButtonEvent::Pressed), None, None, None]
+        );
+    }
+
+    #[test]
+    fn time_wraps_around() {
+        let low = Cell::new(false);
+        let mut button = Button::new(MockPin { low: &low });
+        // 押している間に u32 の時刻が一周する
+        let mut clock = FakeClock { now: u32::MAX - 10 };
+        button.update(clock.now);
+
+        low.set(true);
+        clock.run(&mut button, 21);
+        assert_eq!(button.next_event(), Some(ButtonEvent::Pressed));
+        clock.run(&mut button, 800);
+        assert_eq!(button.next_event(), Some(ButtonEvent::LongPress));
+        clock.run(&mut button, 200);
+        assert_eq!(button.next_event(), Some(ButtonEvent::Repeat));
+        assert_eq!(button.next_event(), None);
+
+        // Repeat の時刻が一周する直前でも、1周分待たずに発生する
+        let low = Cell::new(true);
+        let mut button = Button::new(MockPin { low: &low });
+        let mut clock = FakeClock {
+            now: u32::MAX - 900,
+        };
+        clock.run(&mut button, 821);
+        assert_eq!(
+            events(&mut button),
+            [
+                Some(ButtonEvent::Pressed),
+                Some(ButtonEvent::LongPress),
+                None,
+                None
+            ]
+        );
+        clock.run(&mut button, 200);
+        assert_eq!(button.next_event(), Some(ButtonEvent::Repeat));
+    }
+
+    #[test]
+    fn active_high_pin() {
+        let low = Cell::new(true);
+        let mut button = Button::new(MockPin { low: &low }).active_high();
+        let mut clock = FakeClock { now: 0 };
+        clock.run(&mut button, 50);
+        assert_eq!(button.next_event(), None);
+        low.set(false);
+        clock.run(&mut button, 21);
+        assert_eq!(button.next_event(), Some(ButtonEvent::Pressed));
+    }
+
+    #[test]
+    fn full_queue_drops_new_events() {
+        let mut state = ButtonState::new(ButtonConfig {
+            debounce_ms: 0,
+            long_press_ms: 1000,
+            repeat_interval_ms: 0,
+            double_click_ms: 0,
+        });
+        // 取り出さずに5回押して離すと、10個のうち先の8個だけが残る
+        for i in 0..5 {
+            state.update(true, i * 10);
+            state.update(false, i * 10 + 5);
+        }
+        for i in 0..8 {
+            let expected = if i % 2 == 0 {
+                ButtonEvent::Pressed
+            } else {
+                ButtonEvent::Released
+            };
+            assert_eq!(state.next_event(), Some(expected));
+        }
+        assert_eq!(state.next_event(), None);
+    }
+}
@@ -0,0 +1,682 @@
+// LEDの点滅パターンと、LEDドライバです
+// Sequencer に経過時間 [ms] を渡して進めると、LEDを点灯すべきかどうかを返します
+//
+// Led と PwmLed は embedded-hal の OutputPin (点灯/消灯) または PwmPin (明るさ調整) に対して
+// 実装しているので、ユーザーLED以外のLEDにも使えます
+// 点滅パターンは `set_pattern` で設定し、タイマ割り込みなどから
+// 経過時間 [ms] を渡して `tick` を呼び出すと進みます (待ち時間でブロックしません)
+
+use core::convert::TryFrom;
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::PwmPin;
+
+// 点滅パターン
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    Off,
+    On,
+    Blink { on_ms: u32, off_ms: u32 },
+    // 心臓の鼓動のように2回ずつ点滅する
+    Heartbeat,
+    // 文字列をモールス信号で繰り返し送る (A-Z, 0-9 と空白以外の文字は無視する)
+    // unit_ms は短点1つの長さ
+    Morse { text: &'static str, unit_ms: u32 },
+}
+
+impl Pattern {
+    pub const SOS: Pattern = Pattern::Morse {
+        text: "SOS",
+        unit_ms: 150,
+    };
+
+    // period_ms 周期で、点灯と消灯が同じ長さの点滅
+    pub fn blink(period_ms: u32) -> Self {
+        Pattern::Blink {
+            on_ms: period_ms / 2,
+            off_ms: period_ms - period_ms / 2,
+        }
+    }
+
+    // index 番目の (点灯するか, 長さ[ms]) を返す
+    // パターンの最後まで進んだときは None を返す
+    fn step(&self, index: usize) -> Option<(bool, u32)> {
+        match *self {
+            Pattern::Off => step_of(&[(false, u32::MAX)], index),
+            Pattern::On => step_of(&[(true, u32::MAX)], index),
+            Pattern::Blink { on_ms, off_ms } => step_of(&[(true, on_ms), (false, off_ms)], index),
+            Pattern::Heartbeat => step_of(&HEARTBEAT, index),
+            Pattern::Morse { text, unit_ms } => morse_step(text, unit_ms, index),
+        }
+    }
+}
+
+const HEARTBEAT: [(bool, u32); 4] = [(true, 100), (false, 150), (true, 100), (false, 650)];
+
+fn step_of(steps: &[(bool, u32)], index: usize) -> Option<(bool, u32)> {
+    steps.get(index).copied()
+}
+
+fn morse_code(c: char) -> Option<&'static str> {
+    let code = match c.to_ascii_uppercase() {
+        'A' => ".-",
+        'B' => "-...",
+        'C' => "-.-.",
+        'D' => "-..",
+        'E' => ".",
+        'F' => "..-.",
+        'G' => "--.",
+        'H' => "....",
+        'I' => "..",
+        'J' => ".---",
+        'K' => "-.-",
+        'L' => ".-..",
+        'M' => "--",
+        'N' => "-.",
+        'O' => "---",
+        'P' => ".--.",
+        'Q' => "--.-",
+        'R' => ".-.",
+        'S' => "...",
+        'T' => "-",
+        'U' => "..-",
+        'V' => "...-",
+        'W' => ".--",
+        'X' => "-..-",
+        'Y' => "-.--",
+        'Z' => "--..",
+        '0' => "-----",
+        '1' => ".----",
+        '2' => "..---",
+        '3' => "...--",
+        '4' => "....-",
+        '5' => ".....",
+        '6' => "-....",
+        '7' => "--...",
+        '8' => "---..",
+        '9' => "----.",
+        _ => return None,
+    };
+    Some(code)
+}
+
+// 短点は1単位、長点は3単位点灯する
+// 符号の間は1単位、文字の間は3単位、単語の間は7単位消灯する
+fn morse_step(text: &str, unit_ms: u32, index: usize) -> Option<(bool, u32)> {
+    let mut i = 0;
+    let mut gap_units = 0;
+    for c in text.chars() {
+        if c == ' ' {
+            if gap_units > 0 {
+                gap_units = 7;
+            }
+            continue;
+        }
+        let code = match morse_code(c) {
+            Some(code) => code,
+            None => continue,
+        };
+        for symbol in code.bytes() {
+            if gap_units > 0 {
+                if i == index {
+                    return Some((false, gap_units * unit_ms));
+                }
+                i += 1;
+            }
+            let units = if symbol == b'-' { 3 } else { 1 };
+            if i == index {
+                return Some((true, units * unit_ms));
+            }
+            i += 1;
+            gap_units = 1;
+        }
+        gap_units = 3;
+    }
+    // 繰り返す前に単語の間の長さだけ消灯する
+    if i > 0 && i == index {
+        return Some((false, 7 * unit_ms));
+    }
+    None
+}
+
+// パターンを時間に沿って進めます
+// ピンには触らないので、LEDの種類に関係なく使えます
+#[derive(Debug, Clone)]
+pub struct Sequencer {
+    pattern: Pattern,
+    index: usize,
+    on: bool,
+    remaining_ms: u32,
+}
+
+impl Sequencer {
+    pub fn new(pattern: Pattern) -> Self {
+        let mut sequencer = Sequencer {
+            pattern,
+            index: 0,
+            on: false,
+            remaining_ms: 0,
+        };
+        sequencer.load();
+        sequencer
+    }
+
+    pub fn pattern(&self) -> Pattern {
+        self.pattern
+    }
+
+    // パターンを最初から始めます
+    pub fn set_pattern(&mut self, pattern: Pattern) {
+        self.pattern = pattern;
+        self.index = 0;
+        self.load();
+    }
+
+    // 経過時間 [ms] だけパターンを進め、LEDを点灯すべきかを返します
+    pub fn tick(&mut self, elapsed_ms: u32) -> bool {
+        let mut elapsed_ms = elapsed_ms;
+        while elapsed_ms >= self.remaining_ms {
+            elapsed_ms -= self.remaining_ms;
+            self.index += 1;
+            self.load();
+        }
+        self.remaining_ms -= elapsed_ms;
+        self.on
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    fn load(&mut self) {
+        let step = match self.pattern.step(self.index) {
+            Some(step) => Some(step),
+            None => {
+                // 最後まで進んだら最初に戻る
+                self.index = 0;
+                self.pattern.step(0)
+            }
+        };
+        // 点滅する要素がないパターンは消灯したままにする
+        let (on, duration_ms) = step.unwrap_or((false, u32::MAX));
+        self.on = on;
+        // 長さ0の要素が続いて tick が止まらなくならないようにする
+        self.remaining_ms = duration_ms.max(1);
+    }
+}
+
+// 点灯/消灯だけできるLEDのドライバです
+pub struct Led<P> {
+    pin: P,
+    on: bool,
+    sequencer: Option<Sequencer>,
+}
+
+impl<P: OutputPin> Led<P> {
+    // 出力モードのピンからドライバを作ります (消灯した状態で始まります)
+    pub fn new(pin: P) -> Self {
+        let mut led = Led {
+            pin,
+            on: false,
+            sequencer: None,
+        };
+        led.set(false);
+        led
+    }
+
+    // LEDを点灯します (パターンは止まります)
+    pub fn turn_on(&mut self) {
+        self.sequencer = None;
+        self.set(true);
+    }
+
+    // LEDを消灯します (パターンは止まります)
+    pub fn turn_off(&mut self) {
+        self.sequencer = None;
+        self.set(false);
+    }
+
+    // LEDが点灯しているときは消灯し、消灯しているときは点灯します
+    pub fn toggle(&mut self) {
+        if self.on {
+            self.turn_off();
+        } else {
+            self.turn_on();
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    // 点滅パターンを最初から始めます
+    pub fn set_pattern(&mut self, pattern: Pattern) {
+        let sequencer = Sequencer::new(pattern);
+        self.set(sequencer.is_on());
+        self.sequencer = Some(sequencer);
+    }
+
+    // 設定されている点滅パターンを経過時間 [ms] だけ進めます
+    pub fn tick(&mut self, elapsed_ms: u32) {
+        if let Some(sequencer) = self.sequencer.as_mut() {
+            let on = sequencer.tick(elapsed_ms);
+            self.set(on);
+        }
+    }
+
+    pub fn free(self) -> P {
+        self.pin
+    }
+
+    fn set(&mut self, on: bool) {
+        if on {
+            self.pin.set_high().ok();
+        } else {
+            self.pin.set_low().ok();
+        }
+        self.on = on;
+    }
+}
+
+// PWMで明るさを変えられるLEDのドライバです
+// パターンの点灯部分は `set_brightness` で設定した明るさで光ります
+pub struct PwmLed<P> {
+    pwm: P,
+    brightness: u8,
+    on: bool,
+    sequencer: Option<Sequencer>,
+}
+
+impl<P> PwmLed<P>
+where
+    P: PwmPin,
+    P::Duty: Into<u32> + TryFrom<u32>,
+{
+    pub fn new(pwm: P) -> Self {
+        let mut led = PwmLed {
+            pwm,
+            brightness: u8::MAX,
+            on: false,
+            sequencer: None,
+        };
+        led.pwm.enable();
+        led.set(false);
+        led
+    }
+
+    // 明るさ (0: 消灯, 255: 最大)
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+        let on = self.on;
+        self.set(on);
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    pub fn turn_on(&mut self) {
+        self.sequencer = None;
+        self.set(true);
+    }
+
+    pub fn turn_off(&mut self) {
+        self.sequencer = None;
+        self.set(false);
+    }
+
+    pub fn set_pattern(&mut self, pattern: Pattern) {
+        let sequencer = Sequencer::new(pattern);
+        self.set(sequencer.is_on());
+        self.sequencer = Some(sequencer);
+    }
+
+    pub fn tick(&mut self, elapsed_ms: u32) {
+        if let Some(sequencer) = self.sequencer.as_mut() {
+            let on = sequencer.tick(elapsed_ms);
+            self.set(on);
+        }
+    }
+
+    pub fn free(mut self) -> P {
+        self.pwm.disable();
+        self.pwm
+    }
+
+    fn set(&mut self, on: bool) {
+        let brightness = if on { self.brightness as u32 } else { 0 };
+        let max_duty: u32 = self.pwm.get_max_duty().into();
+        // 255 * u32::MAX はあふれるので64bitで計算する
+        let duty = (max_duty as u64 * brightness as u64 / u8::MAX as u64) as u32;
+        if let Ok(duty) = P::Duty::try_from(duty) {
+            self.pwm.set_duty(duty);
+        }
+        self.on = on;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::RefCell;
+    use core::convert::Infallible;
+    use std::vec::Vec;
+
+    // 出力した状態 (Highか) を順に記録するピン
+    struct MockPin<'a> {
+        levels: &'a RefCell<Vec<bool>>,
+    }
+
+    impl OutputPin for MockPin<'_> {
+        type Error = Infallible;
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.levels.borrow_mut().push(true);
+            Ok(())
+        }
+
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.levels.borrow_mut().push(false);
+            Ok(())
+        }
+    }
+
+    // 設定したデューティ比と有効/無効を記録するPWMのピン (最大デューティは1000)
+    struct MockPwm<'a> {
+        duties: &'a RefCell<Vec<u16>>,
+        enabled: bool,
+    }
+
+    impl PwmPin for MockPwm<'_> {
+        type Duty = u16;
+
+        fn disable(&mut self) {
+            self.enabled = false;
+        }
+
+        fn enable(&mut self) {
+            self.enabled = true;
+        }
+
+        fn get_duty(&self) -> u16 {
+            *self.duties.borrow().last().unwrap_or(&0)
+        }
+
+        fn get_max_duty(&self) -> u16 {
+            1000
+        }
+
+        fn set_duty(&mut self, duty: u16) {
+            self.duties.borrow_mut().push(duty);
+        }
+    }
+
+    #[test]
+    fn led_drives_pin() {
+        let levels = RefCell::new(Vec::new());
+        let mut led = Led::new(MockPin { levels: &levels });
+        // 作った直後は消灯している
+        assert_eq!(*levels.borrow(), [false]);
+        assert!(!led.is_on());
+
+        led.turn_on();
+        led.toggle();
+        led.toggle();
+        assert!(led.is_on());
+        led.turn_off();
+        assert_eq!(*levels.borrow(), [false, true, false, true, false]);
+    }
+
+    #[test]
+    fn led_follows_pattern() {
+        let levels = RefCell::new(Vec::new());
+        let mut led = Led::new(MockPin { levels: &levels });
+        led.set_pattern(Pattern::blink(100));
+        for _ in 0..4 {
+            led.tick(50);
+        }
+        assert_eq!(*levels.borrow(), [false, true, false, true, false, true]);
+
+        // 点灯・消灯するとパターンは止まり、tick してもピンは変わらない
+        led.turn_on();
+        led.tick(50);
+        led.tick(50);
+        assert!(led.is_on());
+        assert_eq!(levels.borrow()[6..], [true]);
+        led.free();
+    }
+
+    #[test]
+    fn pwm_led_scales_duty() {
+        let duties = RefCell::new(Vec::new());
+        let mut led = PwmLed::new(MockPwm {
+            duties: &duties,
+            enabled: false,
+        });
+        assert!(led.pwm.enabled);
+        assert_eq!(*duties.borrow(), [0]);
+
+        led.turn_on();
+        // 消灯中に明るさを変えても点灯しない
+        led.turn_off();
+        led.set_brightness(51);
+        led.turn_on();
+        assert_eq!(led.brightness(), 51);
+        assert_eq!(*duties.borrow(), [0, 1000, 0, 0, 200]);
+
+        let pwm = led.free();
+        assert!(!pwm.enabled);
+    }
+
+    #[test]
+    fn pwm_led_follows_pattern() {
+        let duties = RefCell::new(Vec::new());
+        let mut led = PwmLed::new(MockPwm {
+            duties: &duties,
+            enabled: false,
+        });
+        led.set_brightness(255 / 2);
+        led.set_pattern(Pattern::Blink {
+            on_ms: 10,
+            off_ms: 30,
+        });
+        led.tick(10);
+        led.tick(30);
+        assert!(led.is_on());
+        assert_eq!(*duties.borrow(), [0, 0, 498, 0, 498]);
+    }
+
+    // パターンを1周分 (点灯するか, 長さ[単位]) の列にする
+    fn morse_units(text: &'static str) -> Vec<(bool, u32)> {
+        let pattern = Pattern::Morse { text, unit_ms: 10 };
+        (0..)
+            .map(|i| pattern.step(i))
+            .take_while(|step| step.is_some())
+            .map(|step| {
+                let (on, ms) = step.unwrap();
+                (on, ms / 10)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn blink_timing() {
+        let mut sequencer = Sequencer::new(Pattern::blink(1000));
+        assert!(sequencer.is_on());
+        assert!(sequencer.tick(499));
+        assert!(!sequencer.tick(1));
+        assert!(!sequencer.tick(499));
+        assert!(sequencer.tick(1));
+        // 1回の tick で何周分進んでもよい
+        assert!(sequencer.tick(10_250));
+        assert!(!sequencer.tick(250));
+
+        // 周期が奇数のときは消灯の方が長い
+        assert_eq!(
+            Pattern::blink(5),
+            Pattern::Blink {
+                on_ms: 2,
+                off_ms: 3
+            }
+        );
+    }
+
+    #[test]
+    fn heartbeat_timing() {
+        let mut sequencer = Sequencer::new(Pattern::Heartbeat);
+        let mut on_ms = Vec::new();
+        for t in 0..2000 {
+            if sequencer.is_on() {
+                on_ms.push(t);
+            }
+            sequencer.tick(1);
+        }
+        // 1秒に2回、100[ms]ずつ点灯する
+        let starts: Vec<u32> = on_ms
+            .iter()
+            .enumerate()
+            .filter(|(i, t)| *i == 0 || on_ms[i - 1] + 1 != **t)
+            .map(|(_, t)| *t)
+            .collect();
+        assert_eq!(starts, [0, 250, 1000, 1250]);
+        assert_eq!(on_ms.len(), 400);
+    }
+
+    #[test]
+    fn set_pattern_restarts() {
+        let mut sequencer = Sequencer::new(Pattern::blink(100));
+        sequencer.tick(70);
+        assert!(!sequencer.is_on());
+        sequencer.set_pattern(Pattern::blink(100));
+        assert!(sequencer.is_on());
+        assert!(!sequencer.tick(50));
+        assert_eq!(sequencer.pattern(), Pattern::blink(100));
+    }
+
+    #[test]
+    fn steady_patterns() {
+        let mut off = Sequencer::new(Pattern::Off);
+        let mut on = Sequencer::new(Pattern::On);
+        for _ in 0..10 {
+            assert!(!off.tick(u32::MAX / 2));
+            assert!(on.tick(u32::MAX / 2));
+        }
+    }
+
+    #[test]
+    fn zero_length_steps_do_not_hang() {
+        let mut sequencer = Sequencer::new(Pattern::Blink {
+            on_ms: 0,
+            off_ms: 0,
+        });
+        sequencer.tick(1000);
+        let mut sequencer = Sequencer::new(Pattern::Morse {
+            text: "SOS",
+            unit_ms: 0,
+        });
+        sequencer.tick(1000);
+    }
+
+    #[test]
+    fn morse_sos() {
+        let dot = [(true, 1)];
+        let dash = [(true, 3)];
+        let mut expected = Vec::new();
+        // S: ... / O: --- / S: ...
+        for (i, letter) in [&dot, &dash, &dot].iter().enumerate() {
+            if i > 0 {
+                // 文字の間は3単位
+                expected.push((false, 3));
+            }
+            for j in 0..3 {
+                if j > 0 {
+                    // 符号の間は1単位
+                    expected.push((false, 1));
+                }
+                expected.extend_from_slice(*letter);
+            }
+        }
+        // 繰り返す前に7単位空ける
+        expected.push((false, 7));
+        assert_eq!(morse_units("SOS"), expected);
+        assert_eq!(
+            Pattern::SOS,
+            Pattern::Morse {
+                text: "SOS",
+                unit_ms: 150
+            }
+        );
+    }
+
+    #[test]
+    fn morse_words_and_letters() {
+        // 単語の間は7単位
+        assert_eq!(
+            morse_units("E T"),
+            [(true, 1), (false, 7), (true, 3), (false, 7)]
+        );
+        // 小文字は大文字と同じ、数字も送れる
+        assert_eq!(morse_units("a"), morse_units("A"));
+        assert_eq!(
+            morse_units("0"),
+            [
+                (true, 3),
+                (false, 1),
+                (true, 3),
+                (false, 1),
+                (true, 3),
+                (false, 1),
+                (true, 3),
+                (false, 1),
+                (true, 3),
+                (false, 7)
+            ]
+        );
+        // 前後と連続した空白、送れない文字は無視する
+        assert_eq!(morse_units("  E  T "), morse_units("E T"));
+        assert_eq!(morse_units("E?T"), morse_units("ET"));
+        assert_eq!(
+            morse_units("ET"),
+            [(true, 1), (false, 3), (true, 3), (false, 7)]
+        );
+    }
+
+    #[test]
+    fn morse_without_symbols_stays_off() {
+        assert!(morse_units("?! ").is_empty());
+        let mut sequencer = Sequencer::new(Pattern::Morse {
+            text: "?! ",
+            unit_ms: 100,
+        });
+        assert!(!sequencer.is_on());
+        assert!(!sequencer.tick(10_000));
+    }
+
+    #[test]
+    fn sequencer_follows_morse_timing() {
+        // "EE" は 点灯1 消灯3 点灯1 消灯7 の繰り返し
+        let mut sequencer = Sequencer::new(Pattern::Morse {
+            text: "EE",
+            unit_ms: 100,
+        });
+        let expected = [
+            (0, true),
+            (99, true),
+            (100, false),
+            (399, false),
+            (400, true),
+            (500, false),
+            (1199, false),
+            (1200, true),
+        ];
+        let mut now = 0;
+        for (t, on) in expected.iter() {
+            sequencer.tick(t - now);
+            now = *t;
+            assert_eq!(sequencer.is_on(), *on, "at {} ms", t);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+#![cfg_attr(not(test), no_std)]
+
+// ハードウェアに依存しない部品をまとめたクレートです
+// ピンは embedded-hal のトレイト、時刻は呼び出し側から渡す [ms] で扱うので、
+// ホストPCでもテストできます
+//
+// acquisition: 割り込みハンドラから main() へサンプルをブロック単位で渡すNバッファ
+// button:     チャタリングを取り除いてイベントを発生させるボタンドライバ
+// led:        OutputPin / PwmPin のLEDドライバと、点滅パターン (点滅・ハートビート・モールス信号)
+// melody:     RTTTL と MML の文字列を音符にして、ブロックせずに再生する
+// soft_timer: 1つのティックで多数のタイマを動かすタイマホイール
+// spsc:       書き込み側と読み出し側が1つずつのロックフリーなキュー
+// stopwatch:  ラップ付きのストップウォッチとカウントダウンタイマ
+// timebase:   32.768[kHz]のカウンタで数えた時刻 (Instant) と時間の長さ (Duration)
+
+pub mod acquisition;
+pub mod button;
+pub mod led;
+pub mod melody;
+pub mod soft_timer;
+pub mod spsc;
+pub mod stopwatch;
+pub mod timebase;
+
+pub use button::{Button, ButtonConfig, ButtonEvent, ButtonState};
+pub use led::{Led, PwmLed};
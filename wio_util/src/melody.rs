@@ -0,0 +1,874 @@
+// ブザーでメロディを鳴らすモジュールです
+// RTTTL (着メロの書式) と MML の文字列を音符の列に変換し、待ち時間でブロックせずに再生します
+//
+// 再生は `Player::tick` に経過時間 [ms] を渡して進めます
+// タイマ割り込みからでも、メインループからでも呼び出せます
+// ブザーは `Tone` トレイトを通して鳴らします (Wio Terminalのブザーは wio-examples の Buzzer)
+//
+// RTTTL: "名前:d=4,o=5,b=120:8c,8d,8e,4p,2c6."
+//   d: 音長の既定値, o: オクターブの既定値, b: テンポ (1分あたりの4分音符の数)
+// MML: "T120 O4 L8 CDEF G4 R4 >C<B A+2."
+//   T: テンポ, O: オクターブ, L: 音長の既定値, < >: オクターブを下げる/上げる, R: 休符
+//   音名の後ろの + # は半音上げ、- は半音下げ、数字は音長、. は付点
+
+// 周波数を指定してブザーを鳴らせるもの
+pub trait Tone {
+    fn start_tone(&mut self, frequency_hz: u32);
+    fn stop_tone(&mut self);
+}
+
+// 音符1つ分 (frequency_hz が0のときは休符)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Note {
+    pub frequency_hz: u32,
+    pub duration_ms: u32,
+}
+
+impl Note {
+    pub const fn new(frequency_hz: u32, duration_ms: u32) -> Self {
+        Note {
+            frequency_hz,
+            duration_ms,
+        }
+    }
+
+    pub const fn rest(duration_ms: u32) -> Self {
+        Note::new(0, duration_ms)
+    }
+
+    pub fn is_rest(&self) -> bool {
+        self.frequency_hz == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MelodyErrorKind {
+    InvalidHeader, // RTTTLのヘッダ (名前:既定値:) が正しくない
+    UnexpectedCharacter(char),
+    InvalidValue, // 数値が範囲外
+}
+
+// position は元の文字列でのバイト位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MelodyError {
+    pub position: usize,
+    pub kind: MelodyErrorKind,
+}
+
+impl MelodyError {
+    fn new(position: usize, kind: MelodyErrorKind) -> Self {
+        MelodyError { position, kind }
+    }
+}
+
+// 8オクターブ目のドからシまでの周波数 [Hz]
+// オクターブが1つ下がるごとに半分になる
+const OCTAVE8_HZ: [u32; 12] = [
+    4186, 4435, 4699, 4978, 5274, 5588, 5920, 6272, 6645, 7040, 7459, 7902,
+];
+const MAX_OCTAVE: i32 = 8;
+
+// オクターブと半音の位置 (ド=0, シ=11) から周波数を求めます
+// 半音の位置が範囲外 (ドのフラット、シのシャープ) のときは隣のオクターブになります
+pub fn note_frequency(octave: i32, semitone: i32) -> Option<u32> {
+    let index = octave * 12 + semitone;
+    let octave = index.div_euclid(12);
+    let semitone = index.rem_euclid(12) as usize;
+    if !(0..=MAX_OCTAVE).contains(&octave) {
+        return None;
+    }
+    let shift = (MAX_OCTAVE - octave) as u32;
+    let hz = OCTAVE8_HZ[semitone];
+    // 四捨五入して半分にする
+    Some(if shift == 0 {
+        hz
+    } else {
+        (hz + (1 << (shift - 1))) >> shift
+    })
+}
+
+// 全音符の長さ [ms] から、length 分音符 (付点の数 dots) の長さを求めます
+fn note_duration(tempo_bpm: u32, length: u32, dots: u32) -> u32 {
+    // テンポは1分あたりの4分音符の数
+    let whole_ms = 240_000 / tempo_bpm;
+    let base = whole_ms / length;
+    let mut duration = base;
+    let mut extra = base;
+    for _ in 0..dots {
+        extra /= 2;
+        duration += extra;
+    }
+    duration
+}
+
+fn semitone_of(letter: u8) -> Option<i32> {
+    let semitone = match letter.to_ascii_lowercase() {
+        b'c' => 0,
+        b'd' => 2,
+        b'e' => 4,
+        b'f' => 5,
+        b'g' => 7,
+        b'a' => 9,
+        b'b' | b'h' => 11,
+        _ => return None,
+    };
+    Some(semitone)
+}
+
+// 文字列の読み取り位置
+#[derive(Clone)]
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    offset: usize, // 元の文字列での先頭の位置 (エラー位置の計算に使う)
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_whitespace() {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn position(&self) -> usize {
+        self.offset + self.pos
+    }
+
+    // 数字が続いていれば読み取る
+    fn number(&mut self) -> Result<Option<u32>, MelodyError> {
+        let start = self.position();
+        let mut value: Option<u32> = None;
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            self.pos += 1;
+            let digit = (c - b'0') as u32;
+            value = Some(
+                value
+                    .unwrap_or(0)
+                    .checked_mul(10)
+                    .and_then(|v| v.checked_add(digit))
+                    .ok_or_else(|| MelodyError::new(start, MelodyErrorKind::InvalidValue))?,
+            );
+        }
+        Ok(value)
+    }
+
+    fn unexpected(&self) -> MelodyError {
+        let kind = match self.peek() {
+            Some(c) => MelodyErrorKind::UnexpectedCharacter(c as char),
+            None => MelodyErrorKind::InvalidValue,
+        };
+        MelodyError::new(self.position(), kind)
+    }
+}
+
+fn check_range(value: u32, min: u32, max: u32, position: usize) -> Result<u32, MelodyError> {
+    if value < min || value > max {
+        Err(MelodyError::new(position, MelodyErrorKind::InvalidValue))
+    } else {
+        Ok(value)
+    }
+}
+
+// RTTTLの音符を順に返すイテレータです
+#[derive(Clone)]
+pub struct RtttlNotes<'a> {
+    cursor: Cursor<'a>,
+    duration: u32,
+    octave: u32,
+    tempo_bpm: u32,
+}
+
+// RTTTLのヘッダを読み取ります (音符はイテレータで順に読み取ります)
+pub fn parse_rtttl(source: &str) -> Result<RtttlNotes<'_>, MelodyError> {
+    let header_error = |position| MelodyError::new(position, MelodyErrorKind::InvalidHeader);
+    let name_end = source.find(':').ok_or_else(|| header_error(0))?;
+    let defaults_start = name_end + 1;
+    let defaults_end = source[defaults_start..]
+        .find(':')
+        .map(|i| defaults_start + i)
+        .ok_or_else(|| header_error(defaults_start))?;
+
+    // 既定値は仕様で決められている
+    let mut duration = 4;
+    let mut octave = 6;
+    let mut tempo_bpm = 63;
+    let mut cursor = Cursor {
+        bytes: &source.as_bytes()[..defaults_end],
+        pos: defaults_start,
+        offset: 0,
+    };
+    loop {
+        cursor.skip_whitespace();
+        let key = match cursor.bump() {
+            Some(key) => key.to_ascii_lowercase(),
+            None => break,
+        };
+        cursor.skip_whitespace();
+        if !cursor.eat(b'=') {
+            return Err(header_error(cursor.position()));
+        }
+        cursor.skip_whitespace();
+        let position = cursor.position();
+        let value = cursor.number()?.ok_or_else(|| header_error(position))?;
+        match key {
+            b'd' => duration = check_range(value, 1, 64, position)?,
+            b'o' => octave = check_range(value, 0, MAX_OCTAVE as u32, position)?,
+            b'b' => tempo_bpm = check_range(value, 1, 900, position)?,
+            _ => return Err(header_error(position - 2)),
+        }
+        cursor.skip_whitespace();
+        if !cursor.eat(b',') && cursor.peek().is_some() {
+            return Err(cursor.unexpected());
+        }
+    }
+
+    let notes_start = defaults_end + 1;
+    Ok(RtttlNotes {
+        cursor: Cursor {
+            bytes: &source.as_bytes()[notes_start..],
+            pos: 0,
+            offset: notes_start,
+        },
+        duration,
+        octave,
+        tempo_bpm,
+    })
+}
+
+impl<'a> RtttlNotes<'a> {
+    // 1つの音符 "[音長]音名[#][.][オクターブ][.]"
+    fn parse_note(&mut self) -> Result<Note, MelodyError> {
+        let c = &mut self.cursor;
+        let length_position = c.position();
+        let length = match c.number()? {
+            Some(length) => check_range(length, 1, 64, length_position)?,
+            None => self.duration,
+        };
+
+        let letter_position = c.position();
+        let letter = c.bump().ok_or_else(|| c.unexpected())?;
+        let semitone = if letter.eq_ignore_ascii_case(&b'p') {
+            None
+        } else {
+            match semitone_of(letter) {
+                Some(semitone) => Some(semitone),
+                None => {
+                    return Err(MelodyError::new(
+                        letter_position,
+                        MelodyErrorKind::UnexpectedCharacter(letter as char),
+                    ))
+                }
+            }
+        };
+        let sharp = if c.eat(b'#') { 1 } else { 0 };
+        // 付点はオクターブの前後どちらに書かれることもある
+        let mut dots = if c.eat(b'.') { 1 } else { 0 };
+        let octave_position = c.position();
+        let octave = match c.number()? {
+            Some(octave) => check_range(octave, 0, MAX_OCTAVE as u32, octave_position)?,
+            None => self.octave,
+        };
+        if c.eat(b'.') {
+            dots += 1;
+        }
+
+        let duration_ms = note_duration(self.tempo_bpm, length, dots);
+        match semitone {
+            None => Ok(Note::rest(duration_ms)),
+            Some(semitone) => note_frequency(octave as i32, semitone + sharp)
+                .map(|hz| Note::new(hz, duration_ms))
+                .ok_or_else(|| MelodyError::new(octave_position, MelodyErrorKind::InvalidValue)),
+        }
+    }
+}
+
+impl<'a> Iterator for RtttlNotes<'a> {
+    type Item = Result<Note, MelodyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cursor.skip_whitespace();
+        self.cursor.peek()?;
+        let note = self.parse_note();
+        if note.is_ok() {
+            self.cursor.skip_whitespace();
+            if !self.cursor.eat(b',') && self.cursor.peek().is_some() {
+                let error = self.cursor.unexpected();
+                self.cursor.pos = self.cursor.bytes.len();
+                return Some(Err(error));
+            }
+        } else {
+            // エラーの後は読み進めない
+            self.cursor.pos = self.cursor.bytes.len();
+        }
+        Some(note)
+    }
+}
+
+// MMLの音符を順に返すイテレータです
+#[derive(Clone)]
+pub struct MmlNotes<'a> {
+    cursor: Cursor<'a>,
+    octave: i32,
+    length: u32,
+    tempo_bpm: u32,
+}
+
+pub fn parse_mml(source: &str) -> MmlNotes<'_> {
+    MmlNotes {
+        cursor: Cursor {
+            bytes: source.as_bytes(),
+            pos: 0,
+            offset: 0,
+        },
+        octave: 4,
+        length: 4,
+        tempo_bpm: 120,
+    }
+}
+
+impl<'a> MmlNotes<'a> {
+    fn length_and_dots(&mut self) -> Result<(u32, u32), MelodyError> {
+        let position = self.cursor.position();
+        let length = match self.cursor.number()? {
+            Some(length) => check_range(length, 1, 64, position)?,
+            None => self.length,
+        };
+        let mut dots = 0;
+        while self.cursor.eat(b'.') {
+            dots += 1;
+        }
+        Ok((length, dots))
+    }
+
+    fn parse_next(&mut self) -> Option<Result<Note, MelodyError>> {
+        loop {
+            self.cursor.skip_whitespace();
+            let position = self.cursor.position();
+            let command = self.cursor.bump()?;
+            let result = match command.to_ascii_lowercase() {
+                b'<' => {
+                    self.octave -= 1;
+                    continue;
+                }
+                b'>' => {
+                    self.octave += 1;
+                    continue;
+                }
+                c @ b'o' | c @ b'l' | c @ b't' => {
+                    let value_position = self.cursor.position();
+                    let value = match self.cursor.number() {
+                        Ok(Some(value)) => value,
+                        Ok(None) => return Some(Err(self.cursor.unexpected())),
+                        Err(e) => return Some(Err(e)),
+                    };
+                    let result = match c {
+                        b'o' => check_range(value, 0, MAX_OCTAVE as u32, value_position)
+                            .map(|v| self.octave = v as i32),
+                        b'l' => check_range(value, 1, 64, value_position).map(|v| self.length = v),
+                        _ => check_range(value, 1, 900, value_position).map(|v| self.tempo_bpm = v),
+                    };
+                    match result {
+                        Ok(()) => continue,
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                b'r' | b'p' => self
+                    .length_and_dots()
+                    .map(|(length, dots)| Note::rest(note_duration(self.tempo_bpm, length, dots))),
+                letter => match semitone_of(letter) {
+                    Some(semitone) if letter != b'h' => {
+                        let mut semitone = semitone;
+                        if self.cursor.eat(b'+') || self.cursor.eat(b'#') {
+                            semitone += 1;
+                        } else if self.cursor.eat(b'-') {
+                            semitone -= 1;
+                        }
+                        self.length_and_dots().and_then(|(length, dots)| {
+                            let duration_ms = note_duration(self.tempo_bpm, length, dots);
+                            note_frequency(self.octave, semitone)
+                                .map(|hz| Note::new(hz, duration_ms))
+                                .ok_or_else(|| {
+                                    MelodyError::new(position, MelodyErrorKind::InvalidValue)
+                                })
+                        })
+                    }
+                    _ => Err(MelodyError::new(
+                        position,
+                        MelodyErrorKind::UnexpectedCharacter(command as char),
+                    )),
+                },
+            };
+            return Some(result);
+        }
+    }
+}
+
+impl<'a> Iterator for MmlNotes<'a> {
+    type Item = Result<Note, MelodyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let note = self.parse_next();
+        if let Some(Err(_)) = note {
+            // エラーの後は読み進めない
+            self.cursor.pos = self.cursor.bytes.len();
+        }
+        note
+    }
+}
+
+// 再生するメロディ
+#[derive(Clone)]
+pub enum Melody {
+    Rtttl(RtttlNotes<'static>),
+    Mml(MmlNotes<'static>),
+    Notes(core::slice::Iter<'static, Note>),
+}
+
+impl Melody {
+    pub fn rtttl(source: &'static str) -> Result<Self, MelodyError> {
+        parse_rtttl(source).map(Melody::Rtttl)
+    }
+
+    pub fn mml(source: &'static str) -> Self {
+        Melody::Mml(parse_mml(source))
+    }
+
+    pub fn notes(notes: &'static [Note]) -> Self {
+        Melody::Notes(notes.iter())
+    }
+}
+
+impl Iterator for Melody {
+    type Item = Result<Note, MelodyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Melody::Rtttl(notes) => notes.next(),
+            Melody::Mml(notes) => notes.next(),
+            Melody::Notes(notes) => notes.next().map(|note| Ok(*note)),
+        }
+    }
+}
+
+// メロディを待ち時間でブロックせずに再生します
+pub struct Player<T> {
+    tone: T,
+    melody: Option<Melody>,
+    sound_ms: u32, // 今の音符を鳴らす残り時間
+    gap_ms: u32,   // 今の音符の後の無音の残り時間
+    error: Option<MelodyError>,
+}
+
+impl<T: Tone> Player<T> {
+    pub fn new(tone: T) -> Self {
+        Player {
+            tone,
+            melody: None,
+            sound_ms: 0,
+            gap_ms: 0,
+            error: None,
+        }
+    }
+
+    // 再生中のメロディを止めて、新しいメロディを最初の音符から鳴らします
+    pub fn play(&mut self, melody: Melody) {
+        self.tone.stop_tone();
+        self.melody = Some(melody);
+        self.error = None;
+        self.next_note();
+    }
+
+    pub fn stop(&mut self) {
+        self.tone.stop_tone();
+        self.melody = None;
+        self.sound_ms = 0;
+        self.gap_ms = 0;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.melody.is_some()
+    }
+
+    // 再生中に文字列の誤りが見つかったときは、そこで再生を止めてエラーを残します
+    pub fn take_error(&mut self) -> Option<MelodyError> {
+        self.error.take()
+    }
+
+    // 経過時間 [ms] だけ再生を進めます
+    pub fn tick(&mut self, elapsed_ms: u32) {
+        let mut elapsed_ms = elapsed_ms;
+        while self.melody.is_some() {
+            if self.sound_ms > 0 {
+                if elapsed_ms < self.sound_ms {
+                    self.sound_ms -= elapsed_ms;
+                    return;
+                }
+                elapsed_ms -= self.sound_ms;
+                self.sound_ms = 0;
+                self.tone.stop_tone();
+            }
+            if elapsed_ms < self.gap_ms {
+                self.gap_ms -= elapsed_ms;
+                return;
+            }
+            elapsed_ms -= self.gap_ms;
+            self.gap_ms = 0;
+            self.next_note();
+        }
+    }
+
+    pub fn tone_mut(&mut self) -> &mut T {
+        &mut self.tone
+    }
+
+    pub fn free(mut self) -> T {
+        self.tone.stop_tone();
+        self.tone
+    }
+
+    fn next_note(&mut self) {
+        let next = match self.melody.as_mut() {
+            Some(melody) => melody.next(),
+            None => return,
+        };
+        match next {
+            Some(Ok(note)) if note.is_rest() => {
+                self.sound_ms = 0;
+                self.gap_ms = note.duration_ms;
+            }
+            Some(Ok(note)) => {
+                // 同じ高さの音符が続いても区切れて聞こえるように、最後の1/8は鳴らさない
+                self.gap_ms = note.duration_ms / 8;
+                self.sound_ms = note.duration_ms - self.gap_ms;
+                if self.sound_ms > 0 {
+                    self.tone.start_tone(note.frequency_hz);
+                }
+            }
+            Some(Err(e)) => {
+                self.error = Some(e);
+                self.stop();
+            }
+            None => self.stop(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn notes<I: Iterator<Item = Result<Note, MelodyError>>>(notes: I) -> Vec<Note> {
+        notes.map(|note| note.unwrap()).collect()
+    }
+
+    fn first_error<I: Iterator<Item = Result<Note, MelodyError>>>(notes: I) -> MelodyError {
+        notes.filter_map(|note| note.err()).next().unwrap()
+    }
+
+    #[test]
+    fn frequencies() {
+        assert_eq!(note_frequency(4, 9), Some(440));
+        assert_eq!(note_frequency(5, 9), Some(880));
+        assert_eq!(note_frequency(4, 0), Some(262));
+        assert_eq!(note_frequency(8, 11), Some(7902));
+        // ドのフラットは1つ下のオクターブのシ
+        assert_eq!(note_frequency(5, -1), note_frequency(4, 11));
+        assert_eq!(note_frequency(4, 12), note_frequency(5, 0));
+        assert_eq!(note_frequency(0, -1), None);
+        assert_eq!(note_frequency(8, 12), None);
+    }
+
+    #[test]
+    fn rtttl_header_defaults() {
+        // 既定値は d=4, o=6, b=63
+        let melody = notes(parse_rtttl("x::c,8a").unwrap());
+        assert_eq!(
+            melody,
+            [
+                Note::new(note_frequency(6, 0).unwrap(), 240_000 / 63 / 4),
+                Note::new(note_frequency(6, 9).unwrap(), 240_000 / 63 / 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn rtttl_notes() {
+        let melody =
+            notes(parse_rtttl("Test : d=8, o=5, b=120 : c, 4d#6, p, 2a., 4b.6, 16h4, c7").unwrap());
+        assert_eq!(
+            melody,
+            [
+                Note::new(523, 250),
+                Note::new(1245, 500),
+                Note::rest(250),
+                // 付点はオクターブの前にも後ろにも書ける
+                Note::new(880, 1500),
+                Note::new(1976, 750),
+                Note::new(494, 125),
+                Note::new(2093, 250),
+            ]
+        );
+    }
+
+    #[test]
+    fn rtttl_tempo() {
+        let slow = notes(parse_rtttl("x:b=60:4c").unwrap());
+        let fast = notes(parse_rtttl("x:b=240:4c").unwrap());
+        assert_eq!(slow[0].duration_ms, 1000);
+        assert_eq!(fast[0].duration_ms, 250);
+    }
+
+    #[test]
+    fn rtttl_errors() {
+        let header = |source| parse_rtttl(source).err().unwrap();
+        assert_eq!(
+            header("no header"),
+            MelodyError::new(0, MelodyErrorKind::InvalidHeader)
+        );
+        assert_eq!(
+            header("x:d=4"),
+            MelodyError::new(2, MelodyErrorKind::InvalidHeader)
+        );
+        assert_eq!(
+            header("x:q=4:c"),
+            MelodyError::new(2, MelodyErrorKind::InvalidHeader)
+        );
+        assert_eq!(
+            header("x:d4:c"),
+            MelodyError::new(3, MelodyErrorKind::InvalidHeader)
+        );
+        assert_eq!(
+            header("x:d=0:c"),
+            MelodyError::new(4, MelodyErrorKind::InvalidValue)
+        );
+        assert_eq!(
+            header("x:o=9:c"),
+            MelodyError::new(4, MelodyErrorKind::InvalidValue)
+        );
+        assert_eq!(
+            header("x:b=0:c"),
+            MelodyError::new(4, MelodyErrorKind::InvalidValue)
+        );
+
+        let error = first_error(parse_rtttl("x::c,q,d").unwrap());
+        assert_eq!(
+            error,
+            MelodyError::new(5, MelodyErrorKind::UnexpectedCharacter('q'))
+        );
+        let error = first_error(parse_rtttl("x::c9").unwrap());
+        assert_eq!(error, MelodyError::new(4, MelodyErrorKind::InvalidValue));
+        let error = first_error(parse_rtttl("x::c d").unwrap());
+        assert_eq!(
+            error,
+            MelodyError::new(5, MelodyErrorKind::UnexpectedCharacter('d'))
+        );
+        let error = first_error(parse_rtttl("x::99999999999c").unwrap());
+        assert_eq!(error, MelodyError::new(3, MelodyErrorKind::InvalidValue));
+
+        // エラーの後は音符を返さない
+        let mut melody = parse_rtttl("x::c,q,d").unwrap();
+        assert!(melody.next().unwrap().is_ok());
+        assert!(melody.next().unwrap().is_err());
+        assert!(melody.next().is_none());
+    }
+
+    #[test]
+    fn mml_defaults_and_commands() {
+        // 既定値は T120 O4 L4
+        let melody = notes(parse_mml("C R D8. L2 E T60 F"));
+        assert_eq!(
+            melody,
+            [
+                Note::new(262, 500),
+                Note::rest(500),
+                Note::new(294, 375),
+                Note::new(330, 1000),
+                Note::new(349, 2000),
+            ]
+        );
+    }
+
+    #[test]
+    fn mml_octaves_and_accidentals() {
+        let melody = notes(parse_mml("O5 A >A <<A C+ C# D- c.. p"));
+        assert_eq!(
+            melody,
+            [
+                Note::new(880, 500),
+                Note::new(1760, 500),
+                Note::new(440, 500),
+                Note::new(277, 500),
+                Note::new(277, 500),
+                Note::new(277, 500),
+                // 付点2つで 1 + 1/2 + 1/4 倍
+                Note::new(262, 875),
+                Note::rest(500),
+            ]
+        );
+        // B- と A+ は同じ音
+        assert_eq!(notes(parse_mml("B-")), notes(parse_mml("A+")));
+    }
+
+    #[test]
+    fn mml_errors() {
+        let error = first_error(parse_mml("C X D"));
+        assert_eq!(
+            error,
+            MelodyError::new(2, MelodyErrorKind::UnexpectedCharacter('X'))
+        );
+        // H は RTTTL だけの音名
+        let error = first_error(parse_mml("H"));
+        assert_eq!(
+            error,
+            MelodyError::new(0, MelodyErrorKind::UnexpectedCharacter('H'))
+        );
+        let error = first_error(parse_mml("O C"));
+        assert_eq!(
+            error,
+            MelodyError::new(1, MelodyErrorKind::UnexpectedCharacter(' '))
+        );
+        let error = first_error(parse_mml("T"));
+        assert_eq!(error, MelodyError::new(1, MelodyErrorKind::InvalidValue));
+        let error = first_error(parse_mml("O9"));
+        assert_eq!(error, MelodyError::new(1, MelodyErrorKind::InvalidValue));
+        let error = first_error(parse_mml("L0"));
+        assert_eq!(error, MelodyError::new(1, MelodyErrorKind::InvalidValue));
+        // オクターブを下げすぎた音符は鳴らせない
+        let error = first_error(parse_mml("O0 C <C"));
+        assert_eq!(error, MelodyError::new(6, MelodyErrorKind::InvalidValue));
+
+        let mut melody = parse_mml("C X D");
+        assert!(melody.next().unwrap().is_ok());
+        assert!(melody.next().unwrap().is_err());
+        assert!(melody.next().is_none());
+    }
+
+    // 呼び出された時刻と周波数を記録するブザー (0は止めたとき)
+    #[derive(Default)]
+    struct FakeTone {
+        now: u32,
+        log: Vec<(u32, u32)>,
+    }
+
+    impl Tone for FakeTone {
+        fn start_tone(&mut self, frequency_hz: u32) {
+            self.log.push((self.now, frequency_hz));
+        }
+
+        fn stop_tone(&mut self) {
+            self.log.push((self.now, 0));
+        }
+    }
+
+    // 1[ms]ずつ進める
+    fn run(player: &mut Player<FakeTone>, ms: u32) {
+        for _ in 0..ms {
+            player.tone_mut().now += 1;
+            player.tick(1);
+        }
+    }
+
+    static NOTES: [Note; 3] = [Note::new(440, 80), Note::rest(40), Note::new(880, 160)];
+
+    #[test]
+    fn player_timing() {
+        let mut player = Player::new(FakeTone::default());
+        player.play(Melody::notes(&NOTES));
+        assert!(player.is_playing());
+        run(&mut player, 300);
+        assert!(!player.is_playing());
+        // 音符の最後の1/8は鳴らさない (最後の2つは再生の終わりと free で止めたとき)
+        assert_eq!(
+            player.free().log,
+            [
+                (0, 0),
+                (0, 440),
+                (70, 0),
+                (120, 880),
+                (260, 0),
+                (280, 0),
+                (300, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn player_long_ticks() {
+        // 1回の tick で複数の音符を進めても、鳴らす順番は変わらない
+        let mut player = Player::new(FakeTone::default());
+        player.play(Melody::notes(&NOTES));
+        player.tick(150);
+        assert!(player.is_playing());
+        player.tick(1000);
+        assert!(!player.is_playing());
+        let frequencies: Vec<u32> = player.free().log.iter().map(|(_, hz)| *hz).collect();
+        assert_eq!(frequencies, [0, 440, 0, 880, 0, 0, 0]);
+    }
+
+    #[test]
+    fn player_stop_and_restart() {
+        let mut player = Player::new(FakeTone::default());
+        player.play(Melody::notes(&NOTES));
+        run(&mut player, 30);
+        player.stop();
+        assert!(!player.is_playing());
+        run(&mut player, 100);
+        player.play(Melody::mml("T120 L4 A"));
+        run(&mut player, 500);
+        assert_eq!(
+            player.free().log,
+            [
+                (0, 0),
+                (0, 440),
+                (30, 0),
+                (130, 0),
+                (130, 440),
+                (568, 0),
+                (630, 0),
+                (630, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn player_keeps_error() {
+        let mut player = Player::new(FakeTone::default());
+        player.play(Melody::mml("A8 X"));
+        run(&mut player, 300);
+        assert!(!player.is_playing());
+        assert_eq!(
+            player.take_error(),
+            Some(MelodyError::new(
+                3,
+                MelodyErrorKind::UnexpectedCharacter('X')
+            ))
+        );
+        assert_eq!(player.take_error(), None);
+    }
+}
@@ -0,0 +1,415 @@
+// 多数のワンショット/周期タイマを1つのティックで動かすタイマホイールです
+// `tick` を呼ぶたびに1ティック進みます
+// タイマは満了時刻ごとにスロットに振り分けてあるので、`tick` では現在のスロットだけを調べます
+//
+// 満了の通知はフラグ (`take_fired` で確認する) か、コールバック (`tick` が返す) で受け取れます
+// コールバックは TimerWheel を借用していない状態で呼び出すので、その中でタイマを開始し直せます
+// (割り込みハンドラから進める TimerService は wio-examples の soft_timer にあります)
+
+// 同時に使えるタイマの数 (フラグをu32のビットで管理するので32まで)
+pub const MAX_TIMERS: usize = 32;
+const SLOTS: usize = 16;
+
+// タイマが満了したときの動作
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    // フラグを立てる (`take_fired` で確認する)
+    Flag,
+    // 関数を呼び出す (`tick` が返すので、TimerService では割り込みハンドラの中で呼ばれる)
+    Callback(fn()),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerError {
+    Full, // タイマの数が MAX_TIMERS を超えた
+}
+
+// タイマを識別するハンドル
+// 取り消したタイマの番号が再利用されても、古いハンドルでは操作できません
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerId {
+    index: u8,
+    generation: u16,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    deadline: u32, // 満了するティック
+    period: u32,   // 0ならワンショット
+    action: Action,
+    generation: u16,
+    next: Option<u8>, // 同じスロットの次のタイマ
+}
+
+pub struct TimerWheel {
+    now: u32,
+    entries: [Option<Entry>; MAX_TIMERS],
+    generations: [u16; MAX_TIMERS],
+    slots: [Option<u8>; SLOTS],
+    fired: u32, // Action::Flag のタイマが満了したビット
+}
+
+// 1ティックの間に満了したコールバック
+pub struct Callbacks {
+    callbacks: [Option<fn()>; MAX_TIMERS],
+    len: usize,
+    next: usize,
+}
+
+impl Iterator for Callbacks {
+    type Item = fn();
+
+    fn next(&mut self) -> Option<fn()> {
+        if self.next >= self.len {
+            return None;
+        }
+        self.next += 1;
+        self.callbacks[self.next - 1]
+    }
+}
+
+impl TimerWheel {
+    pub const fn new() -> Self {
+        TimerWheel {
+            now: 0,
+            entries: [None; MAX_TIMERS],
+            generations: [0; MAX_TIMERS],
+            slots: [None; SLOTS],
+            fired: 0,
+        }
+    }
+
+    // 現在のティック
+    pub fn now(&self) -> u32 {
+        self.now
+    }
+
+    // delay_ticks 後に1回だけ満了するタイマを開始します (0のときは次のティック)
+    pub fn start_once(&mut self, delay_ticks: u32, action: Action) -> Result<TimerId, TimerError> {
+        self.insert_new(delay_ticks, 0, action)
+    }
+
+    // period_ticks ごとに満了するタイマを開始します
+    pub fn start_periodic(
+        &mut self,
+        period_ticks: u32,
+        action: Action,
+    ) -> Result<TimerId, TimerError> {
+        let period_ticks = period_ticks.max(1);
+        self.insert_new(period_ticks, period_ticks, action)
+    }
+
+    // タイマを止めて、確認されていないフラグも下ろします
+    // すでに満了したワンショットタイマや古いハンドルのときは何もせず false を返します
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        if !self.is_active(id) {
+            return false;
+        }
+        let index = id.index as usize;
+        let deadline = self.entries[index].unwrap().deadline;
+        self.unlink(index, deadline);
+        self.entries[index] = None;
+        self.fired &= !(1 << index);
+        true
+    }
+
+    pub fn is_active(&self, id: TimerId) -> bool {
+        match self.entries.get(id.index as usize) {
+            Some(Some(entry)) => entry.generation == id.generation,
+            _ => false,
+        }
+    }
+
+    // タイマが満了していればフラグを下ろして true を返します
+    // 前回の確認から何回満了していても、true を返すのは1回だけです
+    // 満了したワンショットタイマは、番号が再利用されるまで確認できます
+    pub fn take_fired(&mut self, id: TimerId) -> bool {
+        let index = id.index as usize;
+        if self.generations.get(index) != Some(&id.generation) {
+            // 番号が別のタイマに再利用されている
+            return false;
+        }
+        let bit = 1 << index;
+        let fired = self.fired & bit != 0;
+        self.fired &= !bit;
+        fired
+    }
+
+    // 1ティック進めて、満了したタイマのフラグを立てます
+    // 満了したコールバックを返すので、呼び出し側で呼んでください
+    pub fn tick(&mut self) -> Callbacks {
+        self.now = self.now.wrapping_add(1);
+        let slot = self.now as usize % SLOTS;
+        let mut callbacks = Callbacks {
+            callbacks: [None; MAX_TIMERS],
+            len: 0,
+            next: 0,
+        };
+
+        // スロットのリストを外してから、満了していないものを戻す
+        let mut cursor = self.slots[slot].take();
+        while let Some(index) = cursor {
+            let index = index as usize;
+            let mut entry = self.entries[index].unwrap();
+            cursor = entry.next;
+            if entry.deadline != self.now {
+                // 次の周回以降に満了する
+                self.link(index, entry.deadline);
+                continue;
+            }
+
+            match entry.action {
+                Action::Flag => self.fired |= 1 << index,
+                Action::Callback(callback) => {
+                    callbacks.callbacks[callbacks.len] = Some(callback);
+                    callbacks.len += 1;
+                }
+            }
+            if entry.period > 0 {
+                entry.deadline = entry.deadline.wrapping_add(entry.period);
+                self.entries[index] = Some(entry);
+                self.link(index, entry.deadline);
+            } else {
+                self.entries[index] = None;
+            }
+        }
+        callbacks
+    }
+
+    fn insert_new(
+        &mut self,
+        delay_ticks: u32,
+        period: u32,
+        action: Action,
+    ) -> Result<TimerId, TimerError> {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.is_none())
+            .ok_or(TimerError::Full)?;
+        let generation = self.generations[index].wrapping_add(1);
+        self.generations[index] = generation;
+        // 前にこの番号を使っていたタイマのフラグを引き継がない
+        self.fired &= !(1 << index);
+        let deadline = self.now.wrapping_add(delay_ticks.max(1));
+        self.entries[index] = Some(Entry {
+            deadline,
+            period,
+            action,
+            generation,
+            next: None,
+        });
+        self.link(index, deadline);
+        Ok(TimerId {
+            index: index as u8,
+            generation,
+        })
+    }
+
+    // スロットのリストの先頭に加える
+    fn link(&mut self, index: usize, deadline: u32) {
+        let slot = deadline as usize % SLOTS;
+        if let Some(entry) = self.entries[index].as_mut() {
+            entry.next = self.slots[slot];
+        }
+        self.slots[slot] = Some(index as u8);
+    }
+
+    // スロットのリストから外す
+    fn unlink(&mut self, index: usize, deadline: u32) {
+        let slot = deadline as usize % SLOTS;
+        let next = self.entries[index].and_then(|entry| entry.next);
+        if self.slots[slot] == Some(index as u8) {
+            self.slots[slot] = next;
+            return;
+        }
+        let mut cursor = self.slots[slot];
+        while let Some(current) = cursor {
+            let current = current as usize;
+            let entry = self.entries[current].as_mut().unwrap();
+            if entry.next == Some(index as u8) {
+                entry.next = next;
+                return;
+            }
+            cursor = entry.next;
+        }
+    }
+}
+
+impl Default for TimerWheel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use std::vec::Vec;
+
+    // n ティック進めて、満了したコールバックを呼ぶ
+    fn run(wheel: &mut TimerWheel, ticks: u32) {
+        for _ in 0..ticks {
+            for callback in wheel.tick() {
+                callback();
+            }
+        }
+    }
+
+    // フラグが立ったティックを集める
+    fn fired_ticks(wheel: &mut TimerWheel, id: TimerId, ticks: u32) -> Vec<u32> {
+        let mut fired = Vec::new();
+        for _ in 0..ticks {
+            run(wheel, 1);
+            if wheel.take_fired(id) {
+                fired.push(wheel.now());
+            }
+        }
+        fired
+    }
+
+    #[test]
+    fn one_shot_fires_once() {
+        let mut wheel = TimerWheel::new();
+        let id = wheel.start_once(5, Action::Flag).unwrap();
+        assert!(wheel.is_active(id));
+        assert_eq!(fired_ticks(&mut wheel, id, 40), [5]);
+        assert!(!wheel.is_active(id));
+        // 満了したワンショットは取り消せない
+        assert!(!wheel.cancel(id));
+    }
+
+    #[test]
+    fn zero_delay_fires_next_tick() {
+        let mut wheel = TimerWheel::new();
+        let id = wheel.start_once(0, Action::Flag).unwrap();
+        assert_eq!(fired_ticks(&mut wheel, id, 3), [1]);
+    }
+
+    #[test]
+    fn periodic_and_long_delays() {
+        let mut wheel = TimerWheel::new();
+        let periodic = wheel.start_periodic(7, Action::Flag).unwrap();
+        // スロットの数より長いタイマは次の周回まで待つ
+        let long = wheel
+            .start_once(SLOTS as u32 * 2 + 3, Action::Flag)
+            .unwrap();
+        let mut periodic_ticks = Vec::new();
+        let mut long_ticks = Vec::new();
+        for _ in 0..40 {
+            run(&mut wheel, 1);
+            if wheel.take_fired(periodic) {
+                periodic_ticks.push(wheel.now());
+            }
+            if wheel.take_fired(long) {
+                long_ticks.push(wheel.now());
+            }
+        }
+        assert_eq!(periodic_ticks, [7, 14, 21, 28, 35]);
+        assert_eq!(long_ticks, [35]);
+    }
+
+    #[test]
+    fn flag_is_taken_once() {
+        let mut wheel = TimerWheel::new();
+        let id = wheel.start_periodic(2, Action::Flag).unwrap();
+        run(&mut wheel, 10);
+        assert!(wheel.take_fired(id));
+        assert!(!wheel.take_fired(id));
+    }
+
+    #[test]
+    fn cancel_clears_flag() {
+        let mut wheel = TimerWheel::new();
+        let id = wheel.start_periodic(2, Action::Flag).unwrap();
+        run(&mut wheel, 3);
+        assert!(wheel.cancel(id));
+        assert!(!wheel.take_fired(id));
+        run(&mut wheel, 10);
+        assert!(!wheel.take_fired(id));
+        assert!(!wheel.cancel(id));
+    }
+
+    #[test]
+    fn stale_handles() {
+        let mut wheel = TimerWheel::new();
+        let old = wheel.start_once(1, Action::Flag).unwrap();
+        run(&mut wheel, 1);
+        // 満了したワンショットの番号を、次のタイマが再利用する
+        let new = wheel.start_periodic(3, Action::Flag).unwrap();
+        assert_eq!(old.index, new.index);
+        // 古いフラグは新しいタイマに引き継がれず、古いハンドルでも取り出せない
+        assert!(!wheel.take_fired(new));
+        assert!(!wheel.take_fired(old));
+        // 古いハンドルで新しいタイマを取り消せない
+        assert!(!wheel.cancel(old));
+        assert!(!wheel.is_active(old));
+        run(&mut wheel, 3);
+        assert!(!wheel.take_fired(old));
+        assert!(wheel.take_fired(new));
+        // 取り消しに失敗しても新しいタイマのフラグは残る
+        run(&mut wheel, 3);
+        assert!(!wheel.cancel(old));
+        assert!(wheel.take_fired(new));
+    }
+
+    #[test]
+    fn cancel_keeps_other_timers_in_slot() {
+        let mut wheel = TimerWheel::new();
+        // 同じスロットに3つ並べて、真ん中を取り消す
+        let a = wheel.start_once(4, Action::Flag).unwrap();
+        let b = wheel.start_once(4 + SLOTS as u32, Action::Flag).unwrap();
+        let c = wheel.start_once(4, Action::Flag).unwrap();
+        assert!(wheel.cancel(b));
+        run(&mut wheel, 4);
+        assert!(wheel.take_fired(a));
+        assert!(wheel.take_fired(c));
+        run(&mut wheel, SLOTS as u32);
+        assert!(!wheel.take_fired(b));
+    }
+
+    #[test]
+    fn full() {
+        let mut wheel = TimerWheel::new();
+        for _ in 0..MAX_TIMERS {
+            wheel.start_periodic(1, Action::Flag).unwrap();
+        }
+        assert_eq!(wheel.start_once(1, Action::Flag), Err(TimerError::Full));
+    }
+
+    static CALLS: AtomicU32 = AtomicU32::new(0);
+
+    fn count() {
+        CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn callbacks_are_returned() {
+        let mut wheel = TimerWheel::new();
+        let id = wheel.start_periodic(5, Action::Callback(count)).unwrap();
+        wheel.start_once(5, Action::Callback(count)).unwrap();
+        let mut calls = 0;
+        for _ in 0..10 {
+            calls += wheel.tick().count();
+        }
+        assert_eq!(calls, 3);
+        // コールバックのタイマはフラグを立てない
+        assert!(!wheel.take_fired(id));
+    }
+
+    #[test]
+    fn callback_can_restart_timers() {
+        // 借用を返してから呼ぶので、コールバックの中でタイマを開始できる
+        let mut wheel = TimerWheel::new();
+        wheel.start_once(2, Action::Callback(count)).unwrap();
+        let mut restarted = None;
+        for _ in 0..2 {
+            for _callback in wheel.tick() {
+                restarted = Some(wheel.start_once(3, Action::Flag).unwrap());
+            }
+        }
+        let restarted = restarted.unwrap();
+        assert_eq!(fired_ticks(&mut wheel, restarted, 5), [5]);
+    }
+}
@@ -0,0 +1,258 @@
+// 割り込みハンドラと main() とで、割り込みを禁止せずにデータを受け渡すためのキューです
+// 割り込みハンドラでサンプルを書き込み、main() で読み出すような用途に向いています
+//
+//   static SAMPLES: SpscQueue<u16, 256> = SpscQueue::new();
+//   let (mut producer, mut consumer) = SAMPLES.split().unwrap();
+//   producer.enqueue(sample).ok();          // 割り込みハンドラ
+//   while let Some(sample) = consumer.dequeue() { ... }   // main()
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// 書き込み側と読み出し側が1つずつのロックフリーなキューです
+// 最大 N 個の要素を保持できます
+pub struct SpscQueue<T, const N: usize> {
+    buffer: UnsafeCell<[MaybeUninit<T>; N]>,
+    // 読み出し位置と書き込み位置は 0..2N の範囲で進め、N で割った余りを配列の添字にする
+    // (0..N にすると、空のときといっぱいのときを区別できない)
+    head: AtomicUsize, // 読み出し側だけが書き換える
+    tail: AtomicUsize, // 書き込み側だけが書き換える
+    split: AtomicBool,
+}
+
+// Producer と Consumer がそれぞれ1つしかないことを split で保証しているので、
+// 別々のコンテキストから同時に使っても同じ要素に触れることはない
+unsafe impl<T: Send, const N: usize> Sync for SpscQueue<T, N> {}
+
+impl<T, const N: usize> SpscQueue<T, N> {
+    const UNINIT: MaybeUninit<T> = MaybeUninit::uninit();
+
+    pub const fn new() -> Self {
+        SpscQueue {
+            buffer: UnsafeCell::new([Self::UNINIT; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            split: AtomicBool::new(false),
+        }
+    }
+
+    // 書き込み側と読み出し側に分けます
+    // 2回目以降の呼び出しは None を返します
+    pub fn split(&self) -> Option<(Producer<'_, T, N>, Consumer<'_, T, N>)> {
+        if self.split.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some((Producer { queue: self }, Consumer { queue: self }))
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        distance(head, tail, N)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn slot(&self, position: usize) -> *mut MaybeUninit<T> {
+        unsafe { (*self.buffer.get()).as_mut_ptr().add(position % N) }
+    }
+}
+
+impl<T, const N: usize> Default for SpscQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn advance(position: usize, n: usize) -> usize {
+    if position + 1 == 2 * n {
+        0
+    } else {
+        position + 1
+    }
+}
+
+fn distance(head: usize, tail: usize, n: usize) -> usize {
+    if tail >= head {
+        tail - head
+    } else {
+        tail + 2 * n - head
+    }
+}
+
+impl<T, const N: usize> Drop for SpscQueue<T, N> {
+    fn drop(&mut self) {
+        let head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        let mut position = head;
+        while position != tail {
+            unsafe { (*self.slot(position)).as_mut_ptr().drop_in_place() };
+            position = advance(position, N);
+        }
+    }
+}
+
+// キューの書き込み側
+pub struct Producer<'a, T, const N: usize> {
+    queue: &'a SpscQueue<T, N>,
+}
+
+unsafe impl<'a, T: Send, const N: usize> Send for Producer<'a, T, N> {}
+
+impl<'a, T, const N: usize> Producer<'a, T, N> {
+    // キューがいっぱいのときは書き込まずに value を返します
+    pub fn enqueue(&mut self, value: T) -> Result<(), T> {
+        let queue = self.queue;
+        let tail = queue.tail.load(Ordering::Relaxed);
+        let head = queue.head.load(Ordering::Acquire);
+        if distance(head, tail, N) >= N {
+            return Err(value);
+        }
+        unsafe { (*queue.slot(tail)).as_mut_ptr().write(value) };
+        // 要素を書き終えてから、読み出し側に見えるようにする
+        queue.tail.store(advance(tail, N), Ordering::Release);
+        Ok(())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.queue.len() >= N
+    }
+}
+
+// キューの読み出し側
+pub struct Consumer<'a, T, const N: usize> {
+    queue: &'a SpscQueue<T, N>,
+}
+
+unsafe impl<'a, T: Send, const N: usize> Send for Consumer<'a, T, N> {}
+
+impl<'a, T, const N: usize> Consumer<'a, T, N> {
+    pub fn dequeue(&mut self) -> Option<T> {
+        let queue = self.queue;
+        let head = queue.head.load(Ordering::Relaxed);
+        let tail = queue.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let value = unsafe { (*queue.slot(head)).as_ptr().read() };
+        // 要素を読み終えてから、書き込み側が上書きできるようにする
+        queue.head.store(advance(head, N), Ordering::Release);
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn split_only_once() {
+        let queue: SpscQueue<u8, 4> = SpscQueue::new();
+        assert!(queue.split().is_some());
+        assert!(queue.split().is_none());
+    }
+
+    #[test]
+    fn full_and_empty() {
+        let queue: SpscQueue<u8, 3> = SpscQueue::new();
+        let (mut producer, mut consumer) = queue.split().unwrap();
+        assert!(consumer.is_empty());
+        assert_eq!(consumer.dequeue(), None);
+        for i in 0..3 {
+            assert_eq!(producer.enqueue(i), Ok(()));
+        }
+        assert!(producer.is_full());
+        assert_eq!(consumer.len(), 3);
+        // いっぱいのときは書き込まずに返す
+        assert_eq!(producer.enqueue(9), Err(9));
+        assert_eq!(consumer.dequeue(), Some(0));
+        assert!(!producer.is_full());
+        assert_eq!(producer.enqueue(3), Ok(()));
+        assert_eq!(consumer.dequeue(), Some(1));
+        assert_eq!(consumer.dequeue(), Some(2));
+        assert_eq!(consumer.dequeue(), Some(3));
+        assert_eq!(consumer.dequeue(), None);
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn wrap_around() {
+        // 読み書きの位置が 2N を何周もしても順番が崩れない
+        let queue: SpscQueue<u32, 5> = SpscQueue::new();
+        let (mut producer, mut consumer) = queue.split().unwrap();
+        let mut next_write = 0;
+        let mut next_read = 0;
+        for round in 0..100 {
+            for _ in 0..(round % 5 + 1) {
+                producer.enqueue(next_write).unwrap();
+                next_write += 1;
+            }
+            assert_eq!(consumer.len(), (next_write - next_read) as usize);
+            while let Some(value) = consumer.dequeue() {
+                assert_eq!(value, next_read);
+                next_read += 1;
+            }
+        }
+        assert_eq!(next_read, next_write);
+    }
+
+    #[test]
+    fn drops_remaining_values() {
+        let value = Rc::new(());
+        {
+            let queue: SpscQueue<Rc<()>, 4> = SpscQueue::new();
+            let (mut producer, mut consumer) = queue.split().unwrap();
+            for _ in 0..4 {
+                producer.enqueue(value.clone()).unwrap();
+            }
+            drop(consumer.dequeue());
+            assert_eq!(Rc::strong_count(&value), 4);
+        }
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn concurrent_push_pop() {
+        const COUNT: u32 = 200_000;
+        let queue: SpscQueue<u32, 16> = SpscQueue::new();
+        let (mut producer, mut consumer) = queue.split().unwrap();
+        let received = thread::scope(|scope| {
+            scope.spawn(move || {
+                for i in 0..COUNT {
+                    let mut value = i;
+                    while let Err(v) = producer.enqueue(value) {
+                        value = v;
+                        thread::yield_now();
+                    }
+                }
+            });
+            let mut received = Vec::with_capacity(COUNT as usize);
+            while received.len() < COUNT as usize {
+                match consumer.dequeue() {
+                    Some(value) => received.push(value),
+                    None => thread::yield_now(),
+                }
+            }
+            received
+        });
+        assert!(received.iter().copied().eq(0..COUNT));
+    }
+}
@@ -0,0 +1,351 @@
+// ハードウェアに依存しないストップウォッチとカウントダウンタイマです
+// 時刻はすべて呼び出し側から `now_ms` (単調増加する時刻 [ms]) として渡します
+// 時刻はu32の範囲で一周しても構いません
+
+use core::fmt;
+use heapless::consts::*;
+use heapless::Vec;
+
+// 記録できるラップの数
+pub type MaxLaps = U16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopwatchState {
+    Idle,    // リセット直後
+    Running, // 計測中
+    Stopped, // 一時停止中 (再開できる)
+}
+
+// ラップの記録
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lap {
+    pub number: u32,   // 1から始まる番号
+    pub lap_ms: u32,   // 前回のラップからの時間
+    pub split_ms: u32, // 計測開始からの時間
+}
+
+pub struct Stopwatch {
+    state: StopwatchState,
+    started_at: u32,     // 計測を (再) 開始した時刻
+    accumulated_ms: u32, // 一時停止するまでに計測した時間
+    last_split_ms: u32,  // 直前のラップの split_ms
+    lap_count: u32,
+    laps: Vec<Lap, MaxLaps>,
+}
+
+impl Stopwatch {
+    pub fn new() -> Self {
+        Stopwatch {
+            state: StopwatchState::Idle,
+            started_at: 0,
+            accumulated_ms: 0,
+            last_split_ms: 0,
+            lap_count: 0,
+            laps: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> StopwatchState {
+        self.state
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state == StopwatchState::Running
+    }
+
+    // 計測を開始します (一時停止中なら再開します)
+    pub fn start(&mut self, now_ms: u32) {
+        if self.state != StopwatchState::Running {
+            self.started_at = now_ms;
+            self.state = StopwatchState::Running;
+        }
+    }
+
+    // 計測を一時停止します
+    pub fn stop(&mut self, now_ms: u32) {
+        if self.state == StopwatchState::Running {
+            self.accumulated_ms = self.elapsed_ms(now_ms);
+            self.state = StopwatchState::Stopped;
+        }
+    }
+
+    // 計測した時間とラップを消去します
+    pub fn reset(&mut self) {
+        *self = Stopwatch::new();
+    }
+
+    // 計測を開始してからの時間 (一時停止していた時間は含まない)
+    pub fn elapsed_ms(&self, now_ms: u32) -> u32 {
+        match self.state {
+            StopwatchState::Running => self
+                .accumulated_ms
+                .wrapping_add(now_ms.wrapping_sub(self.started_at)),
+            _ => self.accumulated_ms,
+        }
+    }
+
+    // 計測を止めずに、計測開始からの時間を返します
+    pub fn split(&self, now_ms: u32) -> u32 {
+        self.elapsed_ms(now_ms)
+    }
+
+    // ラップを記録します (計測中でないときは何もしません)
+    // 記録できる数を超えたときは古いラップから捨てます
+    pub fn lap(&mut self, now_ms: u32) -> Option<Lap> {
+        if self.state != StopwatchState::Running {
+            return None;
+        }
+        let split_ms = self.elapsed_ms(now_ms);
+        self.lap_count += 1;
+        let lap = Lap {
+            number: self.lap_count,
+            lap_ms: split_ms.wrapping_sub(self.last_split_ms),
+            split_ms,
+        };
+        self.last_split_ms = split_ms;
+        if self.laps.len() == self.laps.capacity() {
+            // 一番古いラップを末尾に回してから取り除く
+            self.laps.rotate_left(1);
+            self.laps.pop();
+        }
+        self.laps.push(lap).ok();
+        Some(lap)
+    }
+
+    // 記録したラップ (古い順)
+    pub fn laps(&self) -> &[Lap] {
+        &self.laps
+    }
+
+    // 直前のラップからの時間
+    pub fn current_lap_ms(&self, now_ms: u32) -> u32 {
+        self.elapsed_ms(now_ms).wrapping_sub(self.last_split_ms)
+    }
+}
+
+impl Default for Stopwatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountdownState {
+    Idle,
+    Running,
+    Paused,
+    Expired, // 0になってアラームを呼び出した
+}
+
+// 設定した時間から0まで数えて、0になったらアラームを呼び出すタイマです
+pub struct Countdown<F> {
+    duration_ms: u32,
+    state: CountdownState,
+    started_at: u32,
+    remaining_at_start: u32, // 開始 (再開) した時点の残り時間
+    alarm: F,
+}
+
+impl<F: FnMut()> Countdown<F> {
+    pub fn new(duration_ms: u32, alarm: F) -> Self {
+        Countdown {
+            duration_ms,
+            state: CountdownState::Idle,
+            started_at: 0,
+            remaining_at_start: duration_ms,
+            alarm,
+        }
+    }
+
+    pub fn state(&self) -> CountdownState {
+        self.state
+    }
+
+    // 時間を設定し直します (カウントダウンは止まります)
+    pub fn set_duration(&mut self, duration_ms: u32) {
+        self.duration_ms = duration_ms;
+        self.reset();
+    }
+
+    pub fn start(&mut self, now_ms: u32) {
+        match self.state {
+            CountdownState::Idle | CountdownState::Paused => {
+                self.started_at = now_ms;
+                self.state = CountdownState::Running;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn pause(&mut self, now_ms: u32) {
+        if self.state == CountdownState::Running {
+            self.remaining_at_start = self.remaining_ms(now_ms);
+            self.state = CountdownState::Paused;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.state = CountdownState::Idle;
+        self.remaining_at_start = self.duration_ms;
+    }
+
+    pub fn remaining_ms(&self, now_ms: u32) -> u32 {
+        match self.state {
+            CountdownState::Running => self
+                .remaining_at_start
+                .saturating_sub(now_ms.wrapping_sub(self.started_at)),
+            CountdownState::Expired => 0,
+            _ => self.remaining_at_start,
+        }
+    }
+
+    // 定期的に呼び出してください
+    // 0になったときに1回だけアラームを呼び出し、true を返します
+    pub fn update(&mut self, now_ms: u32) -> bool {
+        if self.state == CountdownState::Running && self.remaining_ms(now_ms) == 0 {
+            self.state = CountdownState::Expired;
+            (self.alarm)();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// 時間 [ms] を "mm:ss.cc" (分:秒.1/100秒) の形式で表示します
+// 100分以上のときは分の桁が増えます
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinSecCenti(pub u32);
+
+impl fmt::Display for MinSecCenti {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let centis = self.0 / 10;
+        let minutes = centis / 6000;
+        let seconds = centis / 100 % 60;
+        let centis = centis % 100;
+        write!(f, "{:02}:{:02}.{:02}", minutes, seconds, centis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+    use std::string::ToString;
+
+    #[test]
+    fn elapsed_excludes_pauses() {
+        let mut stopwatch = Stopwatch::new();
+        assert_eq!(stopwatch.elapsed_ms(500), 0);
+        stopwatch.start(1000);
+        assert_eq!(stopwatch.elapsed_ms(1500), 500);
+        stopwatch.stop(2000);
+        assert_eq!(stopwatch.state(), StopwatchState::Stopped);
+        assert_eq!(stopwatch.elapsed_ms(9000), 1000);
+        stopwatch.start(10_000);
+        assert_eq!(stopwatch.elapsed_ms(10_250), 1250);
+        // 計測中にもう一度 start しても開始時刻は変わらない
+        stopwatch.start(10_500);
+        assert_eq!(stopwatch.split(11_000), 2000);
+        stopwatch.reset();
+        assert_eq!(stopwatch.state(), StopwatchState::Idle);
+        assert_eq!(stopwatch.elapsed_ms(12_000), 0);
+    }
+
+    #[test]
+    fn time_wraparound() {
+        let mut stopwatch = Stopwatch::new();
+        stopwatch.start(u32::MAX - 99);
+        assert_eq!(stopwatch.elapsed_ms(200), 300);
+        assert_eq!(stopwatch.lap(200).unwrap().split_ms, 300);
+    }
+
+    #[test]
+    fn lap_and_split() {
+        let mut stopwatch = Stopwatch::new();
+        assert_eq!(stopwatch.lap(0), None);
+        stopwatch.start(0);
+        let first = stopwatch.lap(1200).unwrap();
+        assert_eq!(
+            first,
+            Lap {
+                number: 1,
+                lap_ms: 1200,
+                split_ms: 1200,
+            }
+        );
+        // 一時停止していた時間はラップに含めない
+        stopwatch.stop(2000);
+        assert_eq!(stopwatch.lap(2500), None);
+        stopwatch.start(5000);
+        assert_eq!(stopwatch.current_lap_ms(5300), 1100);
+        let second = stopwatch.lap(5300).unwrap();
+        assert_eq!(
+            second,
+            Lap {
+                number: 2,
+                lap_ms: 1100,
+                split_ms: 2300,
+            }
+        );
+        assert_eq!(stopwatch.current_lap_ms(5300), 0);
+        assert_eq!(stopwatch.laps(), [first, second]);
+    }
+
+    #[test]
+    fn laps_roll_over() {
+        let mut stopwatch = Stopwatch::new();
+        stopwatch.start(0);
+        let capacity = stopwatch.laps.capacity();
+        let total = capacity as u32 + 3;
+        for i in 1..=total {
+            stopwatch.lap(i * 100);
+        }
+        // 古い3つが捨てられ、残りは古い順に並ぶ
+        let laps = stopwatch.laps();
+        assert_eq!(laps.len(), capacity);
+        for (lap, number) in laps.iter().zip(4..) {
+            assert_eq!(lap.number, number);
+            assert_eq!(lap.lap_ms, 100);
+            assert_eq!(lap.split_ms, number * 100);
+        }
+        assert_eq!(laps.last().unwrap().number, total);
+    }
+
+    #[test]
+    fn countdown_fires_once() {
+        let alarms = Cell::new(0);
+        let mut countdown = Countdown::new(1000, || alarms.set(alarms.get() + 1));
+        assert!(!countdown.update(5000));
+        countdown.start(0);
+        assert!(!countdown.update(999));
+        assert_eq!(countdown.remaining_ms(400), 600);
+        assert!(countdown.update(1000));
+        assert!(!countdown.update(2000));
+        assert_eq!(countdown.state(), CountdownState::Expired);
+        assert_eq!(countdown.remaining_ms(2000), 0);
+        assert_eq!(alarms.get(), 1);
+    }
+
+    #[test]
+    fn countdown_pause_and_reset() {
+        let mut countdown = Countdown::new(1000, || {});
+        countdown.start(0);
+        countdown.pause(300);
+        assert_eq!(countdown.state(), CountdownState::Paused);
+        assert_eq!(countdown.remaining_ms(5000), 700);
+        countdown.start(5000);
+        assert!(!countdown.update(5699));
+        assert!(countdown.update(5700));
+        countdown.set_duration(2000);
+        assert_eq!(countdown.state(), CountdownState::Idle);
+        assert_eq!(countdown.remaining_ms(0), 2000);
+    }
+
+    #[test]
+    fn format_min_sec_centi() {
+        assert_eq!(MinSecCenti(0).to_string(), "00:00.00");
+        assert_eq!(MinSecCenti(61_239).to_string(), "01:01.23");
+        assert_eq!(MinSecCenti(6_000_000).to_string(), "100:00.00");
+    }
+}
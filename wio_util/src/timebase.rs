@@ -0,0 +1,231 @@
+// 32.768[kHz]のカウンタで数えた時刻と時間の長さです
+// カウンタの読み出しはハードウェアに依存するので、wio-examples の timebase で行います
+// ここでは単位の変換と、32bitカウンタを64bitに拡張する計算だけを扱います
+
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+
+// カウンタの周波数 [Hz]
+pub const TICK_HZ: u64 = 32_768;
+
+// 時間の長さ (カウンタのティック数で保持する)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Duration {
+    ticks: u64,
+}
+
+impl Duration {
+    pub const ZERO: Duration = Duration { ticks: 0 };
+
+    pub const fn from_ticks(ticks: u64) -> Self {
+        Duration { ticks }
+    }
+
+    // ティックの単位に切り上げる
+    pub const fn from_micros(micros: u64) -> Self {
+        Duration::from_ticks((micros * TICK_HZ).div_ceil(1_000_000))
+    }
+
+    pub const fn from_millis(millis: u64) -> Self {
+        Duration::from_ticks((millis * TICK_HZ).div_ceil(1000))
+    }
+
+    pub const fn from_secs(secs: u64) -> Self {
+        Duration::from_ticks(secs * TICK_HZ)
+    }
+
+    pub const fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    // 単位未満は切り捨てる
+    pub const fn as_micros(&self) -> u64 {
+        self.ticks * 1_000_000 / TICK_HZ
+    }
+
+    pub const fn as_millis(&self) -> u64 {
+        self.ticks * 1000 / TICK_HZ
+    }
+
+    pub const fn as_secs(&self) -> u64 {
+        self.ticks / TICK_HZ
+    }
+
+    pub fn checked_sub(self, rhs: Duration) -> Option<Duration> {
+        self.ticks.checked_sub(rhs.ticks).map(Duration::from_ticks)
+    }
+
+    pub fn saturating_sub(self, rhs: Duration) -> Duration {
+        Duration::from_ticks(self.ticks.saturating_sub(rhs.ticks))
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, rhs: Duration) -> Duration {
+        Duration::from_ticks(self.ticks + rhs.ticks)
+    }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, rhs: Duration) -> Duration {
+        Duration::from_ticks(self.ticks - rhs.ticks)
+    }
+}
+
+impl SubAssign for Duration {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+// ある時点の時刻 (TimeBase を作ってからのティック数)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    ticks: u64,
+}
+
+impl Instant {
+    pub const fn from_ticks(ticks: u64) -> Self {
+        Instant { ticks }
+    }
+
+    pub const fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    // earlier からの経過時間 (earlier の方が後の時刻なら0)
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_ticks(self.ticks.saturating_sub(earlier.ticks))
+    }
+
+    // 起動してからの経過時間
+    pub fn since_start(&self) -> Duration {
+        Duration::from_ticks(self.ticks)
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Duration {
+        self.duration_since(rhs)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Instant {
+        Instant::from_ticks(self.ticks + rhs.ticks)
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, rhs: Duration) -> Instant {
+        Instant::from_ticks(self.ticks - rhs.ticks)
+    }
+}
+
+// 32bitカウンタの値を64bitに拡張する
+// overflows: 割り込みハンドラで数えたオーバーフローの回数
+// pending:   オーバーフローしたが、まだ割り込みハンドラで数えていないか
+// count:     カウンタの値 (pending のときはオーバーフローの後に読み直した値)
+pub fn extend(overflows: u32, pending: bool, count: u32) -> Instant {
+    let high = if pending {
+        overflows.wrapping_add(1)
+    } else {
+        overflows
+    };
+    Instant::from_ticks(((high as u64) << 32) | count as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions_round_up_to_ticks() {
+        // 1[us]は1ティックに満たないが、切り上げて1ティックにする
+        assert_eq!(Duration::from_micros(0).ticks(), 0);
+        assert_eq!(Duration::from_micros(1).ticks(), 1);
+        assert_eq!(Duration::from_micros(30).ticks(), 1);
+        assert_eq!(Duration::from_micros(31).ticks(), 2);
+        // 1[ms]は32.768ティック
+        assert_eq!(Duration::from_millis(1).ticks(), 33);
+        assert_eq!(Duration::from_millis(1000).ticks(), TICK_HZ);
+        assert_eq!(Duration::from_millis(125).ticks(), 4096);
+        assert_eq!(Duration::from_secs(2).ticks(), 2 * TICK_HZ);
+    }
+
+    #[test]
+    fn conversions_truncate_to_units() {
+        let duration = Duration::from_ticks(TICK_HZ + TICK_HZ / 2 - 1);
+        assert_eq!(duration.as_secs(), 1);
+        assert_eq!(duration.as_millis(), 1499);
+        assert_eq!(duration.as_micros(), 1_499_969);
+        // 切り上げたティックを戻すと、元の値以上で1ティック分を超えない
+        for millis in [1, 7, 999, 12_345] {
+            let duration = Duration::from_millis(millis);
+            assert!(duration.as_micros() >= millis * 1000);
+            assert!(duration.as_micros() < millis * 1000 + 31);
+        }
+    }
+
+    #[test]
+    fn duration_arithmetic() {
+        let a = Duration::from_ticks(100);
+        let b = Duration::from_ticks(30);
+        assert_eq!(a + b, Duration::from_ticks(130));
+        assert_eq!(a - b, Duration::from_ticks(70));
+        assert_eq!(b.checked_sub(a), None);
+        assert_eq!(b.saturating_sub(a), Duration::ZERO);
+        let mut c = a;
+        c += b;
+        c -= Duration::from_ticks(10);
+        assert_eq!(c, Duration::from_ticks(120));
+    }
+
+    #[test]
+    fn instant_arithmetic() {
+        let start = Instant::from_ticks(1000);
+        let later = start + Duration::from_millis(1);
+        assert_eq!(later - start, Duration::from_ticks(33));
+        assert_eq!(later - Duration::from_ticks(33), start);
+        // 前後を逆にすると0
+        assert_eq!(start - later, Duration::ZERO);
+        assert_eq!(start.duration_since(later), Duration::ZERO);
+        assert_eq!(later.since_start(), Duration::from_ticks(1033));
+    }
+
+    #[test]
+    fn extend_across_rollover() {
+        // 32bitカウンタが一周する直前
+        let before = extend(0, false, 0xFFFF_FFFF);
+        assert_eq!(before.ticks(), 0xFFFF_FFFF);
+        // 一周したが、割り込みハンドラではまだ数えていない
+        let pending = extend(0, true, 5);
+        assert_eq!(pending.ticks(), 0x1_0000_0005);
+        // 割り込みハンドラで数えた後も同じ時刻になる
+        let counted = extend(1, false, 5);
+        assert_eq!(counted, pending);
+        assert_eq!(counted - before, Duration::from_ticks(6));
+        assert_eq!(extend(7, true, 0).ticks(), 8 << 32);
+    }
+}
@@ -0,0 +1,52 @@
+// 周波数が上下する音のウォーターフォールをシミュレータで表示するサンプルです
+// ウィンドウをクリックすると色が切り替わります
+// `cargo run --example sim --features sim`
+
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use embedded_graphics_simulator::*;
+use std::{thread, time};
+use wio_waterfall::{Colormap, Waterfall};
+
+const SAMPLE_RATE: f32 = 20833.0;
+const BINS: usize = 128;
+
+fn main() {
+    let mut display: SimulatorDisplay<Rgb565> = SimulatorDisplay::new(Size::new(320, 240));
+    let output_settings = OutputSettingsBuilder::new().build();
+    let mut window = Window::new("Waterfall", &output_settings);
+
+    let mut waterfall: Waterfall<BINS, 228> =
+        Waterfall::new(Point::new(32, 0), 2, Colormap::Viridis);
+    waterfall.draw_axis(&mut display, SAMPLE_RATE).unwrap();
+
+    let interval = time::Duration::from_millis(30);
+    let mut frame = 0u32;
+    'running: loop {
+        for event in window.events() {
+            match event {
+                SimulatorEvent::Quit => break 'running,
+                SimulatorEvent::MouseButtonUp { .. } => waterfall.next_colormap(),
+                _ => {}
+            }
+        }
+
+        // ゆっくり上下する基音と、その2倍音
+        let t = frame as f32 * 0.05;
+        let fundamental = 40.0 + 25.0 * t.sin();
+        let levels = (0..BINS).map(|bin| {
+            let bin = bin as f32;
+            let noise = ((bin * 12.9898 + frame as f32 * 78.233).sin() * 43758.545).fract();
+            let tone = |center: f32, level: f32| level - 6.0 * (bin - center) * (bin - center);
+            let floor = -85.0 + 10.0 * noise.abs();
+            floor
+                .max(tone(fundamental, -35.0))
+                .max(tone(fundamental * 2.0, -50.0))
+        });
+        waterfall.push(levels);
+        waterfall.draw(&mut display).unwrap();
+        window.update(&display);
+
+        frame += 1;
+        thread::sleep(interval);
+    }
+}
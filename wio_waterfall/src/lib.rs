@@ -0,0 +1,483 @@
+#![cfg_attr(not(test), no_std)]
+
+// スペクトラムを時間方向に並べて表示するウォーターフォール (スペクトログラム) です
+// 1フレーム分のレベル [dBFS] を1行の色に変換し、新しい行を上に追加して古い行を下へ送ります
+// 上部には周波数軸を表示します
+//
+// DrawTarget<Rgb565> に描画するので、実機でもシミュレータでも同じように動きます
+//
+//   let mut waterfall: Waterfall<128, 200> = Waterfall::new(Point::new(32, 0), 2, Colormap::Viridis);
+//   waterfall.push(spectrum.levels());
+//   waterfall.draw(&mut display)?;
+
+use core::fmt::{self, Write};
+use embedded_graphics::{
+    egline, egrectangle, egtext,
+    fonts::Font6x8,
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::*,
+    primitive_style, text_style,
+};
+
+// 周波数軸の高さ [px]
+pub const AXIS_HEIGHT: i32 = 12;
+
+const AXIS_COLOR: Rgb565 = Rgb565::WHITE;
+const BG_COLOR: Rgb565 = Rgb565::BLACK;
+// 周波数軸のラベルの最小の間隔 [px]
+const LABEL_SPACING: i32 = 40;
+const LABEL_STEPS_HZ: [u32; 10] = [
+    100, 200, 500, 1_000, 2_000, 5_000, 10_000, 20_000, 50_000, 100_000,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colormap {
+    Viridis,   // 紫→緑→黄
+    Inferno,   // 黒→赤→黄
+    Grayscale, // 黒→白
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 3] = [Colormap::Viridis, Colormap::Inferno, Colormap::Grayscale];
+
+    // 0〜255の値を色に変換します
+    pub fn color(&self, value: u8) -> Rgb565 {
+        // 9点の代表色の間を直線で補間する
+        const VIRIDIS: [(u8, u8, u8); 9] = [
+            (68, 1, 84),
+            (71, 44, 122),
+            (59, 81, 139),
+            (44, 113, 142),
+            (33, 144, 141),
+            (39, 173, 129),
+            (92, 200, 99),
+            (170, 220, 50),
+            (253, 231, 37),
+        ];
+        const INFERNO: [(u8, u8, u8); 9] = [
+            (0, 0, 4),
+            (31, 12, 72),
+            (85, 15, 109),
+            (136, 34, 106),
+            (186, 54, 85),
+            (227, 89, 51),
+            (249, 140, 10),
+            (249, 201, 50),
+            (252, 255, 164),
+        ];
+        let table = match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Inferno => &INFERNO,
+            Colormap::Grayscale => return Rgb888::new(value, value, value).into(),
+        };
+
+        let position = value as u32 * (table.len() as u32 - 1);
+        let index = (position / 255) as usize;
+        let fraction = position % 255;
+        let (r0, g0, b0) = table[index];
+        let (r1, g1, b1) = table[(index + 1).min(table.len() - 1)];
+        let mix = |a: u8, b: u8| (a as u32 * (255 - fraction) + b as u32 * fraction) / 255;
+        Rgb888::new(mix(r0, r1) as u8, mix(g0, g1) as u8, mix(b0, b1) as u8).into()
+    }
+}
+
+// BINS 個のビンを ROWS 行分保持するウォーターフォール
+// 描画する大きさは 幅 BINS * bin_width [px]、高さ AXIS_HEIGHT + ROWS [px] です
+pub struct Waterfall<const BINS: usize, const ROWS: usize> {
+    top_left: Point,
+    bin_width: u32,
+    colormap: Colormap,
+    palette: [Rgb565; 256], // 値から色への変換表
+    floor_dbfs: f32,
+    ceiling_dbfs: f32,
+    rows: [[u8; BINS]; ROWS], // 色に変換する前の値 (0〜255)
+    newest: usize,            // 最も新しい行
+    count: usize,             // 追加した行の数
+}
+
+impl<const BINS: usize, const ROWS: usize> Waterfall<BINS, ROWS> {
+    pub fn new(top_left: Point, bin_width: u32, colormap: Colormap) -> Self {
+        let mut waterfall = Waterfall {
+            top_left,
+            bin_width: bin_width.max(1),
+            colormap,
+            palette: [BG_COLOR; 256],
+            floor_dbfs: -90.0,
+            ceiling_dbfs: -30.0,
+            rows: [[0; BINS]; ROWS],
+            newest: 0,
+            count: 0,
+        };
+        waterfall.set_colormap(colormap);
+        waterfall
+    }
+
+    pub fn colormap(&self) -> Colormap {
+        self.colormap
+    }
+
+    // 色を変えます (次の draw から全体に反映されます)
+    pub fn set_colormap(&mut self, colormap: Colormap) {
+        self.colormap = colormap;
+        for (value, color) in self.palette.iter_mut().enumerate() {
+            *color = colormap.color(value as u8);
+        }
+    }
+
+    // 次の色に切り替えます
+    pub fn next_colormap(&mut self) {
+        let index = Colormap::ALL
+            .iter()
+            .position(|&colormap| colormap == self.colormap)
+            .unwrap_or(0);
+        self.set_colormap(Colormap::ALL[(index + 1) % Colormap::ALL.len()]);
+    }
+
+    // floor_dbfs 以下を色の最小値、ceiling_dbfs 以上を最大値にします
+    pub fn set_range(&mut self, floor_dbfs: f32, ceiling_dbfs: f32) {
+        self.floor_dbfs = floor_dbfs;
+        self.ceiling_dbfs = ceiling_dbfs;
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(
+            BINS as u32 * self.bin_width,
+            AXIS_HEIGHT as u32 + ROWS as u32,
+        )
+    }
+
+    // 表示している行を消します
+    pub fn clear(&mut self) {
+        self.count = 0;
+    }
+
+    // 1フレーム分のレベル [dBFS] を一番上の行として追加します
+    // BINS 個より多いレベルは無視し、少ないときは残りを最小値にします
+    pub fn push<I: IntoIterator<Item = f32>>(&mut self, levels: I) {
+        self.newest = (self.newest + 1) % ROWS;
+        let range = self.ceiling_dbfs - self.floor_dbfs;
+        let mut levels = levels.into_iter();
+        for value in self.rows[self.newest].iter_mut() {
+            *value = match levels.next() {
+                Some(level) => {
+                    let ratio = (level - self.floor_dbfs) / range;
+                    if ratio <= 0.0 {
+                        0
+                    } else if ratio >= 1.0 {
+                        255
+                    } else {
+                        (ratio * 255.0) as u8
+                    }
+                }
+                None => 0,
+            };
+        }
+        if self.count < ROWS {
+            self.count += 1;
+        }
+    }
+
+    // 行をすべて描画します
+    // 新しい行が上、古い行が下になります
+    pub fn draw<D: DrawTarget<Rgb565>>(&self, display: &mut D) -> Result<(), D::Error> {
+        let left = self.top_left.x;
+        let top = self.top_left.y + AXIS_HEIGHT;
+        let bin_width = self.bin_width as usize;
+        let pixels = (0..ROWS).flat_map(move |y| {
+            let row = if y < self.count {
+                Some(&self.rows[(self.newest + ROWS - y) % ROWS])
+            } else {
+                None
+            };
+            (0..BINS * bin_width).map(move |x| {
+                let color = match row {
+                    Some(row) => self.palette[row[x / bin_width] as usize],
+                    None => BG_COLOR,
+                };
+                Pixel(Point::new(left + x as i32, top + y as i32), color)
+            })
+        });
+        display.draw_iter(pixels)
+    }
+
+    // 周波数軸を描画します
+    // sample_rate はFFTしたサンプルのサンプリングレート [Hz] (FFTの点数は BINS * 2 とします)
+    pub fn draw_axis<D: DrawTarget<Rgb565>>(
+        &self,
+        display: &mut D,
+        sample_rate: f32,
+    ) -> Result<(), D::Error> {
+        let width = (BINS as u32 * self.bin_width) as i32;
+        let left = self.top_left.x;
+        let top = self.top_left.y;
+        let bottom = top + AXIS_HEIGHT - 1;
+        egrectangle!(
+            top_left = (left, top),
+            bottom_right = (left + width - 1, bottom),
+            style = primitive_style!(fill_color = BG_COLOR)
+        )
+        .draw(display)?;
+
+        // 1[px] あたりの周波数から、ラベルが重ならない間隔を選ぶ
+        let hz_per_px = sample_rate / 2.0 / width as f32;
+        let step = LABEL_STEPS_HZ
+            .iter()
+            .copied()
+            .find(|&step| step as f32 / hz_per_px >= LABEL_SPACING as f32)
+            .unwrap_or(LABEL_STEPS_HZ[LABEL_STEPS_HZ.len() - 1]);
+
+        let mut frequency = 0;
+        while (frequency as f32) < sample_rate / 2.0 {
+            let x = left + (frequency as f32 / hz_per_px) as i32;
+            egline!(
+                start = (x, bottom - 2),
+                end = (x, bottom),
+                style = primitive_style!(stroke_color = AXIS_COLOR, stroke_width = 1)
+            )
+            .draw(display)?;
+
+            let mut label = Label::new();
+            write_frequency(&mut label, frequency).ok();
+            let label_width = label.len() as i32 * 6;
+            // ラベルはティックの中央に置き、はみ出すものは表示しない
+            let label_left = (x - label_width / 2).max(left);
+            if label_left + label_width <= left + width {
+                egtext!(
+                    text = label.as_str(),
+                    top_left = (label_left, top),
+                    style = text_style!(font = Font6x8, text_color = AXIS_COLOR)
+                )
+                .draw(display)?;
+            }
+            frequency += step;
+        }
+        Ok(())
+    }
+}
+
+// "500", "2k", "1.5k" の形式で周波数を書く
+fn write_frequency<W: Write>(w: &mut W, hz: u32) -> fmt::Result {
+    if hz < 1000 {
+        write!(w, "{}", hz)
+    } else if hz.is_multiple_of(1000) {
+        write!(w, "{}k", hz / 1000)
+    } else {
+        write!(w, "{}.{}k", hz / 1000, hz % 1000 / 100)
+    }
+}
+
+// ラベル用の固定長の文字列
+struct Label {
+    buffer: [u8; 8],
+    len: usize,
+}
+
+impl Label {
+    fn new() -> Self {
+        Label {
+            buffer: [0; 8],
+            len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buffer[..self.len]).unwrap_or("")
+    }
+}
+
+impl Write for Label {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > self.buffer.len() {
+            return Err(fmt::Error);
+        }
+        self.buffer[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    // 描画されたピクセルを記録する
+    struct Recorder {
+        pixels: Vec<Pixel<Rgb565>>,
+    }
+
+    impl Recorder {
+        fn new() -> Self {
+            Recorder { pixels: Vec::new() }
+        }
+
+        // 最後に描画された色
+        fn color_at(&self, x: i32, y: i32) -> Option<Rgb565> {
+            self.pixels
+                .iter()
+                .rev()
+                .find(|pixel| pixel.0 == Point::new(x, y))
+                .map(|pixel| pixel.1)
+        }
+    }
+
+    impl DrawTarget<Rgb565> for Recorder {
+        type Error = core::convert::Infallible;
+
+        fn draw_pixel(&mut self, pixel: Pixel<Rgb565>) -> Result<(), Self::Error> {
+            self.pixels.push(pixel);
+            Ok(())
+        }
+
+        fn size(&self) -> Size {
+            Size::new(320, 240)
+        }
+    }
+
+    #[test]
+    fn colormap_interpolates_between_colors() {
+        // 両端は表の最初と最後の色
+        assert_eq!(Colormap::Viridis.color(0), Rgb888::new(68, 1, 84).into());
+        assert_eq!(
+            Colormap::Viridis.color(255),
+            Rgb888::new(253, 231, 37).into()
+        );
+        assert_eq!(Colormap::Inferno.color(0), Rgb888::new(0, 0, 4).into());
+        assert_eq!(
+            Colormap::Inferno.color(255),
+            Rgb888::new(252, 255, 164).into()
+        );
+        // 16 * 8 = 128 なので、最初の2色をほぼ半分ずつ混ぜた色
+        assert_eq!(Colormap::Viridis.color(16), Rgb888::new(69, 22, 103).into());
+        assert_eq!(Colormap::Grayscale.color(0), Rgb565::BLACK);
+        assert_eq!(Colormap::Grayscale.color(255), Rgb565::WHITE);
+        assert_eq!(
+            Colormap::Grayscale.color(100),
+            Rgb888::new(100, 100, 100).into()
+        );
+    }
+
+    #[test]
+    fn push_maps_dbfs_to_values() {
+        let mut waterfall: Waterfall<6, 2> = Waterfall::new(Point::zero(), 1, Colormap::Viridis);
+        // 既定の範囲は -90〜-30[dBFS]
+        waterfall.push([-100.0, -90.0, -60.0, -30.0, -10.0].iter().copied());
+        // 足りない最後のビンは最小値
+        assert_eq!(waterfall.rows[waterfall.newest], [0, 0, 127, 255, 255, 0]);
+
+        waterfall.set_range(-64.0, 0.0);
+        waterfall.push(
+            [-48.0, -16.0, 0.0, 10.0, -64.0, -32.0, -1.0]
+                .iter()
+                .copied(),
+        );
+        assert_eq!(
+            waterfall.rows[waterfall.newest],
+            [63, 191, 255, 255, 0, 127]
+        );
+    }
+
+    // 指定した値 (0〜255) の行を追加する
+    fn push_values<const BINS: usize, const ROWS: usize>(
+        waterfall: &mut Waterfall<BINS, ROWS>,
+        values: &[u8],
+    ) {
+        // 0〜256[dBFS] を範囲にすると、値 * 256 / 255 のレベルがちょうどその値になる
+        waterfall.set_range(0.0, 256.0);
+        waterfall.push(values.iter().map(|&value| value as f32 * 256.0 / 255.0));
+    }
+
+    #[test]
+    fn draw_puts_newest_row_on_top() {
+        let top_left = Point::new(10, 20);
+        let mut waterfall: Waterfall<2, 3> = Waterfall::new(top_left, 2, Colormap::Grayscale);
+        push_values(&mut waterfall, &[255, 0]);
+
+        // 1行だけのときは、残りの行は背景色
+        let mut recorder = Recorder::new();
+        waterfall.draw(&mut recorder).unwrap();
+        assert_eq!(recorder.pixels.len(), 2 * 2 * 3);
+        let row_colors = |recorder: &Recorder, row: i32| -> Vec<Rgb565> {
+            let y = top_left.y + AXIS_HEIGHT + row;
+            (0..4)
+                .map(|x| recorder.color_at(top_left.x + x, y).unwrap())
+                .collect()
+        };
+        let gray = |value: u8| Colormap::Grayscale.color(value);
+        assert_eq!(
+            row_colors(&recorder, 0),
+            [gray(255), gray(255), gray(0), gray(0)]
+        );
+        assert_eq!(row_colors(&recorder, 1), [BG_COLOR; 4]);
+
+        // 行数より多く追加すると、古い行から消える
+        push_values(&mut waterfall, &[64, 128]);
+        push_values(&mut waterfall, &[32, 96]);
+        push_values(&mut waterfall, &[160, 224]);
+        let mut recorder = Recorder::new();
+        waterfall.draw(&mut recorder).unwrap();
+        assert_eq!(
+            row_colors(&recorder, 0),
+            [gray(160), gray(160), gray(224), gray(224)]
+        );
+        assert_eq!(
+            row_colors(&recorder, 1),
+            [gray(32), gray(32), gray(96), gray(96)]
+        );
+        assert_eq!(
+            row_colors(&recorder, 2),
+            [gray(64), gray(64), gray(128), gray(128)]
+        );
+
+        waterfall.clear();
+        let mut recorder = Recorder::new();
+        waterfall.draw(&mut recorder).unwrap();
+        assert!(recorder.pixels.iter().all(|pixel| pixel.1 == BG_COLOR));
+    }
+
+    // 周波数軸のティックの x 座標
+    fn tick_positions<const BINS: usize, const ROWS: usize>(
+        waterfall: &Waterfall<BINS, ROWS>,
+        sample_rate: f32,
+    ) -> Vec<i32> {
+        let mut recorder = Recorder::new();
+        waterfall.draw_axis(&mut recorder, sample_rate).unwrap();
+        // 軸の一番下の行にはティックだけがある
+        let mut xs: Vec<i32> = recorder
+            .pixels
+            .iter()
+            .filter(|pixel| pixel.0.y == AXIS_HEIGHT - 1 && pixel.1 == AXIS_COLOR)
+            .map(|pixel| pixel.0.x)
+            .collect();
+        xs.dedup();
+        xs
+    }
+
+    #[test]
+    fn draw_axis_selects_label_step() {
+        let waterfall: Waterfall<128, 1> = Waterfall::new(Point::zero(), 2, Colormap::Viridis);
+        // 22050[Hz] / 256[px] なので、40[px] 以上離れる最小の間隔は 5k
+        assert_eq!(tick_positions(&waterfall, 44_100.0), [0, 58, 116, 174, 232]);
+        // 4000[Hz] / 256[px] なので 1k
+        assert_eq!(tick_positions(&waterfall, 8_000.0), [0, 64, 128, 192]);
+    }
+
+    #[test]
+    fn frequency_labels() {
+        let format = |hz: u32| {
+            let mut label = Label::new();
+            write_frequency(&mut label, hz).unwrap();
+            std::string::String::from(label.as_str())
+        };
+        assert_eq!(format(0), "0");
+        assert_eq!(format(500), "500");
+        assert_eq!(format(2_000), "2k");
+        assert_eq!(format(1_500), "1.5k");
+        assert_eq!(format(20_000), "20k");
+    }
+}